@@ -7,11 +7,15 @@ use rustysd::control;
 use rustysd::logging;
 use rustysd::notification_handler;
 use rustysd::platform;
+use rustysd::services;
 use rustysd::signal_handler;
 use rustysd::socket_activation;
+use rustysd::state_persist;
+use rustysd::status_file;
 use rustysd::units;
 use signal_hook::iterator::Signals;
-use std::sync::{Arc, Mutex, RwLock};
+use rustysd::lock_order::{LockLevel, OrderedMutex, OrderedRwLock};
+use std::sync::{Arc, Mutex};
 
 fn find_shell_path() -> Option<std::path::PathBuf> {
     let possible_paths = vec![
@@ -146,27 +150,38 @@ fn prepare_runtimeinfo(conf: &config::Config, dry_run: bool) -> Arc<units::Runti
     // wrap units into mutexes
     let unit_table: std::collections::HashMap<_, _> = unit_table
         .into_iter()
-        .map(|(id, unit)| (id, Arc::new(Mutex::new(unit))))
+        .map(|(id, unit)| (id, Arc::new(OrderedMutex::new(LockLevel::Unit, unit))))
         .collect();
-    let unit_table = Arc::new(RwLock::new(unit_table));
+    let unit_table = Arc::new(OrderedRwLock::new(LockLevel::UnitTable, unit_table));
 
     // init the status map
     let mut status_table = std::collections::HashMap::new();
     for id in unit_table.read().unwrap().keys() {
-        status_table.insert(*id, Arc::new(Mutex::new(units::UnitStatus::NeverStarted)));
+        status_table.insert(
+            *id,
+            Arc::new(OrderedMutex::new(
+                LockLevel::Status,
+                units::UnitStatus::NeverStarted,
+            )),
+        );
     }
-    let status_table = Arc::new(RwLock::new(status_table));
+    let status_table = Arc::new(OrderedRwLock::new(LockLevel::StatusTable, status_table));
 
-    let pid_table = Arc::new(Mutex::new(std::collections::HashMap::new()));
+    let pid_table = Arc::new(units::PidTable::default());
 
     let run_info = Arc::new(units::RuntimeInfo {
         unit_table: unit_table.clone(),
         pid_table: pid_table.clone(),
-        fd_store: Arc::new(std::sync::RwLock::new(rustysd::fd_store::FDStore::default())),
+        fd_store: Arc::new(OrderedRwLock::new(
+            LockLevel::FdStore,
+            rustysd::fd_store::FDStore::default(),
+        )),
         status_table: status_table.clone(),
 
         last_id: Arc::new(Mutex::new(first_id)),
         config: conf.clone(),
+        events: units::EventSubscribers::default(),
+        state_file_lock: Mutex::new(()),
     });
 
     run_info
@@ -174,7 +189,7 @@ fn prepare_runtimeinfo(conf: &config::Config, dry_run: bool) -> Arc<units::Runti
 
 fn start_notification_handler_thread(run_info: units::ArcRuntimeInfo, eventfd: platform::EventFd) {
     std::thread::spawn(move || {
-        notification_handler::handle_all_streams(eventfd, run_info.unit_table.clone());
+        notification_handler::handle_all_streams(eventfd, run_info.clone());
     });
 }
 fn start_stdout_handler_thread(run_info: units::ArcRuntimeInfo, eventfd: platform::EventFd) {
@@ -187,6 +202,39 @@ fn start_stderr_handler_thread(run_info: units::ArcRuntimeInfo, eventfd: platfor
         notification_handler::handle_all_std_err(eventfd, run_info.clone());
     });
 }
+fn start_deadline_checker_thread(
+    run_info: units::ArcRuntimeInfo,
+    notification_socket_path: std::path::PathBuf,
+    eventfds: Arc<Vec<platform::EventFd>>,
+) {
+    std::thread::spawn(move || {
+        services::check_deadlines_loop(run_info, notification_socket_path, eventfds);
+    });
+}
+fn start_health_check_thread(
+    run_info: units::ArcRuntimeInfo,
+    notification_socket_path: std::path::PathBuf,
+    eventfds: Arc<Vec<platform::EventFd>>,
+) {
+    std::thread::spawn(move || {
+        services::check_health_loop(run_info, notification_socket_path, eventfds);
+    });
+}
+fn start_unit_dir_watcher_thread(
+    run_info: units::ArcRuntimeInfo,
+    notification_socket_path: std::path::PathBuf,
+    eventfds: Arc<Vec<platform::EventFd>>,
+) {
+    std::thread::spawn(move || {
+        units::watch_unit_dirs_loop(run_info, notification_socket_path, eventfds);
+    });
+}
+fn start_status_file_thread(run_info: units::ArcRuntimeInfo, path: std::path::PathBuf) {
+    std::thread::spawn(move || {
+        status_file::write_status_file_loop(run_info, path);
+    });
+}
+
 fn start_signal_handler_thread(
     signals: Signals,
     run_info: units::ArcRuntimeInfo,
@@ -201,10 +249,36 @@ fn start_signal_handler_thread(
     handle
 }
 
+/// Print the order the units would be activated in, without starting any of them
+fn test_boot(conf: &config::Config) {
+    let run_info = prepare_runtimeinfo(conf, false);
+    let order = units::test_boot(run_info.clone());
+
+    let unit_table = run_info.unit_table.read().unwrap();
+    let name = |id: &units::UnitId| unit_table.get(id).unwrap().lock().unwrap().conf.name();
+    println!("Activation order:");
+    for (idx, id) in order.iter().enumerate() {
+        println!("{:4}: {}", idx + 1, name(id));
+    }
+    let mut not_activated = unit_table
+        .keys()
+        .filter(|id| !order.contains(id))
+        .map(name)
+        .collect::<Vec<_>>();
+    if !not_activated.is_empty() {
+        not_activated.sort();
+        println!("Never activated:");
+        for unit_name in not_activated {
+            println!("      {}", unit_name);
+        }
+    }
+}
+
 #[derive(Default)]
 struct CliArgs {
     conf_path: Option<std::path::PathBuf>,
     dry_run: bool,
+    test_boot: bool,
     show_help: bool,
     unknown_arg: Option<String>
 }
@@ -238,6 +312,10 @@ fn parse_args() -> CliArgs {
                 cli_args.dry_run = true;
                 idx += 1;
             }
+            "-t" | "--test-boot" => {
+                cli_args.test_boot = true;
+                idx += 1;
+            }
             "-h" | "--help" => {
                 cli_args.show_help = true;
                 idx += 1;
@@ -256,7 +334,8 @@ fn main() {
 
     let cli_args = parse_args();
 
-    let usage = "Usage: rustysd [-c | --config PATH] [-d | --dry-run] [-h | --help]";
+    let usage =
+        "Usage: rustysd [-c | --config PATH] [-d | --dry-run] [-t | --test-boot] [-h | --help]";
     if cli_args.show_help {
         println!("{}", usage);
         std::process::exit(0);
@@ -280,6 +359,11 @@ fn main() {
         }
     };
 
+    if cli_args.test_boot {
+        test_boot(&conf);
+        std::process::exit(0);
+    }
+
     #[cfg(feature = "cgroups")]
     {
         platform::cgroups::move_to_own_cgroup(&std::path::PathBuf::from("/sys/fs/cgroup")).unwrap();
@@ -297,6 +381,11 @@ fn main() {
 
     let run_info = prepare_runtimeinfo(&conf, cli_args.dry_run);
 
+    // services that a previous rustysd process left running are taken over instead of being started again
+    if let Some(path) = &conf.state_file {
+        state_persist::adopt_services(&run_info, state_persist::load_state(path));
+    }
+
     let notification_eventfd = platform::make_event_fd().unwrap();
     let stdout_eventfd = platform::make_event_fd().unwrap();
     let stderr_eventfd = platform::make_event_fd().unwrap();
@@ -313,6 +402,7 @@ fn main() {
         signal_hook::SIGTERM,
         signal_hook::SIGINT,
         signal_hook::SIGQUIT,
+        signal_hook::SIGUSR1,
     ]) {
         Ok(signals) => signals,
         Err(e) => {
@@ -330,6 +420,26 @@ fn main() {
     start_notification_handler_thread(run_info.clone(), notification_eventfd);
     start_stdout_handler_thread(run_info.clone(), stdout_eventfd);
     start_stderr_handler_thread(run_info.clone(), stderr_eventfd);
+    start_deadline_checker_thread(
+        run_info.clone(),
+        conf.notification_sockets_dir.clone(),
+        Arc::new(eventfds.clone()),
+    );
+    start_health_check_thread(
+        run_info.clone(),
+        conf.notification_sockets_dir.clone(),
+        Arc::new(eventfds.clone()),
+    );
+    if conf.watch_unit_dirs {
+        start_unit_dir_watcher_thread(
+            run_info.clone(),
+            conf.notification_sockets_dir.clone(),
+            Arc::new(eventfds.clone()),
+        );
+    }
+    if let Some(path) = &conf.status_file {
+        start_status_file_thread(run_info.clone(), path.clone());
+    }
 
     socket_activation::start_socketactivation_thread(
         run_info.clone(),
@@ -8,7 +8,18 @@
 //! ### General config
 //! 1. Where to find the units (one or more directories)
 //! 1. notification-socket directory (where the unix-domain sockets are placed on which services can notify rustysd)
+//! 1. The name of the notification socket of each service, a template with %n (unit name), %N (unit name without
+//!    suffix), %p (prefix before the '@'), %i (instance after the '@') and %% (a literal '%'). It needs %n, %N or
+//!    %p and %i, so each unit gets its own socket
 //! 1. Which unit is the target that should be started
+//! 1. The maximum length of lines of service output (longer lines get split)
+//! 1. How much unterminated service output is kept around per stream (older output gets truncated)
+//! 1. How much of the recent stdout of each service is kept for the logs command of the control interface
+//! 1. The format of service output (plain prefixed lines or json objects)
+//! 1. Whether or not to send service output to journald if its socket exists (off by default)
+//! 1. Wether or not to watch the unit directories and load changed unit files automatically (off by default)
+//! 1. Where to write a plain text summary of the state of all units (not written by default)
+//! 1. Where to save the pids of running services, so a new rustysd process can adopt them (not written by default)
 
 use std::{collections::HashMap, fs::File, io::Read, path::PathBuf};
 use toml;
@@ -20,18 +31,37 @@ pub struct LoggingConfig {
     pub log_dir: PathBuf,
 }
 
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum LogFormat {
+    Plain,
+    Json,
+}
+
 #[derive(Debug, Clone)]
 pub struct Config {
     pub unit_dirs: Vec<PathBuf>,
     pub target_unit: String,
     pub notification_sockets_dir: PathBuf,
+    pub notification_socket_name: String,
+    pub max_log_line_length: usize,
+    pub max_output_buffer_size: usize,
+    pub stdout_capture_size: usize,
+    pub log_format: LogFormat,
+    pub log_to_journald: bool,
+    pub watch_unit_dirs: bool,
+    pub status_file: Option<PathBuf>,
+    pub state_file: Option<PathBuf>,
 }
 
+/// The name the notification sockets always had before the name could be configured
+pub const DEFAULT_NOTIFICATION_SOCKET_NAME: &str = "%n.notifiy_socket";
+
 #[derive(Debug)]
 enum SettingValue {
     Str(String),
     Array(Vec<SettingValue>),
     Boolean(bool),
+    Number(u64),
 }
 
 fn load_toml(
@@ -83,6 +113,45 @@ fn load_toml(
                 SettingValue::Str(val.clone()),
             );
         }
+        if let Some(toml::Value::Boolean(val)) = map.get("log_to_journald") {
+            settings.insert("log.to.journald".to_owned(), SettingValue::Boolean(*val));
+        }
+        if let Some(toml::Value::Boolean(val)) = map.get("watch_unit_dirs") {
+            settings.insert("watch.unit.dirs".to_owned(), SettingValue::Boolean(*val));
+        }
+        if let Some(toml::Value::String(val)) = map.get("notification_socket_name") {
+            settings.insert(
+                "notification.socket.name".to_owned(),
+                SettingValue::Str(val.clone()),
+            );
+        }
+        if let Some(toml::Value::String(val)) = map.get("status_file") {
+            settings.insert("status.file".to_owned(), SettingValue::Str(val.clone()));
+        }
+        if let Some(toml::Value::String(val)) = map.get("state_file") {
+            settings.insert("state.file".to_owned(), SettingValue::Str(val.clone()));
+        }
+        if let Some(toml::Value::String(val)) = map.get("log_format") {
+            settings.insert("log.format".to_owned(), SettingValue::Str(val.clone()));
+        }
+        if let Some(toml::Value::Integer(val)) = map.get("max_log_line_length") {
+            settings.insert(
+                "max.log.line.length".to_owned(),
+                SettingValue::Number(*val as u64),
+            );
+        }
+        if let Some(toml::Value::Integer(val)) = map.get("max_output_buffer_size") {
+            settings.insert(
+                "max.output.buffer.size".to_owned(),
+                SettingValue::Number(*val as u64),
+            );
+        }
+        if let Some(toml::Value::Integer(val)) = map.get("stdout_capture_size") {
+            settings.insert(
+                "stdout.capture.size".to_owned(),
+                SettingValue::Number(*val as u64),
+            );
+        }
     }
     Ok(())
 }
@@ -133,6 +202,45 @@ fn load_json(
                 SettingValue::Str(val.clone()),
             );
         }
+        if let Some(serde_json::Value::Bool(val)) = map.get("log_to_journald") {
+            settings.insert("log.to.journald".to_owned(), SettingValue::Boolean(*val));
+        }
+        if let Some(serde_json::Value::Bool(val)) = map.get("watch_unit_dirs") {
+            settings.insert("watch.unit.dirs".to_owned(), SettingValue::Boolean(*val));
+        }
+        if let Some(serde_json::Value::String(val)) = map.get("notification_socket_name") {
+            settings.insert(
+                "notification.socket.name".to_owned(),
+                SettingValue::Str(val.clone()),
+            );
+        }
+        if let Some(serde_json::Value::String(val)) = map.get("status_file") {
+            settings.insert("status.file".to_owned(), SettingValue::Str(val.clone()));
+        }
+        if let Some(serde_json::Value::String(val)) = map.get("state_file") {
+            settings.insert("state.file".to_owned(), SettingValue::Str(val.clone()));
+        }
+        if let Some(serde_json::Value::String(val)) = map.get("log_format") {
+            settings.insert("log.format".to_owned(), SettingValue::Str(val.clone()));
+        }
+        if let Some(serde_json::Value::Number(val)) = map.get("max_log_line_length") {
+            if let Some(val) = val.as_u64() {
+                settings.insert("max.log.line.length".to_owned(), SettingValue::Number(val));
+            }
+        }
+        if let Some(serde_json::Value::Number(val)) = map.get("max_output_buffer_size") {
+            if let Some(val) = val.as_u64() {
+                settings.insert(
+                    "max.output.buffer.size".to_owned(),
+                    SettingValue::Number(val),
+                );
+            }
+        }
+        if let Some(serde_json::Value::Number(val)) = map.get("stdout_capture_size") {
+            if let Some(val) = val.as_u64() {
+                settings.insert("stdout.capture.size".to_owned(), SettingValue::Number(val));
+            }
+        }
     }
     Ok(())
 }
@@ -195,11 +303,64 @@ pub fn load_config(config_path: &Option<PathBuf>) -> (LoggingConfig, Result<Conf
         SettingValue::Str(s) => Some(PathBuf::from(s)),
         _ => None,
     });
+    let notification_socket_name = match settings.get("notification.socket.name") {
+        Some(SettingValue::Str(s)) => s.clone(),
+        _ => DEFAULT_NOTIFICATION_SOCKET_NAME.to_owned(),
+    };
     let target_unit = settings.get("target.unit").map(|name| match name {
         SettingValue::Str(s) => Some(s.clone()),
         _ => None,
     });
 
+    let max_log_line_length = settings.get("max.log.line.length").map(|val| match val {
+        SettingValue::Number(n) => Some(*n as usize),
+        SettingValue::Str(s) => s.parse::<usize>().ok(),
+        _ => None,
+    });
+
+    let max_output_buffer_size = settings.get("max.output.buffer.size").map(|val| match val {
+        SettingValue::Number(n) => Some(*n as usize),
+        SettingValue::Str(s) => s.parse::<usize>().ok(),
+        _ => None,
+    });
+
+    let stdout_capture_size = settings.get("stdout.capture.size").map(|val| match val {
+        SettingValue::Number(n) => Some(*n as usize),
+        SettingValue::Str(s) => s.parse::<usize>().ok(),
+        _ => None,
+    });
+
+    let log_to_journald = settings.get("log.to.journald").map(|val| match val {
+        SettingValue::Boolean(b) => *b,
+        SettingValue::Str(s) => crate::units::string_to_bool(s),
+        _ => false,
+    });
+
+    let watch_unit_dirs = settings.get("watch.unit.dirs").map(|val| match val {
+        SettingValue::Boolean(b) => *b,
+        SettingValue::Str(s) => crate::units::string_to_bool(s),
+        _ => false,
+    });
+
+    let status_file = match settings.get("status.file") {
+        Some(SettingValue::Str(s)) => Some(PathBuf::from(s)),
+        _ => None,
+    };
+
+    let state_file = match settings.get("state.file") {
+        Some(SettingValue::Str(s)) => Some(PathBuf::from(s)),
+        _ => None,
+    };
+
+    let log_format = match settings.get("log.format") {
+        Some(SettingValue::Str(s)) => match s.to_lowercase().as_str() {
+            "plain" => Ok(LogFormat::Plain),
+            "json" => Ok(LogFormat::Json),
+            _ => Err(format!("Unknown log format: {}", s)),
+        },
+        _ => Ok(LogFormat::Plain),
+    };
+
     let unit_dirs = settings.get("unit.dirs").map(|dir| match dir {
         SettingValue::Str(s) => vec![PathBuf::from(s)],
         SettingValue::Array(arr) => arr
@@ -228,9 +389,29 @@ pub fn load_config(config_path: &Option<PathBuf>) -> (LoggingConfig, Result<Conf
         notification_sockets_dir: notification_sockets_dir
             .unwrap_or_else(|| Some(PathBuf::from("./notifications")))
             .unwrap(),
+        notification_socket_name,
+
+        max_log_line_length: max_log_line_length.unwrap_or(Some(4096)).unwrap_or(4096),
+        max_output_buffer_size: max_output_buffer_size
+            .unwrap_or(Some(64 * 1024))
+            .unwrap_or(64 * 1024),
+        stdout_capture_size: stdout_capture_size
+            .unwrap_or(Some(64 * 1024))
+            .unwrap_or(64 * 1024),
+        log_format: log_format.clone().unwrap_or(LogFormat::Plain),
+        log_to_journald: log_to_journald.unwrap_or(false),
+        watch_unit_dirs: watch_unit_dirs.unwrap_or(false),
+        status_file,
+        state_file,
     };
 
-    let conf = if let Some(json_conf) = json_conf {
+    let conf = if let Err(e) = log_format {
+        Err(e)
+    } else if let Err(e) =
+        crate::services::check_notification_socket_name_template(&config.notification_socket_name)
+    {
+        Err(e)
+    } else if let Some(json_conf) = json_conf {
         if toml_conf.is_some() {
             Err(format!("Found both json and toml conf!"))
         } else {
@@ -28,8 +28,22 @@ pub enum Command {
     ListUnits(Option<UnitIdKind>),
     Status(Option<String>),
     Restart(String),
+    ReloadUnit(String),
+    TakeOutput(String),
+    /// Name of the service and wether to follow new output
+    Logs(String, bool),
+    /// Rotate the log files of one or all services
+    RotateLogs(Option<String>),
+    /// Everything rustysd knows about all units in one document
+    DumpJson,
+    /// Counters and gauges of all units in the Prometheus text format
+    Metrics,
     LoadNew(Vec<String>),
     LoadAllNew,
+    /// Parse all unit files again, add new units and update the changed ones
+    DaemonReload,
+    /// Stream state changes of all units until the connection is closed
+    Subscribe,
     Stop(String),
     Shutdown,
 }
@@ -73,6 +87,57 @@ fn parse_command(call: &super::jsonrpc2::Call) -> Result<Command, ParseError> {
             };
             Command::Restart(name)
         }
+        "reload-unit" => {
+            let name = match &call.params {
+                Some(Value::String(s)) => s.clone(),
+                _ => {
+                    return Err(ParseError::ParamsInvalid(
+                        "Params must be a single string".into(),
+                    ))
+                }
+            };
+            Command::ReloadUnit(name)
+        }
+        "take-output" => {
+            let name = match &call.params {
+                Some(Value::String(s)) => s.clone(),
+                _ => {
+                    return Err(ParseError::ParamsInvalid(
+                        "Params must be a single string".into(),
+                    ))
+                }
+            };
+            Command::TakeOutput(name)
+        }
+        "rotate-logs" => {
+            let name = match &call.params {
+                Some(Value::String(s)) => Some(s.clone()),
+                None => None,
+                _ => {
+                    return Err(ParseError::ParamsInvalid(
+                        "Params must be either none or a single string".into(),
+                    ))
+                }
+            };
+            Command::RotateLogs(name)
+        }
+        "logs" => {
+            let invalid = || {
+                ParseError::ParamsInvalid(
+                    "Params must be a single string or a string followed by \"follow\"".into(),
+                )
+            };
+            match &call.params {
+                Some(Value::String(s)) => Command::Logs(s.clone(), false),
+                Some(Value::Array(params)) => match params.as_slice() {
+                    [Value::String(name), Value::String(follow)] if follow == "follow" => {
+                        Command::Logs(name.clone(), true)
+                    }
+                    _ => return Err(invalid()),
+                },
+                _ => return Err(invalid()),
+            }
+        }
         "stop" => {
             let name = match &call.params {
                 Some(params) => match params {
@@ -120,7 +185,11 @@ fn parse_command(call: &super::jsonrpc2::Call) -> Result<Command, ParseError> {
             Command::ListUnits(kind)
         }
         "shutdown" => Command::Shutdown,
+        "dump-json" => Command::DumpJson,
+        "metrics" => Command::Metrics,
         "reload" => Command::LoadAllNew,
+        "daemon-reload" => Command::DaemonReload,
+        "subscribe" => Command::Subscribe,
         "enable" => {
             let names = match &call.params {
                 Some(params) => match params {
@@ -194,10 +263,35 @@ pub fn format_target(socket_unit: &Unit, status: UnitStatus) -> Value {
     Value::Object(map)
 }
 
+/// Formats a duration like "2d 3h 4min 5s", leaving out leading zero parts
+pub fn format_uptime(uptime: std::time::Duration) -> String {
+    let secs = uptime.as_secs();
+    let days = secs / (60 * 60 * 24);
+    let hours = (secs / (60 * 60)) % 24;
+    let mins = (secs / 60) % 60;
+    let secs = secs % 60;
+
+    let mut parts = Vec::new();
+    if days > 0 {
+        parts.push(format!("{}d", days));
+    }
+    if days > 0 || hours > 0 {
+        parts.push(format!("{}h", hours));
+    }
+    if days > 0 || hours > 0 || mins > 0 {
+        parts.push(format!("{}min", mins));
+    }
+    parts.push(format!("{}s", secs));
+    parts.join(" ")
+}
+
 pub fn format_service(srvc_unit: &Unit, status: UnitStatus) -> Value {
     let mut map = serde_json::Map::new();
     map.insert("Name".into(), Value::String(srvc_unit.conf.name()));
     map.insert("Status".into(), Value::String(format!("{:?}", status)));
+    if srvc_unit.pending_reload.is_some() {
+        map.insert("NeedsRestart".into(), Value::Bool(true));
+    }
     if let UnitSpecialized::Service(srvc) = &srvc_unit.specialized {
         map.insert(
             "Sockets".into(),
@@ -213,17 +307,180 @@ pub fn format_service(srvc_unit: &Unit, status: UnitStatus) -> Value {
                 "UpSince".into(),
                 Value::String(format!("{:?}", instant.elapsed())),
             );
+            map.insert(
+                "Uptime".into(),
+                Value::String(format_uptime(instant.elapsed())),
+            );
         }
         map.insert(
             "Restarted".into(),
             Value::String(format!("{:?}", srvc.runtime_info.restarted)),
         );
+        if let Some(reason) = &srvc.runtime_info.last_restart_reason {
+            map.insert(
+                "LastRestartReason".into(),
+                Value::String(reason.to_string()),
+            );
+        }
+        if srvc.service_config.health_check.is_some() {
+            map.insert("Healthy".into(), Value::Bool(!srvc.is_unhealthy()));
+            map.insert(
+                "HealthCheckFailures".into(),
+                Value::from(srvc.runtime_info.health_check_failures),
+            );
+        }
+        if let Some(code) = srvc.runtime_info.last_exit {
+            let exit = match code {
+                crate::signal_handler::ChildTermination::Exit(code) => {
+                    format!("exited with code {}", code)
+                }
+                crate::signal_handler::ChildTermination::Signal(signal) => {
+                    format!("killed by signal {:?}", signal)
+                }
+            };
+            map.insert("LastExit".into(), Value::String(exit));
+        }
+        if let Some(instant) = srvc.runtime_info.exited_at {
+            map.insert(
+                "ExitedSince".into(),
+                Value::String(format_uptime(instant.elapsed())),
+            );
+        }
+    }
+    Value::Object(map)
+}
+
+fn format_termination(termination: crate::signal_handler::ChildTermination) -> Value {
+    let mut map = serde_json::Map::new();
+    match termination {
+        crate::signal_handler::ChildTermination::Exit(code) => {
+            map.insert("ExitCode".into(), Value::from(code));
+        }
+        crate::signal_handler::ChildTermination::Signal(signal) => {
+            map.insert("Signal".into(), Value::String(format!("{:?}", signal)));
+        }
     }
     Value::Object(map)
 }
 
-use std::sync::{Arc, Mutex};
-fn find_unit_with_name(unit_name: &str, unit_table_locked: &UnitTable) -> Option<Arc<Mutex<Unit>>> {
+fn format_event(event: UnitEvent) -> Value {
+    let mut map = serde_json::Map::new();
+    map.insert("Unit".into(), Value::String(event.unit_name));
+    let name = match event.kind {
+        UnitEventKind::Started(pid) => {
+            map.insert("Pid".into(), Value::from(pid.as_raw()));
+            "started"
+        }
+        UnitEventKind::Ready => "ready",
+        UnitEventKind::Exited(termination) => {
+            map.insert("Termination".into(), format_termination(termination));
+            "exited"
+        }
+        UnitEventKind::Failed(reason) => {
+            map.insert("Reason".into(), Value::String(reason));
+            "failed"
+        }
+        UnitEventKind::Restarted(reason) => {
+            map.insert("Reason".into(), Value::String(format!("{}", reason)));
+            "restarted"
+        }
+        UnitEventKind::Unhealthy(reason) => {
+            map.insert("Reason".into(), Value::String(reason));
+            "unhealthy"
+        }
+    };
+    map.insert("Event".into(), Value::String(name.into()));
+    Value::Object(map)
+}
+
+fn dump_unit(
+    unit: &Unit,
+    status: UnitStatus,
+    names: &std::collections::HashMap<UnitId, String>,
+) -> Value {
+    let id_names = |ids: &Vec<UnitId>| {
+        Value::Array(
+            ids.iter()
+                .filter_map(|id| names.get(id))
+                .map(|name| Value::String(name.clone()))
+                .collect(),
+        )
+    };
+    let mut map = serde_json::Map::new();
+    map.insert("Name".into(), Value::String(unit.conf.name()));
+    map.insert("Type".into(), Value::String(format!("{:?}", unit.id.0)));
+    map.insert("Status".into(), Value::String(format!("{:?}", status)));
+    map.insert("Wants".into(), id_names(&unit.install.wants));
+    map.insert("Requires".into(), id_names(&unit.install.requires));
+    map.insert("WantedBy".into(), id_names(&unit.install.wanted_by));
+    map.insert("RequiredBy".into(), id_names(&unit.install.required_by));
+    map.insert("Before".into(), id_names(&unit.install.before));
+    map.insert("After".into(), id_names(&unit.install.after));
+    if let UnitSpecialized::Service(srvc) = &unit.specialized {
+        map.insert(
+            "Pid".into(),
+            srvc.pid
+                .map(|pid| Value::from(pid.as_raw()))
+                .unwrap_or(Value::Null),
+        );
+        map.insert("Restarted".into(), Value::from(srvc.runtime_info.restarted));
+        map.insert(
+            "NeedsRestart".into(),
+            Value::from(unit.pending_reload.is_some()),
+        );
+        map.insert(
+            "LastRestartReason".into(),
+            srvc.runtime_info
+                .last_restart_reason
+                .as_ref()
+                .map(|reason| Value::String(reason.to_string()))
+                .unwrap_or(Value::Null),
+        );
+        map.insert(
+            "UptimeSecs".into(),
+            srvc.runtime_info
+                .up_since
+                .map(|instant| Value::from(instant.elapsed().as_secs()))
+                .unwrap_or(Value::Null),
+        );
+        map.insert(
+            "LastExit".into(),
+            srvc.runtime_info
+                .last_exit
+                .map(format_termination)
+                .unwrap_or(Value::Null),
+        );
+    }
+    Value::Object(map)
+}
+
+/// Dump all units with their dependencies and runtime information
+fn dump_units(run_info: &ArcRuntimeInfo) -> Value {
+    let unit_table_locked = run_info.unit_table.read().unwrap();
+    // collect the names first so no two units need to be locked at the same time
+    let names: std::collections::HashMap<UnitId, String> = unit_table_locked
+        .iter()
+        .map(|(id, unit)| (*id, unit.lock().unwrap().conf.name()))
+        .collect();
+    let mut ids: Vec<_> = unit_table_locked.keys().cloned().collect();
+    ids.sort();
+    Value::Array(
+        ids.iter()
+            .map(|id| {
+                let unit_locked = unit_table_locked.get(id).unwrap().lock().unwrap();
+                let status = run_info.current_status(id).unwrap();
+                dump_unit(&unit_locked, status, &names)
+            })
+            .collect(),
+    )
+}
+
+use crate::lock_order::OrderedMutex;
+use std::sync::Arc;
+fn find_unit_with_name(
+    unit_name: &str,
+    unit_table_locked: &UnitTable,
+) -> Option<Arc<OrderedMutex<Unit>>> {
     trace!("Find unit for name: {}", unit_name);
     let mut srvc: Vec<_> = unit_table_locked
         .values()
@@ -244,7 +501,7 @@ fn find_unit_with_name(unit_name: &str, unit_table_locked: &UnitTable) -> Option
 fn find_units_with_pattern(
     name_pattern: &str,
     unit_table_locked: &UnitTable,
-) -> Vec<Arc<Mutex<Unit>>> {
+) -> Vec<Arc<OrderedMutex<Unit>>> {
     trace!("Find units matching pattern: {}", name_pattern);
     let units: Vec<_> = unit_table_locked
         .values()
@@ -275,8 +532,19 @@ pub fn execute_command(
             } else {
                 return Err(format!("No unit found with name: {}", unit_name));
             };
+            if let Some(unit) = run_info.unit_table.read().unwrap().get(&id) {
+                let unit_locked = &mut *unit.lock().unwrap();
+                let name = unit_locked.conf.name();
+                if let UnitSpecialized::Service(srvc) = &mut unit_locked.specialized {
+                    srvc.set_restart_reason(&name, crate::services::RestartReason::Manual);
+                    run_info.events.publish(
+                        &name,
+                        UnitEventKind::Restarted(crate::services::RestartReason::Manual),
+                    );
+                }
+            }
 
-            crate::units::reactivate_unit(
+            crate::units::reactivate_unit_recursive(
                 id,
                 run_info,
                 notification_socket_path,
@@ -284,6 +552,102 @@ pub fn execute_command(
             )
             .map_err(|e| format!("{}", e))?;
         }
+        Command::ReloadUnit(unit_name) => {
+            let unit = if let Some(unit) =
+                find_unit_with_name(&unit_name, &run_info.unit_table.read().unwrap())
+            {
+                unit
+            } else {
+                return Err(format!("No unit found with name: {}", unit_name));
+            };
+            let unit_locked = &mut *unit.lock().unwrap();
+            let name = unit_locked.conf.name();
+            let id = unit_locked.id;
+            if let UnitSpecialized::Service(srvc) = &mut unit_locked.specialized {
+                let status = run_info
+                    .status_table
+                    .read()
+                    .unwrap()
+                    .get(&id)
+                    .unwrap()
+                    .clone();
+                {
+                    let status_locked = &mut *status.lock().unwrap();
+                    if *status_locked != UnitStatus::Started {
+                        return Err(format!(
+                            "Only running services can be reloaded. {} is {:?}",
+                            name, status_locked
+                        ));
+                    }
+                    *status_locked = UnitStatus::Reloading;
+                }
+                let result = srvc.reload(id, &name, run_info.clone());
+                {
+                    // the service might have been stopped or failed in the meantime
+                    let status_locked = &mut *status.lock().unwrap();
+                    if *status_locked == UnitStatus::Reloading {
+                        *status_locked = UnitStatus::Started;
+                    }
+                }
+                result.map_err(|e| format!("{}", e))?;
+            } else {
+                return Err(format!("Only services can be reloaded: {}", unit_name));
+            }
+        }
+        Command::TakeOutput(unit_name) => {
+            let unit = if let Some(unit) =
+                find_unit_with_name(&unit_name, &run_info.unit_table.read().unwrap())
+            {
+                unit
+            } else {
+                return Err(format!("No unit found with name: {}", unit_name));
+            };
+            let unit_locked = &mut *unit.lock().unwrap();
+            if let UnitSpecialized::Service(srvc) = &mut unit_locked.specialized {
+                let (stdout, stderr) = srvc.take_output_buffers();
+                let mut map = serde_json::Map::new();
+                map.insert(
+                    "Stdout".into(),
+                    Value::String(String::from_utf8_lossy(&stdout).into_owned()),
+                );
+                map.insert(
+                    "Stderr".into(),
+                    Value::String(String::from_utf8_lossy(&stderr).into_owned()),
+                );
+                result_vec.as_array_mut().unwrap().push(Value::Object(map));
+            } else {
+                return Err(format!("Only services have output buffers: {}", unit_name));
+            }
+        }
+        Command::Logs(unit_name, _follow) => {
+            let (lines, next_line) = get_captured_lines(&unit_name, &run_info, 0)?;
+            result_vec = format_log_lines(lines, next_line);
+        }
+        Command::RotateLogs(unit_name) => match unit_name {
+            Some(unit_name) => {
+                let unit = if let Some(unit) =
+                    find_unit_with_name(&unit_name, &run_info.unit_table.read().unwrap())
+                {
+                    unit
+                } else {
+                    return Err(format!("No unit found with name: {}", unit_name));
+                };
+                let unit_locked = &mut *unit.lock().unwrap();
+                let name = unit_locked.conf.name();
+                if let UnitSpecialized::Service(srvc) = &mut unit_locked.specialized {
+                    srvc.rotate_logs(&name)?;
+                } else {
+                    return Err(format!("Only services have log files: {}", unit_name));
+                }
+            }
+            None => crate::signal_handler::rotate_all_logs(&run_info),
+        },
+        Command::DumpJson => {
+            result_vec = dump_units(&run_info);
+        }
+        Command::Metrics => {
+            result_vec = Value::String(super::metrics::format_metrics(&run_info));
+        }
         Command::Stop(unit_name) => {
             let id = if let Some(unit) =
                 find_unit_with_name(&unit_name, &*run_info.unit_table.read().unwrap())
@@ -304,17 +668,7 @@ pub fn execute_command(
                     let units = find_units_with_pattern(&name, unit_table_locked);
                     for unit in units {
                         let unit_locked = unit.lock().unwrap();
-                        let status = {
-                            run_info
-                                .status_table
-                                .read()
-                                .unwrap()
-                                .get(&unit_locked.id)
-                                .unwrap()
-                                .lock()
-                                .unwrap()
-                                .clone()
-                        };
+                        let status = run_info.current_status(&unit_locked.id).unwrap();
                         if name.ends_with(".service") {
                             result_vec
                                 .as_array_mut()
@@ -342,17 +696,7 @@ pub fn execute_command(
                         .iter()
                         .map(|(_id, unit)| {
                             let unit_locked = &unit.lock().unwrap();
-                            let status = {
-                                run_info
-                                    .status_table
-                                    .read()
-                                    .unwrap()
-                                    .get(&unit_locked.id)
-                                    .unwrap()
-                                    .lock()
-                                    .unwrap()
-                                    .clone()
-                            };
+                            let status = run_info.current_status(&unit_locked.id).unwrap();
                             match unit_locked.specialized {
                                 UnitSpecialized::Socket(_) => format_socket(&unit_locked, status),
                                 UnitSpecialized::Service(_) => format_service(&unit_locked, status),
@@ -445,11 +789,116 @@ pub fn execute_command(
                 .unwrap()
                 .push(Value::Object(response_object));
         }
+        Command::Subscribe => {
+            // the events are streamed by listen_on_commands, this only confirms the subscription
+            let mut response_object = serde_json::Map::new();
+            response_object.insert("Subscribed".into(), Value::Bool(true));
+            result_vec = Value::Object(response_object);
+        }
+        Command::DaemonReload => {
+            let report = crate::units::reload_all_units(run_info)?;
+            let names =
+                |names: Vec<String>| Value::Array(names.into_iter().map(Value::String).collect());
+            let mut response_object = serde_json::Map::new();
+            response_object.insert("Added".into(), names(report.added));
+            response_object.insert("Updated".into(), names(report.updated));
+            response_object.insert("NeedsRestart".into(), names(report.needs_restart));
+            response_object.insert("NotReloaded".into(), names(report.not_reloaded));
+            result_vec
+                .as_array_mut()
+                .unwrap()
+                .push(Value::Object(response_object));
+        }
     }
 
     Ok(result_vec)
 }
 
+/// The captured stdout lines of the service starting at line number since, and the number of the next line
+fn get_captured_lines(
+    unit_name: &str,
+    run_info: &ArcRuntimeInfo,
+    since: u64,
+) -> Result<(Vec<Vec<u8>>, u64), String> {
+    let unit =
+        if let Some(unit) = find_unit_with_name(unit_name, &run_info.unit_table.read().unwrap()) {
+            unit
+        } else {
+            return Err(format!("No unit found with name: {}", unit_name));
+        };
+    let unit_locked = unit.lock().unwrap();
+    if let UnitSpecialized::Service(srvc) = &unit_locked.specialized {
+        Ok(srvc.stdout_capture.lines_since(since))
+    } else {
+        Err(format!("Only services have logs: {}", unit_name))
+    }
+}
+
+fn format_log_lines(lines: Vec<Vec<u8>>, next_line: u64) -> Value {
+    let mut map = serde_json::Map::new();
+    map.insert(
+        "Lines".into(),
+        Value::Array(
+            lines
+                .iter()
+                .map(|line| Value::String(String::from_utf8_lossy(line).into_owned()))
+                .collect(),
+        ),
+    );
+    map.insert("NextLine".into(), Value::Number(next_line.into()));
+    Value::Object(map)
+}
+
+/// Send new lines of the service as additional responses until the connection gets closed or the unit disappears
+fn follow_logs<T: Write>(
+    source: &mut T,
+    run_info: &ArcRuntimeInfo,
+    unit_name: &str,
+    id: Option<Value>,
+    mut next_line: u64,
+) {
+    loop {
+        std::thread::sleep(std::time::Duration::from_millis(200));
+        let (lines, new_next_line) = match get_captured_lines(unit_name, run_info, next_line) {
+            Ok(res) => res,
+            Err(e) => {
+                trace!("Stop following logs: {}", e);
+                return;
+            }
+        };
+        next_line = new_next_line;
+        if lines.is_empty() {
+            continue;
+        }
+        let msg = super::jsonrpc2::make_result_response(
+            id.clone(),
+            format_log_lines(lines, new_next_line),
+        );
+        let response_string = serde_json::to_string_pretty(&msg).unwrap();
+        if let Err(e) = source.write_all(response_string.as_bytes()) {
+            trace!("Stop following logs of {}: {}", unit_name, e);
+            return;
+        }
+    }
+}
+
+/// Send each event as a line of json until the connection gets closed
+fn stream_events<T: Write>(
+    source: &mut T,
+    events: std::sync::mpsc::Receiver<UnitEvent>,
+    id: Option<Value>,
+) {
+    for event in events {
+        let msg = super::jsonrpc2::make_result_response(id.clone(), format_event(event));
+        let mut response_string = serde_json::to_string(&msg).unwrap();
+        response_string.push('\n');
+        if let Err(e) = source.write_all(response_string.as_bytes()) {
+            trace!("Stop streaming events: {}", e);
+            return;
+        }
+    }
+}
+
 use std::io::Read;
 use std::io::Write;
 pub fn listen_on_commands<T: 'static + Read + Write + Send>(
@@ -505,6 +954,16 @@ pub fn listen_on_commands<T: 'static + Read + Write + Send>(
                             }
                             Ok(cmd) => {
                                 trace!("Execute command: {:?}", cmd);
+                                let follow_logs_of = match &cmd {
+                                    Command::Logs(name, true) => Some(name.clone()),
+                                    _ => None,
+                                };
+                                // subscribe before answering so no event after the answer is missed
+                                let events = match &cmd {
+                                    Command::Subscribe => Some(run_info.events.subscribe()),
+                                    _ => None,
+                                };
+                                let id = call.id.clone();
                                 let msg = match execute_command(
                                     cmd,
                                     run_info.clone(),
@@ -519,6 +978,39 @@ pub fn listen_on_commands<T: 'static + Read + Write + Send>(
                                         super::jsonrpc2::make_error_response(call.id, err)
                                     }
                                     Ok(result) => {
+                                        if let Some(name) = follow_logs_of {
+                                            let next_line =
+                                                result["NextLine"].as_u64().unwrap_or(0);
+                                            let msg = super::jsonrpc2::make_result_response(
+                                                id.clone(),
+                                                result,
+                                            );
+                                            let response_string =
+                                                serde_json::to_string_pretty(&msg).unwrap();
+                                            source.write_all(response_string.as_bytes()).unwrap();
+                                            // following ends with the connection
+                                            follow_logs(
+                                                source.as_mut(),
+                                                &run_info,
+                                                &name,
+                                                id,
+                                                next_line,
+                                            );
+                                            return;
+                                        }
+                                        if let Some(events) = events {
+                                            let msg = super::jsonrpc2::make_result_response(
+                                                id.clone(),
+                                                result,
+                                            );
+                                            let mut response_string =
+                                                serde_json::to_string(&msg).unwrap();
+                                            response_string.push('\n');
+                                            source.write_all(response_string.as_bytes()).unwrap();
+                                            // streaming ends with the connection
+                                            stream_events(source.as_mut(), events, id);
+                                            return;
+                                        }
                                         super::jsonrpc2::make_result_response(call.id, result)
                                     }
                                 };
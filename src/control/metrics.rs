@@ -0,0 +1,106 @@
+//! The metrics call of the control interface. Answers with counters and gauges in the Prometheus text format, so a
+//! scraper (or a small proxy in front of the control socket) can collect them.
+
+use crate::units::*;
+
+/// Label values are in double quotes, so these have to be escaped
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+fn push_header(out: &mut String, name: &str, kind: &str, help: &str) {
+    out.push_str(&format!("# HELP {} {}\n", name, help));
+    out.push_str(&format!("# TYPE {} {}\n", name, kind));
+}
+
+/// The metrics of all services (sorted by name) and the counts of active and failed units
+pub fn format_metrics(run_info: &RuntimeInfo) -> String {
+    let mut services = Vec::new();
+    let mut active = 0;
+    let mut failed = 0;
+    for (id, unit) in run_info.unit_table.read().unwrap().iter() {
+        let unit_locked = unit.lock().unwrap();
+        let status = run_info.current_status(id);
+        match status {
+            Some(UnitStatus::Started)
+            | Some(UnitStatus::StartedWaitingForSocket)
+            | Some(UnitStatus::Reloading) => active += 1,
+            Some(UnitStatus::Failed(_)) => failed += 1,
+            _ => {}
+        }
+        if let UnitSpecialized::Service(srvc) = &unit_locked.specialized {
+            let up = srvc.pid.is_some()
+                && (status == Some(UnitStatus::Started) || status == Some(UnitStatus::Reloading));
+            let uptime = match srvc.runtime_info.up_since {
+                Some(up_since) if up => up_since.elapsed().as_secs_f64(),
+                _ => 0.0,
+            };
+            services.push((
+                unit_locked.conf.name(),
+                srvc.runtime_info.restarted,
+                up,
+                uptime,
+            ));
+        }
+    }
+    services.sort_by(|left, right| left.0.cmp(&right.0));
+
+    let mut out = String::new();
+    push_header(
+        &mut out,
+        "rustysd_service_restarts_total",
+        "counter",
+        "How often the service has been restarted",
+    );
+    for (name, restarted, _, _) in &services {
+        out.push_str(&format!(
+            "rustysd_service_restarts_total{{unit=\"{}\"}} {}\n",
+            escape_label(name),
+            restarted
+        ));
+    }
+    push_header(
+        &mut out,
+        "rustysd_service_up",
+        "gauge",
+        "Whether the main process of the service is running",
+    );
+    for (name, _, up, _) in &services {
+        out.push_str(&format!(
+            "rustysd_service_up{{unit=\"{}\"}} {}\n",
+            escape_label(name),
+            if *up { 1 } else { 0 }
+        ));
+    }
+    push_header(
+        &mut out,
+        "rustysd_service_uptime_seconds",
+        "gauge",
+        "Seconds since the service became ready, 0 if it is not running",
+    );
+    for (name, _, _, uptime) in &services {
+        out.push_str(&format!(
+            "rustysd_service_uptime_seconds{{unit=\"{}\"}} {:.3}\n",
+            escape_label(name),
+            uptime
+        ));
+    }
+    push_header(
+        &mut out,
+        "rustysd_units_active",
+        "gauge",
+        "Number of units that are started",
+    );
+    out.push_str(&format!("rustysd_units_active {}\n", active));
+    push_header(
+        &mut out,
+        "rustysd_units_failed",
+        "gauge",
+        "Number of units that failed",
+    );
+    out.push_str(&format!("rustysd_units_failed {}\n", failed));
+    out
+}
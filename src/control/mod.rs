@@ -2,5 +2,6 @@
 
 mod control;
 pub mod jsonrpc2;
+pub mod metrics;
 
 pub use control::*;
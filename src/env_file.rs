@@ -0,0 +1,108 @@
+//! Parse the files of EnvironmentFile=. Each line is a KEY=VALUE assignment, empty lines and lines starting with '#' or
+//! ';' are ignored. Values can be (partly) quoted: in double quotes the escapes \n, \t, \r and \ before one of \ " $ `
+//! work, single quotes keep everything as it is. Outside of quotes a '#' that starts a word begins a comment and a
+//! backslash keeps the next character. Whitespace around the value is removed unless it is quoted.
+
+use std::path::PathBuf;
+
+/// Parse the value after the '='
+fn parse_value(raw: &str) -> Result<String, String> {
+    let mut value = String::new();
+    // everything up to here is kept, unquoted whitespace after it gets trimmed at the end
+    let mut keep = 0;
+    let mut chars = raw.trim_start().chars();
+    let mut after_whitespace = true;
+    while let Some(c) = chars.next() {
+        match c {
+            '#' if after_whitespace => break,
+            '"' => loop {
+                match chars.next() {
+                    Some('"') => break,
+                    Some('\\') => match chars.next() {
+                        Some('n') => value.push('\n'),
+                        Some('t') => value.push('\t'),
+                        Some('r') => value.push('\r'),
+                        Some(c @ '\\') | Some(c @ '"') | Some(c @ '$') | Some(c @ '`') => {
+                            value.push(c)
+                        }
+                        Some(c) => {
+                            value.push('\\');
+                            value.push(c);
+                        }
+                        None => return Err(format!("Unterminated double quote in: {}", raw)),
+                    },
+                    Some(c) => value.push(c),
+                    None => return Err(format!("Unterminated double quote in: {}", raw)),
+                }
+            },
+            '\'' => loop {
+                match chars.next() {
+                    Some('\'') => break,
+                    Some(c) => value.push(c),
+                    None => return Err(format!("Unterminated single quote in: {}", raw)),
+                }
+            },
+            '\\' => match chars.next() {
+                Some(c) => value.push(c),
+                None => return Err(format!("Trailing backslash in: {}", raw)),
+            },
+            c => value.push(c),
+        }
+        after_whitespace = c.is_whitespace();
+        if !after_whitespace {
+            keep = value.len();
+        }
+    }
+    value.truncate(keep);
+    Ok(value)
+}
+
+fn valid_var_name(name: &str) -> bool {
+    !name.is_empty()
+        && !name.starts_with(|c: char| c.is_ascii_digit())
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Parse the content of an environment file into the assignments in the order they appear in
+pub fn parse_env_file(content: &str) -> Result<Vec<(String, String)>, String> {
+    let mut vars = Vec::new();
+    for (idx, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+            continue;
+        }
+        let pos = match line.find('=') {
+            Some(pos) => pos,
+            None => return Err(format!("Line {} is not an assignment: {}", idx + 1, line)),
+        };
+        let name = line[..pos].trim_end();
+        if !valid_var_name(name) {
+            return Err(format!(
+                "Line {} has an invalid variable name: {}",
+                idx + 1,
+                name
+            ));
+        }
+        let value =
+            parse_value(&line[pos + 1..]).map_err(|e| format!("Line {}: {}", idx + 1, e))?;
+        vars.push((name.to_owned(), value));
+    }
+    Ok(vars)
+}
+
+/// Read all files in order, later assignments of a variable override earlier ones. Files that do not exist are skipped
+/// if they are marked with ignore_missing
+pub fn read_env_files(files: &[(PathBuf, bool)]) -> Result<Vec<(String, String)>, String> {
+    let mut vars = Vec::new();
+    for (path, ignore_missing) in files {
+        let content = match std::fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(e) if *ignore_missing && e.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(format!("Could not read environment file {:?}: {}", path, e)),
+        };
+        let file_vars = parse_env_file(&content)
+            .map_err(|e| format!("Invalid environment file {:?}: {}", path, e))?;
+        vars.extend(file_vars);
+    }
+    Ok(vars)
+}
@@ -0,0 +1,68 @@
+//! Forward lines of service output to systemd-journald using its native protocol.
+//! Each entry is one datagram sent to /run/systemd/journal/socket containing newline separated KEY=value fields.
+//! Values that contain a newline use the binary encoding: KEY\n<little endian u64 length><value>\n
+
+use crate::syslog_sink::SyslogSeverity;
+use std::os::unix::net::UnixDatagram;
+
+pub const JOURNAL_SOCKET_PATH: &str = "/run/systemd/journal/socket";
+
+pub fn journal_available() -> bool {
+    std::path::Path::new(JOURNAL_SOCKET_PATH).exists()
+}
+
+fn append_field(buf: &mut Vec<u8>, key: &str, value: &[u8]) {
+    buf.extend(key.as_bytes());
+    if value.contains(&b'\n') {
+        buf.push(b'\n');
+        buf.extend(&(value.len() as u64).to_le_bytes());
+    } else {
+        buf.push(b'=');
+    }
+    buf.extend(value);
+    buf.push(b'\n');
+}
+
+pub fn format_entry(
+    severity: SyslogSeverity,
+    ident: &str,
+    pid: nix::unistd::Pid,
+    unit: &str,
+    msg: &[u8],
+    extra_fields: &std::collections::HashMap<String, String>,
+) -> Vec<u8> {
+    let mut buf = Vec::new();
+    append_field(
+        &mut buf,
+        "PRIORITY",
+        format!("{}", severity as u8).as_bytes(),
+    );
+    append_field(&mut buf, "SYSLOG_IDENTIFIER", ident.as_bytes());
+    // Fields starting with an underscore (_PID, _SYSTEMD_UNIT, ...) are trusted fields and get dropped by journald
+    // if a client sends them. These are the user settable equivalents.
+    append_field(&mut buf, "SYSLOG_PID", format!("{}", pid).as_bytes());
+    append_field(&mut buf, "UNIT", unit.as_bytes());
+    append_field(&mut buf, "MESSAGE", msg);
+    for (key, value) in extra_fields {
+        append_field(&mut buf, key, value.as_bytes());
+    }
+    buf
+}
+
+/// Send one line to the journal. Fails if the journal socket does not exist or nobody listens on it,
+/// the caller is expected to fall back to some other output in that case.
+pub fn send_line(
+    severity: SyslogSeverity,
+    ident: &str,
+    pid: nix::unistd::Pid,
+    unit: &str,
+    msg: &[u8],
+    extra_fields: &std::collections::HashMap<String, String>,
+) -> std::io::Result<()> {
+    let socket = UnixDatagram::unbound()?;
+    socket.send_to(
+        &format_entry(severity, ident, pid, unit, msg, extra_fields),
+        JOURNAL_SOCKET_PATH,
+    )?;
+    Ok(())
+}
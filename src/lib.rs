@@ -24,7 +24,10 @@
 pub mod config;
 pub mod control;
 pub mod dbus_wait;
+pub mod env_file;
 pub mod fd_store;
+pub mod journald_sink;
+pub mod lock_order;
 pub mod logging;
 pub mod notification_handler;
 pub mod platform;
@@ -33,6 +36,10 @@ pub mod shutdown;
 pub mod signal_handler;
 pub mod socket_activation;
 pub mod sockets;
+pub mod state_persist;
+pub mod status_file;
+pub mod syslog_sink;
+pub mod tmpfiles;
 pub mod units;
 
 #[macro_use]
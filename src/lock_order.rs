@@ -0,0 +1,145 @@
+//! Locks of the shared state that know their place in the lock hierarchy. rustysd avoids deadlocks by always taking
+//! the locks in the same order:
+//!
+//! unit_table -> unit -> status_table -> status -> pid_table -> fd_store
+//!
+//! Locks of the same level (e.g. multiple units) can be held at the same time, lock_all takes them sorted by id.
+//! With the debug_lock_order feature every thread keeps a stack of the levels it holds and panics if a lock is taken
+//! that comes earlier in the order than one that is already held. This fires before the thread blocks, so it catches
+//! wrong orders even if the deadlock itself would only happen under rare timing. Without the feature nothing is checked.
+
+use std::ops::{Deref, DerefMut};
+use std::sync::{
+    LockResult, Mutex, MutexGuard, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard,
+};
+
+#[derive(Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Debug)]
+pub enum LockLevel {
+    UnitTable,
+    Unit,
+    StatusTable,
+    Status,
+    PidTable,
+    FdStore,
+}
+
+#[cfg(feature = "debug_lock_order")]
+thread_local! {
+    static HELD_LOCKS: std::cell::RefCell<Vec<LockLevel>> = std::cell::RefCell::new(Vec::new());
+}
+
+/// Marks a lock of a level as held by this thread until it is dropped
+pub struct HeldLock {
+    #[cfg(feature = "debug_lock_order")]
+    level: LockLevel,
+}
+
+#[cfg(feature = "debug_lock_order")]
+pub fn acquire(level: LockLevel) -> HeldLock {
+    HELD_LOCKS.with(|held| {
+        let mut held = held.borrow_mut();
+        if let Some(highest) = held.iter().max() {
+            if level < *highest {
+                panic!(
+                    "Lock order violation: tried to lock {:?} while holding {:?}",
+                    level, *held
+                );
+            }
+        }
+        held.push(level);
+    });
+    HeldLock { level }
+}
+
+#[cfg(not(feature = "debug_lock_order"))]
+pub fn acquire(_level: LockLevel) -> HeldLock {
+    HeldLock {}
+}
+
+#[cfg(feature = "debug_lock_order")]
+impl Drop for HeldLock {
+    fn drop(&mut self) {
+        // guards do not have to be dropped in the reverse order they were taken in
+        let _ = HELD_LOCKS.try_with(|held| {
+            let mut held = held.borrow_mut();
+            if let Some(pos) = held.iter().rposition(|level| *level == self.level) {
+                held.remove(pos);
+            }
+        });
+    }
+}
+
+/// A guard of one of the locks below. Derefs to the guarded value
+pub struct OrderedGuard<G> {
+    guard: G,
+    // dropped after the guard
+    _held: HeldLock,
+}
+
+impl<G: Deref> Deref for OrderedGuard<G> {
+    type Target = G::Target;
+    fn deref(&self) -> &Self::Target {
+        &self.guard
+    }
+}
+
+impl<G: DerefMut> DerefMut for OrderedGuard<G> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.guard
+    }
+}
+
+/// Attach the held level to the guard of a plain lock, for locks that need more than lock() (like the pid table, which
+/// is waited on with a condvar)
+pub fn wrap_guard<G>(res: LockResult<G>, held: HeldLock) -> LockResult<OrderedGuard<G>> {
+    match res {
+        Ok(guard) => Ok(OrderedGuard { guard, _held: held }),
+        Err(poisoned) => Err(PoisonError::new(OrderedGuard {
+            guard: poisoned.into_inner(),
+            _held: held,
+        })),
+    }
+}
+
+pub struct OrderedMutex<T> {
+    level: LockLevel,
+    lock: Mutex<T>,
+}
+
+impl<T> OrderedMutex<T> {
+    pub fn new(level: LockLevel, value: T) -> Self {
+        OrderedMutex {
+            level,
+            lock: Mutex::new(value),
+        }
+    }
+
+    pub fn lock(&self) -> LockResult<OrderedGuard<MutexGuard<'_, T>>> {
+        let held = acquire(self.level);
+        wrap_guard(self.lock.lock(), held)
+    }
+}
+
+pub struct OrderedRwLock<T> {
+    level: LockLevel,
+    lock: RwLock<T>,
+}
+
+impl<T> OrderedRwLock<T> {
+    pub fn new(level: LockLevel, value: T) -> Self {
+        OrderedRwLock {
+            level,
+            lock: RwLock::new(value),
+        }
+    }
+
+    pub fn read(&self) -> LockResult<OrderedGuard<RwLockReadGuard<'_, T>>> {
+        let held = acquire(self.level);
+        wrap_guard(self.lock.read(), held)
+    }
+
+    pub fn write(&self) -> LockResult<OrderedGuard<RwLockWriteGuard<'_, T>>> {
+        let held = acquire(self.level);
+        wrap_guard(self.lock.write(), held)
+    }
+}
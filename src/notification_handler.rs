@@ -6,11 +6,19 @@ use crate::platform::reset_event_fd;
 use crate::platform::EventFd;
 use crate::services::Service;
 use crate::units::*;
-use std::{collections::HashMap, os::unix::io::AsRawFd};
+use std::{
+    collections::HashMap,
+    os::unix::io::{AsRawFd, IntoRawFd},
+};
 
-fn collect_from_srvc<F>(unit_table: ArcMutUnitTable, f: F) -> HashMap<i32, UnitId>
+/// Longer notification messages are dropped, so a misbehaving service can not make rustysd buffer arbitrary amounts
+/// of data. This is still plenty for long STATUS= strings.
+pub const NOTIFICATION_BUFFER_MAX: usize = 64 * 1024;
+
+/// The fds selected by select_fd for all services that currently have one
+fn collect_from_srvc<F>(unit_table: &ArcMutUnitTable, select_fd: &F) -> HashMap<i32, UnitId>
 where
-    F: Fn(&mut HashMap<i32, UnitId>, &Service, UnitId),
+    F: Fn(&Service) -> Option<i32>,
 {
     unit_table
         .read()
@@ -19,20 +27,34 @@ where
         .fold(HashMap::new(), |mut map, (id, srvc_unit)| {
             let srvc_unit_locked = srvc_unit.lock().unwrap();
             if let UnitSpecialized::Service(srvc) = &srvc_unit_locked.specialized {
-                f(&mut map, &srvc, id.clone());
+                if let Some(fd) = select_fd(srvc) {
+                    map.insert(fd, *id);
+                }
             }
             map
         })
 }
 
-pub fn handle_all_streams(eventfd: EventFd, unit_table: ArcMutUnitTable) {
+/// The loop behind all the handlers in this module: select on the fd that select_fd returns for each service and call
+/// handle_readable with the (locked) unit whenever its fd is readable. The fd is non-blocking while handle_readable
+/// runs. The eventfd interrupts the select, so fds of services that have been started in the meantime are picked up.
+/// If an fd got closed while it was still in the unit table, forget_closed has to remove it from the unit without
+/// closing it again, the number might already belong to another file.
+fn handle_all_fds<F, H, C>(
+    eventfd: EventFd,
+    unit_table: ArcMutUnitTable,
+    stream_name: &str,
+    select_fd: F,
+    mut handle_readable: H,
+    mut forget_closed: C,
+) where
+    F: Fn(&Service) -> Option<i32>,
+    H: FnMut(i32, &mut Unit),
+    C: FnMut(i32, &mut Unit),
+{
     loop {
         // need to collect all again. There might be a newly started service
-        let fd_to_srvc_id = collect_from_srvc(unit_table.clone(), |map, srvc, id| {
-            if let Some(socket) = &srvc.notifications {
-                map.insert(socket.as_raw_fd(), id);
-            }
-        });
+        let fd_to_srvc_id = collect_from_srvc(&unit_table, &select_fd);
 
         let mut fdset = nix::sys::select::FdSet::new();
         for fd in fd_to_srvc_id.keys() {
@@ -41,220 +63,487 @@ pub fn handle_all_streams(eventfd: EventFd, unit_table: ArcMutUnitTable) {
         fdset.insert(eventfd.read_end());
 
         let result = nix::sys::select::select(None, Some(&mut fdset), None, None, None);
-        match result {
+        let ready_fds: Vec<i32> = match result {
             Ok(_) => {
                 if fdset.contains(eventfd.read_end()) {
-                    trace!("Interrupted notification select because the eventfd fired");
+                    trace!(
+                        "Interrupted {} select because the eventfd fired",
+                        stream_name
+                    );
                     reset_event_fd(eventfd);
                     trace!("Reset eventfd value");
                 }
-                let mut buf = [0u8; 512];
+                fd_to_srvc_id
+                    .keys()
+                    .filter(|fd| fdset.contains(**fd))
+                    .copied()
+                    .collect()
+            }
+            // a signal arrived, nothing happened to the fds
+            Err(nix::Error::Sys(nix::errno::Errno::EINTR)) => continue,
+            // one of the fds was closed while it was still in the unit table. It has to be removed from there,
+            // otherwise select would fail with the same fd forever
+            Err(nix::Error::Sys(nix::errno::Errno::EBADF)) => {
+                let closed_fds: Vec<i32> = fd_to_srvc_id
+                    .keys()
+                    .filter(|fd| !fd_is_open(**fd))
+                    .copied()
+                    .collect();
+                warn!(
+                    "Pruning closed fds {:?} from the {} select",
+                    closed_fds, stream_name
+                );
                 let unit_table_locked = &*unit_table.read().unwrap();
-                for (fd, id) in &fd_to_srvc_id {
-                    if fdset.contains(*fd) {
-                        if let Some(srvc_unit) = unit_table_locked.get(id) {
-                            let srvc_unit_locked = &mut *srvc_unit.lock().unwrap();
-                            if let UnitSpecialized::Service(srvc) =
-                                &mut srvc_unit_locked.specialized
-                            {
-                                if let Some(socket) = &srvc.notifications {
-                                    let old_flags =
-                                        nix::fcntl::fcntl(*fd, nix::fcntl::FcntlArg::F_GETFL)
-                                            .unwrap();
-
-                                    let old_flags =
-                                        nix::fcntl::OFlag::from_bits(old_flags).unwrap();
-                                    let mut new_flags = old_flags.clone();
-                                    new_flags.insert(nix::fcntl::OFlag::O_NONBLOCK);
-                                    nix::fcntl::fcntl(
-                                        *fd,
-                                        nix::fcntl::FcntlArg::F_SETFL(new_flags),
-                                    )
-                                    .unwrap();
-                                    let bytes = {
-                                        match socket.recv(&mut buf[..]) {
-                                            Ok(b) => b,
-                                            Err(e) => match e.kind() {
-                                                std::io::ErrorKind::WouldBlock => 0,
-                                                _ => panic!("{}", e),
-                                            },
-                                        }
-                                    };
-                                    nix::fcntl::fcntl(
-                                        *fd,
-                                        nix::fcntl::FcntlArg::F_SETFL(old_flags),
-                                    )
-                                    .unwrap();
-                                    let note_str =
-                                        String::from_utf8(buf[..bytes].to_vec()).unwrap();
-                                    srvc.notifications_buffer.push_str(&note_str);
-                                    crate::notification_handler::handle_notifications_from_buffer(
-                                        srvc,
-                                        &srvc_unit_locked.conf.name(),
-                                    );
-                                }
-                            }
-                        }
+                for fd in closed_fds {
+                    let srvc_unit = match unit_table_locked.get(&fd_to_srvc_id[&fd]) {
+                        Some(srvc_unit) => srvc_unit,
+                        None => continue,
+                    };
+                    let srvc_unit_locked = &mut *srvc_unit.lock().unwrap();
+                    // the service might have gotten a new fd in the meantime
+                    let still_selected = match &srvc_unit_locked.specialized {
+                        UnitSpecialized::Service(srvc) => select_fd(srvc) == Some(fd),
+                        _ => false,
+                    };
+                    if still_selected {
+                        forget_closed(fd, srvc_unit_locked);
                     }
                 }
+                continue;
             }
             Err(e) => {
                 warn!("Error while selecting: {}", e);
+                continue;
             }
-        }
-    }
-}
+        };
 
-pub fn handle_all_std_out(eventfd: EventFd, run_info: ArcRuntimeInfo) {
-    loop {
-        // need to collect all again. There might be a newly started service
-        let fd_to_srvc_id = collect_from_srvc(run_info.unit_table.clone(), |map, srvc, id| {
-            if let Some(fd) = &srvc.stdout_dup {
-                map.insert(fd.0, id);
+        let unit_table_locked = &*unit_table.read().unwrap();
+        for fd in ready_fds {
+            let srvc_unit = match unit_table_locked.get(&fd_to_srvc_id[&fd]) {
+                Some(srvc_unit) => srvc_unit,
+                None => continue,
+            };
+            let srvc_unit_locked = &mut *srvc_unit.lock().unwrap();
+
+            // closed fds have no flags to change
+            let old_flags = nix::fcntl::fcntl(fd, nix::fcntl::FcntlArg::F_GETFL)
+                .ok()
+                .and_then(nix::fcntl::OFlag::from_bits);
+            if let Some(old_flags) = old_flags {
+                let mut new_flags = old_flags;
+                new_flags.insert(nix::fcntl::OFlag::O_NONBLOCK);
+                nix::fcntl::fcntl(fd, nix::fcntl::FcntlArg::F_SETFL(new_flags)).unwrap();
             }
-        });
 
-        let mut fdset = nix::sys::select::FdSet::new();
-        for fd in fd_to_srvc_id.keys() {
-            fdset.insert(*fd);
+            handle_readable(fd, srvc_unit_locked);
+
+            // the handler might have closed the fd already if it was not usable anymore
+            if let Some(old_flags) = old_flags {
+                let _ = nix::fcntl::fcntl(fd, nix::fcntl::FcntlArg::F_SETFL(old_flags));
+            }
         }
-        fdset.insert(eventfd.read_end());
+    }
+}
 
-        let result = nix::sys::select::select(None, Some(&mut fdset), None, None, None);
-        match result {
-            Ok(_) => {
-                if fdset.contains(eventfd.read_end()) {
-                    trace!("Interrupted stdout select because the eventfd fired");
-                    reset_event_fd(eventfd);
-                    trace!("Reset eventfd value");
-                }
-                let mut buf = [0u8; 512];
-                let unit_table_locked = &*run_info.unit_table.read().unwrap();
-                for (fd, id) in &fd_to_srvc_id {
-                    if fdset.contains(*fd) {
-                        if let Some(srvc_unit) = unit_table_locked.get(id) {
-                            let mut srvc_unit_locked = srvc_unit.lock().unwrap();
-                            let name = srvc_unit_locked.conf.name();
-                            let status_table_locked = run_info.status_table.read().unwrap();
-                            let status = status_table_locked
-                                .get(&srvc_unit_locked.id)
-                                .unwrap()
-                                .lock()
-                                .unwrap();
-
-                            let old_flags =
-                                nix::fcntl::fcntl(*fd, nix::fcntl::FcntlArg::F_GETFL).unwrap();
-                            let old_flags = nix::fcntl::OFlag::from_bits(old_flags).unwrap();
-                            let mut new_flags = old_flags.clone();
-                            new_flags.insert(nix::fcntl::OFlag::O_NONBLOCK);
-                            nix::fcntl::fcntl(*fd, nix::fcntl::FcntlArg::F_SETFL(new_flags))
-                                .unwrap();
-
-                            ////
-                            let bytes = match nix::unistd::read(*fd, &mut buf[..]) {
-                                Ok(b) => b,
-                                Err(nix::Error::Sys(nix::errno::EWOULDBLOCK)) => 0,
-                                Err(e) => panic!("{}", e),
-                            };
-                            ////
-
-                            nix::fcntl::fcntl(*fd, nix::fcntl::FcntlArg::F_SETFL(old_flags))
-                                .unwrap();
-
-                            if let UnitSpecialized::Service(srvc) =
-                                &mut srvc_unit_locked.specialized
-                            {
-                                srvc.stdout_buffer.extend(&buf[..bytes]);
-                                srvc.log_stdout_lines(&name, &status).unwrap();
-                            }
+fn fd_is_open(fd: i32) -> bool {
+    nix::fcntl::fcntl(fd, nix::fcntl::FcntlArg::F_GETFD).is_ok()
+}
+
+pub fn handle_all_streams(eventfd: EventFd, run_info: ArcRuntimeInfo) {
+    // grows if a datagram does not fit, so keep it around between iterations
+    let mut buf = vec![0u8; 512];
+    handle_all_fds(
+        eventfd,
+        run_info.unit_table.clone(),
+        "notification",
+        |srvc| srvc.notifications.as_ref().map(|socket| socket.as_raw_fd()),
+        |_fd, srvc_unit| {
+            let name = srvc_unit.conf.name();
+            let srvc = match &mut srvc_unit.specialized {
+                UnitSpecialized::Service(srvc) => srvc,
+                _ => return,
+            };
+            let socket = match &srvc.notifications {
+                Some(socket) => socket,
+                None => return,
+            };
+            let (bytes, sender) = match recv_notification_datagram(socket, &mut buf) {
+                Ok(received) => received,
+                Err(e) => match e.kind() {
+                    std::io::ErrorKind::WouldBlock => (0, None),
+                    _ => {
+                        // dont select on this socket again. It will be recreated when the service starts again
+                        warn!(
+                            "Error while reading notifications of service {}: {}",
+                            name, e
+                        );
+                        let socket = srvc.notifications.take();
+                        // dropping a socket that is closed already would close the fd again
+                        if e.raw_os_error() == Some(libc::EBADF) {
+                            let _ = socket.map(IntoRawFd::into_raw_fd);
                         }
+                        return;
                     }
-                }
+                },
+            };
+            if bytes > 0 && !notification_allowed(srvc, sender) {
+                warn!(
+                    "Ignoring notification for service {} from process {:?} (NotifyAccess={:?})",
+                    name, sender, srvc.service_config.notifyaccess
+                );
+                return;
             }
-            Err(e) => {
-                warn!("Error while selecting: {}", e);
+            // invalid utf8 from a buggy service must not crash rustysd
+            let note_str = String::from_utf8_lossy(&buf[..bytes]);
+            srvc.notifications_buffer.push_str(&note_str);
+            handle_notifications_from_buffer(srvc, &name, &run_info.pid_table);
+        },
+        |_fd, srvc_unit| {
+            if let UnitSpecialized::Service(srvc) = &mut srvc_unit.specialized {
+                // dropping the socket would close the fd again
+                let _ = srvc.notifications.take().map(IntoRawFd::into_raw_fd);
             }
+        },
+    );
+}
+
+/// Whether NotifyAccess= allows the sender to send notifications for this service.
+/// main: only the main process. exec: the main process and all processes in its process group or its cgroup.
+/// all: everyone.
+/// If the sender is unknown (no SCM_CREDENTIALS on this platform) the notification is allowed unless NotifyAccess=none.
+pub fn notification_allowed(srvc: &Service, sender: Option<nix::unistd::Pid>) -> bool {
+    let sender = match (srvc.service_config.notifyaccess, sender) {
+        (NotifyKind::None, _) => return false,
+        (NotifyKind::All, _) | (_, None) => return true,
+        (_, Some(sender)) => sender,
+    };
+    if srvc.service_config.notifyaccess == NotifyKind::Exec {
+        srvc.owns_process(sender)
+    } else {
+        srvc.pid == Some(sender)
+    }
+}
+
+/// Set SO_PASSCRED so the sender of each notification can be checked against NotifyAccess=
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub fn enable_sender_credentials(socket: &std::os::unix::net::UnixDatagram) -> Result<(), String> {
+    nix::sys::socket::setsockopt(
+        socket.as_raw_fd(),
+        nix::sys::socket::sockopt::PassCred,
+        &true,
+    )
+    .map_err(|e| format!("Could not set SO_PASSCRED on notification socket: {}", e))
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+pub fn enable_sender_credentials(_socket: &std::os::unix::net::UnixDatagram) -> Result<(), String> {
+    Ok(())
+}
+
+/// recv() that also returns the pid of the sender if the socket has SO_PASSCRED set
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn recv_with_sender(
+    socket: &std::os::unix::net::UnixDatagram,
+    buf: &mut [u8],
+) -> std::io::Result<(usize, Option<nix::unistd::Pid>)> {
+    let mut iov = libc::iovec {
+        iov_base: buf.as_mut_ptr() as *mut libc::c_void,
+        iov_len: buf.len(),
+    };
+    let cmsg_space =
+        unsafe { libc::CMSG_SPACE(std::mem::size_of::<libc::ucred>() as libc::c_uint) } as usize;
+    let mut cmsg_buf = vec![0u8; cmsg_space];
+    let mut msg: libc::msghdr = unsafe { std::mem::zeroed() };
+    msg.msg_iov = &mut iov;
+    msg.msg_iovlen = 1;
+    msg.msg_control = cmsg_buf.as_mut_ptr() as *mut libc::c_void;
+    msg.msg_controllen = cmsg_space as _;
+
+    let bytes = unsafe { libc::recvmsg(socket.as_raw_fd(), &mut msg, 0) };
+    if bytes < 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    let mut sender = None;
+    let mut cmsg = unsafe { libc::CMSG_FIRSTHDR(&msg) };
+    while !cmsg.is_null() {
+        let header = unsafe { &*cmsg };
+        if header.cmsg_level == libc::SOL_SOCKET && header.cmsg_type == libc::SCM_CREDENTIALS {
+            let cred =
+                unsafe { std::ptr::read_unaligned(libc::CMSG_DATA(cmsg) as *const libc::ucred) };
+            sender = Some(nix::unistd::Pid::from_raw(cred.pid));
         }
+        cmsg = unsafe { libc::CMSG_NXTHDR(&msg, cmsg) };
     }
+    Ok((bytes as usize, sender))
 }
 
-pub fn handle_all_std_err(eventfd: EventFd, run_info: ArcRuntimeInfo) {
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+fn recv_with_sender(
+    socket: &std::os::unix::net::UnixDatagram,
+    buf: &mut [u8],
+) -> std::io::Result<(usize, Option<nix::unistd::Pid>)> {
+    socket.recv(buf).map(|bytes| (bytes, None))
+}
+
+/// Receive one datagram from a notification socket. The datagram is peeked first and the buffer is
+/// grown until the whole datagram fits, so long messages (e.g. long STATUS= strings) do not get truncated.
+/// Datagrams longer than NOTIFICATION_BUFFER_MAX are dropped, for these 0 bytes are returned.
+/// Also returns the pid of the sender if it is known.
+pub fn recv_notification_datagram(
+    socket: &std::os::unix::net::UnixDatagram,
+    buf: &mut Vec<u8>,
+) -> std::io::Result<(usize, Option<nix::unistd::Pid>)> {
     loop {
-        // need to collect all again. There might be a newly started service
-        let fd_to_srvc_id = collect_from_srvc(run_info.unit_table.clone(), |map, srvc, id| {
-            if let Some(fd) = &srvc.stderr_dup {
-                map.insert(fd.0, id);
+        let peeked = unsafe {
+            libc::recv(
+                socket.as_raw_fd(),
+                buf.as_mut_ptr() as *mut libc::c_void,
+                buf.len(),
+                libc::MSG_PEEK,
+            )
+        };
+        if peeked < 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        if peeked as usize > NOTIFICATION_BUFFER_MAX {
+            // receiving into the too small buffer discards the rest of the datagram
+            let (_, sender) = recv_with_sender(socket, &mut buf[..])?;
+            warn!(
+                "Dropped notification datagram from process {:?}, it is longer than {} bytes",
+                sender, NOTIFICATION_BUFFER_MAX
+            );
+            return Ok((0, sender));
+        }
+        if (peeked as usize) < buf.len() {
+            break;
+        }
+        let new_len = buf.len() * 2;
+        buf.resize(new_len, 0);
+    }
+    recv_with_sender(socket, &mut buf[..])
+}
+
+/// Read everything that is currently available on the (non-blocking) fd. The bool is true if the fd is not usable
+/// anymore (all write ends are closed or the fd itself is invalid) and should not be selected on again.
+fn read_all_available(fd: i32) -> (Vec<u8>, bool) {
+    let mut output = Vec::new();
+    let mut buf = [0u8; 512];
+    loop {
+        match nix::unistd::read(fd, &mut buf[..]) {
+            Ok(0) => return (output, true),
+            Ok(bytes) => output.extend(&buf[..bytes]),
+            Err(nix::Error::Sys(nix::errno::EWOULDBLOCK)) => return (output, false),
+            Err(nix::Error::Sys(nix::errno::Errno::EINTR)) => continue,
+            Err(e) => {
+                warn!(
+                    "Error while reading output of a service from fd {}: {}",
+                    fd, e
+                );
+                return (output, true);
             }
-        });
+        }
+    }
+}
 
-        let mut fdset = nix::sys::select::FdSet::new();
-        for fd in fd_to_srvc_id.keys() {
-            fdset.insert(*fd);
+/// Close both ends of a pipe for service output that can not be read from anymore.
+/// prepare_service will create a new one if the service gets started again.
+fn drop_dead_pipe(pipe: &mut Option<(i32, i32)>, name: &str) {
+    if let Some((r, w)) = pipe.take() {
+        trace!("Dropping dead output pipe of service {}", name);
+        let _ = nix::unistd::close(r);
+        let _ = nix::unistd::close(w);
+    }
+}
+
+/// Forget a pipe for service output whose read end has been closed already. Only the write end is still owned here.
+fn forget_closed_pipe(pipe: &mut Option<(i32, i32)>, name: &str) {
+    if let Some((_, w)) = pipe.take() {
+        trace!("Forgetting closed output pipe of service {}", name);
+        let _ = nix::unistd::close(w);
+    }
+}
+
+/// Which output stream of a service a handler reads
+#[derive(Clone, Copy)]
+enum OutputStream {
+    Stdout,
+    Stderr,
+}
+
+impl OutputStream {
+    fn pipe(self, srvc: &mut Service) -> &mut Option<(i32, i32)> {
+        match self {
+            OutputStream::Stdout => &mut srvc.stdout_dup,
+            OutputStream::Stderr => &mut srvc.stderr_dup,
         }
-        fdset.insert(eventfd.read_end());
+    }
+}
 
-        let result = nix::sys::select::select(None, Some(&mut fdset), None, None, None);
-        match result {
-            Ok(_) => {
-                if fdset.contains(eventfd.read_end()) {
-                    trace!("Interrupted stderr select because the eventfd fired");
-                    reset_event_fd(eventfd);
-                    trace!("Reset eventfd value");
+fn handle_all_output(eventfd: EventFd, run_info: ArcRuntimeInfo, stream: OutputStream) {
+    let stream_name = match stream {
+        OutputStream::Stdout => "stdout",
+        OutputStream::Stderr => "stderr",
+    };
+    handle_all_fds(
+        eventfd,
+        run_info.unit_table.clone(),
+        stream_name,
+        |srvc| match stream {
+            OutputStream::Stdout => srvc.stdout_dup.map(|pipe| pipe.0),
+            OutputStream::Stderr => srvc.stderr_dup.map(|pipe| pipe.0),
+        },
+        |fd, srvc_unit| {
+            let name = srvc_unit.conf.name();
+            let status = run_info.current_status(&srvc_unit.id);
+            let (output, dead) = read_all_available(fd);
+
+            if let UnitSpecialized::Service(srvc) = &mut srvc_unit.specialized {
+                match stream {
+                    OutputStream::Stdout => srvc.stdout_buffer.extend(&output),
+                    OutputStream::Stderr => srvc.stderr_buffer.extend(&output),
                 }
-                let mut buf = [0u8; 512];
-                let unit_table_locked = &*run_info.unit_table.read().unwrap();
-                for (fd, id) in &fd_to_srvc_id {
-                    if fdset.contains(*fd) {
-                        if let Some(srvc_unit) = unit_table_locked.get(id) {
-                            let mut srvc_unit_locked = srvc_unit.lock().unwrap();
-                            let name = srvc_unit_locked.conf.name();
-                            let status_table_locked = run_info.status_table.read().unwrap();
-                            let status = status_table_locked
-                                .get(&srvc_unit_locked.id)
-                                .unwrap()
-                                .lock()
-                                .unwrap();
-
-                            let old_flags =
-                                nix::fcntl::fcntl(*fd, nix::fcntl::FcntlArg::F_GETFL).unwrap();
-                            let old_flags = nix::fcntl::OFlag::from_bits(old_flags).unwrap();
-                            let mut new_flags = old_flags.clone();
-                            new_flags.insert(nix::fcntl::OFlag::O_NONBLOCK);
-                            nix::fcntl::fcntl(*fd, nix::fcntl::FcntlArg::F_SETFL(new_flags))
-                                .unwrap();
-
-                            ////
-                            let bytes = match nix::unistd::read(*fd, &mut buf[..]) {
-                                Ok(b) => b,
-                                Err(nix::Error::Sys(nix::errno::EWOULDBLOCK)) => 0,
-                                Err(e) => panic!("{}", e),
-                            };
-                            ////
-                            nix::fcntl::fcntl(*fd, nix::fcntl::FcntlArg::F_SETFL(old_flags))
-                                .unwrap();
-
-                            if let UnitSpecialized::Service(srvc) =
-                                &mut srvc_unit_locked.specialized
-                            {
-                                srvc.stderr_buffer.extend(&buf[..bytes]);
-                                srvc.log_stderr_lines(&name, &status).unwrap();
-                            }
-                        }
-                    }
+                // a failed write must not stop this thread, it handles the output of all services
+                let logged = match (&status, stream) {
+                    (Some(status), OutputStream::Stdout) => srvc
+                        .log_stdout_lines(&name, status, &run_info.config)
+                        .map_err(|e| e.to_string()),
+                    (Some(status), OutputStream::Stderr) => srvc
+                        .log_stderr_lines(&name, status, &run_info.config)
+                        .map_err(|e| e.to_string()),
+                    (None, _) => Err("the unit has no status".to_owned()),
+                };
+                if let Err(e) = logged {
+                    warn!(
+                        "Error while logging {} of service {}: {}",
+                        stream_name, name, e
+                    );
+                }
+                if dead && fd_is_open(fd) {
+                    drop_dead_pipe(stream.pipe(srvc), &name);
+                } else if dead {
+                    forget_closed_pipe(stream.pipe(srvc), &name);
                 }
             }
-            Err(e) => {
-                warn!("Error while selecting: {}", e);
+        },
+        |_fd, srvc_unit| {
+            let name = srvc_unit.conf.name();
+            if let UnitSpecialized::Service(srvc) = &mut srvc_unit.specialized {
+                forget_closed_pipe(stream.pipe(srvc), &name);
             }
+        },
+    );
+}
+
+pub fn handle_all_std_out(eventfd: EventFd, run_info: ArcRuntimeInfo) {
+    handle_all_output(eventfd, run_info, OutputStream::Stdout);
+}
+
+pub fn handle_all_std_err(eventfd: EventFd, run_info: ArcRuntimeInfo) {
+    handle_all_output(eventfd, run_info, OutputStream::Stderr);
+}
+
+/// The parent of a process according to /proc/<pid>/status
+fn parent_pid(pid: nix::unistd::Pid) -> Option<nix::unistd::Pid> {
+    let status = std::fs::read_to_string(format!("/proc/{}/status", pid)).ok()?;
+    status
+        .lines()
+        .find_map(|line| line.strip_prefix("PPid:"))
+        .and_then(|ppid| ppid.trim().parse().ok())
+        .map(nix::unistd::Pid::from_raw)
+}
+
+/// Whether the process is a descendant of one of the processes (the main and the control processes of a service). A
+/// process whose parent chain reaches rustysd first is an orphan (rustysd is the subreaper of all services) that can not
+/// be attributed to the service anymore, it might belong to any other service.
+fn is_descendant_of(pid: nix::unistd::Pid, ancestors: &[nix::unistd::Pid]) -> bool {
+    let own_pid = nix::unistd::getpid();
+    let mut current = pid;
+    loop {
+        match parent_pid(current) {
+            Some(parent) if ancestors.contains(&parent) => return true,
+            Some(parent) if parent != own_pid && parent.as_raw() > 1 => current = parent,
+            _ => return false,
+        }
+    }
+}
+
+/// MAINPID=, the service forked again and the new process should be tracked as its main process
+fn set_main_pid(srvc: &mut Service, name: &str, new_pid: nix::unistd::Pid, pid_table: &PidTable) {
+    let old_pid = match srvc.pid {
+        Some(pid) => pid,
+        None => {
+            warn!(
+                "Ignoring MAINPID={} from service {}, it has no main process",
+                new_pid, name
+            );
+            return;
         }
+    };
+    if new_pid == old_pid {
+        return;
+    }
+    let pid_table_locked = &mut *pid_table.lock().unwrap();
+    let (id, srvc_type) = match pid_table_locked.get(&old_pid) {
+        Some(PidEntry::Service(id, srvc_type)) => (*id, *srvc_type),
+        _ => {
+            warn!(
+                "Ignoring MAINPID={} from service {}, its main process {} already exited",
+                new_pid, name, old_pid
+            );
+            return;
+        }
+    };
+    // the control processes (ExecStartPre= etc.) of the service may have started the new main process too
+    let mut ancestors: Vec<nix::unistd::Pid> = pid_table_locked
+        .iter()
+        .filter(|(_, entry)| matches!(entry, PidEntry::Helper(helper_id, _) if *helper_id == id))
+        .map(|(pid, _)| *pid)
+        .collect();
+    ancestors.push(old_pid);
+    // processes that already have an entry belong to another service or are helpers
+    if pid_table_locked.contains_key(&new_pid) || !is_descendant_of(new_pid, &ancestors) {
+        warn!(
+            "Ignoring MAINPID={} from service {}, it is not a descendant of its main or control processes",
+            new_pid, name
+        );
+        return;
     }
+    let entry = PidEntry::Service(id, srvc_type);
+    pid_table_locked.remove(&old_pid);
+    pid_table_locked.insert(new_pid, entry);
+    srvc.pid = Some(new_pid);
+    // the process group is stored negated, ready for kill()
+    if let Ok(pgid) = nix::unistd::getpgid(Some(new_pid)) {
+        srvc.process_group = Some(nix::unistd::Pid::from_raw(-pgid.as_raw()));
+    }
+    trace!(
+        "Service {} changed its main process from {} to {}",
+        name,
+        old_pid,
+        new_pid
+    );
 }
 
-pub fn handle_notification_message(msg: &str, srvc: &mut Service, name: &str) {
-    let split: Vec<_> = msg.split('=').collect();
-    match split[0] {
+pub fn handle_notification_message(
+    msg: &str,
+    srvc: &mut Service,
+    name: &str,
+    pid_table: &PidTable,
+) {
+    // only split at the first '=', the value may contain more of them
+    let (key, value) = match msg.find('=') {
+        Some(pos) => (&msg[..pos], &msg[pos + 1..]),
+        None => {
+            warn!(
+                "Ignoring malformed notification from service {}: {}",
+                name, msg
+            );
+            return;
+        }
+    };
+    match key {
         "STATUS" => {
-            srvc.status_msgs.push(split[1].to_owned());
+            srvc.status_msgs.push(value.to_owned());
             trace!(
                 "New status message pushed from service {}: {}",
                 name,
@@ -264,20 +553,30 @@ pub fn handle_notification_message(msg: &str, srvc: &mut Service, name: &str) {
         "READY" => {
             srvc.signaled_ready = true;
         }
+        "RELOADING" => {
+            srvc.signaled_reloading = true;
+        }
+        "MAINPID" => match value.parse::<i32>() {
+            Ok(pid) if pid > 0 => {
+                set_main_pid(srvc, name, nix::unistd::Pid::from_raw(pid), pid_table)
+            }
+            _ => warn!("Ignoring invalid MAINPID from service {}: {}", name, value),
+        },
         _ => {
-            warn!("Unknown notification name{}", split[0]);
+            warn!("Unknown notification name{}", key);
         }
     }
 }
 
-pub fn handle_notifications_from_buffer(srvc: &mut Service, name: &str) {
-    while srvc.notifications_buffer.contains('\n') {
-        let (line, rest) = srvc
-            .notifications_buffer
-            .split_at(srvc.notifications_buffer.find('\n').unwrap());
-        let line = line.to_owned();
-        srvc.notifications_buffer = rest[1..].to_owned();
-
-        handle_notification_message(&line, srvc, name);
+/// Handle all assignments in the buffer. The buffer is expected to contain whole datagrams, and one datagram can contain
+/// multiple newline separated assignments. The last one does not need to be terminated by a newline, the end of the
+/// datagram ends it.
+pub fn handle_notifications_from_buffer(srvc: &mut Service, name: &str, pid_table: &PidTable) {
+    let buffer = std::mem::take(&mut srvc.notifications_buffer);
+    for line in buffer.split('\n') {
+        if line.is_empty() {
+            continue;
+        }
+        handle_notification_message(line, srvc, name, pid_table);
     }
 }
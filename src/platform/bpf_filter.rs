@@ -0,0 +1,293 @@
+//! Classic BPF programs. The instruction helpers are shared with the seccomp filters, the rest implements
+//! IPAddressAllow= and IPAddressDeny= for the network sockets of socket units: a socket filter is attached to each
+//! socket that drops all packets from addresses that are not allowed. For stream sockets this drops the connection
+//! requests, so these connections never show up in accept().
+//! Services get the same filter for all their sockets by eBPF programs on their cgroup (cgroups::ip_filter).
+//!
+//! Like in systemd an address that matches IPAddressAllow= is always allowed, otherwise it is dropped if it matches
+//! IPAddressDeny=. All other addresses are allowed.
+
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+// These are not (all) in the libc crate. See include/uapi/linux/filter.h and bpf_common.h
+#[repr(C)]
+pub struct SockFilter {
+    pub code: u16,
+    pub jt: u8,
+    pub jf: u8,
+    pub k: u32,
+}
+
+#[repr(C)]
+pub struct SockFprog {
+    pub len: libc::c_ushort,
+    pub filter: *const SockFilter,
+}
+
+pub const BPF_LD_W_ABS: u16 = 0x20;
+pub const BPF_LD_B_ABS: u16 = 0x30;
+pub const BPF_JMP_JA: u16 = 0x05;
+pub const BPF_JMP_JEQ_K: u16 = 0x15;
+pub const BPF_JMP_JGE_K: u16 = 0x35;
+pub const BPF_JMP_JSET_K: u16 = 0x45;
+pub const BPF_ALU_AND_K: u16 = 0x54;
+pub const BPF_RET_K: u16 = 0x06;
+
+pub fn stmt(code: u16, k: u32) -> SockFilter {
+    SockFilter {
+        code,
+        jt: 0,
+        jf: 0,
+        k,
+    }
+}
+
+pub fn jump(code: u16, k: u32, jt: u8, jf: u8) -> SockFilter {
+    SockFilter { code, jt, jf, k }
+}
+
+/// Socket filters can load from the network header (instead of the transport header) with offsets relative to this
+const SKF_NET_OFF: u32 = (-0x0010_0000i32) as u32;
+const IPV4_SOURCE_OFFSET: u32 = 12;
+const IPV6_SOURCE_OFFSET: u32 = 8;
+
+/// The kernel refuses longer programs
+const BPF_MAXINSNS: usize = 4096;
+
+/// Socket filters return how many bytes of the packet to keep, so 0 drops the packet
+const PACKET_ACCEPT: u32 = 0xffff_ffff;
+const PACKET_DROP: u32 = 0;
+
+/// An address prefix like 10.0.0.0/8 or fe80::/64
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub struct IpAddressPrefix {
+    pub addr: IpAddr,
+    pub prefix_len: u8,
+}
+
+impl IpAddressPrefix {
+    fn v4(addr: [u8; 4], prefix_len: u8) -> Self {
+        IpAddressPrefix {
+            addr: IpAddr::V4(Ipv4Addr::from(addr)),
+            prefix_len,
+        }
+    }
+
+    fn v6(addr: Ipv6Addr, prefix_len: u8) -> Self {
+        IpAddressPrefix {
+            addr: IpAddr::V6(addr),
+            prefix_len,
+        }
+    }
+
+    /// Parse an address with an optional prefix length or one of the names systemd knows (any, localhost, link-local
+    /// and multicast). The names expand to prefixes of both address families.
+    pub fn parse(entry: &str) -> Result<Vec<IpAddressPrefix>, String> {
+        match entry {
+            "any" => {
+                return Ok(vec![
+                    Self::v4([0, 0, 0, 0], 0),
+                    Self::v6(Ipv6Addr::UNSPECIFIED, 0),
+                ])
+            }
+            "localhost" => {
+                return Ok(vec![
+                    Self::v4([127, 0, 0, 0], 8),
+                    Self::v6(Ipv6Addr::LOCALHOST, 128),
+                ])
+            }
+            "link-local" => {
+                return Ok(vec![
+                    Self::v4([169, 254, 0, 0], 16),
+                    Self::v6(Ipv6Addr::new(0xfe80, 0, 0, 0, 0, 0, 0, 0), 64),
+                ])
+            }
+            "multicast" => {
+                return Ok(vec![
+                    Self::v4([224, 0, 0, 0], 4),
+                    Self::v6(Ipv6Addr::new(0xff00, 0, 0, 0, 0, 0, 0, 0), 8),
+                ])
+            }
+            _ => {}
+        }
+        let (addr, prefix_len) = match entry.find('/') {
+            Some(pos) => (&entry[..pos], Some(&entry[pos + 1..])),
+            None => (entry, None),
+        };
+        let addr: IpAddr = addr
+            .parse()
+            .map_err(|e| format!("Invalid address {}: {}", entry, e))?;
+        let max_len = if addr.is_ipv4() { 32 } else { 128 };
+        let prefix_len = match prefix_len {
+            Some(len) => match len.parse::<u8>() {
+                Ok(len) if len <= max_len => len,
+                _ => return Err(format!("Invalid prefix length in {}", entry)),
+            },
+            None => max_len,
+        };
+        Ok(vec![IpAddressPrefix { addr, prefix_len }])
+    }
+
+    /// The address as 32 bit words in network order, each with the mask of the bits that belong to the prefix. Words
+    /// that are not part of the prefix at all are left out.
+    pub fn masked_words(&self) -> Vec<(u32, u32)> {
+        let words: Vec<u32> = match self.addr {
+            IpAddr::V4(addr) => vec![u32::from(addr)],
+            IpAddr::V6(addr) => addr
+                .segments()
+                .chunks(2)
+                .map(|pair| (u32::from(pair[0]) << 16) | u32::from(pair[1]))
+                .collect(),
+        };
+        let mut remaining = u32::from(self.prefix_len);
+        let mut masked = Vec::new();
+        for word in words {
+            if remaining == 0 {
+                break;
+            }
+            let mask = if remaining >= 32 {
+                0xffff_ffff
+            } else {
+                !(0xffff_ffff >> remaining)
+            };
+            masked.push((word & mask, mask));
+            remaining = remaining.saturating_sub(32);
+        }
+        masked
+    }
+}
+
+/// Parse the lines of IPAddressAllow= or IPAddressDeny=. Lines are merged, an empty line resets the list.
+pub fn parse_ip_address_lines(lines: &[String]) -> Result<Vec<IpAddressPrefix>, String> {
+    let mut prefixes = Vec::new();
+    for line in lines {
+        if line.trim().is_empty() {
+            prefixes.clear();
+            continue;
+        }
+        for entry in line.split_whitespace() {
+            prefixes.extend(IpAddressPrefix::parse(entry)?);
+        }
+    }
+    Ok(prefixes)
+}
+
+#[derive(Clone, Eq, PartialEq, Debug, Default)]
+pub struct IpAddressFilter {
+    pub allow: Vec<IpAddressPrefix>,
+    pub deny: Vec<IpAddressPrefix>,
+}
+
+impl IpAddressFilter {
+    /// Nothing is denied, so there is no need to attach a filter
+    pub fn is_empty(&self) -> bool {
+        self.deny.is_empty()
+    }
+}
+
+/// Append a compare of the source address against each prefix to prog, returning verdict if one matches. The source
+/// address is at source_offset in the network header.
+fn push_prefix_checks<'a>(
+    prog: &mut Vec<SockFilter>,
+    prefixes: impl Iterator<Item = &'a IpAddressPrefix>,
+    source_offset: u32,
+    verdict: u32,
+) {
+    for prefix in prefixes {
+        let words = prefix.masked_words();
+        for (idx, (word, mask)) in words.iter().enumerate() {
+            // if this word does not match skip the rest of the words of this prefix and the return
+            let skip = 3 * (words.len() - idx - 1) + 1;
+            prog.push(stmt(
+                BPF_LD_W_ABS,
+                SKF_NET_OFF + source_offset + 4 * idx as u32,
+            ));
+            prog.push(stmt(BPF_ALU_AND_K, *mask));
+            prog.push(jump(BPF_JMP_JEQ_K, *word, 0, skip as u8));
+        }
+        prog.push(stmt(BPF_RET_K, verdict));
+    }
+}
+
+/// The checks for one address family: first the allowed prefixes, then the denied ones. Everything else is accepted
+fn build_family_checks(filter: &IpAddressFilter, v4: bool, source_offset: u32) -> Vec<SockFilter> {
+    let mut prog = Vec::new();
+    let of_family = |prefix: &&IpAddressPrefix| prefix.addr.is_ipv4() == v4;
+    push_prefix_checks(
+        &mut prog,
+        filter.allow.iter().filter(of_family),
+        source_offset,
+        PACKET_ACCEPT,
+    );
+    push_prefix_checks(
+        &mut prog,
+        filter.deny.iter().filter(of_family),
+        source_offset,
+        PACKET_DROP,
+    );
+    prog.push(stmt(BPF_RET_K, PACKET_ACCEPT));
+    prog
+}
+
+/// Build the socket filter: look at the IP version in the network header and check the source address against the
+/// prefixes of that family. IPv4 packets can arrive at IPv6 sockets too (as v4 mapped addresses), so both are checked
+/// on all sockets.
+pub fn build_ip_filter(filter: &IpAddressFilter) -> Vec<SockFilter> {
+    let v4_checks = build_family_checks(filter, true, IPV4_SOURCE_OFFSET);
+    let v6_checks = build_family_checks(filter, false, IPV6_SOURCE_OFFSET);
+    let mut prog = vec![
+        stmt(BPF_LD_B_ABS, SKF_NET_OFF),
+        stmt(BPF_ALU_AND_K, 0xf0),
+        jump(BPF_JMP_JEQ_K, 0x40, 1, 0),
+        stmt(BPF_JMP_JA, v4_checks.len() as u32),
+    ];
+    prog.extend(v4_checks);
+    // the version is still in the accumulator. Other versions do not show up on IP sockets
+    prog.push(jump(BPF_JMP_JEQ_K, 0x60, 1, 0));
+    prog.push(stmt(BPF_RET_K, PACKET_ACCEPT));
+    prog.extend(v6_checks);
+    prog
+}
+
+/// Attach the filter for IPAddressAllow= and IPAddressDeny= to a TCP or UDP socket
+#[cfg(target_os = "linux")]
+pub fn attach_ip_filter(
+    fd: std::os::unix::io::RawFd,
+    filter: &IpAddressFilter,
+) -> Result<(), String> {
+    let prog = build_ip_filter(filter);
+    if prog.len() > BPF_MAXINSNS {
+        return Err(format!(
+            "Too many addresses in IPAddressAllow/IPAddressDeny, the filter has {} instructions",
+            prog.len()
+        ));
+    }
+    let fprog = SockFprog {
+        len: prog.len() as libc::c_ushort,
+        filter: prog.as_ptr(),
+    };
+    let res = unsafe {
+        libc::setsockopt(
+            fd,
+            libc::SOL_SOCKET,
+            libc::SO_ATTACH_FILTER,
+            &fprog as *const SockFprog as *const libc::c_void,
+            std::mem::size_of::<SockFprog>() as libc::socklen_t,
+        )
+    };
+    if res == -1 {
+        return Err(format!(
+            "Could not attach the IP address filter: {}",
+            std::io::Error::last_os_error()
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn attach_ip_filter(
+    _fd: std::os::unix::io::RawFd,
+    _filter: &IpAddressFilter,
+) -> Result<(), String> {
+    Err("IPAddressAllow and IPAddressDeny are only supported on linux".into())
+}
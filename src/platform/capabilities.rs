@@ -0,0 +1,268 @@
+//! Capabilities for AmbientCapabilities= and CapabilityBoundingSet=. Sets of capabilities are kept as bitmasks
+//! (bit n is the capability with number n), which is what the kernel interfaces use too.
+
+/// All capabilities known to rustysd, by number (the index)
+const CAPABILITY_NAMES: &[&str] = &[
+    "CAP_CHOWN",
+    "CAP_DAC_OVERRIDE",
+    "CAP_DAC_READ_SEARCH",
+    "CAP_FOWNER",
+    "CAP_FSETID",
+    "CAP_KILL",
+    "CAP_SETGID",
+    "CAP_SETUID",
+    "CAP_SETPCAP",
+    "CAP_LINUX_IMMUTABLE",
+    "CAP_NET_BIND_SERVICE",
+    "CAP_NET_BROADCAST",
+    "CAP_NET_ADMIN",
+    "CAP_NET_RAW",
+    "CAP_IPC_LOCK",
+    "CAP_IPC_OWNER",
+    "CAP_SYS_MODULE",
+    "CAP_SYS_RAWIO",
+    "CAP_SYS_CHROOT",
+    "CAP_SYS_PTRACE",
+    "CAP_SYS_PACCT",
+    "CAP_SYS_ADMIN",
+    "CAP_SYS_BOOT",
+    "CAP_SYS_NICE",
+    "CAP_SYS_RESOURCE",
+    "CAP_SYS_TIME",
+    "CAP_SYS_TTY_CONFIG",
+    "CAP_MKNOD",
+    "CAP_LEASE",
+    "CAP_AUDIT_WRITE",
+    "CAP_AUDIT_CONTROL",
+    "CAP_SETFCAP",
+    "CAP_MAC_OVERRIDE",
+    "CAP_MAC_ADMIN",
+    "CAP_SYSLOG",
+    "CAP_WAKE_ALARM",
+    "CAP_BLOCK_SUSPEND",
+    "CAP_AUDIT_READ",
+    "CAP_PERFMON",
+    "CAP_BPF",
+    "CAP_CHECKPOINT_RESTORE",
+];
+
+const ALL_CAPABILITIES: u64 = (1 << CAPABILITY_NAMES.len()) - 1;
+
+pub const CAP_SYS_MODULE: u32 = 16;
+pub const CAP_SYS_ADMIN: u32 = 21;
+
+/// Remove a capability from a bounding set. None (the bounding set is left as it is) is treated as all capabilities
+pub fn bounding_set_without(bounding_set: Option<u64>, cap: u32) -> u64 {
+    bounding_set.unwrap_or(ALL_CAPABILITIES) & !(1 << cap)
+}
+
+/// The number of a capability name like CAP_NET_BIND_SERVICE (case insensitive)
+pub fn capability_from_name(name: &str) -> Option<u32> {
+    CAPABILITY_NAMES
+        .iter()
+        .position(|known| known.eq_ignore_ascii_case(name))
+        .map(|nr| nr as u32)
+}
+
+/// Parse the lines of AmbientCapabilities= or CapabilityBoundingSet= like systemd does: lines are merged. A line with the
+/// ~ prefix removes its capabilities, if it is the first line it starts from all capabilities. An empty line resets the
+/// set to no capabilities.
+pub fn parse_capability_lines(lines: &[String]) -> Result<u64, String> {
+    let mut caps = 0;
+    let mut first = true;
+    for line in lines {
+        let line = line.trim();
+        if line.is_empty() {
+            caps = 0;
+            first = true;
+            continue;
+        }
+        let (invert, line) = match line.strip_prefix('~') {
+            Some(rest) => (true, rest),
+            None => (false, line),
+        };
+        let mut line_caps = 0u64;
+        for name in line.split_whitespace() {
+            let nr = capability_from_name(name)
+                .ok_or_else(|| format!("Unknown capability: {}", name))?;
+            line_caps |= 1 << nr;
+        }
+        if !invert {
+            caps |= line_caps;
+        } else if first {
+            caps = ALL_CAPABILITIES & !line_caps;
+        } else {
+            caps &= !line_caps;
+        }
+        first = false;
+    }
+    Ok(caps)
+}
+
+// These are not (all) in the libc crate. See include/uapi/linux/capability.h and prctl.h
+#[repr(C)]
+struct CapUserHeader {
+    version: u32,
+    pid: libc::c_int,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+struct CapUserData {
+    effective: u32,
+    permitted: u32,
+    inheritable: u32,
+}
+
+const LINUX_CAPABILITY_VERSION_3: u32 = 0x2008_0522;
+const PR_SET_KEEPCAPS: libc::c_int = 8;
+const PR_CAPBSET_DROP: libc::c_int = 24;
+const PR_CAP_AMBIENT: libc::c_int = 47;
+const PR_CAP_AMBIENT_RAISE: libc::c_ulong = 2;
+
+/// The highest capability the running kernel knows. Dropping unknown capabilities from the bounding set fails
+#[cfg(target_os = "linux")]
+fn last_cap() -> u32 {
+    std::fs::read_to_string("/proc/sys/kernel/cap_last_cap")
+        .ok()
+        .and_then(|last| last.trim().parse().ok())
+        .unwrap_or(CAPABILITY_NAMES.len() as u32 - 1)
+}
+
+#[cfg(target_os = "linux")]
+fn get_caps() -> Result<[CapUserData; 2], String> {
+    let mut header = CapUserHeader {
+        version: LINUX_CAPABILITY_VERSION_3,
+        pid: 0,
+    };
+    let mut data = [CapUserData::default(); 2];
+    let res = unsafe {
+        libc::syscall(
+            libc::SYS_capget,
+            &mut header as *mut CapUserHeader,
+            data.as_mut_ptr(),
+        )
+    };
+    if res == -1 {
+        return Err(format!(
+            "Could not get capabilities: {}",
+            std::io::Error::last_os_error()
+        ));
+    }
+    Ok(data)
+}
+
+#[cfg(target_os = "linux")]
+fn set_caps(data: &[CapUserData; 2]) -> Result<(), String> {
+    let mut header = CapUserHeader {
+        version: LINUX_CAPABILITY_VERSION_3,
+        pid: 0,
+    };
+    let res = unsafe {
+        libc::syscall(
+            libc::SYS_capset,
+            &mut header as *mut CapUserHeader,
+            data.as_ptr(),
+        )
+    };
+    if res == -1 {
+        return Err(format!(
+            "Could not set capabilities: {}",
+            std::io::Error::last_os_error()
+        ));
+    }
+    Ok(())
+}
+
+/// Keep the permitted capabilities when the uid changes from root to another user. Without this they would all be
+/// cleared and there would be nothing left to make ambient. Must be called before the uid is changed
+#[cfg(target_os = "linux")]
+pub fn keep_caps_on_setuid() -> Result<(), String> {
+    if unsafe { libc::prctl(PR_SET_KEEPCAPS, 1, 0, 0, 0) } == -1 {
+        return Err(format!(
+            "Could not set keepcaps: {}",
+            std::io::Error::last_os_error()
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn keep_caps_on_setuid() -> Result<(), String> {
+    Err("Capabilities are only supported on linux".into())
+}
+
+/// Limit the bounding set (if given) and raise the ambient capabilities, so they survive the exec even if the service
+/// does not run as root. Meant to be called in the forked child after the uid/gid have been changed.
+#[cfg(target_os = "linux")]
+pub fn apply_capabilities(bounding_set: Option<u64>, ambient: u64) -> Result<(), String> {
+    // changing the uid cleared the effective set, but it is needed for changing the bounding set
+    let mut data = get_caps()?;
+    for half in data.iter_mut() {
+        half.effective = half.permitted;
+    }
+    set_caps(&data)?;
+
+    if let Some(bounding_set) = bounding_set {
+        for cap in 0..=last_cap() {
+            if cap < 64 && bounding_set & (1 << cap) != 0 {
+                continue;
+            }
+            if unsafe { libc::prctl(PR_CAPBSET_DROP, cap as libc::c_ulong, 0, 0, 0) } == -1 {
+                return Err(format!(
+                    "Could not drop capability {} from the bounding set: {}",
+                    cap,
+                    std::io::Error::last_os_error()
+                ));
+            }
+        }
+    }
+
+    if ambient != 0 {
+        // ambient capabilities must be permitted and inheritable
+        data[0].inheritable |= ambient as u32;
+        data[1].inheritable |= (ambient >> 32) as u32;
+        set_caps(&data)?;
+        for (cap, cap_name) in CAPABILITY_NAMES.iter().enumerate() {
+            if ambient & (1 << cap) == 0 {
+                continue;
+            }
+            let res = unsafe {
+                libc::prctl(
+                    PR_CAP_AMBIENT,
+                    PR_CAP_AMBIENT_RAISE,
+                    cap as libc::c_ulong,
+                    0,
+                    0,
+                )
+            };
+            if res == -1 {
+                return Err(format!(
+                    "Could not raise ambient capability {}: {}",
+                    cap_name,
+                    std::io::Error::last_os_error()
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Whether the capability is in the effective set of this process. False if the capabilities can not be read
+#[cfg(target_os = "linux")]
+pub fn has_effective(cap: u32) -> bool {
+    match get_caps() {
+        Ok(data) => data[(cap / 32) as usize].effective & (1 << (cap % 32)) != 0,
+        Err(_) => false,
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn has_effective(_cap: u32) -> bool {
+    false
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn apply_capabilities(_bounding_set: Option<u64>, _ambient: u64) -> Result<(), String> {
+    Err("Capabilities are only supported on linux".into())
+}
@@ -0,0 +1,287 @@
+//! DevicePolicy= and DeviceAllow=, the devices the processes of a service may open (or create with mknod).
+//!
+//! With cgroup v1 the rules are written to a cgroup of the devices controller, at the same place in the devices hierarchy
+//! as the freezer cgroup of the service. cgroup v2 has no devices controller anymore. There an eBPF program of the type
+//! BPF_PROG_TYPE_CGROUP_DEVICE is attached to the cgroup of the service, the kernel runs it for each access and denies
+//! it unless one of the rules matches.
+
+use super::ebpf::*;
+use super::CgroupError;
+
+/// These are BPF_DEVCG_ACC_* and the access bits of cgroup v1
+pub const ACCESS_MKNOD: u32 = 1;
+pub const ACCESS_READ: u32 = 2;
+pub const ACCESS_WRITE: u32 = 4;
+const ACCESS_ALL: u32 = ACCESS_MKNOD | ACCESS_READ | ACCESS_WRITE;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum DeviceType {
+    Char,
+    Block,
+}
+
+/// One allowed device (or a range of devices if major or minor are None)
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct DeviceRule {
+    pub typ: DeviceType,
+    pub major: Option<u32>,
+    pub minor: Option<u32>,
+    pub access: u32,
+}
+
+/// Parse the access of a DeviceAllow= entry: any combination of r, w and m. Empty means all of them
+pub fn parse_access(access: &str) -> Result<u32, String> {
+    if access.is_empty() {
+        return Ok(ACCESS_ALL);
+    }
+    let mut bits = 0;
+    for c in access.chars() {
+        bits |= match c {
+            'r' => ACCESS_READ,
+            'w' => ACCESS_WRITE,
+            'm' => ACCESS_MKNOD,
+            _ => return Err(format!("Invalid device access: {}", access)),
+        };
+    }
+    Ok(bits)
+}
+
+/// The majors of all drivers with this name in /proc/devices
+fn driver_majors(typ: DeviceType, name: &str) -> Result<Vec<u32>, String> {
+    let devices = std::fs::read_to_string("/proc/devices")
+        .map_err(|e| format!("Could not read /proc/devices: {}", e))?;
+    let section = match typ {
+        DeviceType::Char => "Character devices:",
+        DeviceType::Block => "Block devices:",
+    };
+    let majors = devices
+        .lines()
+        .skip_while(|line| *line != section)
+        .skip(1)
+        .take_while(|line| !line.is_empty())
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let major = fields.next()?.parse::<u32>().ok()?;
+            if fields.next()? == name {
+                Some(major)
+            } else {
+                None
+            }
+        })
+        .collect();
+    Ok(majors)
+}
+
+impl DeviceRule {
+    /// Resolve a DeviceAllow= entry. It is either the path of a device node or char-NAME / block-NAME, which allows all
+    /// devices of the drivers with that name in /proc/devices
+    pub fn resolve(device: &str, access: u32) -> Result<Vec<DeviceRule>, String> {
+        let class = match device.strip_prefix("char-") {
+            Some(name) => Some((DeviceType::Char, name)),
+            None => device
+                .strip_prefix("block-")
+                .map(|name| (DeviceType::Block, name)),
+        };
+        if let Some((typ, name)) = class {
+            return Ok(driver_majors(typ, name)?
+                .into_iter()
+                .map(|major| DeviceRule {
+                    typ,
+                    major: Some(major),
+                    minor: None,
+                    access,
+                })
+                .collect());
+        }
+
+        let stat = nix::sys::stat::stat(device)
+            .map_err(|e| format!("Could not stat device {}: {}", device, e))?;
+        let typ = match stat.st_mode & libc::S_IFMT {
+            libc::S_IFCHR => DeviceType::Char,
+            libc::S_IFBLK => DeviceType::Block,
+            _ => return Err(format!("{} is not a device node", device)),
+        };
+        Ok(vec![DeviceRule {
+            typ,
+            major: Some(nix::sys::stat::major(stat.st_rdev) as u32),
+            minor: Some(nix::sys::stat::minor(stat.st_rdev) as u32),
+            access,
+        }])
+    }
+
+    /// The devices DevicePolicy=closed always allows: /dev/null, /dev/zero, /dev/full, /dev/random, /dev/urandom,
+    /// /dev/tty, /dev/ptmx and the pseudo terminals
+    pub fn pseudo_devices() -> Result<Vec<DeviceRule>, String> {
+        let char_device = |major, minor| DeviceRule {
+            typ: DeviceType::Char,
+            major: Some(major),
+            minor: Some(minor),
+            access: ACCESS_READ | ACCESS_WRITE,
+        };
+        let mut rules = vec![
+            char_device(1, 3),
+            char_device(1, 5),
+            char_device(1, 7),
+            char_device(1, 8),
+            char_device(1, 9),
+            char_device(5, 0),
+            char_device(5, 2),
+        ];
+        rules.extend(Self::resolve("char-pts", ACCESS_READ | ACCESS_WRITE)?);
+        Ok(rules)
+    }
+
+    /// The line for devices.allow of cgroup v1
+    pub fn v1_line(&self) -> String {
+        let number = |n: Option<u32>| n.map(|n| n.to_string()).unwrap_or_else(|| "*".to_owned());
+        let mut access = String::new();
+        if self.access & ACCESS_READ != 0 {
+            access.push('r');
+        }
+        if self.access & ACCESS_WRITE != 0 {
+            access.push('w');
+        }
+        if self.access & ACCESS_MKNOD != 0 {
+            access.push('m');
+        }
+        format!(
+            "{} {}:{} {}",
+            match self.typ {
+                DeviceType::Char => 'c',
+                DeviceType::Block => 'b',
+            },
+            number(self.major),
+            number(self.minor),
+            access
+        )
+    }
+}
+
+/// The cgroup of the devices controller that belongs to the freezer cgroup of a service. None if the service does not
+/// use the freezer of cgroup v1
+pub fn v1_devices_cgroup(cgroup_path: &std::path::Path) -> Option<std::path::PathBuf> {
+    let mut replaced = false;
+    let devices_cgroup = cgroup_path
+        .components()
+        .map(|component| {
+            if !replaced && component.as_os_str() == "freezer" {
+                replaced = true;
+                std::ffi::OsStr::new("devices")
+            } else {
+                component.as_os_str()
+            }
+        })
+        .collect();
+    if replaced {
+        Some(devices_cgroup)
+    } else {
+        None
+    }
+}
+
+/// Deny everything and then allow the devices of the rules
+pub fn apply_v1_rules(
+    devices_cgroup: &std::path::Path,
+    rules: &[DeviceRule],
+) -> Result<(), CgroupError> {
+    let write = |file: &str, content: &str| {
+        let path = devices_cgroup.join(file);
+        std::fs::write(&path, content).map_err(|e| CgroupError::IOErr(e, format!("{:?}", path)))
+    };
+    std::fs::create_dir_all(devices_cgroup)
+        .map_err(|e| CgroupError::IOErr(e, format!("{:?}", devices_cgroup)))?;
+    write("devices.deny", "a")?;
+    for rule in rules {
+        write("devices.allow", &rule.v1_line())?;
+    }
+    Ok(())
+}
+
+/// Values of the type in bpf_cgroup_dev_ctx
+const BPF_DEVCG_DEV_BLOCK: i32 = 1;
+const BPF_DEVCG_DEV_CHAR: i32 = 2;
+
+// registers used by the program. r1 holds the context when the program starts
+const R_RESULT: u8 = 0;
+const R_CTX: u8 = 1;
+const R_ACCESS: u8 = 2;
+const R_TYPE: u8 = 3;
+const R_MAJOR: u8 = 4;
+const R_MINOR: u8 = 5;
+const R_TMP: u8 = 6;
+
+/// The checks of one rule, the program returns 1 (allow) if all of them match. Each check jumps over the rest of the
+/// rule if it does not match
+fn rule_checks(rule: &DeviceRule) -> Vec<BpfInsn> {
+    let typ = match rule.typ {
+        DeviceType::Char => BPF_DEVCG_DEV_CHAR,
+        DeviceType::Block => BPF_DEVCG_DEV_BLOCK,
+    };
+    // the jumps get their offsets once the length of the rule is known
+    let mut checks = vec![insn(BPF_JMP_JNE_K, R_TYPE, 0, 0, typ)];
+    if rule.access & ACCESS_ALL != ACCESS_ALL {
+        // only the allowed access bits may be requested
+        checks.push(insn(BPF_ALU64_MOV_X, R_TMP, R_ACCESS, 0, 0));
+        checks.push(insn(
+            BPF_ALU32_AND_K,
+            R_TMP,
+            0,
+            0,
+            (!rule.access & ACCESS_ALL) as i32,
+        ));
+        checks.push(insn(BPF_JMP_JNE_K, R_TMP, 0, 0, 0));
+    }
+    if let Some(major) = rule.major {
+        checks.push(insn(BPF_JMP_JNE_K, R_MAJOR, 0, 0, major as i32));
+    }
+    if let Some(minor) = rule.minor {
+        checks.push(insn(BPF_JMP_JNE_K, R_MINOR, 0, 0, minor as i32));
+    }
+    checks.push(insn(BPF_ALU64_MOV_K, R_RESULT, 0, 0, 1));
+    checks.push(insn(BPF_JMP_EXIT, 0, 0, 0, 0));
+
+    let len = checks.len();
+    for (idx, check) in checks.iter_mut().enumerate() {
+        if check.code == BPF_JMP_JNE_K {
+            check.off = (len - idx - 1) as i16;
+        }
+    }
+    checks
+}
+
+/// Build the program for cgroup v2. The context is struct bpf_cgroup_dev_ctx: the access type (access << 16 | type),
+/// the major and the minor of the device, all u32
+pub fn build_device_program(rules: &[DeviceRule]) -> Vec<BpfInsn> {
+    let mut prog = vec![
+        insn(BPF_LDX_MEM_W, R_ACCESS, R_CTX, 0, 0),
+        insn(BPF_ALU64_MOV_X, R_TYPE, R_ACCESS, 0, 0),
+        insn(BPF_ALU32_AND_K, R_TYPE, 0, 0, 0xffff),
+        insn(BPF_ALU32_RSH_K, R_ACCESS, 0, 0, 16),
+        insn(BPF_LDX_MEM_W, R_MAJOR, R_CTX, 4, 0),
+        insn(BPF_LDX_MEM_W, R_MINOR, R_CTX, 8, 0),
+    ];
+    for rule in rules {
+        prog.extend(rule_checks(rule));
+    }
+    prog.push(insn(BPF_ALU64_MOV_K, R_RESULT, 0, 0, 0));
+    prog.push(insn(BPF_JMP_EXIT, 0, 0, 0, 0));
+    prog
+}
+
+const BPF_PROG_TYPE_CGROUP_DEVICE: u32 = 15;
+const BPF_CGROUP_DEVICE: u32 = 6;
+
+/// Load the program for the rules and attach it to the (cgroup v2) cgroup. A program that is attached already (from an
+/// earlier start of the service) is replaced
+pub fn attach_device_program(
+    cgroup_path: &std::path::Path,
+    rules: &[DeviceRule],
+) -> Result<(), CgroupError> {
+    attach_program(
+        cgroup_path,
+        BPF_PROG_TYPE_CGROUP_DEVICE,
+        BPF_CGROUP_DEVICE,
+        &build_device_program(rules),
+        "device filter",
+    )
+}
@@ -0,0 +1,141 @@
+//! eBPF programs for cgroup v2: the instruction format and loading a program and attaching it to a cgroup. The
+//! programs themselves are built in devices (DevicePolicy=, DeviceAllow=) and ip_filter (IPAddressAllow=,
+//! IPAddressDeny=).
+
+use super::CgroupError;
+
+/// struct bpf_insn from include/uapi/linux/bpf.h. These are eBPF instructions, unlike the classic ones in bpf_filter
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct BpfInsn {
+    pub code: u8,
+    /// dst_reg in the lower, src_reg in the upper four bits
+    pub regs: u8,
+    pub off: i16,
+    pub imm: i32,
+}
+
+pub const BPF_LDX_MEM_W: u8 = 0x61;
+pub const BPF_ALU32_AND_K: u8 = 0x54;
+pub const BPF_ALU32_RSH_K: u8 = 0x74;
+pub const BPF_ALU32_MOV_K: u8 = 0xb4;
+pub const BPF_ALU64_ADD_K: u8 = 0x07;
+pub const BPF_ALU64_MOV_X: u8 = 0xbf;
+pub const BPF_ALU64_MOV_K: u8 = 0xb7;
+pub const BPF_JMP_JNE_K: u8 = 0x55;
+pub const BPF_JMP_JNE_X: u8 = 0x5d;
+pub const BPF_JMP_CALL: u8 = 0x85;
+pub const BPF_JMP_EXIT: u8 = 0x95;
+
+/// The read only frame pointer, the stack is below it
+pub const R_FRAME: u8 = 10;
+
+pub fn insn(code: u8, dst: u8, src: u8, off: i16, imm: i32) -> BpfInsn {
+    BpfInsn {
+        code,
+        regs: (src << 4) | dst,
+        off,
+        imm,
+    }
+}
+
+const BPF_PROG_LOAD: libc::c_long = 5;
+const BPF_PROG_ATTACH: libc::c_long = 8;
+
+/// The part of union bpf_attr that BPF_PROG_LOAD uses. The kernel wants the rest of the union to be zero, the padding
+/// takes care of that
+#[repr(C)]
+#[derive(Default)]
+struct ProgLoadAttr {
+    prog_type: u32,
+    insn_cnt: u32,
+    insns: u64,
+    license: u64,
+    log_level: u32,
+    log_size: u32,
+    log_buf: u64,
+    kern_version: u32,
+    prog_flags: u32,
+    padding: [u64; 8],
+}
+
+/// The part of union bpf_attr that BPF_PROG_ATTACH uses
+#[repr(C)]
+#[derive(Default)]
+struct ProgAttachAttr {
+    target_fd: u32,
+    attach_bpf_fd: u32,
+    attach_type: u32,
+    attach_flags: u32,
+    replace_bpf_fd: u32,
+}
+
+#[cfg(target_os = "linux")]
+fn bpf<T>(cmd: libc::c_long, attr: &T) -> Result<libc::c_long, std::io::Error> {
+    let res = unsafe {
+        libc::syscall(
+            libc::SYS_bpf,
+            cmd,
+            attr as *const T,
+            std::mem::size_of::<T>(),
+        )
+    };
+    if res < 0 {
+        Err(std::io::Error::last_os_error())
+    } else {
+        Ok(res)
+    }
+}
+
+/// Load the program and attach it to the (cgroup v2) cgroup. A program of the same attach type that is attached
+/// already (from an earlier start of the service) is replaced. what names the program in errors
+#[cfg(target_os = "linux")]
+pub fn attach_program(
+    cgroup_path: &std::path::Path,
+    prog_type: u32,
+    attach_type: u32,
+    prog: &[BpfInsn],
+    what: &str,
+) -> Result<(), CgroupError> {
+    use std::os::unix::io::{AsRawFd, FromRawFd};
+
+    let license = b"GPL\0";
+    let load_attr = ProgLoadAttr {
+        prog_type,
+        insn_cnt: prog.len() as u32,
+        insns: prog.as_ptr() as u64,
+        license: license.as_ptr() as u64,
+        ..Default::default()
+    };
+    let prog_fd = bpf(BPF_PROG_LOAD, &load_attr)
+        .map_err(|e| CgroupError::IOErr(e, format!("Loading the {}", what)))?;
+    // closed when dropped. The attached program stays loaded as long as it is attached
+    let prog_fd = unsafe { std::fs::File::from_raw_fd(prog_fd as i32) };
+
+    let cgroup = std::fs::File::open(cgroup_path)
+        .map_err(|e| CgroupError::IOErr(e, format!("{:?}", cgroup_path)))?;
+    let attach_attr = ProgAttachAttr {
+        target_fd: cgroup.as_raw_fd() as u32,
+        attach_bpf_fd: prog_fd.as_raw_fd() as u32,
+        attach_type,
+        ..Default::default()
+    };
+    bpf(BPF_PROG_ATTACH, &attach_attr).map_err(|e| {
+        CgroupError::IOErr(e, format!("Attaching the {} to {:?}", what, cgroup_path))
+    })?;
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn attach_program(
+    _cgroup_path: &std::path::Path,
+    _prog_type: u32,
+    _attach_type: u32,
+    _prog: &[BpfInsn],
+    what: &str,
+) -> Result<(), CgroupError> {
+    Err(CgroupError::IOErr(
+        std::io::Error::from(std::io::ErrorKind::Other),
+        format!("The {} is only supported on linux", what),
+    ))
+}
@@ -0,0 +1,173 @@
+//! IPAddressAllow= and IPAddressDeny= of services. The socket filters of socket units (bpf_filter) only work for the
+//! sockets rustysd opens, services create most of their sockets themselves. So two eBPF programs of the type
+//! BPF_PROG_TYPE_CGROUP_SKB are attached to the cgroup of the service (cgroup v2 only). The kernel runs them for the
+//! packets of all sockets of the processes in the cgroup: the ingress program checks the source address of incoming
+//! packets, the egress program the destination address of outgoing ones. Sending a dropped packet fails with EPERM.
+//!
+//! The rules are the same as for socket units: an address that matches IPAddressAllow= always passes, otherwise it is
+//! dropped if it matches IPAddressDeny=. All other addresses pass.
+
+use super::ebpf::*;
+use super::CgroupError;
+use crate::platform::bpf_filter::{IpAddressFilter, IpAddressPrefix};
+
+const BPF_PROG_TYPE_CGROUP_SKB: u32 = 8;
+const BPF_CGROUP_INET_INGRESS: u32 = 0;
+const BPF_CGROUP_INET_EGRESS: u32 = 1;
+const BPF_FUNC_SKB_LOAD_BYTES: i32 = 26;
+
+/// Offset of protocol in struct __sk_buff. It holds the ethertype in network byte order
+const SKB_PROTOCOL_OFFSET: i16 = 16;
+const ETH_P_IP: u16 = 0x0800;
+const ETH_P_IPV6: u16 = 0x86dd;
+
+/// Offsets of the addresses in the IP headers. The packet data of cgroup skb programs starts at the IP header
+const IPV4_SOURCE_OFFSET: i32 = 12;
+const IPV4_DESTINATION_OFFSET: i32 = 16;
+const IPV6_SOURCE_OFFSET: i32 = 8;
+const IPV6_DESTINATION_OFFSET: i32 = 24;
+
+/// cgroup skb programs return 1 to let the packet pass and 0 to drop it
+const PACKET_PASS: i32 = 1;
+const PACKET_DROP: i32 = 0;
+
+// registers used by the program. r1 holds the context when the program starts, r1 to r5 are clobbered by helper calls
+const R_RESULT: u8 = 0;
+const R_CTX: u8 = 1;
+const R_TMP: u8 = 1;
+const R_ARG2: u8 = 2;
+const R_ARG3: u8 = 3;
+const R_ARG4: u8 = 4;
+/// The words of the address are loaded into r2 to r5 after the address has been copied
+const R_FIRST_WORD: u8 = 2;
+const R_SKB: u8 = 6;
+const R_PROTOCOL: u8 = 7;
+
+/// Where the address is copied to on the stack, an IPv6 address needs 16 bytes
+const ADDRESS_STACK_OFFSET: i16 = -16;
+
+/// Compare the address in the registers against the prefix and return the verdict if it matches. Each compare jumps
+/// over the rest of the prefix if it does not match
+fn prefix_checks(prefix: &IpAddressPrefix, verdict: i32) -> Vec<BpfInsn> {
+    let mut checks = Vec::new();
+    for (idx, (word, mask)) in prefix.masked_words().into_iter().enumerate() {
+        // the address was loaded from memory in network byte order, so the values to compare against have to be too
+        checks.push(insn(BPF_ALU64_MOV_X, R_TMP, R_FIRST_WORD + idx as u8, 0, 0));
+        checks.push(insn(BPF_ALU32_AND_K, R_TMP, 0, 0, mask.to_be() as i32));
+        // the 32 bit move zero extends the word. The immediate of a compare would be sign extended
+        checks.push(insn(BPF_ALU32_MOV_K, R_RESULT, 0, 0, word.to_be() as i32));
+        checks.push(insn(BPF_JMP_JNE_X, R_TMP, R_RESULT, 0, 0));
+    }
+    checks.push(insn(BPF_ALU64_MOV_K, R_RESULT, 0, 0, verdict));
+    checks.push(insn(BPF_JMP_EXIT, 0, 0, 0, 0));
+
+    let len = checks.len();
+    for (idx, check) in checks.iter_mut().enumerate() {
+        if check.code == BPF_JMP_JNE_X {
+            check.off = (len - idx - 1) as i16;
+        }
+    }
+    checks
+}
+
+/// The checks for one address family: copy the address at address_offset in the IP header to the stack, then check the
+/// allowed prefixes and then the denied ones. Everything else passes
+fn family_checks(filter: &IpAddressFilter, v4: bool, address_offset: i32) -> Vec<BpfInsn> {
+    let words = if v4 { 1 } else { 4 };
+    let mut prog = vec![
+        insn(BPF_ALU64_MOV_X, R_CTX, R_SKB, 0, 0),
+        insn(BPF_ALU64_MOV_K, R_ARG2, 0, 0, address_offset),
+        insn(BPF_ALU64_MOV_X, R_ARG3, R_FRAME, 0, 0),
+        insn(
+            BPF_ALU64_ADD_K,
+            R_ARG3,
+            0,
+            0,
+            i32::from(ADDRESS_STACK_OFFSET),
+        ),
+        insn(BPF_ALU64_MOV_K, R_ARG4, 0, 0, 4 * words),
+        insn(BPF_JMP_CALL, 0, 0, 0, BPF_FUNC_SKB_LOAD_BYTES),
+    ];
+    // a packet that is too short to contain the address passes. The offset is set once the length is known
+    let short_packet_check = prog.len();
+    prog.push(insn(BPF_JMP_JNE_K, R_RESULT, 0, 0, 0));
+    for idx in 0..words {
+        prog.push(insn(
+            BPF_LDX_MEM_W,
+            R_FIRST_WORD + idx as u8,
+            R_FRAME,
+            ADDRESS_STACK_OFFSET + 4 * idx as i16,
+            0,
+        ));
+    }
+
+    let of_family = |prefix: &&IpAddressPrefix| prefix.addr.is_ipv4() == v4;
+    for prefix in filter.allow.iter().filter(of_family) {
+        prog.extend(prefix_checks(prefix, PACKET_PASS));
+    }
+    for prefix in filter.deny.iter().filter(of_family) {
+        prog.extend(prefix_checks(prefix, PACKET_DROP));
+    }
+    prog.push(insn(BPF_ALU64_MOV_K, R_RESULT, 0, 0, PACKET_PASS));
+    prog.push(insn(BPF_JMP_EXIT, 0, 0, 0, 0));
+    prog[short_packet_check].off = (prog.len() - 2 - short_packet_check - 1) as i16;
+    prog
+}
+
+/// Build the ingress (checks the source address) or egress (checks the destination address) program. The context is
+/// struct __sk_buff, its protocol field tells IPv4 and IPv6 packets apart
+pub fn build_ip_program(filter: &IpAddressFilter, egress: bool) -> Vec<BpfInsn> {
+    let (v4_offset, v6_offset) = if egress {
+        (IPV4_DESTINATION_OFFSET, IPV6_DESTINATION_OFFSET)
+    } else {
+        (IPV4_SOURCE_OFFSET, IPV6_SOURCE_OFFSET)
+    };
+    let v4_checks = family_checks(filter, true, v4_offset);
+    let v6_checks = family_checks(filter, false, v6_offset);
+    let mut prog = vec![
+        insn(BPF_ALU64_MOV_X, R_SKB, R_CTX, 0, 0),
+        insn(BPF_LDX_MEM_W, R_PROTOCOL, R_SKB, SKB_PROTOCOL_OFFSET, 0),
+        insn(
+            BPF_JMP_JNE_K,
+            R_PROTOCOL,
+            0,
+            v4_checks.len() as i16,
+            i32::from(ETH_P_IP.to_be()),
+        ),
+    ];
+    prog.extend(v4_checks);
+    prog.push(insn(
+        BPF_JMP_JNE_K,
+        R_PROTOCOL,
+        0,
+        v6_checks.len() as i16,
+        i32::from(ETH_P_IPV6.to_be()),
+    ));
+    prog.extend(v6_checks);
+    // neither IPv4 nor IPv6
+    prog.push(insn(BPF_ALU64_MOV_K, R_RESULT, 0, 0, PACKET_PASS));
+    prog.push(insn(BPF_JMP_EXIT, 0, 0, 0, 0));
+    prog
+}
+
+/// Load the ingress and the egress program for the filter and attach them to the (cgroup v2) cgroup. Programs that are
+/// attached already (from an earlier start of the service) are replaced
+pub fn attach_ip_programs(
+    cgroup_path: &std::path::Path,
+    filter: &IpAddressFilter,
+) -> Result<(), CgroupError> {
+    attach_program(
+        cgroup_path,
+        BPF_PROG_TYPE_CGROUP_SKB,
+        BPF_CGROUP_INET_INGRESS,
+        &build_ip_program(filter, false),
+        "ingress IP address filter",
+    )?;
+    attach_program(
+        cgroup_path,
+        BPF_PROG_TYPE_CGROUP_SKB,
+        BPF_CGROUP_INET_EGRESS,
+        &build_ip_program(filter, true),
+        "egress IP address filter",
+    )
+}
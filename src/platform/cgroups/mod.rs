@@ -9,6 +9,9 @@ use std::io::Read;
 
 mod cgroup1;
 mod cgroup2;
+pub mod devices;
+mod ebpf;
+pub mod ip_filter;
 
 #[derive(Debug)]
 pub enum CgroupError {
@@ -248,6 +251,69 @@ pub fn freeze_kill_thaw_cgroup(
     }
 }
 
+/// How often the OOM killer killed a process in this cgroup. Only cgroup v2 provides this (in memory.events),
+/// it is always 0 with v1 or if the memory controller is not enabled for this cgroup
+pub fn get_oom_kill_count(cgroup_path: &std::path::Path) -> Result<u64, CgroupError> {
+    let events_path = cgroup_path.join("memory.events");
+    if !events_path.exists() {
+        return Ok(0);
+    }
+    let content = fs::read_to_string(&events_path)
+        .map_err(|e| CgroupError::IOErr(e, format!("{:?}", events_path)))?;
+    let count = content
+        .lines()
+        .filter_map(|line| line.strip_prefix("oom_kill "))
+        .filter_map(|count| count.trim().parse::<u64>().ok())
+        .next()
+        .unwrap_or(0);
+    Ok(count)
+}
+
+/// Restrict the devices the processes in the cgroup can access to the rules. With cgroup v1 the processes also have to
+/// join the cgroup of the devices controller (join_devices_cgroup)
+pub fn restrict_devices(
+    cgroup_path: &std::path::PathBuf,
+    rules: &[devices::DeviceRule],
+) -> Result<(), CgroupError> {
+    if use_v2(cgroup_path) {
+        return devices::attach_device_program(cgroup_path, rules);
+    }
+    match devices::v1_devices_cgroup(cgroup_path) {
+        Some(devices_cgroup) => devices::apply_v1_rules(&devices_cgroup, rules),
+        None => Err(CgroupError::NotMounted),
+    }
+}
+
+/// Filter the IP traffic of all processes in the cgroup by IPAddressAllow= and IPAddressDeny=. Needs cgroup v2
+pub fn restrict_ip_addresses(
+    cgroup_path: &std::path::PathBuf,
+    filter: &crate::platform::bpf_filter::IpAddressFilter,
+) -> Result<(), CgroupError> {
+    if !use_v2(cgroup_path) {
+        return Err(CgroupError::IOErr(
+            std::io::Error::from(std::io::ErrorKind::Other),
+            "IPAddressAllow and IPAddressDeny of services need cgroup v2".to_owned(),
+        ));
+    }
+    ip_filter::attach_ip_programs(cgroup_path, filter)
+}
+
+/// Move this process into the cgroup restrict_devices set up. Nothing to do with cgroup v2
+pub fn join_devices_cgroup(cgroup_path: &std::path::PathBuf) -> Result<(), CgroupError> {
+    match devices::v1_devices_cgroup(cgroup_path) {
+        Some(devices_cgroup) if !use_v2(cgroup_path) => move_self_to_cgroup(&devices_cgroup),
+        _ => Ok(()),
+    }
+}
+
+/// Remove the cgroup of the devices controller restrict_devices created (with cgroup v1)
+pub fn remove_devices_cgroup(cgroup_path: &std::path::Path) -> Result<(), CgroupError> {
+    match devices::v1_devices_cgroup(cgroup_path) {
+        Some(devices_cgroup) if devices_cgroup.exists() => remove_cgroup(&devices_cgroup),
+        _ => Ok(()),
+    }
+}
+
 pub fn remove_cgroup(cgroup_path: &std::path::PathBuf) -> Result<(), CgroupError> {
     fs::remove_dir(&cgroup_path).map_err(|e| CgroupError::IOErr(e, format!("{:?}", cgroup_path)))
 }
@@ -29,8 +29,11 @@ mod unix_common;
 pub use drop_privileges::*;
 pub use eventfd::*;
 pub use subreaper::*;
+pub mod bpf_filter;
+pub mod capabilities;
 pub mod grnam;
 pub mod pwnam;
+pub mod seccomp;
 
 //#[cfg(feature = "cgroups")]
 pub mod cgroups;
@@ -0,0 +1,1144 @@
+//! Seccomp filters for SystemCallFilter= (SeccompFilter= is the older name). The filter is a plain BPF program that
+//! either allows the listed syscalls and kills the process on all others, or (with the ~ prefix) kills the process on
+//! the listed syscalls and allows all others. It is built here directly instead of going through libseccomp, so only
+//! the architectures that have a syscall table below are supported.
+
+/// Syscalls that are always allowed, otherwise the filter could not even exec the service or let it exit.
+/// systemd allows the same set implicitly
+const IMPLICITLY_ALLOWED: &[&str] = &[
+    "execve",
+    "exit",
+    "exit_group",
+    "getrlimit",
+    "rt_sigreturn",
+    "clock_gettime",
+    "gettimeofday",
+    "nanosleep",
+    "clock_nanosleep",
+];
+
+/// The name of the built-in profile that allows everything a usual service needs
+pub const SYSTEM_SERVICE_PROFILE: &str = "@system-service";
+
+/// Syscall groups that can be used instead of listing the syscalls one by one. Syscalls that do not exist on the
+/// current architecture (like open on aarch64) are skipped when the group is expanded.
+const GROUPS: &[(&str, &[&str])] = &[
+    (
+        "@network-io",
+        &[
+            "socket",
+            "socketpair",
+            "connect",
+            "bind",
+            "listen",
+            "accept",
+            "accept4",
+            "sendto",
+            "recvfrom",
+            "sendmsg",
+            "recvmsg",
+            "sendmmsg",
+            "recvmmsg",
+            "shutdown",
+            "getsockname",
+            "getpeername",
+            "setsockopt",
+            "getsockopt",
+        ],
+    ),
+    (
+        "@file-system",
+        &[
+            "open",
+            "openat",
+            "openat2",
+            "creat",
+            "close",
+            "read",
+            "write",
+            "readv",
+            "writev",
+            "pread64",
+            "pwrite64",
+            "lseek",
+            "stat",
+            "lstat",
+            "fstat",
+            "newfstatat",
+            "statx",
+            "statfs",
+            "fstatfs",
+            "access",
+            "faccessat",
+            "faccessat2",
+            "mkdir",
+            "mkdirat",
+            "rmdir",
+            "rename",
+            "renameat",
+            "renameat2",
+            "unlink",
+            "unlinkat",
+            "link",
+            "linkat",
+            "symlink",
+            "symlinkat",
+            "readlink",
+            "readlinkat",
+            "chmod",
+            "fchmod",
+            "fchmodat",
+            "getdents",
+            "getdents64",
+            "getcwd",
+            "chdir",
+            "fchdir",
+            "truncate",
+            "ftruncate",
+            "fallocate",
+            "utime",
+            "utimes",
+            "utimensat",
+            "futimesat",
+            "mmap",
+            "munmap",
+            "inotify_init",
+            "inotify_init1",
+            "inotify_add_watch",
+            "inotify_rm_watch",
+        ],
+    ),
+    (
+        "@process",
+        &[
+            "fork",
+            "vfork",
+            "clone",
+            "clone3",
+            "execve",
+            "execveat",
+            "wait4",
+            "waitid",
+            "kill",
+            "tkill",
+            "tgkill",
+            "pidfd_open",
+            "pidfd_send_signal",
+            "getpid",
+            "getppid",
+            "gettid",
+            "setsid",
+            "getsid",
+            "setpgid",
+            "getpgid",
+            "getpgrp",
+            "set_tid_address",
+            "prctl",
+            "arch_prctl",
+            "unshare",
+            "setns",
+        ],
+    ),
+    (
+        "@io-event",
+        &[
+            "epoll_create",
+            "epoll_create1",
+            "epoll_ctl",
+            "epoll_wait",
+            "epoll_pwait",
+            "epoll_pwait2",
+            "select",
+            "pselect6",
+            "poll",
+            "ppoll",
+            "eventfd",
+            "eventfd2",
+        ],
+    ),
+];
+
+/// One SystemCallFilter= line: syscall names and groups. With the ~ prefix the syscalls are forbidden instead of allowed
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FilterEntry {
+    pub deny: bool,
+    pub names: Vec<String>,
+}
+
+impl FilterEntry {
+    pub fn parse(line: &str) -> FilterEntry {
+        let line = line.trim();
+        let (deny, line) = match line.strip_prefix('~') {
+            Some(rest) => (true, rest),
+            None => (false, line),
+        };
+        FilterEntry {
+            deny,
+            names: line
+                .split_whitespace()
+                .map(|name| name.to_owned())
+                .collect(),
+        }
+    }
+}
+
+/// Socket address families by name, for RestrictAddressFamilies=. The numbers are the ones from include/linux/socket.h
+const ADDRESS_FAMILIES: &[(&str, u32)] = &[
+    ("AF_UNIX", 1),
+    ("AF_LOCAL", 1),
+    ("AF_INET", 2),
+    ("AF_AX25", 3),
+    ("AF_IPX", 4),
+    ("AF_APPLETALK", 5),
+    ("AF_NETROM", 6),
+    ("AF_BRIDGE", 7),
+    ("AF_ATMPVC", 8),
+    ("AF_X25", 9),
+    ("AF_INET6", 10),
+    ("AF_ROSE", 11),
+    ("AF_NETBEUI", 13),
+    ("AF_SECURITY", 14),
+    ("AF_KEY", 15),
+    ("AF_NETLINK", 16),
+    ("AF_PACKET", 17),
+    ("AF_ASH", 18),
+    ("AF_ECONET", 19),
+    ("AF_ATMSVC", 20),
+    ("AF_RDS", 21),
+    ("AF_SNA", 22),
+    ("AF_IRDA", 23),
+    ("AF_PPPOX", 24),
+    ("AF_WANPIPE", 25),
+    ("AF_LLC", 26),
+    ("AF_IB", 27),
+    ("AF_MPLS", 28),
+    ("AF_CAN", 29),
+    ("AF_TIPC", 30),
+    ("AF_BLUETOOTH", 31),
+    ("AF_IUCV", 32),
+    ("AF_RXRPC", 33),
+    ("AF_ISDN", 34),
+    ("AF_PHONET", 35),
+    ("AF_IEEE802154", 36),
+    ("AF_CAIF", 37),
+    ("AF_ALG", 38),
+    ("AF_NFC", 39),
+    ("AF_VSOCK", 40),
+    ("AF_KCM", 41),
+    ("AF_QIPCRTR", 42),
+    ("AF_SMC", 43),
+    ("AF_XDP", 44),
+];
+
+/// Namespace types by name, for RestrictNamespaces=. The values are the CLONE_NEW* flags
+const NAMESPACES: &[(&str, u32)] = &[
+    ("cgroup", libc::CLONE_NEWCGROUP as u32),
+    ("ipc", libc::CLONE_NEWIPC as u32),
+    ("net", libc::CLONE_NEWNET as u32),
+    ("mnt", libc::CLONE_NEWNS as u32),
+    ("pid", libc::CLONE_NEWPID as u32),
+    ("user", libc::CLONE_NEWUSER as u32),
+    ("uts", libc::CLONE_NEWUTS as u32),
+];
+
+/// All CLONE_NEW* flags of the known namespace types
+fn all_namespaces() -> u32 {
+    NAMESPACES.iter().fold(0, |acc, (_, flag)| acc | flag)
+}
+
+/// Parse the lines of RestrictNamespaces= like systemd does: yes allows no namespaces, no allows all of them. A list of
+/// namespace types allows only these, with the ~ prefix all except these. Later lines are merged into earlier ones and
+/// an empty line resets everything. Returns the CLONE_NEW* flags of the allowed namespace types, or None if nothing is
+/// restricted.
+pub fn parse_namespaces(lines: &[String]) -> Result<Option<u32>, String> {
+    let mut allowed: Option<u32> = None;
+    for line in lines {
+        let line = line.trim();
+        match line {
+            "" | "no" | "false" | "0" => {
+                allowed = None;
+                continue;
+            }
+            "yes" | "true" | "1" => {
+                allowed = Some(0);
+                continue;
+            }
+            _ => {}
+        }
+        let (invert, line) = match line.strip_prefix('~') {
+            Some(rest) => (true, rest),
+            None => (false, line),
+        };
+        let mut line_flags = 0;
+        for name in line.split_whitespace() {
+            let (_, flag) = NAMESPACES
+                .iter()
+                .find(|(known, _)| *known == name)
+                .ok_or_else(|| format!("Unknown namespace type: {}", name))?;
+            line_flags |= flag;
+        }
+        allowed = Some(match (allowed, invert) {
+            (None, false) => line_flags,
+            (None, true) => all_namespaces() & !line_flags,
+            (Some(allowed), false) => allowed | line_flags,
+            (Some(allowed), true) => allowed & !line_flags,
+        });
+    }
+    Ok(allowed.filter(|allowed| *allowed != all_namespaces()))
+}
+
+/// The address families of RestrictAddressFamilies= and whether they are allowed or denied
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AddressFamilies {
+    /// true: socket() fails for the families, all others are allowed. false: only the families are allowed
+    pub deny_list: bool,
+    pub families: Vec<u32>,
+}
+
+/// Parse the lines of RestrictAddressFamilies= like systemd does: the first line decides whether this is an allow list
+/// or a deny list (~). Later lines of the same kind add to the list, lines of the other kind remove from it. An empty
+/// line resets everything and "none" allows no family at all. Returns None if nothing is restricted.
+pub fn parse_address_families(lines: &[String]) -> Result<Option<AddressFamilies>, String> {
+    let mut restricted: Option<AddressFamilies> = None;
+    for line in lines {
+        let line = line.trim();
+        if line.is_empty() {
+            restricted = None;
+            continue;
+        }
+        if line == "none" {
+            restricted = Some(AddressFamilies {
+                deny_list: false,
+                families: Vec::new(),
+            });
+            continue;
+        }
+        let (deny, line) = match line.strip_prefix('~') {
+            Some(rest) => (true, rest),
+            None => (false, line),
+        };
+        let mut line_families = Vec::new();
+        for name in line.split_whitespace() {
+            let (_, family) = ADDRESS_FAMILIES
+                .iter()
+                .find(|(known, _)| *known == name)
+                .ok_or_else(|| format!("Unknown address family: {}", name))?;
+            line_families.push(*family);
+        }
+        let restricted = restricted.get_or_insert(AddressFamilies {
+            deny_list: deny,
+            families: Vec::new(),
+        });
+        if deny == restricted.deny_list {
+            restricted.families.extend(line_families);
+        } else {
+            restricted
+                .families
+                .retain(|family| !line_families.contains(family));
+        }
+        restricted.families.sort();
+        restricted.families.dedup();
+    }
+    Ok(restricted)
+}
+
+/// The syscall numbers a filter matches and what happens to them
+#[derive(Debug)]
+pub struct ResolvedFilter {
+    /// true: the syscalls kill the process, all others are allowed. false: only the syscalls are allowed
+    pub deny_list: bool,
+    pub syscalls: Vec<libc::c_long>,
+}
+
+#[cfg(all(
+    target_os = "linux",
+    any(target_arch = "x86_64", target_arch = "aarch64")
+))]
+mod tables {
+    /// The syscalls in @system-service that exist on all supported architectures
+    pub const SYSTEM_SERVICE: &[(&str, libc::c_long)] = &[
+        ("read", libc::SYS_read),
+        ("write", libc::SYS_write),
+        ("readv", libc::SYS_readv),
+        ("writev", libc::SYS_writev),
+        ("pread64", libc::SYS_pread64),
+        ("pwrite64", libc::SYS_pwrite64),
+        ("preadv", libc::SYS_preadv),
+        ("pwritev", libc::SYS_pwritev),
+        ("close", libc::SYS_close),
+        ("openat", libc::SYS_openat),
+        ("fstat", libc::SYS_fstat),
+        ("newfstatat", libc::SYS_newfstatat),
+        ("lseek", libc::SYS_lseek),
+        ("mmap", libc::SYS_mmap),
+        ("mprotect", libc::SYS_mprotect),
+        ("munmap", libc::SYS_munmap),
+        ("mremap", libc::SYS_mremap),
+        ("madvise", libc::SYS_madvise),
+        ("mlock", libc::SYS_mlock),
+        ("munlock", libc::SYS_munlock),
+        ("msync", libc::SYS_msync),
+        ("mincore", libc::SYS_mincore),
+        ("brk", libc::SYS_brk),
+        ("rt_sigaction", libc::SYS_rt_sigaction),
+        ("rt_sigprocmask", libc::SYS_rt_sigprocmask),
+        ("rt_sigreturn", libc::SYS_rt_sigreturn),
+        ("rt_sigsuspend", libc::SYS_rt_sigsuspend),
+        ("rt_sigtimedwait", libc::SYS_rt_sigtimedwait),
+        ("rt_sigpending", libc::SYS_rt_sigpending),
+        ("rt_sigqueueinfo", libc::SYS_rt_sigqueueinfo),
+        ("sigaltstack", libc::SYS_sigaltstack),
+        ("ioctl", libc::SYS_ioctl),
+        ("fcntl", libc::SYS_fcntl),
+        ("flock", libc::SYS_flock),
+        ("fsync", libc::SYS_fsync),
+        ("fdatasync", libc::SYS_fdatasync),
+        ("sync", libc::SYS_sync),
+        ("syncfs", libc::SYS_syncfs),
+        ("fallocate", libc::SYS_fallocate),
+        ("getdents64", libc::SYS_getdents64),
+        ("getcwd", libc::SYS_getcwd),
+        ("chdir", libc::SYS_chdir),
+        ("fchdir", libc::SYS_fchdir),
+        ("renameat", libc::SYS_renameat),
+        ("renameat2", libc::SYS_renameat2),
+        ("mkdirat", libc::SYS_mkdirat),
+        ("mknodat", libc::SYS_mknodat),
+        ("unlinkat", libc::SYS_unlinkat),
+        ("symlinkat", libc::SYS_symlinkat),
+        ("linkat", libc::SYS_linkat),
+        ("readlinkat", libc::SYS_readlinkat),
+        ("fchmod", libc::SYS_fchmod),
+        ("fchmodat", libc::SYS_fchmodat),
+        ("fchown", libc::SYS_fchown),
+        ("fchownat", libc::SYS_fchownat),
+        ("faccessat", libc::SYS_faccessat),
+        ("umask", libc::SYS_umask),
+        ("utimensat", libc::SYS_utimensat),
+        ("dup", libc::SYS_dup),
+        ("dup3", libc::SYS_dup3),
+        ("pipe2", libc::SYS_pipe2),
+        ("socket", libc::SYS_socket),
+        ("socketpair", libc::SYS_socketpair),
+        ("connect", libc::SYS_connect),
+        ("accept", libc::SYS_accept),
+        ("accept4", libc::SYS_accept4),
+        ("bind", libc::SYS_bind),
+        ("listen", libc::SYS_listen),
+        ("sendto", libc::SYS_sendto),
+        ("recvfrom", libc::SYS_recvfrom),
+        ("sendmsg", libc::SYS_sendmsg),
+        ("recvmsg", libc::SYS_recvmsg),
+        ("sendmmsg", libc::SYS_sendmmsg),
+        ("recvmmsg", libc::SYS_recvmmsg),
+        ("shutdown", libc::SYS_shutdown),
+        ("getsockname", libc::SYS_getsockname),
+        ("getpeername", libc::SYS_getpeername),
+        ("setsockopt", libc::SYS_setsockopt),
+        ("getsockopt", libc::SYS_getsockopt),
+        ("clone", libc::SYS_clone),
+        ("execve", libc::SYS_execve),
+        ("execveat", libc::SYS_execveat),
+        ("exit", libc::SYS_exit),
+        ("exit_group", libc::SYS_exit_group),
+        ("wait4", libc::SYS_wait4),
+        ("waitid", libc::SYS_waitid),
+        ("kill", libc::SYS_kill),
+        ("tkill", libc::SYS_tkill),
+        ("tgkill", libc::SYS_tgkill),
+        ("getpid", libc::SYS_getpid),
+        ("getppid", libc::SYS_getppid),
+        ("gettid", libc::SYS_gettid),
+        ("getuid", libc::SYS_getuid),
+        ("geteuid", libc::SYS_geteuid),
+        ("getgid", libc::SYS_getgid),
+        ("getegid", libc::SYS_getegid),
+        ("getgroups", libc::SYS_getgroups),
+        ("setuid", libc::SYS_setuid),
+        ("setgid", libc::SYS_setgid),
+        ("setreuid", libc::SYS_setreuid),
+        ("setregid", libc::SYS_setregid),
+        ("setgroups", libc::SYS_setgroups),
+        ("setresuid", libc::SYS_setresuid),
+        ("setresgid", libc::SYS_setresgid),
+        ("getresuid", libc::SYS_getresuid),
+        ("getresgid", libc::SYS_getresgid),
+        ("setfsuid", libc::SYS_setfsuid),
+        ("setfsgid", libc::SYS_setfsgid),
+        ("setsid", libc::SYS_setsid),
+        ("getsid", libc::SYS_getsid),
+        ("setpgid", libc::SYS_setpgid),
+        ("getpgid", libc::SYS_getpgid),
+        ("getrlimit", libc::SYS_getrlimit),
+        ("setrlimit", libc::SYS_setrlimit),
+        ("prlimit64", libc::SYS_prlimit64),
+        ("getrusage", libc::SYS_getrusage),
+        ("times", libc::SYS_times),
+        ("uname", libc::SYS_uname),
+        ("sysinfo", libc::SYS_sysinfo),
+        ("prctl", libc::SYS_prctl),
+        ("capget", libc::SYS_capget),
+        ("capset", libc::SYS_capset),
+        ("personality", libc::SYS_personality),
+        ("getpriority", libc::SYS_getpriority),
+        ("setpriority", libc::SYS_setpriority),
+        ("ioprio_get", libc::SYS_ioprio_get),
+        ("ioprio_set", libc::SYS_ioprio_set),
+        ("clock_gettime", libc::SYS_clock_gettime),
+        ("clock_getres", libc::SYS_clock_getres),
+        ("clock_nanosleep", libc::SYS_clock_nanosleep),
+        ("gettimeofday", libc::SYS_gettimeofday),
+        ("nanosleep", libc::SYS_nanosleep),
+        ("timer_create", libc::SYS_timer_create),
+        ("timer_settime", libc::SYS_timer_settime),
+        ("timer_gettime", libc::SYS_timer_gettime),
+        ("timer_getoverrun", libc::SYS_timer_getoverrun),
+        ("timer_delete", libc::SYS_timer_delete),
+        ("getitimer", libc::SYS_getitimer),
+        ("setitimer", libc::SYS_setitimer),
+        ("futex", libc::SYS_futex),
+        ("set_robust_list", libc::SYS_set_robust_list),
+        ("get_robust_list", libc::SYS_get_robust_list),
+        ("set_tid_address", libc::SYS_set_tid_address),
+        ("sched_yield", libc::SYS_sched_yield),
+        ("sched_getaffinity", libc::SYS_sched_getaffinity),
+        ("sched_setaffinity", libc::SYS_sched_setaffinity),
+        ("sched_getparam", libc::SYS_sched_getparam),
+        ("sched_getscheduler", libc::SYS_sched_getscheduler),
+        ("sched_get_priority_max", libc::SYS_sched_get_priority_max),
+        ("sched_get_priority_min", libc::SYS_sched_get_priority_min),
+        ("epoll_create1", libc::SYS_epoll_create1),
+        ("epoll_ctl", libc::SYS_epoll_ctl),
+        ("epoll_pwait", libc::SYS_epoll_pwait),
+        ("ppoll", libc::SYS_ppoll),
+        ("pselect6", libc::SYS_pselect6),
+        ("eventfd2", libc::SYS_eventfd2),
+        ("timerfd_create", libc::SYS_timerfd_create),
+        ("timerfd_settime", libc::SYS_timerfd_settime),
+        ("timerfd_gettime", libc::SYS_timerfd_gettime),
+        ("signalfd4", libc::SYS_signalfd4),
+        ("inotify_init1", libc::SYS_inotify_init1),
+        ("inotify_add_watch", libc::SYS_inotify_add_watch),
+        ("inotify_rm_watch", libc::SYS_inotify_rm_watch),
+        ("getrandom", libc::SYS_getrandom),
+        ("memfd_create", libc::SYS_memfd_create),
+        ("restart_syscall", libc::SYS_restart_syscall),
+        ("splice", libc::SYS_splice),
+        ("tee", libc::SYS_tee),
+        ("vmsplice", libc::SYS_vmsplice),
+        ("copy_file_range", libc::SYS_copy_file_range),
+        ("getxattr", libc::SYS_getxattr),
+        ("lgetxattr", libc::SYS_lgetxattr),
+        ("fgetxattr", libc::SYS_fgetxattr),
+        ("listxattr", libc::SYS_listxattr),
+        ("llistxattr", libc::SYS_llistxattr),
+        ("flistxattr", libc::SYS_flistxattr),
+        ("setxattr", libc::SYS_setxattr),
+        ("lsetxattr", libc::SYS_lsetxattr),
+        ("fsetxattr", libc::SYS_fsetxattr),
+        ("removexattr", libc::SYS_removexattr),
+        ("lremovexattr", libc::SYS_lremovexattr),
+        ("fremovexattr", libc::SYS_fremovexattr),
+        ("mq_open", libc::SYS_mq_open),
+        ("mq_unlink", libc::SYS_mq_unlink),
+        ("mq_timedsend", libc::SYS_mq_timedsend),
+        ("mq_timedreceive", libc::SYS_mq_timedreceive),
+        ("mq_notify", libc::SYS_mq_notify),
+        ("mq_getsetattr", libc::SYS_mq_getsetattr),
+        ("shmget", libc::SYS_shmget),
+        ("shmat", libc::SYS_shmat),
+        ("shmdt", libc::SYS_shmdt),
+        ("shmctl", libc::SYS_shmctl),
+        ("semget", libc::SYS_semget),
+        ("semop", libc::SYS_semop),
+        ("semtimedop", libc::SYS_semtimedop),
+        ("semctl", libc::SYS_semctl),
+        ("msgget", libc::SYS_msgget),
+        ("msgsnd", libc::SYS_msgsnd),
+        ("msgrcv", libc::SYS_msgrcv),
+        ("msgctl", libc::SYS_msgctl),
+        ("name_to_handle_at", libc::SYS_name_to_handle_at),
+        ("sched_setparam", libc::SYS_sched_setparam),
+        ("sched_setscheduler", libc::SYS_sched_setscheduler),
+    ];
+
+    /// The syscalls in @system-service that are architecture specific, or have architecture specific numbers
+    #[cfg(target_arch = "x86_64")]
+    pub const SYSTEM_SERVICE_ARCH: &[(&str, libc::c_long)] = &[
+        ("ftruncate", libc::SYS_ftruncate),
+        ("truncate", libc::SYS_truncate),
+        ("fadvise64", libc::SYS_fadvise64),
+        ("statfs", libc::SYS_statfs),
+        ("fstatfs", libc::SYS_fstatfs),
+        ("sendfile", libc::SYS_sendfile),
+        ("rseq", 334),
+        ("statx", 332),
+        ("open", libc::SYS_open),
+        ("creat", libc::SYS_creat),
+        ("stat", libc::SYS_stat),
+        ("lstat", libc::SYS_lstat),
+        ("access", libc::SYS_access),
+        ("pipe", libc::SYS_pipe),
+        ("dup2", libc::SYS_dup2),
+        ("poll", libc::SYS_poll),
+        ("select", libc::SYS_select),
+        ("epoll_create", libc::SYS_epoll_create),
+        ("epoll_wait", libc::SYS_epoll_wait),
+        ("fork", libc::SYS_fork),
+        ("vfork", libc::SYS_vfork),
+        ("getdents", libc::SYS_getdents),
+        ("readlink", libc::SYS_readlink),
+        ("rename", libc::SYS_rename),
+        ("mkdir", libc::SYS_mkdir),
+        ("rmdir", libc::SYS_rmdir),
+        ("unlink", libc::SYS_unlink),
+        ("symlink", libc::SYS_symlink),
+        ("link", libc::SYS_link),
+        ("chmod", libc::SYS_chmod),
+        ("chown", libc::SYS_chown),
+        ("lchown", libc::SYS_lchown),
+        ("mknod", libc::SYS_mknod),
+        ("time", libc::SYS_time),
+        ("alarm", libc::SYS_alarm),
+        ("pause", libc::SYS_pause),
+        ("arch_prctl", libc::SYS_arch_prctl),
+        ("getpgrp", libc::SYS_getpgrp),
+        ("eventfd", libc::SYS_eventfd),
+        ("signalfd", libc::SYS_signalfd),
+        ("inotify_init", libc::SYS_inotify_init),
+        ("utimes", libc::SYS_utimes),
+        ("utime", libc::SYS_utime),
+        ("futimesat", libc::SYS_futimesat),
+    ];
+    #[cfg(target_arch = "aarch64")]
+    pub const SYSTEM_SERVICE_ARCH: &[(&str, libc::c_long)] = &[("rseq", 293), ("statx", 291)];
+
+    /// Newer syscalls in @system-service the libc crate does not know (on all architectures) yet. Since linux 5.1
+    /// syscall numbers are the same on all architectures
+    pub const SYSTEM_SERVICE_NEW: &[(&str, libc::c_long)] = &[
+        ("membarrier", libc::SYS_membarrier),
+        ("mlock2", libc::SYS_mlock2),
+        ("preadv2", libc::SYS_preadv2),
+        ("pwritev2", libc::SYS_pwritev2),
+        ("pidfd_send_signal", 424),
+        ("pidfd_open", 434),
+        ("clone3", 435),
+        ("close_range", 436),
+        ("openat2", 437),
+        ("faccessat2", 439),
+        ("epoll_pwait2", 441),
+    ];
+
+    /// Syscalls that are known but not part of @system-service. They need to be allowed explicitly
+    pub const PRIVILEGED: &[(&str, libc::c_long)] = &[
+        ("mount", libc::SYS_mount),
+        ("umount2", libc::SYS_umount2),
+        ("pivot_root", libc::SYS_pivot_root),
+        ("chroot", libc::SYS_chroot),
+        ("reboot", libc::SYS_reboot),
+        ("swapon", libc::SYS_swapon),
+        ("swapoff", libc::SYS_swapoff),
+        ("init_module", libc::SYS_init_module),
+        ("finit_module", libc::SYS_finit_module),
+        ("delete_module", libc::SYS_delete_module),
+        ("kexec_load", libc::SYS_kexec_load),
+        ("sethostname", libc::SYS_sethostname),
+        ("setdomainname", libc::SYS_setdomainname),
+        ("settimeofday", libc::SYS_settimeofday),
+        ("clock_settime", libc::SYS_clock_settime),
+        ("adjtimex", libc::SYS_adjtimex),
+        ("clock_adjtime", libc::SYS_clock_adjtime),
+        ("ptrace", libc::SYS_ptrace),
+        ("process_vm_readv", libc::SYS_process_vm_readv),
+        ("process_vm_writev", libc::SYS_process_vm_writev),
+        ("bpf", libc::SYS_bpf),
+        ("perf_event_open", libc::SYS_perf_event_open),
+        ("unshare", libc::SYS_unshare),
+        ("setns", libc::SYS_setns),
+        ("acct", libc::SYS_acct),
+        ("quotactl", libc::SYS_quotactl),
+        ("keyctl", libc::SYS_keyctl),
+        ("add_key", libc::SYS_add_key),
+        ("request_key", libc::SYS_request_key),
+        ("syslog", libc::SYS_syslog),
+        ("vhangup", libc::SYS_vhangup),
+        ("open_by_handle_at", libc::SYS_open_by_handle_at),
+        ("lookup_dcookie", libc::SYS_lookup_dcookie),
+    ];
+
+    #[cfg(target_arch = "x86_64")]
+    pub const AUDIT_ARCH: u32 = 0xC000_003E;
+    #[cfg(target_arch = "aarch64")]
+    pub const AUDIT_ARCH: u32 = 0xC000_00B7;
+}
+
+#[cfg(all(
+    target_os = "linux",
+    any(target_arch = "x86_64", target_arch = "aarch64")
+))]
+fn lookup(name: &str) -> Option<libc::c_long> {
+    tables::SYSTEM_SERVICE
+        .iter()
+        .chain(tables::SYSTEM_SERVICE_ARCH)
+        .chain(tables::SYSTEM_SERVICE_NEW)
+        .chain(tables::PRIVILEGED)
+        .find(|(known, _)| *known == name)
+        .map(|(_, nr)| *nr)
+}
+
+/// Expand the syscall names and groups to syscall numbers. Unknown names and groups are an error.
+#[cfg(all(
+    target_os = "linux",
+    any(target_arch = "x86_64", target_arch = "aarch64")
+))]
+fn resolve_names(names: &[String]) -> Result<Vec<libc::c_long>, String> {
+    let mut nrs = Vec::new();
+    for name in names {
+        if name == SYSTEM_SERVICE_PROFILE {
+            nrs.extend(
+                tables::SYSTEM_SERVICE
+                    .iter()
+                    .chain(tables::SYSTEM_SERVICE_ARCH)
+                    .chain(tables::SYSTEM_SERVICE_NEW)
+                    .map(|(_, nr)| *nr),
+            );
+        } else if name.starts_with('@') {
+            let (_, members) = GROUPS
+                .iter()
+                .find(|(group, _)| group == name)
+                .ok_or_else(|| format!("Unknown syscall group: {}", name))?;
+            nrs.extend(members.iter().filter_map(|member| lookup(member)));
+        } else {
+            nrs.push(lookup(name).ok_or_else(|| format!("Unknown syscall: {}", name))?);
+        }
+    }
+    Ok(nrs)
+}
+
+/// Resolve the filter lines to syscall numbers like systemd does: the first line decides whether this is an allow list
+/// or a deny list (~). Later lines of the same kind add to the list, lines of the other kind remove from it.
+/// An allow list always contains the implicitly allowed syscalls.
+#[cfg(all(
+    target_os = "linux",
+    any(target_arch = "x86_64", target_arch = "aarch64")
+))]
+pub fn resolve_filter(entries: &[FilterEntry]) -> Result<ResolvedFilter, String> {
+    let deny_list = entries.first().map(|entry| entry.deny).unwrap_or(false);
+    let mut nrs = Vec::new();
+    for entry in entries {
+        let entry_nrs = resolve_names(&entry.names)?;
+        if entry.deny == deny_list {
+            nrs.extend(entry_nrs);
+        } else {
+            nrs.retain(|nr| !entry_nrs.contains(nr));
+        }
+    }
+    if !deny_list {
+        for name in IMPLICITLY_ALLOWED {
+            nrs.push(lookup(name).unwrap());
+        }
+    }
+    nrs.sort();
+    nrs.dedup();
+    Ok(ResolvedFilter {
+        deny_list,
+        syscalls: nrs,
+    })
+}
+
+#[cfg(not(all(
+    target_os = "linux",
+    any(target_arch = "x86_64", target_arch = "aarch64")
+)))]
+pub fn resolve_filter(_entries: &[FilterEntry]) -> Result<ResolvedFilter, String> {
+    Err("SystemCallFilter is not supported on this platform".into())
+}
+
+use super::bpf_filter::{
+    jump, stmt, SockFilter, SockFprog, BPF_ALU_AND_K, BPF_JMP_JEQ_K, BPF_JMP_JGE_K, BPF_JMP_JSET_K,
+    BPF_LD_W_ABS, BPF_RET_K,
+};
+
+// These are not (all) in the libc crate. See include/uapi/linux/seccomp.h and prctl.h
+const SECCOMP_RET_KILL_PROCESS: u32 = 0x8000_0000;
+const SECCOMP_RET_ALLOW: u32 = 0x7fff_0000;
+const SECCOMP_RET_ERRNO: u32 = 0x0005_0000;
+const SECCOMP_MODE_FILTER: libc::c_ulong = 2;
+const PR_SET_SECCOMP: libc::c_int = 22;
+const PR_SET_NO_NEW_PRIVS: libc::c_int = 38;
+
+// offsets into struct seccomp_data
+const SECCOMP_DATA_NR: u32 = 0;
+const SECCOMP_DATA_ARCH: u32 = 4;
+/// The lower half of the first argument (on little endian architectures)
+const SECCOMP_DATA_ARG0_LOW: u32 = 16;
+/// The lower half of the second argument (on little endian architectures)
+const SECCOMP_DATA_ARG1_LOW: u32 = 24;
+/// The lower half of the third argument (on little endian architectures)
+const SECCOMP_DATA_ARG2_LOW: u32 = 32;
+
+/// personality(0xffffffff) only queries the current personality
+const PERSONALITY_QUERY: u32 = 0xffff_ffff;
+
+/// Not in the libc crate yet, the number is the same on all architectures
+const SYS_CLONE3: u32 = 435;
+
+/// x32 syscalls on x86_64 have this bit set. They must not be allowed by accident
+const X32_SYSCALL_BIT: u32 = 0x4000_0000;
+
+/// Build the BPF program: kill the process if the architecture does not match, then either allow the listed syscalls
+/// and kill the process on every other syscall, or the other way around for a deny list.
+fn build_filter(audit_arch: u32, filter: &ResolvedFilter) -> Vec<SockFilter> {
+    let (listed, others) = if filter.deny_list {
+        (SECCOMP_RET_KILL_PROCESS, SECCOMP_RET_ALLOW)
+    } else {
+        (SECCOMP_RET_ALLOW, SECCOMP_RET_KILL_PROCESS)
+    };
+    let mut prog = vec![
+        stmt(BPF_LD_W_ABS, SECCOMP_DATA_ARCH),
+        jump(BPF_JMP_JEQ_K, audit_arch, 1, 0),
+        stmt(BPF_RET_K, SECCOMP_RET_KILL_PROCESS),
+        stmt(BPF_LD_W_ABS, SECCOMP_DATA_NR),
+        jump(BPF_JMP_JGE_K, X32_SYSCALL_BIT, 0, 1),
+        stmt(BPF_RET_K, SECCOMP_RET_KILL_PROCESS),
+    ];
+    // one compare + return per syscall keeps every jump short, the jump offsets are only 8 bits wide
+    for nr in &filter.syscalls {
+        prog.push(jump(BPF_JMP_JEQ_K, *nr as u32, 0, 1));
+        prog.push(stmt(BPF_RET_K, listed));
+    }
+    prog.push(stmt(BPF_RET_K, others));
+    prog
+}
+
+/// Neither this process nor anything it execs can gain privileges (by setuid binaries or file capabilities) anymore.
+/// This is needed for NoNewPrivileges= and for installing a seccomp filter without CAP_SYS_ADMIN
+pub fn set_no_new_privs() -> Result<(), String> {
+    if unsafe { libc::prctl(PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0) } == -1 {
+        return Err(format!(
+            "Could not set no_new_privs: {}",
+            std::io::Error::last_os_error()
+        ));
+    }
+    Ok(())
+}
+
+/// The start of the programs of the single settings below: kill the process if the architecture is not the native one,
+/// the syscall numbers differ there (e.g. i386 syscalls through int 0x80 on x86_64) and would get around the filter.
+/// Then load the syscall number with the x32 bit cleared, the x32 syscalls that are filtered use the native numbers.
+fn native_syscall_nr(audit_arch: u32) -> Vec<SockFilter> {
+    vec![
+        stmt(BPF_LD_W_ABS, SECCOMP_DATA_ARCH),
+        jump(BPF_JMP_JEQ_K, audit_arch, 1, 0),
+        stmt(BPF_RET_K, SECCOMP_RET_KILL_PROCESS),
+        stmt(BPF_LD_W_ABS, SECCOMP_DATA_NR),
+        stmt(BPF_ALU_AND_K, !X32_SYSCALL_BIT),
+    ]
+}
+
+/// Build the BPF program for LockPersonality=: personality() may only be called with the current personality or to
+/// query it, other calls fail with EPERM. All other syscalls are not touched.
+fn build_personality_filter(audit_arch: u32, current: u32) -> Vec<SockFilter> {
+    let mut prog = native_syscall_nr(audit_arch);
+    prog.extend(vec![
+        jump(BPF_JMP_JEQ_K, libc::SYS_personality as u32, 1, 0),
+        stmt(BPF_RET_K, SECCOMP_RET_ALLOW),
+        stmt(BPF_LD_W_ABS, SECCOMP_DATA_ARG0_LOW),
+        jump(BPF_JMP_JEQ_K, current, 2, 0),
+        jump(BPF_JMP_JEQ_K, PERSONALITY_QUERY, 1, 0),
+        stmt(BPF_RET_K, SECCOMP_RET_ERRNO | libc::EPERM as u32),
+        stmt(BPF_RET_K, SECCOMP_RET_ALLOW),
+    ]);
+    prog
+}
+
+/// Build the BPF program for MemoryDenyWriteExecute=, the same rules as in systemd: mmap() may not map memory that is
+/// writable and executable, mprotect() and pkey_mprotect() may not make memory executable and shmat() may not attach
+/// executable shared memory. These calls fail with EPERM, all other syscalls are not touched.
+fn build_memory_deny_write_execute_filter(audit_arch: u32) -> Vec<SockFilter> {
+    let write_exec = (libc::PROT_WRITE | libc::PROT_EXEC) as u32;
+    let deny = SECCOMP_RET_ERRNO | libc::EPERM as u32;
+    let mut prog = native_syscall_nr(audit_arch);
+    prog.extend(vec![
+        // mmap: deny if prot contains both PROT_WRITE and PROT_EXEC
+        jump(BPF_JMP_JEQ_K, libc::SYS_mmap as u32, 0, 5),
+        stmt(BPF_LD_W_ABS, SECCOMP_DATA_ARG2_LOW),
+        stmt(BPF_ALU_AND_K, write_exec),
+        jump(BPF_JMP_JEQ_K, write_exec, 0, 1),
+        stmt(BPF_RET_K, deny),
+        stmt(BPF_RET_K, SECCOMP_RET_ALLOW),
+    ]);
+    // these have the flag to check in the third argument too: deny if it is set
+    for (nr, flag) in &[
+        (libc::SYS_mprotect, libc::PROT_EXEC as u32),
+        (libc::SYS_pkey_mprotect, libc::PROT_EXEC as u32),
+        (libc::SYS_shmat, libc::SHM_EXEC as u32),
+    ] {
+        prog.push(jump(BPF_JMP_JEQ_K, *nr as u32, 0, 4));
+        prog.push(stmt(BPF_LD_W_ABS, SECCOMP_DATA_ARG2_LOW));
+        prog.push(jump(BPF_JMP_JSET_K, *flag, 0, 1));
+        prog.push(stmt(BPF_RET_K, deny));
+        prog.push(stmt(BPF_RET_K, SECCOMP_RET_ALLOW));
+    }
+    prog.push(stmt(BPF_RET_K, SECCOMP_RET_ALLOW));
+    prog
+}
+
+/// Build the BPF program for ProtectKernelModules=: loading and unloading kernel modules fails with EPERM. All other
+/// syscalls are not touched.
+fn build_protect_kernel_modules_filter(audit_arch: u32) -> Vec<SockFilter> {
+    let mut prog = native_syscall_nr(audit_arch);
+    for nr in &[
+        libc::SYS_init_module,
+        libc::SYS_finit_module,
+        libc::SYS_delete_module,
+    ] {
+        prog.push(jump(BPF_JMP_JEQ_K, *nr as u32, 0, 1));
+        prog.push(stmt(BPF_RET_K, SECCOMP_RET_ERRNO | libc::EPERM as u32));
+    }
+    prog.push(stmt(BPF_RET_K, SECCOMP_RET_ALLOW));
+    prog
+}
+
+/// Build the BPF program for RestrictAddressFamilies=: socket() fails with EAFNOSUPPORT for the families that are not
+/// allowed. All other syscalls are not touched. The native architectures have no socketcall(), which would hide the
+/// family from the filter, the i386 one is killed like all foreign syscalls.
+fn build_address_family_filter(audit_arch: u32, restricted: &AddressFamilies) -> Vec<SockFilter> {
+    let deny = SECCOMP_RET_ERRNO | libc::EAFNOSUPPORT as u32;
+    let (listed, others) = if restricted.deny_list {
+        (deny, SECCOMP_RET_ALLOW)
+    } else {
+        (SECCOMP_RET_ALLOW, deny)
+    };
+    let mut prog = native_syscall_nr(audit_arch);
+    prog.extend(vec![
+        jump(BPF_JMP_JEQ_K, libc::SYS_socket as u32, 1, 0),
+        stmt(BPF_RET_K, SECCOMP_RET_ALLOW),
+        stmt(BPF_LD_W_ABS, SECCOMP_DATA_ARG0_LOW),
+    ]);
+    for family in &restricted.families {
+        prog.push(jump(BPF_JMP_JEQ_K, *family, 0, 1));
+        prog.push(stmt(BPF_RET_K, listed));
+    }
+    prog.push(stmt(BPF_RET_K, others));
+    prog
+}
+
+/// Build the BPF program for RestrictNamespaces=: unshare() and clone() fail with EPERM if they would create a namespace
+/// that is not allowed, setns() fails if it would join one. The flags of clone3() are in memory the filter can not read,
+/// so it fails with ENOSYS to make the libc fall back to clone() (like systemd does). All other syscalls are not touched.
+fn build_namespace_filter(audit_arch: u32, allowed: u32) -> Vec<SockFilter> {
+    let denied = all_namespaces() & !allowed;
+    let deny = SECCOMP_RET_ERRNO | libc::EPERM as u32;
+    let mut prog = native_syscall_nr(audit_arch);
+    prog.extend(vec![
+        jump(BPF_JMP_JEQ_K, SYS_CLONE3, 0, 1),
+        stmt(BPF_RET_K, SECCOMP_RET_ERRNO | libc::ENOSYS as u32),
+    ]);
+    // the flags are the first argument of both
+    for nr in &[libc::SYS_unshare, libc::SYS_clone] {
+        prog.push(jump(BPF_JMP_JEQ_K, *nr as u32, 0, 4));
+        prog.push(stmt(BPF_LD_W_ABS, SECCOMP_DATA_ARG0_LOW));
+        prog.push(jump(BPF_JMP_JSET_K, denied, 0, 1));
+        prog.push(stmt(BPF_RET_K, deny));
+        prog.push(stmt(BPF_RET_K, SECCOMP_RET_ALLOW));
+    }
+    // setns(fd, nstype): 0 means any namespace type, which can not be checked
+    prog.push(jump(BPF_JMP_JEQ_K, libc::SYS_setns as u32, 0, 5));
+    prog.push(stmt(BPF_LD_W_ABS, SECCOMP_DATA_ARG1_LOW));
+    prog.push(jump(BPF_JMP_JEQ_K, 0, 1, 0));
+    prog.push(jump(BPF_JMP_JSET_K, denied, 0, 1));
+    prog.push(stmt(BPF_RET_K, deny));
+    prog.push(stmt(BPF_RET_K, SECCOMP_RET_ALLOW));
+    prog.push(stmt(BPF_RET_K, SECCOMP_RET_ALLOW));
+    prog
+}
+
+/// Build the BPF program for RestrictRealtime=: sched_setscheduler() fails with EPERM for SCHED_FIFO and SCHED_RR. The
+/// policy of sched_setattr() is in memory the filter can not read, so it always fails with EPERM (SCHED_DEADLINE can only
+/// be set with it). sched_setparam() is not touched, it can only raise the priority of a process that already has a
+/// realtime policy. All other syscalls are not touched.
+fn build_restrict_realtime_filter(audit_arch: u32) -> Vec<SockFilter> {
+    let deny = SECCOMP_RET_ERRNO | libc::EPERM as u32;
+    let mut prog = native_syscall_nr(audit_arch);
+    prog.extend(vec![
+        jump(BPF_JMP_JEQ_K, libc::SYS_sched_setattr as u32, 0, 1),
+        stmt(BPF_RET_K, deny),
+        jump(BPF_JMP_JEQ_K, libc::SYS_sched_setscheduler as u32, 1, 0),
+        stmt(BPF_RET_K, SECCOMP_RET_ALLOW),
+        // sched_setscheduler(pid, policy, param): the policy may have SCHED_RESET_ON_FORK or'ed in
+        stmt(BPF_LD_W_ABS, SECCOMP_DATA_ARG1_LOW),
+        stmt(BPF_ALU_AND_K, !(libc::SCHED_RESET_ON_FORK as u32)),
+        jump(BPF_JMP_JEQ_K, libc::SCHED_FIFO as u32, 2, 0),
+        jump(BPF_JMP_JEQ_K, libc::SCHED_RR as u32, 1, 0),
+        stmt(BPF_RET_K, SECCOMP_RET_ALLOW),
+        stmt(BPF_RET_K, deny),
+    ]);
+    prog
+}
+
+/// Install a BPF program as seccomp filter. Without CAP_SYS_ADMIN the kernel only allows this with no_new_privs, so it
+/// is set in that case (like systemd does). With CAP_SYS_ADMIN it is left alone, setuid binaries keep working unless
+/// NoNewPrivileges= is set.
+fn load_program(prog: &[SockFilter]) -> Result<(), String> {
+    use crate::platform::capabilities::{has_effective, CAP_SYS_ADMIN};
+
+    let fprog = SockFprog {
+        len: prog.len() as libc::c_ushort,
+        filter: prog.as_ptr(),
+    };
+    if !has_effective(CAP_SYS_ADMIN) {
+        set_no_new_privs()?;
+    }
+    unsafe {
+        if libc::prctl(
+            PR_SET_SECCOMP,
+            SECCOMP_MODE_FILTER,
+            &fprog as *const SockFprog,
+        ) == -1
+        {
+            return Err(format!(
+                "Could not install seccomp filter: {}",
+                std::io::Error::last_os_error()
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Install the filter for this process and everything it execs.
+/// Meant to be called in the forked child right before exec.
+#[cfg(all(
+    target_os = "linux",
+    any(target_arch = "x86_64", target_arch = "aarch64")
+))]
+pub fn install_filter(entries: &[FilterEntry]) -> Result<(), String> {
+    let filter = resolve_filter(entries)?;
+    load_program(&build_filter(tables::AUDIT_ARCH, &filter))
+}
+
+/// Keep this process and everything it execs from changing the personality (the execution domain), like
+/// LockPersonality= in systemd.
+#[cfg(all(
+    target_os = "linux",
+    any(target_arch = "x86_64", target_arch = "aarch64")
+))]
+pub fn lock_personality() -> Result<(), String> {
+    let current = unsafe { libc::personality(PERSONALITY_QUERY as libc::c_ulong) };
+    if current == -1 {
+        return Err(format!(
+            "Could not get the current personality: {}",
+            std::io::Error::last_os_error()
+        ));
+    }
+    load_program(&build_personality_filter(
+        tables::AUDIT_ARCH,
+        current as u32,
+    ))
+}
+
+#[cfg(not(all(
+    target_os = "linux",
+    any(target_arch = "x86_64", target_arch = "aarch64")
+)))]
+pub fn lock_personality() -> Result<(), String> {
+    Err("LockPersonality is not supported on this platform".into())
+}
+
+/// Keep this process and everything it execs from creating memory that is writable and executable at the same time
+/// (or becomes executable later), like MemoryDenyWriteExecute= in systemd.
+#[cfg(all(
+    target_os = "linux",
+    any(target_arch = "x86_64", target_arch = "aarch64")
+))]
+pub fn memory_deny_write_execute() -> Result<(), String> {
+    load_program(&build_memory_deny_write_execute_filter(tables::AUDIT_ARCH))
+}
+
+#[cfg(not(all(
+    target_os = "linux",
+    any(target_arch = "x86_64", target_arch = "aarch64")
+)))]
+pub fn memory_deny_write_execute() -> Result<(), String> {
+    Err("MemoryDenyWriteExecute is not supported on this platform".into())
+}
+
+/// Keep this process and everything it execs from loading or unloading kernel modules, like ProtectKernelModules= in
+/// systemd.
+#[cfg(all(
+    target_os = "linux",
+    any(target_arch = "x86_64", target_arch = "aarch64")
+))]
+pub fn protect_kernel_modules() -> Result<(), String> {
+    load_program(&build_protect_kernel_modules_filter(tables::AUDIT_ARCH))
+}
+
+#[cfg(not(all(
+    target_os = "linux",
+    any(target_arch = "x86_64", target_arch = "aarch64")
+)))]
+pub fn protect_kernel_modules() -> Result<(), String> {
+    Err("ProtectKernelModules is not supported on this platform".into())
+}
+
+/// Restrict the address families this process and everything it execs can create sockets for, like
+/// RestrictAddressFamilies= in systemd.
+#[cfg(all(
+    target_os = "linux",
+    any(target_arch = "x86_64", target_arch = "aarch64")
+))]
+pub fn restrict_address_families(restricted: &AddressFamilies) -> Result<(), String> {
+    load_program(&build_address_family_filter(tables::AUDIT_ARCH, restricted))
+}
+
+#[cfg(not(all(
+    target_os = "linux",
+    any(target_arch = "x86_64", target_arch = "aarch64")
+)))]
+pub fn restrict_address_families(_restricted: &AddressFamilies) -> Result<(), String> {
+    Err("RestrictAddressFamilies is not supported on this platform".into())
+}
+
+/// Keep this process and everything it execs from creating or joining namespaces of the types that are not allowed,
+/// like RestrictNamespaces= in systemd.
+#[cfg(all(
+    target_os = "linux",
+    any(target_arch = "x86_64", target_arch = "aarch64")
+))]
+pub fn restrict_namespaces(allowed: u32) -> Result<(), String> {
+    load_program(&build_namespace_filter(tables::AUDIT_ARCH, allowed))
+}
+
+#[cfg(not(all(
+    target_os = "linux",
+    any(target_arch = "x86_64", target_arch = "aarch64")
+)))]
+pub fn restrict_namespaces(_allowed: u32) -> Result<(), String> {
+    Err("RestrictNamespaces is not supported on this platform".into())
+}
+
+/// Keep this process and everything it execs from switching to a realtime scheduling policy, like RestrictRealtime= in
+/// systemd.
+#[cfg(all(
+    target_os = "linux",
+    any(target_arch = "x86_64", target_arch = "aarch64")
+))]
+pub fn restrict_realtime() -> Result<(), String> {
+    load_program(&build_restrict_realtime_filter(tables::AUDIT_ARCH))
+}
+
+#[cfg(not(all(
+    target_os = "linux",
+    any(target_arch = "x86_64", target_arch = "aarch64")
+)))]
+pub fn restrict_realtime() -> Result<(), String> {
+    Err("RestrictRealtime is not supported on this platform".into())
+}
+
+#[cfg(not(all(
+    target_os = "linux",
+    any(target_arch = "x86_64", target_arch = "aarch64")
+)))]
+pub fn install_filter(entries: &[FilterEntry]) -> Result<(), String> {
+    resolve_filter(entries).map(|_| ())
+}
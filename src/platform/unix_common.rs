@@ -49,3 +49,40 @@ pub fn make_seqpacket_socket(path: &std::path::PathBuf) -> Result<RawFd, String>
 
     Ok(fd)
 }
+
+/// Open a unix socket in the linux abstract namespace (the name without the leading '@').
+/// Stream and sequential packet sockets are put into listening mode.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub fn make_abstract_unix_socket(
+    name: &str,
+    sock_type: nix::sys::socket::SockType,
+) -> Result<RawFd, String> {
+    use nix::sys::socket::{AddressFamily, SockAddr, SockFlag, SockType, UnixAddr};
+    let unix_addr = UnixAddr::new_abstract(name.as_bytes())
+        .map_err(|e| format!("Invalid abstract socket name @{}: {}", name, e))?;
+    let fd = nix::sys::socket::socket(AddressFamily::Unix, sock_type, SockFlag::empty(), None)
+        .map_err(|e| format!("Could not open abstract socket @{}: {}", name, e))?;
+    let setup = nix::sys::socket::bind(fd, &SockAddr::Unix(unix_addr)).and_then(|_| {
+        if sock_type == SockType::Datagram {
+            Ok(())
+        } else {
+            nix::sys::socket::listen(fd, 128)
+        }
+    });
+    if let Err(e) = setup {
+        let _ = nix::unistd::close(fd);
+        return Err(format!("Could not bind abstract socket @{}: {}", name, e));
+    }
+    Ok(fd)
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+pub fn make_abstract_unix_socket(
+    name: &str,
+    _sock_type: nix::sys::socket::SockType,
+) -> Result<RawFd, String> {
+    Err(format!(
+        "Abstract unix sockets (@{}) are only supported on linux",
+        name
+    ))
+}
@@ -0,0 +1,128 @@
+//! Periodically check deadlines of running services (for now only RuntimeMaxSec=) and stop the services that exceeded them.
+//! They are started again if Restart=always (within the start limits).
+//!
+//! The same thread checks whether the main processes adopted from the state file are still running.
+
+use crate::platform::EventFd;
+use crate::services::RestartReason;
+use crate::units::*;
+use std::sync::Arc;
+
+const DEADLINE_CHECK_INTERVAL: std::time::Duration = std::time::Duration::from_secs(1);
+
+pub fn check_deadlines_loop(
+    run_info: ArcRuntimeInfo,
+    notification_socket_path: std::path::PathBuf,
+    eventfds: Arc<Vec<EventFd>>,
+) {
+    loop {
+        check_deadlines(&run_info, &notification_socket_path, &eventfds);
+        crate::state_persist::check_adopted_services(&run_info);
+        std::thread::sleep(DEADLINE_CHECK_INTERVAL);
+    }
+}
+
+pub fn check_deadlines(
+    run_info: &ArcRuntimeInfo,
+    notification_socket_path: &std::path::Path,
+    eventfds: &Arc<Vec<EventFd>>,
+) {
+    let now = std::time::Instant::now();
+    let expired: Vec<UnitId> = run_info
+        .unit_table
+        .read()
+        .unwrap()
+        .values()
+        .filter_map(|unit| {
+            let unit_locked = unit.lock().unwrap();
+            match &unit_locked.specialized {
+                UnitSpecialized::Service(srvc) if srvc.runtime_max_exceeded(now) => {
+                    Some(unit_locked.id)
+                }
+                _ => None,
+            }
+        })
+        .collect();
+
+    for id in expired {
+        match stop_after_runtime_max(id, run_info) {
+            AfterRuntimeMax::Stay => {}
+            AfterRuntimeMax::Restart => {
+                if let Err(e) = crate::units::activate_unit(
+                    id,
+                    run_info.clone(),
+                    notification_socket_path.to_path_buf(),
+                    eventfds.clone(),
+                    true,
+                ) {
+                    error!("Error while restarting service {}: {}", id, e);
+                }
+            }
+            AfterRuntimeMax::StartLimitHit(name, action) => {
+                if let Err(e) = super::fail_after_start_limit(id, &name, action, run_info.clone()) {
+                    error!("Error while stopping service {}: {}", name, e);
+                }
+            }
+        }
+    }
+}
+
+/// What happens with a service after it was stopped because of RuntimeMaxSec=
+enum AfterRuntimeMax {
+    Stay,
+    Restart,
+    /// Restart=always but the service was restarted too often already
+    StartLimitHit(String, StartLimitAction),
+}
+
+/// Stop the service like any other (ExecStop=, SIGTERM, FinalKillSignal= for the rest, ExecStopPost=)
+fn stop_after_runtime_max(id: UnitId, run_info: &ArcRuntimeInfo) -> AfterRuntimeMax {
+    let unit = match run_info.unit_table.read().unwrap().get(&id) {
+        Some(unit) => unit.clone(),
+        None => return AfterRuntimeMax::Stay,
+    };
+    let unit_locked = &mut *unit.lock().unwrap();
+    let name = unit_locked.conf.name();
+
+    {
+        let status_table_locked = run_info.status_table.read().unwrap();
+        let mut status_locked = status_table_locked.get(&id).unwrap().lock().unwrap();
+        // the service might have been stopped between collecting and locking it.
+        // Setting the status to Stopping makes the exit handler ignore the exit of the main process
+        if *status_locked != UnitStatus::Started && *status_locked != UnitStatus::Reloading {
+            return AfterRuntimeMax::Stay;
+        }
+        *status_locked = UnitStatus::Stopping;
+    }
+
+    warn!("Service {} exceeded its RuntimeMaxSec=. Stopping it", name);
+    let stop_result = unit_locked.deactivate(run_info.clone());
+    if let Err(e) = &stop_result {
+        error!("Error while stopping service {}: {}", name, e);
+    }
+
+    let after = match &mut unit_locked.specialized {
+        UnitSpecialized::Service(srvc) if srvc.service_config.restart == ServiceRestart::Always => {
+            if srvc.record_restart(std::time::Instant::now()) {
+                srvc.set_restart_reason(&name, RestartReason::RuntimeMaxExceeded);
+                run_info.events.publish(
+                    &name,
+                    UnitEventKind::Restarted(RestartReason::RuntimeMaxExceeded),
+                );
+                AfterRuntimeMax::Restart
+            } else {
+                AfterRuntimeMax::StartLimitHit(name.clone(), srvc.service_config.start_limit_action)
+            }
+        }
+        _ => AfterRuntimeMax::Stay,
+    };
+
+    let new_status = match stop_result {
+        Ok(()) if matches!(after, AfterRuntimeMax::Restart) => UnitStatus::Stopped,
+        Ok(()) => UnitStatus::StoppedFinal("RuntimeMaxSec reached".into()),
+        Err(e) => UnitStatus::StoppedFinal(format!("RuntimeMaxSec reached. {}", e)),
+    };
+    let status_table_locked = run_info.status_table.read().unwrap();
+    *status_table_locked.get(&id).unwrap().lock().unwrap() = new_status;
+    after
+}
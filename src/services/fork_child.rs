@@ -1,6 +1,7 @@
 use crate::fd_store::FDStore;
 use crate::platform::setenv;
 use crate::services::Service;
+use crate::units::{IoSchedClass, ProcSubset, ProtectSystem, SchedPolicy, ServiceConfig};
 use std::os::unix::io::RawFd;
 
 fn close_all_unneeded_fds(_srvc: &mut Service, _fd_store: &FDStore) {
@@ -149,16 +150,696 @@ fn prepare_exec_args(srvc: &Service) -> (std::ffi::CString, Vec<std::ffi::CStrin
     (cmd, args)
 }
 
+#[cfg(target_os = "linux")]
+fn set_oom_score_adjust(adjust: i32) -> Result<(), String> {
+    std::fs::write("/proc/self/oom_score_adj", format!("{}", adjust))
+        .map_err(|e| format!("Could not write /proc/self/oom_score_adj: {}", e))
+}
+
+#[cfg(not(target_os = "linux"))]
+fn set_oom_score_adjust(_adjust: i32) -> Result<(), String> {
+    Err("OOMScoreAdjust is only supported on linux".into())
+}
+
+#[cfg(target_os = "linux")]
+fn set_io_priority(class: Option<IoSchedClass>, priority: Option<i32>) -> Result<(), String> {
+    // See the kernel docs in Documentation/block/ioprio.rst and include/uapi/linux/ioprio.h:
+    // the class is stored in the bits above IOPRIO_CLASS_SHIFT (13), the priority (data) in the bits below
+    const IOPRIO_CLASS_SHIFT: i32 = 13;
+    const IOPRIO_CLASS_RT: i32 = 1;
+    const IOPRIO_CLASS_BE: i32 = 2;
+    const IOPRIO_CLASS_IDLE: i32 = 3;
+    const IOPRIO_WHO_PROCESS: i32 = 1;
+
+    // like systemd: no class means best-effort, no priority means 4. The idle class has no priority levels
+    let (class, data) = match class.unwrap_or(IoSchedClass::BestEffort) {
+        IoSchedClass::Realtime => (IOPRIO_CLASS_RT, priority.unwrap_or(4)),
+        IoSchedClass::BestEffort => (IOPRIO_CLASS_BE, priority.unwrap_or(4)),
+        IoSchedClass::Idle => (IOPRIO_CLASS_IDLE, 0),
+    };
+    let ioprio = (class << IOPRIO_CLASS_SHIFT) | data;
+
+    // who = 0 means the calling process
+    let res = unsafe { libc::syscall(libc::SYS_ioprio_set, IOPRIO_WHO_PROCESS, 0, ioprio) };
+    if res == -1 {
+        Err(format!(
+            "ioprio_set failed: {}",
+            std::io::Error::last_os_error()
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn set_io_priority(_class: Option<IoSchedClass>, _priority: Option<i32>) -> Result<(), String> {
+    Err("IOSchedulingClass and IOSchedulingPriority are only supported on linux".into())
+}
+
+#[cfg(target_os = "linux")]
+fn set_cpu_scheduling(
+    policy: Option<SchedPolicy>,
+    priority: Option<i32>,
+    reset_on_fork: bool,
+) -> Result<(), String> {
+    let policy = policy.unwrap_or(SchedPolicy::Other);
+    let mut policy_raw = match policy {
+        SchedPolicy::Other => libc::SCHED_OTHER,
+        SchedPolicy::Batch => libc::SCHED_BATCH,
+        SchedPolicy::Idle => libc::SCHED_IDLE,
+        SchedPolicy::Fifo => libc::SCHED_FIFO,
+        SchedPolicy::RR => libc::SCHED_RR,
+    };
+    if reset_on_fork {
+        policy_raw |= libc::SCHED_RESET_ON_FORK;
+    }
+    // the priorities were checked against the policy while parsing. Realtime policies default to the lowest priority
+    let priority = priority.unwrap_or(if policy.is_realtime() { 1 } else { 0 });
+    let param = libc::sched_param {
+        sched_priority: priority,
+    };
+
+    // nix does not wrap sched_setscheduler, pid 0 means the calling process
+    let res = unsafe { libc::sched_setscheduler(0, policy_raw, &param) };
+    if res == -1 {
+        Err(format!(
+            "sched_setscheduler failed: {}",
+            std::io::Error::last_os_error()
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn set_cpu_scheduling(
+    _policy: Option<SchedPolicy>,
+    _priority: Option<i32>,
+    _reset_on_fork: bool,
+) -> Result<(), String> {
+    Err("CPUSchedulingPolicy and CPUSchedulingPriority are only supported on linux".into())
+}
+
+fn set_nice(nice: i32) -> Result<(), String> {
+    // setpriority sets the absolute niceness, nice() would only add to the current one. nix wraps neither
+    let res = unsafe { libc::setpriority(libc::PRIO_PROCESS as _, 0, nice) };
+    if res == -1 {
+        Err(format!(
+            "setpriority failed: {}",
+            std::io::Error::last_os_error()
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn set_cpu_affinity(cpus: &[usize]) -> Result<(), String> {
+    let mut cpu_set = nix::sched::CpuSet::new();
+    for cpu in cpus {
+        cpu_set
+            .set(*cpu)
+            .map_err(|e| format!("Could not add cpu {} to the affinity mask: {}", cpu, e))?;
+    }
+    nix::sched::sched_setaffinity(nix::unistd::Pid::from_raw(0), &cpu_set)
+        .map_err(|e| format!("sched_setaffinity failed: {}", e))
+}
+
+#[cfg(not(target_os = "linux"))]
+fn set_cpu_affinity(_cpus: &[usize]) -> Result<(), String> {
+    Err("CPUAffinity is only supported on linux".into())
+}
+
+/// Change the root to RootDirectory=. This is done after the mounts of the mount namespace, so these are relative to the
+/// root of rustysd (BindPaths= can mount into the new root), and before dropping privileges, chroot needs
+/// CAP_SYS_CHROOT
+fn change_root(root: &std::path::Path) -> Result<(), String> {
+    if !root.is_dir() {
+        return Err(format!("RootDirectory {:?} is not a directory", root));
+    }
+    nix::unistd::chroot(root).map_err(|e| format!("chroot to {:?} failed: {}", root, e))?;
+    nix::unistd::chdir("/").map_err(|e| format!("chdir to / in {:?} failed: {}", root, e))
+}
+
+/// Enter the namespaces of JoinsNamespaceOf=. The files are /proc/<pid>/ns/* links, so the kernel knows their type
+#[cfg(target_os = "linux")]
+fn join_namespaces(namespaces: &[std::fs::File]) -> Result<(), String> {
+    use std::os::unix::io::AsRawFd;
+    for ns in namespaces {
+        nix::sched::setns(ns.as_raw_fd(), nix::sched::CloneFlags::empty())
+            .map_err(|e| format!("setns failed: {}", e))?;
+    }
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn join_namespaces(namespaces: &[std::fs::File]) -> Result<(), String> {
+    if namespaces.is_empty() {
+        Ok(())
+    } else {
+        Err("JoinsNamespaceOf is only supported on linux".into())
+    }
+}
+
+/// Kernel tunables that ProtectKernelTunables= makes read-only. Paths that do not exist on this kernel are skipped
+#[cfg(target_os = "linux")]
+const KERNEL_TUNABLES: &[&str] = &[
+    "/proc/sys",
+    "/proc/sysrq-trigger",
+    "/proc/latency_stats",
+    "/proc/acpi",
+    "/sys/kernel",
+];
+
+/// Whether the service needs its own mount namespace
+fn needs_mount_namespace(conf: &ServiceConfig) -> bool {
+    conf.private_tmp
+        || conf.root_directory.is_some()
+        || conf.mount_api_vfs
+        || conf.proc_subset != ProcSubset::All
+        || !conf.temporary_file_system.is_empty()
+        || !conf.bind_paths.is_empty()
+        || !conf.bind_read_only_paths.is_empty()
+        || conf.protect_system != ProtectSystem::No
+        || conf.protect_kernel_tunables
+        || conf.protect_control_groups
+}
+
+/// A bind mount of the path onto itself can then be remounted read-only without affecting the original mount
+#[cfg(target_os = "linux")]
+fn bind_read_only(path: &std::path::Path) -> Result<(), String> {
+    use nix::mount::{mount, MsFlags};
+
+    mount(
+        Some(path),
+        path,
+        None::<&str>,
+        MsFlags::MS_BIND | MsFlags::MS_REC,
+        None::<&str>,
+    )
+    .and_then(|()| {
+        mount(
+            None::<&str>,
+            path,
+            None::<&str>,
+            MsFlags::MS_BIND | MsFlags::MS_REMOUNT | MsFlags::MS_RDONLY,
+            None::<&str>,
+        )
+    })
+    .map_err(|e| format!("Could not make {:?} read-only: {}", path, e))
+}
+
+/// The path as it is seen by a service with this RootDirectory= (which is / without one). The mount namespace is set up
+/// before the service changes its root, so everything in it has to be mounted below the root
+#[cfg(target_os = "linux")]
+fn below_root(root: &std::path::Path, path: &std::path::Path) -> std::path::PathBuf {
+    root.join(path.strip_prefix("/").unwrap_or(path))
+}
+
+/// The API file systems MountAPIVFS= provides: mount point, file system type, flags and options
+#[cfg(target_os = "linux")]
+const API_VFS: &[(&str, &str, libc::c_ulong, &str)] = &[
+    (
+        "/proc",
+        "proc",
+        libc::MS_NOSUID | libc::MS_NODEV | libc::MS_NOEXEC,
+        "",
+    ),
+    (
+        "/sys",
+        "sysfs",
+        libc::MS_NOSUID | libc::MS_NODEV | libc::MS_NOEXEC,
+        "",
+    ),
+    // not a devtmpfs, that would expose all devices of the host. It gets the standard nodes only
+    (
+        "/dev",
+        "tmpfs",
+        libc::MS_NOSUID | libc::MS_NOEXEC,
+        "mode=755",
+    ),
+    (
+        "/dev/pts",
+        "devpts",
+        libc::MS_NOSUID | libc::MS_NOEXEC,
+        "newinstance,ptmxmode=0666,mode=0620",
+    ),
+    (
+        "/dev/shm",
+        "tmpfs",
+        libc::MS_NOSUID | libc::MS_NODEV,
+        "mode=1777",
+    ),
+];
+
+/// The device nodes in the /dev of MountAPIVFS=: name, major and minor number. All of them are character devices
+#[cfg(target_os = "linux")]
+const API_DEV_NODES: &[(&str, u64, u64)] = &[
+    ("null", 1, 3),
+    ("zero", 1, 5),
+    ("full", 1, 7),
+    ("random", 1, 8),
+    ("urandom", 1, 9),
+    ("tty", 5, 0),
+];
+
+/// Create the standard device nodes and the links to /proc/self/fd in a fresh /dev
+#[cfg(target_os = "linux")]
+fn populate_dev(dev: &std::path::Path) -> Result<(), String> {
+    use nix::sys::stat::{makedev, mknod, Mode, SFlag};
+    use std::os::unix::fs::PermissionsExt;
+
+    for (name, major, minor) in API_DEV_NODES {
+        let path = dev.join(name);
+        mknod(
+            &path,
+            SFlag::S_IFCHR,
+            Mode::from_bits_truncate(0o666),
+            makedev(*major, *minor),
+        )
+        .map_err(|e| format!("Could not create device node {:?}: {}", path, e))?;
+        // mknod is subject to the umask
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o666))
+            .map_err(|e| format!("Could not set the mode of {:?}: {}", path, e))?;
+    }
+    for (name, target) in &[
+        ("fd", "/proc/self/fd"),
+        ("stdin", "/proc/self/fd/0"),
+        ("stdout", "/proc/self/fd/1"),
+        ("stderr", "/proc/self/fd/2"),
+    ] {
+        let path = dev.join(name);
+        std::os::unix::fs::symlink(target, &path)
+            .map_err(|e| format!("Could not create {:?}: {}", path, e))?;
+    }
+    Ok(())
+}
+
+/// Mount the API file systems for MountAPIVFS= below the RootDirectory= of the service (or / without one). Existing
+/// ones are kept as they are (the kernel refuses to mount sysfs over itself anyway), only missing ones get mounted.
+/// Without RootDirectory= this matters if rustysd runs in a container that does not provide all of them.
+#[cfg(target_os = "linux")]
+fn mount_api_vfs(root: &std::path::Path) -> Result<(), String> {
+    use nix::mount::{mount, MsFlags};
+
+    let mountinfo = std::fs::read_to_string("/proc/self/mountinfo")
+        .map_err(|e| format!("Could not read /proc/self/mountinfo: {}", e))?;
+    // the fifth field is the mount point
+    let mount_points: Vec<&str> = mountinfo
+        .lines()
+        .filter_map(|line| line.split(' ').nth(4))
+        .collect();
+    for (path, fs_type, flags, options) in API_VFS {
+        let path = below_root(root, std::path::Path::new(path));
+        if mount_points
+            .iter()
+            .any(|mount_point| path.as_os_str() == *mount_point)
+        {
+            continue;
+        }
+        std::fs::create_dir_all(&path)
+            .map_err(|e| format!("Could not create mount point {:?}: {}", path, e))?;
+        mount(
+            Some(*fs_type),
+            &path,
+            Some(*fs_type),
+            MsFlags::from_bits_truncate(*flags),
+            Some(*options),
+        )
+        .map_err(|e| format!("Could not mount {} on {:?}: {}", fs_type, path, e))?;
+        if *fs_type == "tmpfs" && path == root.join("dev") {
+            populate_dev(&path)?;
+        }
+        // with a new devpts instance /dev/ptmx has to be the one of that instance
+        let ptmx = root.join("dev/ptmx");
+        if *fs_type == "devpts" {
+            if ptmx.exists() {
+                mount(
+                    Some(&path.join("ptmx")),
+                    &ptmx,
+                    None::<&str>,
+                    MsFlags::MS_BIND,
+                    None::<&str>,
+                )
+                .map_err(|e| format!("Could not bind mount {:?} to {:?}: {}", path, ptmx, e))?;
+            } else {
+                std::os::unix::fs::symlink("pts/ptmx", &ptmx)
+                    .map_err(|e| format!("Could not create {:?}: {}", ptmx, e))?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The (major, minor) version of the running kernel
+#[cfg(target_os = "linux")]
+fn kernel_version() -> Option<(u32, u32)> {
+    let uname = nix::sys::utsname::uname();
+    let mut parts = uname.release().split(|c: char| !c.is_ascii_digit());
+    let major = parts.next()?.parse().ok()?;
+    let minor = parts.next()?.parse().ok()?;
+    Some((major, minor))
+}
+
+/// Mount a new /proc for ProcSubset=pid that hides the processes of other users. Since 5.8 every mount of proc has its
+/// own options and subset=pid also hides everything that does not belong to a process. On older kernels the options
+/// are shared by all mounts of the instance, so hidepid=2 then applies to the /proc of rustysd too
+#[cfg(target_os = "linux")]
+fn mount_proc_subset(root: &std::path::Path) -> Result<(), String> {
+    use nix::mount::{mount, MsFlags};
+
+    let path = root.join("proc");
+    if !path.is_dir() {
+        return Ok(());
+    }
+    let options = match kernel_version() {
+        Some(version) if version >= (5, 8) => "hidepid=invisible,subset=pid",
+        _ => "hidepid=2",
+    };
+    mount(
+        Some("proc"),
+        &path,
+        Some("proc"),
+        MsFlags::MS_NOSUID | MsFlags::MS_NODEV | MsFlags::MS_NOEXEC,
+        Some(options),
+    )
+    .map_err(|e| format!("Could not mount {:?} with {}: {}", path, options, e))
+}
+
+/// Mount an empty tmpfs for TemporaryFileSystem=. The generic mount flags in the options are passed as flags, the rest
+/// goes to tmpfs. Like in systemd the mode is 0755 unless the options set one
+#[cfg(target_os = "linux")]
+fn mount_temporary_file_system(path: &std::path::Path, options: &str) -> Result<(), String> {
+    use nix::mount::{mount, MsFlags};
+
+    let mut flags = MsFlags::MS_NODEV | MsFlags::MS_STRICTATIME;
+    let mut data = Vec::new();
+    for option in options.split(',').filter(|option| !option.is_empty()) {
+        match option {
+            "ro" => flags |= MsFlags::MS_RDONLY,
+            "nosuid" => flags |= MsFlags::MS_NOSUID,
+            "nodev" => flags |= MsFlags::MS_NODEV,
+            "noexec" => flags |= MsFlags::MS_NOEXEC,
+            _ => data.push(option),
+        }
+    }
+    if !data.iter().any(|option| option.starts_with("mode=")) {
+        data.push("mode=0755");
+    }
+
+    std::fs::create_dir_all(path)
+        .map_err(|e| format!("Could not create mount point {:?}: {}", path, e))?;
+    mount(
+        Some("tmpfs"),
+        path,
+        Some("tmpfs"),
+        flags,
+        Some(data.join(",").as_str()),
+    )
+    .map_err(|e| format!("Could not mount a tmpfs on {:?}: {}", path, e))
+}
+
+/// Bind mount source onto destination for BindPaths= and BindReadOnlyPaths=. A missing destination is created as a
+/// directory or an empty file, matching the type of the source.
+#[cfg(target_os = "linux")]
+fn bind_path(
+    source: &std::path::Path,
+    destination: &std::path::Path,
+    read_only: bool,
+) -> Result<(), String> {
+    use nix::mount::{mount, MsFlags};
+
+    let source_meta = std::fs::metadata(source)
+        .map_err(|e| format!("Could not bind mount {:?}: {}", source, e))?;
+    if !destination.exists() {
+        let created = if source_meta.is_dir() {
+            std::fs::create_dir_all(destination)
+        } else {
+            destination
+                .parent()
+                .map(std::fs::create_dir_all)
+                .unwrap_or(Ok(()))
+                .and_then(|()| std::fs::File::create(destination).map(|_| ()))
+        };
+        created
+            .map_err(|e| format!("Could not create bind mount point {:?}: {}", destination, e))?;
+    }
+    mount(
+        Some(source),
+        destination,
+        None::<&str>,
+        MsFlags::MS_BIND | MsFlags::MS_REC,
+        None::<&str>,
+    )
+    .map_err(|e| {
+        format!(
+            "Could not bind mount {:?} to {:?}: {}",
+            source, destination, e
+        )
+    })?;
+    if read_only {
+        mount(
+            None::<&str>,
+            destination,
+            None::<&str>,
+            MsFlags::MS_BIND | MsFlags::MS_REMOUNT | MsFlags::MS_RDONLY,
+            None::<&str>,
+        )
+        .map_err(|e| format!("Could not make {:?} read-only: {}", destination, e))?;
+    }
+    Ok(())
+}
+
+/// Make the cgroup hierarchy read-only. With cgroup v2 that is one mount, with v1 (or the hybrid layout) every controller
+/// is mounted separately below /sys/fs/cgroup. The recursive bind copied those mounts, but they need to be remounted
+/// read-only one by one.
+#[cfg(target_os = "linux")]
+fn protect_control_groups(root: &std::path::Path) -> Result<(), String> {
+    use nix::mount::{mount, MsFlags};
+
+    let cgroup_root = below_root(root, std::path::Path::new("/sys/fs/cgroup"));
+    if !cgroup_root.is_dir() {
+        return Ok(());
+    }
+    bind_read_only(&cgroup_root)?;
+
+    let mountinfo = std::fs::read_to_string("/proc/self/mountinfo")
+        .map_err(|e| format!("Could not read /proc/self/mountinfo: {}", e))?;
+    // the fifth field is the mount point
+    let controllers = mountinfo
+        .lines()
+        .filter_map(|line| line.split(' ').nth(4))
+        .map(std::path::Path::new)
+        .filter(|mount_point| {
+            mount_point.starts_with(&cgroup_root) && *mount_point != cgroup_root.as_path()
+        });
+    for controller in controllers {
+        mount(
+            None::<&str>,
+            controller,
+            None::<&str>,
+            MsFlags::MS_BIND | MsFlags::MS_REMOUNT | MsFlags::MS_RDONLY,
+            None::<&str>,
+        )
+        .map_err(|e| format!("Could not make {:?} read-only: {}", controller, e))?;
+    }
+    Ok(())
+}
+
+/// Give the service its own mount namespace for PrivateTmp=, BindPaths=, BindReadOnlyPaths=, ProtectSystem=,
+/// ProtectKernelTunables= and ProtectControlGroups=. Changes in it do not propagate back. MountAPIVFS= is applied in
+/// the namespace too. With RootDirectory= everything is mounted below the new root
+#[cfg(target_os = "linux")]
+fn setup_mount_namespace(conf: &ServiceConfig) -> Result<(), String> {
+    use nix::mount::{mount, MsFlags};
+
+    nix::sched::unshare(nix::sched::CloneFlags::CLONE_NEWNS)
+        .map_err(|e| format!("Could not create a mount namespace: {}", e))?;
+    mount(
+        None::<&str>,
+        "/",
+        None::<&str>,
+        MsFlags::MS_REC | MsFlags::MS_PRIVATE,
+        None::<&str>,
+    )
+    .map_err(|e| format!("Could not make the mounts private: {}", e))?;
+
+    // first, the other settings might make parts of these read-only
+    let root = conf
+        .root_directory
+        .as_deref()
+        .unwrap_or_else(|| std::path::Path::new("/"));
+    if conf.mount_api_vfs {
+        mount_api_vfs(root)?;
+    }
+    // after MountAPIVFS=, that might have mounted the /proc below RootDirectory=
+    if conf.proc_subset == ProcSubset::Pid {
+        mount_proc_subset(root)?;
+    }
+
+    if conf.private_tmp {
+        // Unlike systemd the tmp dirs are not shared with the ExecStartPre= etc. commands
+        for dir in &["/tmp", "/var/tmp"] {
+            let dir = below_root(root, std::path::Path::new(dir));
+            if !dir.is_dir() {
+                continue;
+            }
+            mount(
+                Some("tmpfs"),
+                &dir,
+                Some("tmpfs"),
+                MsFlags::MS_NOSUID | MsFlags::MS_NODEV,
+                Some("mode=1777"),
+            )
+            .map_err(|e| format!("Could not mount a private {:?}: {}", dir, e))?;
+        }
+    }
+
+    // before the binds, so these can be mounted into the empty file systems
+    for (path, options) in &conf.temporary_file_system {
+        mount_temporary_file_system(&below_root(root, path), options)?;
+    }
+
+    // before ProtectSystem=, so mount points in /usr etc. can still be created. The sources are paths of the host
+    for (source, destination) in &conf.bind_paths {
+        bind_path(source, &below_root(root, destination), false)?;
+    }
+    for (source, destination) in &conf.bind_read_only_paths {
+        bind_path(source, &below_root(root, destination), true)?;
+    }
+
+    let read_only: &[&str] = match conf.protect_system {
+        ProtectSystem::No => &[],
+        ProtectSystem::Yes => &["/usr", "/boot", "/efi"],
+        ProtectSystem::Full => &["/usr", "/boot", "/efi", "/etc"],
+    };
+    for dir in read_only {
+        let dir = below_root(root, std::path::Path::new(dir));
+        if !dir.is_dir() {
+            continue;
+        }
+        bind_read_only(&dir)?;
+    }
+
+    if conf.protect_kernel_tunables {
+        for path in KERNEL_TUNABLES {
+            let path = below_root(root, std::path::Path::new(path));
+            if !path.exists() {
+                continue;
+            }
+            bind_read_only(&path)?;
+        }
+    }
+
+    if conf.protect_control_groups {
+        protect_control_groups(root)?;
+    }
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn setup_mount_namespace(_conf: &ServiceConfig) -> Result<(), String> {
+    Err(
+        "PrivateTmp, ProcSubset, TemporaryFileSystem, BindPaths, BindReadOnlyPaths, ProtectSystem, ProtectKernelTunables and ProtectControlGroups are only supported on linux"
+            .into(),
+    )
+}
+
+#[cfg(target_os = "linux")]
+fn apparmor_enabled() -> bool {
+    std::fs::read_to_string("/sys/module/apparmor/parameters/enabled")
+        .map(|enabled| enabled.trim() == "Y")
+        .unwrap_or(false)
+}
+
+/// Same as aa_change_onexec from libapparmor: the profile is changed when the service is exec'd
+#[cfg(target_os = "linux")]
+fn set_apparmor_profile(profile: &str) -> Result<(), String> {
+    if !apparmor_enabled() {
+        return Ok(());
+    }
+    std::fs::write("/proc/self/attr/exec", format!("exec {}", profile))
+        .map_err(|e| format!("Could not set AppArmor profile {}: {}", profile, e))
+}
+
+#[cfg(not(target_os = "linux"))]
+fn set_apparmor_profile(_profile: &str) -> Result<(), String> {
+    Ok(())
+}
+
+/// Same as setexeccon from libselinux: the context is changed when the service is exec'd
+#[cfg(target_os = "linux")]
+fn set_selinux_context(context: &str) -> Result<(), String> {
+    if !std::path::Path::new("/sys/fs/selinux/enforce").exists() {
+        return Ok(());
+    }
+    std::fs::write("/proc/self/attr/exec", context)
+        .map_err(|e| format!("Could not set SELinux context {}: {}", context, e))
+}
+
+#[cfg(not(target_os = "linux"))]
+fn set_selinux_context(_context: &str) -> Result<(), String> {
+    Ok(())
+}
+
+/// Smack has no label for the next exec like AppArmor and SELinux, so this changes the label of the forked child right
+/// away (like systemd does). This needs CAP_MAC_ADMIN, so it has to happen before the privileges are dropped
+#[cfg(target_os = "linux")]
+fn set_smack_label(label: &str) -> Result<(), String> {
+    if !std::path::Path::new("/sys/fs/smackfs").exists() {
+        return Ok(());
+    }
+    std::fs::write("/proc/self/attr/current", label)
+        .map_err(|e| format!("Could not set Smack label {}: {}", label, e))
+}
+
+#[cfg(not(target_os = "linux"))]
+fn set_smack_label(_label: &str) -> Result<(), String> {
+    Ok(())
+}
+
+/// Give all signals their default action (except SIGPIPE if it should be ignored) and unblock them. Ignored signals and
+/// the signal mask survive the exec, and the std ignores SIGPIPE in rustysd
+fn reset_signals(ignore_sigpipe: bool) {
+    use nix::sys::signal::{sigaction, sigprocmask, SaFlags, SigAction, SigHandler, SigSet};
+    use nix::sys::signal::{SigmaskHow, Signal};
+    let default = SigAction::new(SigHandler::SigDfl, SaFlags::empty(), SigSet::empty());
+    let ignore = SigAction::new(SigHandler::SigIgn, SaFlags::empty(), SigSet::empty());
+    for signal in Signal::iterator() {
+        if signal == Signal::SIGKILL || signal == Signal::SIGSTOP {
+            continue;
+        }
+        let action = if signal == Signal::SIGPIPE && ignore_sigpipe {
+            &ignore
+        } else {
+            &default
+        };
+        unsafe {
+            let _ = sigaction(signal, action);
+        }
+    }
+    let _ = sigprocmask(SigmaskHow::SIG_SETMASK, Some(&SigSet::empty()), None);
+}
+
 fn move_into_new_process_group() {
     //make this process the process group leader
     nix::unistd::setpgid(nix::unistd::getpid(), nix::unistd::Pid::from_raw(0)).unwrap();
 }
 
+/// Changes to the inherited environment, collected before forking
+pub struct EnvChanges {
+    /// Variables that PassEnvironment= / UnsetEnvironment= remove
+    pub removed: Vec<std::ffi::CString>,
+    /// Variables from EnvironmentFile=
+    pub set: Vec<(std::ffi::CString, std::ffi::CString)>,
+}
+
 pub fn after_fork_child(
     srvc: &mut Service,
     name: &str,
     fd_store: &FDStore,
     notify_socket_env_var: &str,
+    env_changes: &EnvChanges,
     new_stdout: RawFd,
     new_stderr: RawFd,
 ) {
@@ -170,6 +851,13 @@ pub fn after_fork_child(
     // DO NOT USE THE LOGGER HERE. It aquires a global lock which might be held at the time of forking
     // But since this is the only thread that is in the child process the lock will never be released!
     move_into_new_process_group();
+    reset_signals(srvc.service_config.ignore_sigpipe);
+
+    // before the fds are duped, that might overwrite the fds of the namespace files
+    if let Err(e) = join_namespaces(&srvc.joined_namespaces) {
+        eprintln!("[FORK_CHILD {}] could not join namespaces: {}", name, e);
+        std::process::exit(1);
+    }
 
     // no more logging after this point!
     // The filedescriptor used by the logger might have been duped to another
@@ -207,10 +895,125 @@ pub fn after_fork_child(
         std::process::exit(1);
     }
 
+    for var in &env_changes.removed {
+        unsafe {
+            libc::unsetenv(var.as_ptr());
+        }
+    }
+    // set before the variables of rustysd, so these win if a file sets one of them
+    for (var, value) in &env_changes.set {
+        unsafe {
+            libc::setenv(var.as_ptr(), value.as_ptr(), 1);
+        }
+    }
     setup_env_vars(names, notify_socket_env_var);
     let (cmd, args) = prepare_exec_args(srvc);
 
+    // needs to happen before dropping privileges, lowering the score requires CAP_SYS_RESOURCE
+    if let Some(adjust) = srvc.service_config.oom_score_adjust {
+        if let Err(e) = set_oom_score_adjust(adjust) {
+            eprintln!("[FORK_CHILD {}] {}", name, e);
+            std::process::exit(1);
+        }
+    }
+
+    let io_class = srvc.service_config.io_scheduling_class;
+    let io_priority = srvc.service_config.io_scheduling_priority;
+    if io_class.is_some() || io_priority.is_some() {
+        if let Err(e) = set_io_priority(io_class, io_priority) {
+            eprintln!("[FORK_CHILD {}] {}", name, e);
+            std::process::exit(1);
+        }
+    }
+
+    let conf = &srvc.service_config;
+    if conf.cpu_scheduling_policy.is_some()
+        || conf.cpu_scheduling_priority.is_some()
+        || conf.cpu_scheduling_reset_on_fork
+    {
+        if let Err(e) = set_cpu_scheduling(
+            conf.cpu_scheduling_policy,
+            conf.cpu_scheduling_priority,
+            conf.cpu_scheduling_reset_on_fork,
+        ) {
+            eprintln!("[FORK_CHILD {}] {}", name, e);
+            std::process::exit(1);
+        }
+    }
+
+    if let Some(nice) = conf.nice {
+        if let Err(e) = set_nice(nice) {
+            eprintln!("[FORK_CHILD {}] {}", name, e);
+            std::process::exit(1);
+        }
+    }
+
+    if !conf.cpu_affinity.is_empty() {
+        if let Err(e) = set_cpu_affinity(&conf.cpu_affinity) {
+            eprintln!("[FORK_CHILD {}] {}", name, e);
+            std::process::exit(1);
+        }
+    }
+
+    if let Some(mask) = conf.umask {
+        nix::sys::stat::umask(nix::sys::stat::Mode::from_bits_truncate(mask));
+    }
+
+    // mounting needs CAP_SYS_ADMIN, so this has to happen before dropping privileges
+    if needs_mount_namespace(conf) {
+        if let Err(e) = setup_mount_namespace(conf) {
+            eprintln!("[FORK_CHILD {}] {}", name, e);
+            std::process::exit(1);
+        }
+    }
+
+    // the new label only takes effect at the exec, so the remaining steps still run with the current one
+    if let Some(profile) = &conf.apparmor_profile {
+        if let Err(e) = set_apparmor_profile(profile) {
+            eprintln!("[FORK_CHILD {}] {}", name, e);
+            std::process::exit(1);
+        }
+    }
+    if let Some(context) = &conf.selinux_context {
+        if let Err(e) = set_selinux_context(context) {
+            eprintln!("[FORK_CHILD {}] {}", name, e);
+            std::process::exit(1);
+        }
+    }
+    // unlike the two above this changes the label right away
+    if let Some(label) = &conf.smack_process_label {
+        if let Err(e) = set_smack_label(label) {
+            eprintln!("[FORK_CHILD {}] {}", name, e);
+            std::process::exit(1);
+        }
+    }
+
+    // after the labels, setting them needs the /proc of rustysd
+    if let Some(root) = &conf.root_directory {
+        if let Err(e) = change_root(root) {
+            eprintln!("[FORK_CHILD {}] {}", name, e);
+            std::process::exit(1);
+        }
+    }
+
+    let conf = &srvc.service_config;
+    let mut bounding_set = conf.capability_bounding_set;
+    // like systemd ProtectKernelModules= also takes away CAP_SYS_MODULE. Changing the bounding set needs privileges
+    // rustysd only has when it runs as root
+    if conf.protect_kernel_modules && nix::unistd::getuid().is_root() {
+        bounding_set = Some(crate::platform::capabilities::bounding_set_without(
+            bounding_set,
+            crate::platform::capabilities::CAP_SYS_MODULE,
+        ));
+    }
+    let set_capabilities = conf.ambient_capabilities != 0 || bounding_set.is_some();
     if nix::unistd::getuid().is_root() {
+        if set_capabilities {
+            if let Err(e) = crate::platform::capabilities::keep_caps_on_setuid() {
+                eprintln!("[FORK_CHILD {}] {}", name, e);
+                std::process::exit(1);
+            }
+        }
         match crate::platform::drop_privileges(srvc.gid, &srvc.supp_gids, srvc.uid) {
             Ok(()) => { /* Happy */ }
             Err(e) => {
@@ -223,7 +1026,77 @@ pub fn after_fork_child(
         }
     }
 
+    if set_capabilities {
+        if let Err(e) = crate::platform::capabilities::apply_capabilities(
+            bounding_set,
+            conf.ambient_capabilities,
+        ) {
+            eprintln!("[FORK_CHILD {}] {}", name, e);
+            std::process::exit(1);
+        }
+    }
+
     eprintln!("EXECV: {:?} {:?}", &cmd, &args);
+
+    if srvc.service_config.no_new_privileges {
+        if let Err(e) = crate::platform::seccomp::set_no_new_privs() {
+            eprintln!("[FORK_CHILD {}] {}", name, e);
+            std::process::exit(1);
+        }
+    }
+
+    // the seccomp filters of these settings are installed before the one of SystemCallFilter=, that one might forbid
+    // the prctl() needed to install them
+    if srvc.service_config.lock_personality {
+        if let Err(e) = crate::platform::seccomp::lock_personality() {
+            eprintln!("[FORK_CHILD {}] {}", name, e);
+            std::process::exit(1);
+        }
+    }
+
+    if srvc.service_config.protect_kernel_modules {
+        if let Err(e) = crate::platform::seccomp::protect_kernel_modules() {
+            eprintln!("[FORK_CHILD {}] {}", name, e);
+            std::process::exit(1);
+        }
+    }
+
+    if let Some(restricted) = &srvc.service_config.restrict_address_families {
+        if let Err(e) = crate::platform::seccomp::restrict_address_families(restricted) {
+            eprintln!("[FORK_CHILD {}] {}", name, e);
+            std::process::exit(1);
+        }
+    }
+
+    if let Some(allowed) = srvc.service_config.restrict_namespaces {
+        if let Err(e) = crate::platform::seccomp::restrict_namespaces(allowed) {
+            eprintln!("[FORK_CHILD {}] {}", name, e);
+            std::process::exit(1);
+        }
+    }
+
+    if srvc.service_config.restrict_realtime {
+        if let Err(e) = crate::platform::seccomp::restrict_realtime() {
+            eprintln!("[FORK_CHILD {}] {}", name, e);
+            std::process::exit(1);
+        }
+    }
+
+    if srvc.service_config.memory_deny_write_execute {
+        if let Err(e) = crate::platform::seccomp::memory_deny_write_execute() {
+            eprintln!("[FORK_CHILD {}] {}", name, e);
+            std::process::exit(1);
+        }
+    }
+
+    // this has to be the last thing before the exec, the filter might forbid the syscalls of all the steps before
+    if let Some(entries) = &srvc.service_config.seccomp_filter {
+        if let Err(e) = crate::platform::seccomp::install_filter(entries) {
+            eprintln!("[FORK_CHILD {}] {}", name, e);
+            std::process::exit(1);
+        }
+    }
+
     let cstr_args = args
         .iter()
         .map(|cstring| cstring.as_c_str())
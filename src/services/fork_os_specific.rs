@@ -14,6 +14,43 @@ pub fn pre_fork_os_specific(srvc: &mut Service) -> Result<(), String> {
                 srvc.platform_specific.cgroup_path, e
             )
         })?;
+        if let Some(rules) = srvc.service_config.device_rules()? {
+            cgroups::restrict_devices(&srvc.platform_specific.cgroup_path, &rules).map_err(
+                |e| {
+                    format!(
+                        "Couldnt restrict the devices of the service cgroup ({:?}): {}",
+                        srvc.platform_specific.cgroup_path, e
+                    )
+                },
+            )?;
+        }
+        if !srvc.service_config.ip_filter.is_empty() {
+            cgroups::restrict_ip_addresses(
+                &srvc.platform_specific.cgroup_path,
+                &srvc.service_config.ip_filter,
+            )
+            .map_err(|e| {
+                format!(
+                    "Couldnt filter the IP addresses of the service cgroup ({:?}): {}",
+                    srvc.platform_specific.cgroup_path, e
+                )
+            })?;
+        }
+    }
+    #[cfg(not(feature = "cgroups"))]
+    {
+        if srvc.service_config.restricts_devices() {
+            return Err(
+                "DevicePolicy and DeviceAllow need rustysd to be built with the cgroups feature"
+                    .into(),
+            );
+        }
+        if !srvc.service_config.ip_filter.is_empty() {
+            return Err(
+                "IPAddressAllow and IPAddressDeny of services need rustysd to be built with the cgroups feature"
+                    .into(),
+            );
+        }
     }
     let _ = srvc;
     Ok(())
@@ -28,6 +65,10 @@ pub fn post_fork_os_specific(srvc: &mut Service) -> Result<(), String> {
         );
         cgroups::move_self_to_cgroup(&srvc.platform_specific.cgroup_path)
             .map_err(|e| format!("postfork os specific: {}", e))?;
+        if srvc.service_config.restricts_devices() {
+            cgroups::join_devices_cgroup(&srvc.platform_specific.cgroup_path)
+                .map_err(|e| format!("postfork os specific: {}", e))?;
+        }
     }
     let _ = srvc;
     Ok(())
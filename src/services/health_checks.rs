@@ -0,0 +1,138 @@
+//! Periodically run the ExecHealthCheck= of running services. This gives liveness supervision for services that do not
+//! support sd_notify: a service whose check fails ExecHealthCheckMaxFailures= times in a row is unhealthy and gets
+//! restarted if Restart=always. If it hit its start limits it fails like a service that exited too often.
+
+use crate::platform::EventFd;
+use crate::services::RestartReason;
+use crate::units::*;
+use std::sync::Arc;
+
+const HEALTH_CHECK_TICK: std::time::Duration = std::time::Duration::from_secs(1);
+
+pub fn check_health_loop(
+    run_info: ArcRuntimeInfo,
+    notification_socket_path: std::path::PathBuf,
+    eventfds: Arc<Vec<EventFd>>,
+) {
+    loop {
+        check_health(&run_info, &notification_socket_path, &eventfds);
+        std::thread::sleep(HEALTH_CHECK_TICK);
+    }
+}
+
+pub fn check_health(
+    run_info: &ArcRuntimeInfo,
+    notification_socket_path: &std::path::Path,
+    eventfds: &Arc<Vec<EventFd>>,
+) {
+    let now = std::time::Instant::now();
+    let due: Vec<UnitId> = run_info
+        .unit_table
+        .read()
+        .unwrap()
+        .values()
+        .filter_map(|unit| {
+            let unit_locked = unit.lock().unwrap();
+            match &unit_locked.specialized {
+                UnitSpecialized::Service(srvc) if srvc.health_check_due(now) => {
+                    Some(unit_locked.id)
+                }
+                _ => None,
+            }
+        })
+        .collect();
+
+    for id in due {
+        match check_service_health(id, run_info) {
+            HealthCheckOutcome::Nothing => {}
+            HealthCheckOutcome::Restart => {
+                restart_unhealthy(id, run_info, notification_socket_path, eventfds)
+            }
+            HealthCheckOutcome::StartLimitHit(name, action) => {
+                if let Err(e) = super::fail_after_start_limit(id, &name, action, run_info.clone()) {
+                    error!("Error while stopping unhealthy service {}: {}", name, e);
+                }
+            }
+        }
+    }
+}
+
+/// What has to happen after a health check. Decided while the service is locked, done after it is unlocked
+enum HealthCheckOutcome {
+    Nothing,
+    /// The service just became unhealthy and gets restarted
+    Restart,
+    /// The service just became unhealthy but was restarted too often already
+    StartLimitHit(String, StartLimitAction),
+}
+
+/// Run the health check of the service and decide what happens if it just became unhealthy
+fn check_service_health(id: UnitId, run_info: &ArcRuntimeInfo) -> HealthCheckOutcome {
+    // checks only make sense while the service is up. Starting services are not ready yet
+    if run_info.current_status(&id) != Some(UnitStatus::Started) {
+        return HealthCheckOutcome::Nothing;
+    }
+    let unit = match run_info.unit_table.read().unwrap().get(&id) {
+        Some(unit) => unit.clone(),
+        None => return HealthCheckOutcome::Nothing,
+    };
+    let unit_locked = &mut *unit.lock().unwrap();
+    let name = unit_locked.conf.name();
+    let srvc = match &mut unit_locked.specialized {
+        UnitSpecialized::Service(srvc) => srvc,
+        _ => return HealthCheckOutcome::Nothing,
+    };
+    // the service might have been stopped between collecting and locking it
+    if srvc.pid.is_none() {
+        return HealthCheckOutcome::Nothing;
+    }
+    let was_unhealthy = srvc.is_unhealthy();
+    let err = match srvc.run_health_check(id, &name, run_info.clone()) {
+        Ok(()) => {
+            if was_unhealthy {
+                info!("Service {} is healthy again", name);
+            }
+            return HealthCheckOutcome::Nothing;
+        }
+        Err(e) => e,
+    };
+    if was_unhealthy || !srvc.is_unhealthy() {
+        return HealthCheckOutcome::Nothing;
+    }
+
+    warn!(
+        "Service {} is unhealthy, ExecHealthCheck failed {} times in a row: {}",
+        name, srvc.runtime_info.health_check_failures, err
+    );
+    run_info
+        .events
+        .publish(&name, UnitEventKind::Unhealthy(format!("{}", err)));
+    if srvc.service_config.restart != ServiceRestart::Always {
+        return HealthCheckOutcome::Nothing;
+    }
+    if !srvc.record_restart(std::time::Instant::now()) {
+        return HealthCheckOutcome::StartLimitHit(name, srvc.service_config.start_limit_action);
+    }
+    srvc.set_restart_reason(&name, RestartReason::HealthCheckFailed);
+    run_info.events.publish(
+        &name,
+        UnitEventKind::Restarted(RestartReason::HealthCheckFailed),
+    );
+    HealthCheckOutcome::Restart
+}
+
+fn restart_unhealthy(
+    id: UnitId,
+    run_info: &ArcRuntimeInfo,
+    notification_socket_path: &std::path::Path,
+    eventfds: &Arc<Vec<EventFd>>,
+) {
+    if let Err(e) = crate::units::reactivate_unit(
+        id,
+        run_info.clone(),
+        notification_socket_path.to_path_buf(),
+        eventfds.clone(),
+    ) {
+        error!("Error while restarting unhealthy service {}: {}", id, e);
+    }
+}
@@ -3,14 +3,38 @@ use crate::services::Service;
 #[cfg(feature = "cgroups")]
 use crate::platform::cgroups;
 
-pub fn kill(srvc: &mut Service, sig: nix::sys::signal::Signal) -> Result<(), String> {
+/// Send the signal to all processes of the service that the os specific tracking knows about (the cgroup)
+pub fn signal(srvc: &mut Service, sig: nix::sys::signal::Signal) -> Result<(), String> {
     #[cfg(feature = "cgroups")]
     {
         cgroups::freeze_kill_thaw_cgroup(&srvc.platform_specific.cgroup_path, sig)
             .map_err(|e| format!("{}", e))?;
-        std::fs::remove_dir(&srvc.platform_specific.cgroup_path).map_err(|e| format!("{}", e))?;
     }
     let _ = srvc;
     let _ = sig;
     Ok(())
 }
+
+/// Whether the os specific tracking (the cgroup) knows the process as one of the service
+pub fn contains(srvc: &Service, pid: nix::unistd::Pid) -> bool {
+    #[cfg(feature = "cgroups")]
+    {
+        if let Ok(pids) = cgroups::get_all_procs(&srvc.platform_specific.cgroup_path) {
+            return pids.contains(&pid);
+        }
+    }
+    let _ = srvc;
+    let _ = pid;
+    false
+}
+
+pub fn kill(srvc: &mut Service, sig: nix::sys::signal::Signal) -> Result<(), String> {
+    signal(srvc, sig)?;
+    #[cfg(feature = "cgroups")]
+    {
+        cgroups::remove_devices_cgroup(&srvc.platform_specific.cgroup_path)
+            .map_err(|e| format!("{}", e))?;
+        std::fs::remove_dir(&srvc.platform_specific.cgroup_path).map_err(|e| format!("{}", e))?;
+    }
+    Ok(())
+}
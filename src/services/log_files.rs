@@ -0,0 +1,108 @@
+//! Write service output to the files given with StandardOutput=file:... / StandardError=file:... and rotate them.
+//! The output still passes through rustysd (like all other output) so the files can be rotated without the service noticing.
+
+use crate::services::Service;
+use crate::units::*;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+fn open_output_file(target: &StdOutputTarget) -> Result<Option<std::fs::File>, String> {
+    let mut options = std::fs::OpenOptions::new();
+    let path = match target {
+        StdOutputTarget::File(path) => {
+            options.write(true);
+            path
+        }
+        StdOutputTarget::AppendFile(path) => {
+            options.append(true);
+            path
+        }
+        StdOutputTarget::Default | StdOutputTarget::Null => return Ok(None),
+    };
+    options
+        .create(true)
+        .open(path)
+        .map(Some)
+        .map_err(|e| format!("Could not open output file {:?}: {}", path, e))
+}
+
+fn target_path(target: &StdOutputTarget) -> Option<&PathBuf> {
+    match target {
+        StdOutputTarget::File(path) | StdOutputTarget::AppendFile(path) => Some(path),
+        StdOutputTarget::Default | StdOutputTarget::Null => None,
+    }
+}
+
+/// Open the files for StandardOutput= and StandardError= if they are not open yet
+pub fn open_output_files(srvc: &mut Service) -> Result<(), String> {
+    if srvc.stdout_file.is_none() {
+        srvc.stdout_file = open_output_file(&srvc.service_config.standard_output)?;
+    }
+    if srvc.stderr_file.is_none() {
+        srvc.stderr_file = open_output_file(&srvc.service_config.standard_error)?;
+    }
+    Ok(())
+}
+
+/// Write one line to the file. Returns false if there is no file or writing failed
+/// so the line can be written somewhere else.
+pub fn write_line_to_file(file: &mut Option<std::fs::File>, name: &str, line: &[u8]) -> bool {
+    if let Some(file) = file {
+        let res = file.write_all(line).and_then(|_| file.write_all(b"\n"));
+        match res {
+            Ok(()) => true,
+            Err(e) => {
+                trace!("Could not write output of service {} to file: {}", name, e);
+                false
+            }
+        }
+    } else {
+        false
+    }
+}
+
+/// Move the file to <path>.1.gz (replacing an older one) compressed with gzip
+pub fn rotate_file(path: &Path) -> std::io::Result<()> {
+    if !path.exists() {
+        return Ok(());
+    }
+    let mut rotated = path.as_os_str().to_owned();
+    rotated.push(".1");
+    let rotated = PathBuf::from(rotated);
+    std::fs::rename(path, &rotated)?;
+
+    let mut compressed = rotated.as_os_str().to_owned();
+    compressed.push(".gz");
+    let out = std::fs::File::create(PathBuf::from(compressed))?;
+    let mut encoder = flate2::write::GzEncoder::new(out, flate2::Compression::default());
+    std::io::copy(&mut std::fs::File::open(&rotated)?, &mut encoder)?;
+    encoder.finish()?;
+    std::fs::remove_file(&rotated)?;
+    Ok(())
+}
+
+impl Service {
+    /// Rotate the files of StandardOutput= and StandardError= and open fresh ones
+    pub fn rotate_logs(&mut self, name: &str) -> Result<(), String> {
+        let stdout_path = target_path(&self.service_config.standard_output).cloned();
+        let stderr_path = target_path(&self.service_config.standard_error).cloned();
+        if stdout_path.is_none() && stderr_path.is_none() {
+            return Ok(());
+        }
+
+        // close the old files before moving them
+        self.stdout_file = None;
+        self.stderr_file = None;
+        if let Some(path) = &stdout_path {
+            rotate_file(path).map_err(|e| format!("Could not rotate {:?}: {}", path, e))?;
+        }
+        if let Some(path) = &stderr_path {
+            // stdout and stderr might go to the same file
+            if stderr_path != stdout_path {
+                rotate_file(path).map_err(|e| format!("Could not rotate {:?}: {}", path, e))?;
+            }
+        }
+        trace!("Rotated log files of service {}", name);
+        open_output_files(self)
+    }
+}
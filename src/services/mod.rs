@@ -3,13 +3,22 @@
 //! 2. processgroupid setting
 //! 3. duping of filedescriptors
 //! 4. signaling processgroup on kill
+mod deadlines;
 mod fork_child;
 mod fork_os_specific;
 mod fork_parent;
+mod health_checks;
 mod kill_os_specific;
+mod log_files;
+mod oom_os_specific;
 mod prepare_service;
 mod service_exit_handler;
 mod services;
 mod start_service;
+mod stdout_capture;
+pub use deadlines::*;
+pub use health_checks::*;
+pub use prepare_service::{check_notification_socket_name_template, notification_socket_name};
 pub use service_exit_handler::*;
 pub use services::*;
+pub use stdout_capture::*;
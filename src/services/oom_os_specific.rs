@@ -0,0 +1,23 @@
+use crate::services::Service;
+
+#[cfg(feature = "cgroups")]
+use crate::platform::cgroups;
+
+/// How often the OOM killer killed a process of this service
+#[cfg(feature = "cgroups")]
+pub fn oom_kill_count(srvc: &Service) -> u64 {
+    cgroups::get_oom_kill_count(&srvc.platform_specific.cgroup_path).unwrap_or_else(|e| {
+        trace!(
+            "Could not read the OOM kill count of the service cgroup: {}",
+            e
+        );
+        0
+    })
+}
+
+/// Without cgroups OOM kills can not be detected
+#[cfg(not(feature = "cgroups"))]
+pub fn oom_kill_count(srvc: &Service) -> u64 {
+    let _ = srvc;
+    0
+}
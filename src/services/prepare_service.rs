@@ -1,17 +1,103 @@
 use crate::services::Service;
+use crate::units::StdOutputTarget;
 use std::os::unix::io::AsRawFd;
 use std::os::unix::net::UnixDatagram;
 
+/// Expand the template for the name of the notification socket of a unit (notification_socket_name in the config).
+/// For foo@bar.service %n is foo@bar.service, %N is foo@bar, %p is foo and %i is bar. Units without an '@' have an
+/// empty instance and their prefix is the whole name.
+pub fn notification_socket_name(template: &str, unit_name: &str) -> Result<String, String> {
+    let without_suffix = match unit_name.rfind('.') {
+        Some(pos) => &unit_name[..pos],
+        None => unit_name,
+    };
+    let (prefix, instance) = match without_suffix.find('@') {
+        Some(pos) => (&without_suffix[..pos], &without_suffix[pos + 1..]),
+        None => (without_suffix, ""),
+    };
+
+    let mut socket_name = String::new();
+    let mut chars = template.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            socket_name.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => socket_name.push_str(unit_name),
+            Some('N') => socket_name.push_str(without_suffix),
+            Some('p') => socket_name.push_str(prefix),
+            Some('i') => socket_name.push_str(instance),
+            Some('%') => socket_name.push('%'),
+            Some(other) => {
+                return Err(format!(
+                    "Unknown specifier %{} in notification socket name: {}",
+                    other, template
+                ))
+            }
+            None => {
+                return Err(format!(
+                    "Notification socket name ends with a single %: {}",
+                    template
+                ))
+            }
+        }
+    }
+    // the socket has to end up in the notification socket dir
+    if socket_name.is_empty()
+        || socket_name.contains('/')
+        || socket_name == "."
+        || socket_name == ".."
+    {
+        return Err(format!(
+            "Invalid notification socket name {:?} for unit {}",
+            socket_name, unit_name
+        ));
+    }
+    Ok(socket_name)
+}
+
+/// Check the template for the name of the notification sockets when the config is loaded. It has to be valid and give
+/// each unit its own socket, so it needs %n, %N or both %p and %i
+pub fn check_notification_socket_name_template(template: &str) -> Result<(), String> {
+    notification_socket_name(template, "check@template.service")?;
+    let mut specifiers = Vec::new();
+    let mut chars = template.chars();
+    while let Some(c) = chars.next() {
+        if c == '%' {
+            if let Some(specifier) = chars.next() {
+                specifiers.push(specifier);
+            }
+        }
+    }
+    let has = |specifier| specifiers.contains(&specifier);
+    if has('n') || has('N') || (has('p') && has('i')) {
+        Ok(())
+    } else {
+        Err(format!(
+            "Notification socket name {} would be the same for all units, it needs %n, %N or %p and %i",
+            template
+        ))
+    }
+}
+
 pub fn prepare_service(
     srvc: &mut Service,
     name: &str,
     notification_socket_path: &std::path::PathBuf,
+    socket_name_template: &str,
 ) -> Result<(), String> {
     // setup socket for notifications from the service
     if !notification_socket_path.exists() {
-        std::fs::create_dir_all(notification_socket_path).unwrap();
+        std::fs::create_dir_all(notification_socket_path).map_err(|e| {
+            format!(
+                "Could not create the notification socket dir {:?}: {}",
+                notification_socket_path, e
+            )
+        })?;
     }
-    let daemon_socket_path = notification_socket_path.join(format!("{}.notifiy_socket", &name));
+    let daemon_socket_path =
+        notification_socket_path.join(notification_socket_name(socket_name_template, name)?);
 
     // NOTIFY_SOCKET
     let notify_socket_env_var = if daemon_socket_path.starts_with(".") {
@@ -33,20 +119,100 @@ pub fn prepare_service(
             nix::fcntl::FcntlArg::F_SETFD(nix::fcntl::FdFlag::FD_CLOEXEC),
         )
         .unwrap();
+        crate::notification_handler::enable_sender_credentials(&stream)?;
 
         srvc.notifications = Some(stream);
     }
 
-    if srvc.stdout_dup.is_none() {
+    // StandardOutput=null and StandardError=null get /dev/null instead of a pipe, there would be nothing to forward
+    if srvc.stdout_dup.is_none() && srvc.service_config.standard_output != StdOutputTarget::Null {
         let (r, w) = nix::unistd::pipe().unwrap();
         srvc.stdout_dup = Some((r, w));
     }
-    if srvc.stderr_dup.is_none() {
+    if srvc.stderr_dup.is_none() && srvc.service_config.standard_error != StdOutputTarget::Null {
         let (r, w) = nix::unistd::pipe().unwrap();
         srvc.stderr_dup = Some((r, w));
     }
 
     srvc.notifications_path = Some(notify_socket_env_var);
 
+    create_exec_directories(srvc)?;
+    if let Some(spec) = &srvc.service_config.tmpfiles_d {
+        crate::tmpfiles::apply_spec(spec)?;
+    }
+    // after creating the directories, the files might be in the LogsDirectory=
+    super::log_files::open_output_files(srvc)?;
+
     Ok(())
 }
+
+const RUNTIME_DIRECTORY_BASE: &str = "/run";
+const STATE_DIRECTORY_BASE: &str = "/var/lib";
+const CACHE_DIRECTORY_BASE: &str = "/var/cache";
+const LOGS_DIRECTORY_BASE: &str = "/var/log";
+const CONFIGURATION_DIRECTORY_BASE: &str = "/etc";
+
+fn create_exec_directory(
+    path: &std::path::Path,
+    mode: u32,
+    uid: nix::unistd::Uid,
+    gid: nix::unistd::Gid,
+) -> Result<(), String> {
+    use std::os::unix::fs::PermissionsExt;
+    std::fs::create_dir_all(path)
+        .map_err(|e| format!("Could not create directory {:?}: {}", path, e))?;
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode))
+        .map_err(|e| format!("Could not set mode of directory {:?}: {}", path, e))?;
+    nix::unistd::chown(path, Some(uid), Some(gid))
+        .map_err(|e| format!("Could not change owner of directory {:?}: {}", path, e))?;
+    Ok(())
+}
+
+/// Create the RuntimeDirectory=, StateDirectory=, CacheDirectory= and LogsDirectory= directories owned by the User=/Group= of the service.
+/// ConfigurationDirectory= directories stay owned by root. Existing directories and their content are kept.
+fn create_exec_directories(srvc: &Service) -> Result<(), String> {
+    let conf = &srvc.service_config;
+    let runtime_mode = conf.runtime_directory_mode.unwrap_or(0o755);
+    for dir in &conf.runtime_directory {
+        let path = std::path::Path::new(RUNTIME_DIRECTORY_BASE).join(dir);
+        create_exec_directory(&path, runtime_mode, srvc.uid, srvc.gid)?;
+    }
+    for dir in &conf.state_directory {
+        let path = std::path::Path::new(STATE_DIRECTORY_BASE).join(dir);
+        create_exec_directory(&path, 0o755, srvc.uid, srvc.gid)?;
+    }
+    for dir in &conf.cache_directory {
+        let path = std::path::Path::new(CACHE_DIRECTORY_BASE).join(dir);
+        create_exec_directory(&path, 0o755, srvc.uid, srvc.gid)?;
+    }
+    for dir in &conf.logs_directory {
+        let path = std::path::Path::new(LOGS_DIRECTORY_BASE).join(dir);
+        create_exec_directory(&path, 0o755, srvc.uid, srvc.gid)?;
+    }
+    for dir in &conf.configuration_directory {
+        let path = std::path::Path::new(CONFIGURATION_DIRECTORY_BASE).join(dir);
+        create_exec_directory(
+            &path,
+            0o755,
+            nix::unistd::Uid::from_raw(0),
+            nix::unistd::Gid::from_raw(0),
+        )?;
+    }
+    Ok(())
+}
+
+/// Remove the RuntimeDirectory= directories after the service stopped. State and cache directories are kept.
+pub fn remove_runtime_directories(srvc: &Service, name: &str) {
+    for dir in &srvc.service_config.runtime_directory {
+        let path = std::path::Path::new(RUNTIME_DIRECTORY_BASE).join(dir);
+        if !path.exists() {
+            continue;
+        }
+        if let Err(e) = std::fs::remove_dir_all(&path) {
+            warn!(
+                "Could not remove runtime directory {:?} of service {}: {}",
+                path, name, e
+            );
+        }
+    }
+}
@@ -1,4 +1,5 @@
 use crate::platform::EventFd;
+use crate::services::RestartReason;
 use crate::signal_handler::ChildTermination;
 use crate::units::*;
 use std::sync::Arc;
@@ -45,6 +46,7 @@ pub fn service_exit_handler(
                     );
                     // this will be collected by the thread that waits for the helper process to exit
                     pid_table_locked.insert(pid, PidEntry::HelperExited(code));
+                    run_info.pid_table.notify_exited();
                     return Ok(());
                 }
                 PidEntry::HelperExited(_) => {
@@ -79,6 +81,8 @@ pub fn service_exit_handler(
                         trace!("Save oneshot service as exited. PID: {}", pid);
                         pid_table_locked.insert(pid, PidEntry::OneshotExited(code));
                     }
+                    // wakes up wait_for for oneshots and wait_for_service_exit for the others
+                    run_info.pid_table.notify_exited();
                     id
                 }
                 PidEntry::Helper(_id, _srvc_name) => {
@@ -111,6 +115,13 @@ pub fn service_exit_handler(
     {
         let unit_locked = &mut *unit.lock().unwrap();
         if let UnitSpecialized::Service(srvc) = &mut unit_locked.specialized {
+            srvc.runtime_info.last_exit = Some(code);
+            srvc.runtime_info.exited_at = Some(std::time::Instant::now());
+            run_info
+                .events
+                .publish(&unit_locked.conf.name(), UnitEventKind::Exited(code));
+            // wake up the handlers of the notification sockets and stdout/stderr so they rebuild their fd sets
+            crate::platform::notify_event_fds(eventfds);
             if srvc.service_config.srcv_type == ServiceType::OneShot {
                 srvc.kill_all_remaining_processes(&unit_locked.conf.name());
                 return Ok(());
@@ -119,40 +130,82 @@ pub fn service_exit_handler(
     }
 
     trace!("Check if we want to restart the unit");
-    let (name, sockets, restart_unit) = {
+    let (name, sockets, restart_unit, success) = {
         let unit_locked = &mut *unit.lock().unwrap();
         let name = unit_locked.conf.name();
         if let UnitSpecialized::Service(srvc) = &mut unit_locked.specialized {
+            // SuccessExitStatus= decides whether this exit counts as a failure
+            let success = srvc.service_config.is_success(&code);
             trace!(
-                "Service with id: {:?}, name: {} pid: {} exited with: {:?}",
+                "Service with id: {:?}, name: {} pid: {} exited with: {:?} (success: {})",
                 srvc_id,
                 unit_locked.conf.name(),
                 pid,
-                code
+                code,
+                success
             );
 
-            if srvc.service_config.restart == ServiceRestart::Always {
+            let oom_killed = srvc.was_oom_killed();
+            let oom_policy = srvc.service_config.oom_policy;
+            if oom_killed {
+                warn!(
+                    "Service {} had a process killed by the OOM killer. OOMPolicy is: {:?}",
+                    name, oom_policy
+                );
+                if oom_policy == OomPolicy::Kill {
+                    srvc.kill_all_remaining_processes(&name);
+                }
+            }
+
+            // OOMPolicy=stop/kill always prevents the restart so a service that is too big does not end up in a restart loop
+            if srvc.service_config.should_restart(&code)
+                && !(oom_killed && oom_policy != OomPolicy::Continue)
+            {
                 let sockets = srvc.socket_names.clone();
-                (name, sockets, true)
+                (name, sockets, true, success)
             } else {
-                (name, Vec::new(), false)
+                (name, Vec::new(), false, success)
             }
         } else {
-            (name, Vec::new(), false)
+            (name, Vec::new(), false, true)
         }
     };
 
     // check that the status is "Started". If thats not the case this service got killed by something else (control interface for example) so dont interfere
     {
-        let status_table_locked = run_info.status_table.read().unwrap();
-        let status_locked = &*status_table_locked.get(&srvc_id).unwrap().lock().unwrap();
-        if *status_locked != UnitStatus::Started && *status_locked != UnitStatus::Starting {
-            trace!("Exit handler ignores exit of service {}. Its status is not 'Started/Starting', it is: {:?}", name, *status_locked);
+        let status = run_info.current_status(&srvc_id).unwrap();
+        if status != UnitStatus::Started
+            && status != UnitStatus::Starting
+            && status != UnitStatus::Reloading
+        {
+            trace!("Exit handler ignores exit of service {}. Its status is not 'Started/Starting', it is: {:?}", name, status);
             return Ok(());
         }
     }
 
-    if restart_unit {
+    let start_limit_action = if restart_unit {
+        let unit_locked = &mut *unit.lock().unwrap();
+        if let UnitSpecialized::Service(srvc) = &mut unit_locked.specialized {
+            if srvc.record_restart(std::time::Instant::now()) {
+                srvc.set_restart_reason(&name, RestartReason::MainProcessExited(code));
+                run_info.events.publish(
+                    &name,
+                    UnitEventKind::Restarted(RestartReason::MainProcessExited(code)),
+                );
+                None
+            } else {
+                Some(srvc.service_config.start_limit_action)
+            }
+        } else {
+            None
+        }
+    } else {
+        None
+    };
+
+    if let Some(action) = start_limit_action {
+        fail_after_start_limit(srvc_id, &name, action, run_info.clone())?;
+    } else if restart_unit {
         {
             // tell socket activation to listen to these sockets again
             for unit in run_info.unit_table.read().unwrap().values() {
@@ -172,6 +225,17 @@ pub fn service_exit_handler(
             Arc::new(eventfds.to_vec()),
         )
         .map_err(|e| format!("{}", e))?;
+    } else if !success {
+        warn!("Service {} failed with: {:?}", name, code);
+        crate::units::deactivate_failed_unit(srvc_id, run_info.clone())
+            .map_err(|e| format!("{}", e))?;
+        let status_table_locked = run_info.status_table.read().unwrap();
+        *status_table_locked.get(&srvc_id).unwrap().lock().unwrap() =
+            UnitStatus::Failed(format!("Exited with: {:?}", code));
+        run_info.events.publish(
+            &name,
+            UnitEventKind::Failed(format!("Exited with: {:?}", code)),
+        );
     } else {
         trace!(
             "Recursively killing all services requiring service {}",
@@ -182,3 +246,35 @@ pub fn service_exit_handler(
     }
     Ok(())
 }
+
+/// The service should have been restarted but hit its start limit (StartLimitBurst= / StartLimitIntervalSec=). Stop it
+/// and the units that require it, mark it as failed and execute its StartLimitAction=
+pub fn fail_after_start_limit(
+    srvc_id: UnitId,
+    name: &str,
+    action: StartLimitAction,
+    run_info: ArcRuntimeInfo,
+) -> Result<(), String> {
+    warn!(
+        "Service {} was restarted too often (StartLimitBurst= / StartLimitIntervalSec=). Not restarting it again",
+        name
+    );
+    crate::units::deactivate_unit_recursive(srvc_id, true, run_info.clone())
+        .map_err(|e| format!("{}", e))?;
+    {
+        let status_table_locked = run_info.status_table.read().unwrap();
+        *status_table_locked.get(&srvc_id).unwrap().lock().unwrap() =
+            UnitStatus::Failed("Start limit hit".into());
+    }
+    run_info
+        .events
+        .publish(name, UnitEventKind::Failed("Start limit hit".into()));
+    if action != StartLimitAction::None {
+        warn!(
+            "Executing StartLimitAction={:?} of service {}",
+            action, name
+        );
+        crate::shutdown::run_start_limit_action(action, run_info);
+    }
+    Ok(())
+}
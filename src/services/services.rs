@@ -1,15 +1,120 @@
 use super::start_service::*;
 use crate::platform::EventFd;
+use crate::syslog_sink::{SyslogFacility, SyslogSeverity};
 use crate::units::*;
 use std::io::Write;
 use std::os::unix::io::RawFd;
 use std::os::unix::net::UnixDatagram;
 use std::process::{Command, Stdio};
 
+/// Why a service was restarted the last time
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum RestartReason {
+    /// The main process terminated (exited or crashed) and Restart= asked for a restart
+    MainProcessExited(crate::signal_handler::ChildTermination),
+    /// The watchdog timed out. Nothing sets this yet, WatchdogSec= is not supported
+    Watchdog,
+    /// The service ran for longer than RuntimeMaxSec= and Restart= asked for a restart
+    RuntimeMaxExceeded,
+    /// Restarted through the control interface
+    Manual,
+    /// A unit this service requires was restarted (contains the name of that unit)
+    Dependency(String),
+    /// ExecHealthCheck= failed too often in a row
+    HealthCheckFailed,
+}
+
+impl std::fmt::Display for RestartReason {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            RestartReason::MainProcessExited(crate::signal_handler::ChildTermination::Exit(
+                code,
+            )) => {
+                write!(fmt, "main process exited with code {}", code)
+            }
+            RestartReason::MainProcessExited(crate::signal_handler::ChildTermination::Signal(
+                signal,
+            )) => write!(fmt, "main process was killed by signal {:?}", signal),
+            RestartReason::Watchdog => write!(fmt, "watchdog timeout"),
+            RestartReason::RuntimeMaxExceeded => write!(fmt, "RuntimeMaxSec reached"),
+            RestartReason::Manual => write!(fmt, "manual restart"),
+            RestartReason::Dependency(unit_name) => {
+                write!(fmt, "required unit {} was restarted", unit_name)
+            }
+            RestartReason::HealthCheckFailed => write!(fmt, "health check failed"),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct ServiceRuntimeInfo {
     pub restarted: u64,
+    pub last_restart_reason: Option<RestartReason>,
     pub up_since: Option<std::time::Instant>,
+    /// How the main process of the service terminated the last time it exited
+    pub last_exit: Option<crate::signal_handler::ChildTermination>,
+    pub exited_at: Option<std::time::Instant>,
+    /// The count of OOM kills in the cgroup of the service when it was started
+    pub oom_kills: u64,
+    /// When the service was restarted within the last StartLimitIntervalSec=
+    pub restart_times: std::collections::VecDeque<std::time::Instant>,
+    /// How many ExecHealthCheck= runs failed in a row
+    pub health_check_failures: u32,
+    pub last_health_check: Option<std::time::Instant>,
+    /// The main process was started by a previous rustysd process and taken over from the state file. The activation
+    /// during the boot leaves it running
+    pub adopted: bool,
+    /// Pid and start time of the adopted main process. It might not be a child of rustysd, so its exit is noticed by
+    /// checking /proc instead of by the signal handler
+    pub adopted_main_process: Option<(nix::unistd::Pid, u64)>,
+}
+
+/// Counts the lines of output of a service in the current rate limiting window
+#[derive(Debug, Default)]
+pub struct LogRateLimitState {
+    window_start: Option<std::time::Instant>,
+    lines: u32,
+    suppressed: u64,
+}
+
+impl LogRateLimitState {
+    /// Decide if one more line may be logged at `now`. Also returns how many lines were suppressed in the previous window
+    /// if a new window just started, so that can be reported once.
+    pub fn allow(
+        &mut self,
+        now: std::time::Instant,
+        interval: Option<std::time::Duration>,
+        burst: Option<u32>,
+    ) -> (bool, u64) {
+        if interval.is_none() && burst.is_none() {
+            return (true, 0);
+        }
+        // same defaults as systemd-journald uses
+        let interval = interval.unwrap_or(std::time::Duration::from_secs(30));
+        let burst = burst.unwrap_or(10000);
+        if interval.as_nanos() == 0 || burst == 0 {
+            return (true, 0);
+        }
+
+        let mut reported = 0;
+        let window_over = match self.window_start {
+            Some(start) => now.duration_since(start) >= interval,
+            None => true,
+        };
+        if window_over {
+            reported = self.suppressed;
+            self.window_start = Some(now);
+            self.lines = 0;
+            self.suppressed = 0;
+        }
+        if self.lines < burst {
+            self.lines += 1;
+            (true, reported)
+        } else {
+            self.suppressed += 1;
+            (false, reported)
+        }
+    }
 }
 
 #[cfg(target_os = "linux")]
@@ -22,6 +127,13 @@ pub struct PlatformSpecificServiceFields {
 #[derive(Debug)]
 pub struct PlatformSpecificServiceFields {}
 
+/// Used for StartLimitIntervalSec= and StartLimitBurst= if only one of them is set
+const DEFAULT_START_LIMIT_INTERVAL: std::time::Duration = std::time::Duration::from_secs(10);
+const DEFAULT_START_LIMIT_BURST: u32 = 5;
+
+/// How long to wait after sending SIGTERM if neither TimeoutStopSec= nor TimeoutSec= are set
+const DEFAULT_TERMINATE_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+
 #[derive(Debug)]
 pub struct Service {
     pub pid: Option<nix::unistd::Pid>,
@@ -35,6 +147,8 @@ pub struct Service {
 
     pub runtime_info: ServiceRuntimeInfo,
     pub signaled_ready: bool,
+    /// Set when the service sent RELOADING=1. Only used for Type=notify-reload
+    pub signaled_reloading: bool,
 
     pub notifications: Option<UnixDatagram>,
     pub notifications_path: Option<std::path::PathBuf>,
@@ -44,9 +158,17 @@ pub struct Service {
     pub notifications_buffer: String,
     pub stdout_buffer: Vec<u8>,
     pub stderr_buffer: Vec<u8>,
+    pub log_rate_limit: LogRateLimitState,
+    /// The most recent lines of stdout, can be queried with the logs command of the control interface
+    pub stdout_capture: super::StdoutCapture,
+    /// Open files for StandardOutput=file:... and StandardError=file:...
+    pub stdout_file: Option<std::fs::File>,
+    pub stderr_file: Option<std::fs::File>,
     pub uid: nix::unistd::Uid,
     pub gid: nix::unistd::Gid,
     pub supp_gids: Vec<nix::unistd::Gid>,
+    /// The namespaces of JoinsNamespaceOf= the next started main process enters. Opened right before each start
+    pub joined_namespaces: Vec<std::fs::File>,
 
     pub platform_specific: PlatformSpecificServiceFields,
 }
@@ -58,6 +180,7 @@ pub enum RunCmdError {
     SpawnError(String, String),
     WaitError(String, String),
     BadExitCode(String, crate::signal_handler::ChildTermination),
+    ReloadSignalFailed(String, String),
     Generic(String),
 }
 
@@ -71,6 +194,9 @@ impl std::fmt::Display for RunCmdError {
                 format!("{} could not be waited on because: {:?}", cmd, err)
             }
             RunCmdError::Timeout(cmd, err) => format!("{} reached its timeout: {:?}", cmd, err),
+            RunCmdError::ReloadSignalFailed(cmd, err) => {
+                format!("{} could not be signaled to reload: {}", cmd, err)
+            }
             RunCmdError::Generic(err) => format!("Generic error: {}", err),
         };
         fmt.write_str(format!("{}", msg).as_str())
@@ -80,15 +206,20 @@ impl std::fmt::Display for RunCmdError {
 pub enum StartResult {
     Started,
     WaitingForSocket,
+    /// An ExecCondition= command exited with 1 to 254. The service has not been started but did not fail either
+    Ignored,
 }
 
 pub enum ServiceErrorReason {
+    ConditionFailed(RunCmdError),
     PrestartFailed(RunCmdError),
     PoststartFailed(RunCmdError),
     StartFailed(RunCmdError),
     PoststopFailed(RunCmdError),
     StopFailed(RunCmdError),
+    ReloadFailed(RunCmdError),
 
+    ConditionAndPoststopFailed(RunCmdError, RunCmdError),
     PrestartAndPoststopFailed(RunCmdError, RunCmdError),
     PoststartAndPoststopFailed(RunCmdError, RunCmdError),
     StartAndPoststopFailed(RunCmdError, RunCmdError),
@@ -103,13 +234,18 @@ impl std::fmt::Display for ServiceErrorReason {
     fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
         let msg = match self {
             // one failed
+            ServiceErrorReason::ConditionFailed(e) => format!("Condition failed: {}", e),
             ServiceErrorReason::PrestartFailed(e) => format!("Perstart failed: {}", e),
             ServiceErrorReason::PoststartFailed(e) => format!("Poststart failed: {}", e),
             ServiceErrorReason::StartFailed(e) => format!("Start failed: {}", e),
             ServiceErrorReason::StopFailed(e) => format!("Stop failed: {}", e),
+            ServiceErrorReason::ReloadFailed(e) => format!("Reload failed: {}", e),
             ServiceErrorReason::PoststopFailed(e) => format!("Poststop failed: {}", e),
 
             // Both failed
+            ServiceErrorReason::ConditionAndPoststopFailed(e, e2) => {
+                format!("Condition failed: {} and Poststop failed too: {}", e, e2)
+            }
             ServiceErrorReason::PrestartAndPoststopFailed(e, e2) => {
                 format!("Perstart failed: {} and Poststop failed too: {}", e, e2)
             }
@@ -163,8 +299,30 @@ impl Service {
         if !allow_ignore || self.socket_names.is_empty() {
             trace!("Start service {}", name);
 
-            super::prepare_service::prepare_service(self, name, &notification_socket_path)
-                .map_err(|e| ServiceErrorReason::PreparingFailed(e))?;
+            super::prepare_service::prepare_service(
+                self,
+                name,
+                &notification_socket_path,
+                &run_info.config.notification_socket_name,
+            )
+            .map_err(|e| ServiceErrorReason::PreparingFailed(e))?;
+            self.runtime_info.oom_kills = super::oom_os_specific::oom_kill_count(self);
+            let condition_met = self.run_condition(id, name, run_info.clone()).map_err(
+                |condition_err| match self.run_poststop(id, name, run_info.clone()) {
+                    Ok(_) => ServiceErrorReason::ConditionFailed(condition_err),
+                    Err(poststop_err) => {
+                        ServiceErrorReason::ConditionAndPoststopFailed(condition_err, poststop_err)
+                    }
+                },
+            )?;
+            if !condition_met {
+                trace!(
+                    "Service {} is skipped because an ExecCondition= was not met",
+                    name
+                );
+                self.close_notification_socket(name);
+                return Ok(StartResult::Ignored);
+            }
             self.run_prestart(id, name, run_info.clone())
                 .map_err(
                     |prestart_err| match self.run_poststop(id, name, run_info.clone()) {
@@ -188,17 +346,33 @@ impl Service {
                         PidEntry::Service(id, self.service_config.srcv_type),
                     );
                     crate::platform::notify_event_fds(&eventfds);
+                    run_info
+                        .events
+                        .publish(name, crate::units::UnitEventKind::Started(new_pid));
                 }
             }
 
-            super::fork_parent::wait_for_service(self, name, run_info.pid_table.clone()).map_err(
-                |start_err| match self.run_poststop(id, name, run_info.clone()) {
+            super::fork_parent::wait_for_service(self, name, &run_info).map_err(|start_err| {
+                match self.run_poststop(id, name, run_info.clone()) {
                     Ok(_) => ServiceErrorReason::StartFailed(start_err),
                     Err(poststop_err) => {
                         ServiceErrorReason::StartAndPoststopFailed(start_err, poststop_err)
                     }
-                },
-            )?;
+                }
+            })?;
+            self.run_additional_exec(id, name, run_info.clone())
+                .map_err(
+                    |start_err| match self.run_poststop(id, name, run_info.clone()) {
+                        Ok(_) => ServiceErrorReason::StartFailed(start_err),
+                        Err(poststop_err) => {
+                            ServiceErrorReason::StartAndPoststopFailed(start_err, poststop_err)
+                        }
+                    },
+                )?;
+            // the service is running now (or for oneshot services: has run successfully)
+            self.runtime_info.up_since = Some(std::time::Instant::now());
+            self.runtime_info.health_check_failures = 0;
+            self.runtime_info.last_health_check = None;
             self.run_poststart(id, name, run_info.clone())
                 .map_err(
                     |poststart_err| match self.run_poststop(id, name, run_info.clone()) {
@@ -220,17 +394,143 @@ impl Service {
         }
     }
 
+    /// Reload a running service. If ExecReload= is set its commands are run, the main process keeps running
+    /// meanwhile. Otherwise Type=notify-reload services get the ReloadSignal= and then the notification socket is
+    /// watched until the service signals RELOADING=1 followed by READY=1 (or the start timeout is reached). All
+    /// other services get SIGHUP.
+    pub fn reload(
+        &mut self,
+        id: UnitId,
+        name: &str,
+        run_info: ArcRuntimeInfo,
+    ) -> Result<(), ServiceErrorReason> {
+        let pid = match self.pid {
+            Some(pid) => pid,
+            None => {
+                return Err(ServiceErrorReason::Generic(format!(
+                    "Service {} is not running",
+                    name
+                )))
+            }
+        };
+
+        if !self.service_config.reload.is_empty() {
+            let timeout = self.get_start_timeout();
+            let cmds = self.service_config.reload.clone();
+            self.run_all_cmds(&cmds, id, name, timeout, run_info)
+                .map_err(ServiceErrorReason::ReloadFailed)?;
+            trace!("Service {} finished reloading", name);
+            return Ok(());
+        }
+
+        if self.service_config.srcv_type != ServiceType::NotifyReload {
+            trace!(
+                "Send SIGHUP to service {} (pid: {}) to request a reload",
+                name,
+                pid
+            );
+            return nix::sys::signal::kill(pid, nix::sys::signal::Signal::SIGHUP).map_err(|e| {
+                ServiceErrorReason::ReloadFailed(RunCmdError::ReloadSignalFailed(
+                    self.service_config.exec.to_string(),
+                    format!("{}", e),
+                ))
+            });
+        }
+
+        self.signaled_ready = false;
+        self.signaled_reloading = false;
+        trace!(
+            "Send {:?} to service {} (pid: {}) to request a reload",
+            self.service_config.reload_signal,
+            name,
+            pid
+        );
+        nix::sys::signal::kill(pid, self.service_config.reload_signal).map_err(|e| {
+            ServiceErrorReason::ReloadFailed(RunCmdError::ReloadSignalFailed(
+                self.service_config.exec.to_string(),
+                format!("{}", e),
+            ))
+        })?;
+
+        let timeout = self.get_start_timeout();
+        super::fork_parent::wait_for_notification(
+            self,
+            name,
+            timeout,
+            &run_info.pid_table,
+            |srvc| srvc.signaled_reloading && srvc.signaled_ready,
+        )
+        .map_err(ServiceErrorReason::ReloadFailed)?;
+        self.signaled_ready = false;
+        self.signaled_reloading = false;
+        trace!("Service {} finished reloading", name);
+        Ok(())
+    }
+
+    /// Send the signal to the processes selected by KillMode=. The cgroup (if any) is only signaled, not removed
+    fn signal_remaining_processes(&mut self, name: &str, signal: nix::sys::signal::Signal) {
+        match self.service_config.kill_mode {
+            KillMode::None => {
+                trace!(
+                    "KillMode of service {} is none, not sending {:?} to any process",
+                    name,
+                    signal
+                );
+                return;
+            }
+            KillMode::Process => {
+                if let Some(pid) = self.pid {
+                    match nix::sys::signal::kill(pid, signal) {
+                        Ok(_) => trace!("Sent {:?} to main process of service {}", signal, name),
+                        Err(e) => error!(
+                            "Error sending {:?} to main process of service {}: {}",
+                            signal, name, e
+                        ),
+                    }
+                }
+                return;
+            }
+            KillMode::ProcessGroup | KillMode::ControlGroup => {}
+        }
+        if let Some(proc_group) = self.process_group {
+            // TODO handle these errors
+            match nix::sys::signal::kill(proc_group, signal) {
+                Ok(_) => trace!("Sent {:?} to process group of service {}", signal, name),
+                Err(e) => error!(
+                    "Error sending {:?} to process group of service {}: {}",
+                    signal, name, e
+                ),
+            }
+        } else {
+            trace!("Tried to signal service that didn't have a process-group. This might have resulted in orphan processes.");
+        }
+        if self.service_config.kill_mode == KillMode::ControlGroup {
+            if let Err(e) = super::kill_os_specific::signal(self, signal) {
+                error!(
+                    "Error sending {:?} os specificly for service {}: {}",
+                    signal, name, e
+                );
+            }
+        }
+    }
+
+    /// Send the FinalKillSignal= to the processes selected by KillMode=
     pub fn kill_all_remaining_processes(&mut self, name: &str) {
+        let signal = self.service_config.final_kill_signal;
+        if self.service_config.kill_mode != KillMode::ControlGroup {
+            self.signal_remaining_processes(name, signal);
+            return;
+        }
         if let Some(proc_group) = self.process_group {
             // TODO handle these errors
-            match nix::sys::signal::kill(proc_group, nix::sys::signal::Signal::SIGKILL) {
+            match nix::sys::signal::kill(proc_group, signal) {
                 Ok(_) => trace!("Success killing process group for service {}", name,),
                 Err(e) => error!("Error killing process group for service {}: {}", name, e,),
             }
         } else {
             trace!("Tried to kill service that didn't have a process-group. This might have resulted in orphan processes.");
         }
-        match super::kill_os_specific::kill(self, nix::sys::signal::Signal::SIGKILL) {
+        match super::kill_os_specific::kill(self, signal) {
             Ok(_) => trace!("Success killing process os specificly for service {}", name,),
             Err(e) => error!(
                 "Error killing process os specificly for service {}: {}",
@@ -239,6 +539,171 @@ impl Service {
         }
     }
 
+    /// Record a restart at now. Returns false (without recording it) if the service has already been restarted
+    /// StartLimitBurst= times within the last StartLimitIntervalSec=
+    pub fn record_restart(&mut self, now: std::time::Instant) -> bool {
+        let conf = &self.service_config;
+        if conf.start_limit_interval.is_some() || conf.start_limit_burst.is_some() {
+            let interval = conf
+                .start_limit_interval
+                .unwrap_or(DEFAULT_START_LIMIT_INTERVAL);
+            let burst = conf.start_limit_burst.unwrap_or(DEFAULT_START_LIMIT_BURST);
+            let times = &mut self.runtime_info.restart_times;
+            while let Some(oldest) = times.front() {
+                if now.duration_since(*oldest) >= interval {
+                    times.pop_front();
+                } else {
+                    break;
+                }
+            }
+            if times.len() >= burst as usize {
+                return false;
+            }
+            times.push_back(now);
+        }
+        self.runtime_info.restarted += 1;
+        true
+    }
+
+    /// Remember and log why the service is being restarted
+    pub fn set_restart_reason(&mut self, name: &str, reason: RestartReason) {
+        info!("Restarting service {}: {}", name, reason);
+        self.runtime_info.last_restart_reason = Some(reason);
+    }
+
+    /// Whether the process belongs to the running service: it is the main process, in the process group of the main
+    /// process or in the cgroup of the service
+    pub fn owns_process(&self, pid: nix::unistd::Pid) -> bool {
+        if self.pid == Some(pid) {
+            return true;
+        }
+        // process_group is saved negated so it can be used with kill()
+        if let (Some(pgid), Ok(pid_pgid)) = (self.process_group, nix::unistd::getpgid(Some(pid))) {
+            if pid_pgid.as_raw() == -pgid.as_raw() {
+                return true;
+            }
+        }
+        super::kill_os_specific::contains(self, pid)
+    }
+
+    /// Whether the OOM killer killed a process of this service since it was started
+    pub fn was_oom_killed(&self) -> bool {
+        super::oom_os_specific::oom_kill_count(self) > self.runtime_info.oom_kills
+    }
+
+    /// Whether the service has been running for longer than RuntimeMaxSec= allows
+    pub fn runtime_max_exceeded(&self, now: std::time::Instant) -> bool {
+        match (&self.service_config.runtime_max, self.runtime_info.up_since) {
+            (Some(Timeout::Duration(max)), Some(up_since)) => now.duration_since(up_since) >= *max,
+            _ => false,
+        }
+    }
+
+    /// Whether ExecHealthCheck= is set and the last check (or the start) was at least the interval ago
+    pub fn health_check_due(&self, now: std::time::Instant) -> bool {
+        if self.service_config.health_check.is_none() {
+            return false;
+        }
+        match self
+            .runtime_info
+            .last_health_check
+            .or(self.runtime_info.up_since)
+        {
+            Some(last) => now.duration_since(last) >= self.service_config.health_check_interval,
+            None => false,
+        }
+    }
+
+    /// Whether ExecHealthCheck= failed ExecHealthCheckMaxFailures= times in a row
+    pub fn is_unhealthy(&self) -> bool {
+        self.service_config.health_check.is_some()
+            && self.runtime_info.health_check_failures
+                >= self.service_config.health_check_max_failures
+    }
+
+    /// Run ExecHealthCheck= once and count the failures in a row. The check gets the interval as timeout so checks
+    /// never overlap
+    pub fn run_health_check(
+        &mut self,
+        id: UnitId,
+        name: &str,
+        run_info: ArcRuntimeInfo,
+    ) -> Result<(), RunCmdError> {
+        let cmd = match &self.service_config.health_check {
+            Some(cmd) => cmd.clone(),
+            None => return Ok(()),
+        };
+        let timeout = Some(self.service_config.health_check_interval);
+        let res = self.run_cmd(&cmd, id, name, timeout, run_info);
+        self.runtime_info.last_health_check = Some(std::time::Instant::now());
+        match &res {
+            Ok(()) => self.runtime_info.health_check_failures = 0,
+            Err(e) => {
+                self.runtime_info.health_check_failures += 1;
+                trace!(
+                    "ExecHealthCheck of service {} failed ({} of {}): {}",
+                    name,
+                    self.runtime_info.health_check_failures,
+                    self.service_config.health_check_max_failures,
+                    e
+                );
+            }
+        }
+        res
+    }
+
+    /// Send SIGTERM to the processes selected by KillMode= and wait for the main process to exit (at most the stop timeout).
+    /// Returns whether it exited in time. Killing all remaining processes is left to the rest of the stop.
+    pub fn terminate(&mut self, name: &str, pid_table: &PidTable) -> bool {
+        if self.service_config.kill_mode == KillMode::None {
+            return true;
+        }
+        let pid = match self.pid {
+            Some(pid) => pid,
+            None => return true,
+        };
+        self.signal_remaining_processes(name, nix::sys::signal::Signal::SIGTERM);
+        let timeout = self.get_stop_timeout().unwrap_or(DEFAULT_TERMINATE_TIMEOUT);
+        // the process is gone once it has been reaped by the signal handler
+        let exited = match self.runtime_info.adopted_main_process {
+            Some((adopted_pid, start_time)) if adopted_pid == pid => {
+                crate::state_persist::wait_for_adopted_exit(pid_table, pid, start_time, timeout)
+            }
+            _ => pid_table.wait_for_service_exit(pid, timeout),
+        };
+        if exited {
+            return true;
+        }
+        trace!(
+            "Service {} did not exit after SIGTERM in {:?}",
+            name,
+            timeout
+        );
+        // Terminate means giving up here, SIGTERM has already been sent
+        if self.service_config.timeout_stop_failure_mode != TimeoutFailureMode::Terminate {
+            self.signal_after_timeout(name, self.service_config.timeout_stop_failure_mode);
+        }
+        false
+    }
+
+    /// Send the signal that belongs to the TimeoutStartFailureMode=/TimeoutStopFailureMode= to the main process
+    pub fn signal_after_timeout(&self, name: &str, mode: TimeoutFailureMode) {
+        let signal = match mode {
+            TimeoutFailureMode::Terminate => nix::sys::signal::Signal::SIGTERM,
+            TimeoutFailureMode::Kill => nix::sys::signal::Signal::SIGKILL,
+            TimeoutFailureMode::Abort => nix::sys::signal::Signal::SIGABRT,
+        };
+        if let Some(pid) = self.pid {
+            warn!(
+                "Service {} reached its timeout. Sending {:?} to the main process",
+                name, signal
+            );
+            if let Err(e) = nix::sys::signal::kill(pid, signal) {
+                trace!("Could not send {:?} to service {}: {}", signal, name, e);
+            }
+        }
+    }
+
     fn stop(
         &mut self,
         id: UnitId,
@@ -249,14 +714,49 @@ impl Service {
 
         if self.service_config.srcv_type != ServiceType::OneShot {
             // already happened when the oneshot process exited in the exit handler
-            self.kill_all_remaining_processes(name);
+            self.terminate(name, &run_info.pid_table);
+            if self.service_config.send_sighup {
+                self.signal_remaining_processes(name, nix::sys::signal::Signal::SIGHUP);
+            }
+            if self.service_config.send_sigkill {
+                self.kill_all_remaining_processes(name);
+            }
         }
 
         self.pid = None;
         self.process_group = None;
+        self.runtime_info.up_since = None;
+        self.runtime_info.adopted_main_process = None;
+        super::prepare_service::remove_runtime_directories(self, name);
+        self.close_notification_socket(name);
         stop_res
     }
 
+    /// Close the notification socket and remove its file. prepare_service creates a new one on the next start
+    pub fn close_notification_socket(&mut self, name: &str) {
+        if let Some(datagram) = self.notifications.take() {
+            match datagram.shutdown(std::net::Shutdown::Both) {
+                Ok(()) => trace!("Closed notification socket for service unit: {}", name),
+                Err(e) => error!(
+                    "Error closing notification socket for service unit {}: {}",
+                    name, e
+                ),
+            }
+        }
+        self.notifications_buffer.clear();
+        if let Some(note_sock_path) = self.notifications_path.take() {
+            if note_sock_path.exists() {
+                match std::fs::remove_file(&note_sock_path) {
+                    Ok(()) => trace!("Removed notification socket for service unit: {}", name),
+                    Err(e) => error!(
+                        "Error removing notification socket for service unit {}: {}",
+                        name, e
+                    ),
+                }
+            }
+        }
+    }
+
     pub fn kill(
         &mut self,
         id: UnitId,
@@ -340,6 +840,19 @@ impl Service {
         cmd.stdout(Stdio::piped());
         cmd.stderr(Stdio::piped());
         cmd.stdin(Stdio::null());
+        if self.service_config.pass_environment.is_some()
+            || !self.service_config.unset_environment.is_empty()
+        {
+            cmd.env_clear();
+            cmd.envs(
+                std::env::vars_os().filter(|(var, _)| self.service_config.passes_env_var(var)),
+            );
+        }
+        let env_file_vars = self
+            .service_config
+            .environment_file_vars()
+            .map_err(RunCmdError::Generic)?;
+        cmd.envs(env_file_vars);
         trace!("Run {:?} for service: {}", cmdline, name);
         let spawn_result = {
             let mut pid_table_locked = run_info.pid_table.lock().unwrap();
@@ -354,11 +867,12 @@ impl Service {
         };
         match spawn_result {
             Ok(mut child) => {
+                // read the output while waiting, a helper that fills a pipe buffer would block until the timeout
+                let stdout_reader = child.stdout.take().map(drain_in_thread);
+                let stderr_reader = child.stderr.take().map(drain_in_thread);
                 trace!("Wait for {:?} for service: {}", cmdline, name);
                 let wait_result: Result<(), RunCmdError> = match wait_for_helper_child(
-                    &mut child,
-                    run_info.pid_table.clone(),
-                    timeout,
+                    &mut child, &run_info, timeout,
                 ) {
                     WaitResult::InTime(Err(e)) => {
                         return Err(RunCmdError::WaitError(
@@ -367,7 +881,7 @@ impl Service {
                         ));
                     }
                     WaitResult::InTime(Ok(exitstatus)) => {
-                        if exitstatus.success() {
+                        if self.service_config.is_success(&exitstatus) {
                             trace!("success running {:?} for service: {}", cmdline, name);
                             Ok(())
                         } else {
@@ -400,20 +914,18 @@ impl Service {
                     }
                 };
                 {
-                    let status_table_locked = run_info.status_table.read().unwrap();
-                    let status = status_table_locked.get(&id).unwrap().lock().unwrap();
-                    use std::io::Read;
-                    if let Some(stream) = &mut child.stderr {
-                        let mut buf = Vec::new();
-                        let _bytes = stream.read_to_end(&mut buf).unwrap();
-                        self.stderr_buffer.extend(buf);
-                        self.log_stderr_lines(name, &status).unwrap();
+                    let status = run_info.current_status(&id).unwrap();
+                    if let Some(reader) = stderr_reader {
+                        self.stderr_buffer.extend(join_drained(reader));
+                        if let Err(e) = self.log_stderr_lines(name, &status, &run_info.config) {
+                            warn!("Error while logging stderr of service {}: {}", name, e);
+                        }
                     }
-                    if let Some(stream) = &mut child.stdout {
-                        let mut buf = Vec::new();
-                        let _bytes = stream.read_to_end(&mut buf).unwrap();
-                        self.stdout_buffer.extend(buf);
-                        self.log_stdout_lines(name, &status).unwrap();
+                    if let Some(reader) = stdout_reader {
+                        self.stdout_buffer.extend(join_drained(reader));
+                        if let Err(e) = self.log_stdout_lines(name, &status, &run_info.config) {
+                            warn!("Error while logging stdout of service {}: {}", name, e);
+                        }
                     }
                 }
 
@@ -458,12 +970,75 @@ impl Service {
         let cmds = self.service_config.stop.clone();
         self.run_all_cmds(&cmds, id, name, timeout, run_info.clone())
     }
+    fn run_pre_check(
+        &mut self,
+        id: UnitId,
+        name: &str,
+        run_info: ArcRuntimeInfo,
+    ) -> Result<(), RunCmdError> {
+        let cmd = match &self.service_config.exec_start_pre_check {
+            Some(cmd) => cmd.clone(),
+            None => return Ok(()),
+        };
+        let timeout = self.get_start_timeout();
+        let mut attempt = 0;
+        loop {
+            match self.run_cmd(&cmd, id, name, timeout, run_info.clone()) {
+                Ok(()) => return Ok(()),
+                Err(e) => {
+                    if attempt >= self.service_config.pre_check_max_retries {
+                        return Err(e);
+                    }
+                    attempt += 1;
+                    trace!(
+                        "ExecStartPreCheck of service {} failed with: {}. Retry {} of {}",
+                        name,
+                        e,
+                        attempt,
+                        self.service_config.pre_check_max_retries
+                    );
+                    std::thread::sleep(self.service_config.pre_check_interval);
+                }
+            }
+        }
+    }
+    /// Run the ExecCondition= commands. Returns false if one of them exited with 1 to 254, which means
+    /// the service should be skipped. Other unsuccessful exits are errors
+    fn run_condition(
+        &mut self,
+        id: UnitId,
+        name: &str,
+        run_info: ArcRuntimeInfo,
+    ) -> Result<bool, RunCmdError> {
+        let timeout = self.get_start_timeout();
+        let cmds = self.service_config.condition.clone();
+        for cmd in &cmds {
+            match self.run_cmd(cmd, id, name, timeout, run_info.clone()) {
+                Ok(()) => {}
+                Err(RunCmdError::BadExitCode(
+                    _,
+                    crate::signal_handler::ChildTermination::Exit(code),
+                )) if (1..=254).contains(&code) => {
+                    trace!(
+                        "ExecCondition {} of service {} exited with: {}",
+                        cmd.to_string(),
+                        name,
+                        code
+                    );
+                    return Ok(false);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(true)
+    }
     fn run_prestart(
         &mut self,
         id: UnitId,
         name: &str,
         run_info: ArcRuntimeInfo,
     ) -> Result<(), RunCmdError> {
+        self.run_pre_check(id, name, run_info.clone())?;
         if self.service_config.startpre.is_empty() {
             return Ok(());
         }
@@ -471,6 +1046,20 @@ impl Service {
         let cmds = self.service_config.startpre.clone();
         self.run_all_cmds(&cmds, id, name, timeout, run_info.clone())
     }
+    /// Run the remaining ExecStart= lines of a oneshot service
+    fn run_additional_exec(
+        &mut self,
+        id: UnitId,
+        name: &str,
+        run_info: ArcRuntimeInfo,
+    ) -> Result<(), RunCmdError> {
+        if self.service_config.additional_exec.is_empty() {
+            return Ok(());
+        }
+        let timeout = self.get_start_timeout();
+        let cmds = self.service_config.additional_exec.clone();
+        self.run_all_cmds(&cmds, id, name, timeout, run_info.clone())
+    }
     fn run_poststart(
         &mut self,
         id: UnitId,
@@ -491,7 +1080,7 @@ impl Service {
         name: &str,
         run_info: ArcRuntimeInfo,
     ) -> Result<(), RunCmdError> {
-        if self.service_config.startpost.is_empty() {
+        if self.service_config.stoppost.is_empty() {
             return Ok(());
         }
         let timeout = self.get_start_timeout();
@@ -499,7 +1088,83 @@ impl Service {
         self.run_all_cmds(&cmds, id, name, timeout, run_info.clone())
     }
 
-    pub fn log_stdout_lines(&mut self, name: &str, status: &UnitStatus) -> std::io::Result<()> {
+    /// Return the output of the service that has not been logged yet (stdout, stderr) and clear the buffers
+    pub fn take_output_buffers(&mut self) -> (Vec<u8>, Vec<u8>) {
+        (
+            std::mem::take(&mut self.stdout_buffer),
+            std::mem::take(&mut self.stderr_buffer),
+        )
+    }
+
+    /// Send the line to syslog if the service has SyslogIdentifier= or SyslogFacility= set.
+    /// Returns false if the line still needs to be written somewhere else.
+    fn log_line_to_syslog(&self, name: &str, severity: SyslogSeverity, line: &[u8]) -> bool {
+        let config = &self.service_config;
+        if config.syslog_identifier.is_none() && config.syslog_facility.is_none() {
+            return false;
+        }
+        let ident = match &config.syslog_identifier {
+            Some(ident) => ident.as_str(),
+            None => name.trim_end_matches(".service"),
+        };
+        let facility = config.syslog_facility.unwrap_or(SyslogFacility::Daemon);
+        let pid = self.pid.unwrap_or_else(nix::unistd::getpid);
+        match crate::syslog_sink::send_line(facility, severity, ident, pid, line) {
+            Ok(()) => true,
+            Err(e) => {
+                trace!(
+                    "Could not send output of service {} to syslog, falling back to stdout/stderr: {}",
+                    name,
+                    e
+                );
+                false
+            }
+        }
+    }
+
+    /// Send the line to journald if that is enabled and the journal socket exists.
+    /// Returns false if the line still needs to be written somewhere else.
+    fn log_line_to_journal(
+        &self,
+        name: &str,
+        severity: SyslogSeverity,
+        line: &[u8],
+        conf: &crate::config::Config,
+    ) -> bool {
+        if !conf.log_to_journald || !crate::journald_sink::journal_available() {
+            return false;
+        }
+        let ident = match &self.service_config.syslog_identifier {
+            Some(ident) => ident.as_str(),
+            None => name.trim_end_matches(".service"),
+        };
+        let pid = self.pid.unwrap_or_else(nix::unistd::getpid);
+        match crate::journald_sink::send_line(
+            severity,
+            ident,
+            pid,
+            name,
+            line,
+            &self.service_config.log_extra_fields,
+        ) {
+            Ok(()) => true,
+            Err(e) => {
+                trace!(
+                    "Could not send output of service {} to the journal, falling back to stdout/stderr: {}",
+                    name,
+                    e
+                );
+                false
+            }
+        }
+    }
+
+    pub fn log_stdout_lines(
+        &mut self,
+        name: &str,
+        status: &UnitStatus,
+        conf: &crate::config::Config,
+    ) -> std::io::Result<()> {
         let mut prefix = String::new();
         prefix.push('[');
         prefix.push_str(name);
@@ -507,25 +1172,69 @@ impl Service {
         prefix.push_str(&format!("[{:?}]", *status));
         prefix.push(' ');
         let mut outbuf: Vec<u8> = Vec::new();
-        while self.stdout_buffer.contains(&b'\n') {
-            let split_pos = self.stdout_buffer.iter().position(|r| *r == b'\n').unwrap();
-            let (line, lines) = self.stdout_buffer.split_at(split_pos + 1);
-
-            // drop \n at the end of the line
-            let line = &line[0..line.len() - 1].to_vec();
-            self.stdout_buffer = lines.to_vec();
+        while let Some(line) = next_line(&mut self.stdout_buffer, conf.max_log_line_length) {
             if line.is_empty() {
                 continue;
             }
+            self.stdout_capture
+                .push_line(&line, conf.stdout_capture_size);
+            let (allowed, suppressed) = self.log_rate_limit.allow(
+                std::time::Instant::now(),
+                self.service_config.log_rate_limit_interval,
+                self.service_config.log_rate_limit_burst,
+            );
+            if suppressed > 0 {
+                std::io::stdout().write_all(
+                    format!("[{}] {} lines suppressed\n", name, suppressed).as_bytes(),
+                )?;
+            }
+            if !allowed {
+                continue;
+            }
+            if self.service_config.standard_output == StdOutputTarget::Null
+                || super::log_files::write_line_to_file(&mut self.stdout_file, name, &line)
+            {
+                continue;
+            }
+            if self.log_line_to_syslog(name, SyslogSeverity::Info, &line)
+                || self.log_line_to_journal(name, SyslogSeverity::Info, &line, conf)
+            {
+                continue;
+            }
             outbuf.clear();
-            outbuf.extend(prefix.as_bytes());
-            outbuf.extend(line);
+            match conf.log_format {
+                crate::config::LogFormat::Plain => {
+                    outbuf.extend(prefix.as_bytes());
+                    outbuf.extend(line);
+                    outbuf.extend(
+                        format_extra_fields_suffix(&self.service_config.log_extra_fields)
+                            .as_bytes(),
+                    );
+                }
+                crate::config::LogFormat::Json => {
+                    outbuf.extend(
+                        format_json_line(
+                            name,
+                            "stdout",
+                            &line,
+                            &self.service_config.log_extra_fields,
+                        )
+                        .as_bytes(),
+                    );
+                }
+            }
             outbuf.push(b'\n');
             std::io::stdout().write_all(&outbuf)?;
         }
+        cap_output_buffer(&mut self.stdout_buffer, conf.max_output_buffer_size);
         Ok(())
     }
-    pub fn log_stderr_lines(&mut self, name: &str, status: &UnitStatus) -> std::io::Result<()> {
+    pub fn log_stderr_lines(
+        &mut self,
+        name: &str,
+        status: &UnitStatus,
+        conf: &crate::config::Config,
+    ) -> std::io::Result<()> {
         let mut prefix = String::new();
         prefix.push('[');
         prefix.push_str(&name);
@@ -535,26 +1244,156 @@ impl Service {
         prefix.push(' ');
 
         let mut outbuf: Vec<u8> = Vec::new();
-        while self.stderr_buffer.contains(&b'\n') {
-            let split_pos = self.stderr_buffer.iter().position(|r| *r == b'\n').unwrap();
-            let (line, lines) = self.stderr_buffer.split_at(split_pos + 1);
-
-            // drop \n at the end of the line
-            let line = &line[0..line.len() - 1].to_vec();
-            self.stderr_buffer = lines.to_vec();
+        while let Some(line) = next_line(&mut self.stderr_buffer, conf.max_log_line_length) {
             if line.is_empty() {
                 continue;
             }
+            let (allowed, suppressed) = self.log_rate_limit.allow(
+                std::time::Instant::now(),
+                self.service_config.log_rate_limit_interval,
+                self.service_config.log_rate_limit_burst,
+            );
+            if suppressed > 0 {
+                std::io::stderr().write_all(
+                    format!("[{}] {} lines suppressed\n", name, suppressed).as_bytes(),
+                )?;
+            }
+            if !allowed {
+                continue;
+            }
+            if self.service_config.standard_error == StdOutputTarget::Null
+                || super::log_files::write_line_to_file(&mut self.stderr_file, name, &line)
+            {
+                continue;
+            }
+            if self.log_line_to_syslog(name, SyslogSeverity::Err, &line)
+                || self.log_line_to_journal(name, SyslogSeverity::Err, &line, conf)
+            {
+                continue;
+            }
             outbuf.clear();
-            outbuf.extend(prefix.as_bytes());
-            outbuf.extend(line);
+            match conf.log_format {
+                crate::config::LogFormat::Plain => {
+                    outbuf.extend(prefix.as_bytes());
+                    outbuf.extend(line);
+                    outbuf.extend(
+                        format_extra_fields_suffix(&self.service_config.log_extra_fields)
+                            .as_bytes(),
+                    );
+                }
+                crate::config::LogFormat::Json => {
+                    outbuf.extend(
+                        format_json_line(
+                            name,
+                            "stderr",
+                            &line,
+                            &self.service_config.log_extra_fields,
+                        )
+                        .as_bytes(),
+                    );
+                }
+            }
             outbuf.push(b'\n');
-            std::io::stderr().write_all(&outbuf).unwrap();
+            std::io::stderr().write_all(&outbuf)?;
         }
+        cap_output_buffer(&mut self.stderr_buffer, conf.max_output_buffer_size);
         Ok(())
     }
 }
 
+/// Format one line of service output as a json object so log aggregators can parse it
+pub fn format_json_line(
+    name: &str,
+    stream: &str,
+    line: &[u8],
+    extra_fields: &std::collections::HashMap<String, String>,
+) -> String {
+    let mut map = serde_json::Map::new();
+    // LogExtraFields= names are all uppercase so they can not collide with the fields below
+    for (key, value) in extra_fields {
+        map.insert(key.clone(), serde_json::Value::String(value.clone()));
+    }
+    map.insert(
+        "timestamp".into(),
+        serde_json::Value::String(chrono::Local::now().to_rfc3339()),
+    );
+    map.insert("unit".into(), serde_json::Value::String(name.to_owned()));
+    map.insert(
+        "stream".into(),
+        serde_json::Value::String(stream.to_owned()),
+    );
+    map.insert(
+        "message".into(),
+        serde_json::Value::String(String::from_utf8_lossy(line).into_owned()),
+    );
+    serde_json::Value::Object(map).to_string()
+}
+
+/// The LogExtraFields= of a service as " {KEY=value ...}" sorted by key, or an empty string if there are none
+pub fn format_extra_fields_suffix(
+    extra_fields: &std::collections::HashMap<String, String>,
+) -> String {
+    if extra_fields.is_empty() {
+        return String::new();
+    }
+    let mut fields: Vec<_> = extra_fields
+        .iter()
+        .map(|(key, value)| format!("{}={}", key, value))
+        .collect();
+    fields.sort();
+    format!(" {{{}}}", fields.join(" "))
+}
+
+/// Take the next complete line (without the \n) out of the buffer. If the buffer holds more than max_line_len bytes
+/// without a newline, the first max_line_len bytes are returned as a line of their own so the buffer cannot grow forever.
+/// A max_line_len of 0 means unlimited.
+pub fn next_line(buffer: &mut Vec<u8>, max_line_len: usize) -> Option<Vec<u8>> {
+    match buffer.iter().position(|r| *r == b'\n') {
+        Some(split_pos) if max_line_len == 0 || split_pos <= max_line_len => {
+            let mut line: Vec<u8> = buffer.drain(..split_pos + 1).collect();
+            // drop \n at the end of the line
+            line.pop();
+            Some(line)
+        }
+        _ => {
+            if max_line_len > 0 && buffer.len() > max_line_len {
+                Some(buffer.drain(..max_line_len).collect())
+            } else {
+                None
+            }
+        }
+    }
+}
+
+/// Keep only the last max_len bytes of output that has not been logged yet. The dropped part is replaced by a marker
+/// so it is visible that output got lost. 0 means unlimited.
+pub fn cap_output_buffer(buffer: &mut Vec<u8>, max_len: usize) {
+    if max_len == 0 || buffer.len() <= max_len {
+        return;
+    }
+    let marker = format!("[truncated {} bytes]", buffer.len() - max_len);
+    buffer.drain(..buffer.len() - max_len);
+    buffer.splice(..0, marker.into_bytes());
+}
+
+/// Read a pipe of a helper process until all its write ends are closed
+fn drain_in_thread<R: std::io::Read + Send + 'static>(
+    mut stream: R,
+) -> std::thread::JoinHandle<Vec<u8>> {
+    std::thread::spawn(move || {
+        let mut buf = Vec::new();
+        if let Err(e) = stream.read_to_end(&mut buf) {
+            warn!("Error while reading output of a helper process: {}", e);
+        }
+        buf
+    })
+}
+
+/// The output read by drain_in_thread. Empty if the reading thread panicked
+fn join_drained(reader: std::thread::JoinHandle<Vec<u8>>) -> Vec<u8> {
+    reader.join().unwrap_or_default()
+}
+
 enum WaitResult {
     TimedOut,
     InTime(std::io::Result<crate::signal_handler::ChildTermination>),
@@ -567,63 +1406,12 @@ enum WaitResult {
 /// that has not been ported to rust
 fn wait_for_helper_child(
     child: &mut std::process::Child,
-    pid_table: ArcMutPidTable,
+    run_info: &ArcRuntimeInfo,
     time_out: Option<std::time::Duration>,
 ) -> WaitResult {
     let pid = nix::unistd::Pid::from_raw(child.id() as i32);
-    let mut counter = 1u64;
-    let start_time = std::time::Instant::now();
-    loop {
-        if let Some(time_out) = time_out {
-            if start_time.elapsed() >= time_out {
-                return WaitResult::TimedOut;
-            }
-        }
-        {
-            let mut pid_table_locked = pid_table.lock().unwrap();
-            match pid_table_locked.get(&pid) {
-                Some(entry) => {
-                    match entry {
-                        PidEntry::OneshotExited(_) => {
-                            // Should never happen
-                            unreachable!(
-                            "Was waiting on helper process but pid got saved as PidEntry::OneshotExited"
-                        );
-                        }
-                        PidEntry::Service(_, _) => {
-                            // Should never happen
-                            unreachable!(
-                            "Was waiting on helper process but pid got saved as PidEntry::Service"
-                        );
-                        }
-                        PidEntry::Helper(_, _) => {
-                            // Need to wait longer
-                        }
-                        PidEntry::HelperExited(_) => {
-                            let entry_owned = pid_table_locked.remove(&pid).unwrap();
-                            if let PidEntry::HelperExited(termination_owned) = entry_owned {
-                                return WaitResult::InTime(Ok(termination_owned));
-                            }
-                        }
-                    }
-                }
-                None => {
-                    // Should not happen. Either there is an Helper entry oder a Exited entry
-                    unreachable!("No entry for child found")
-                }
-            }
-        }
-        // exponential backoff to get low latencies for fast processes
-        // but not hog the cpu for too long
-        // start at 0.05 ms
-        // capped to 10 ms to not introduce too big latencies
-        // TODO review those numbers
-        let sleep_dur = std::time::Duration::from_micros(counter * 50);
-        let sleep_cap = std::time::Duration::from_millis(10);
-        let sleep_dur = sleep_dur.min(sleep_cap);
-        if sleep_dur < sleep_cap {
-            counter = counter * 2;
-        }
-        std::thread::sleep(sleep_dur);
+    match run_info.pid_table.wait_for(pid, time_out) {
+        Some(termination) => WaitResult::InTime(Ok(termination)),
+        None => WaitResult::TimedOut,
     }
 }
@@ -2,6 +2,50 @@ use super::fork_child;
 use crate::fd_store::FDStore;
 use crate::services::RunCmdError;
 use crate::services::Service;
+use crate::units::StdOutputTarget;
+use std::os::unix::io::RawFd;
+
+/// The fd the output of the service is written to: the write end of its pipe, or /dev/null for StandardOutput=null
+/// and StandardError=null. Meant to be called in the forked child
+fn output_fd(pipe: &Option<(RawFd, RawFd)>, target: &StdOutputTarget) -> RawFd {
+    if *target == StdOutputTarget::Null {
+        nix::fcntl::open(
+            "/dev/null",
+            nix::fcntl::OFlag::O_WRONLY,
+            nix::sys::stat::Mode::empty(),
+        )
+        .unwrap()
+    } else if let Some(rwpair) = pipe {
+        rwpair.1
+    } else {
+        unreachable!();
+    }
+}
+
+/// The variables of the environment of rustysd that PassEnvironment= does not pass to the service
+fn removed_env_vars(srvc: &Service) -> Vec<std::ffi::CString> {
+    use std::os::unix::ffi::OsStringExt;
+    std::env::vars_os()
+        .map(|(var, _)| var)
+        .filter(|var| !srvc.service_config.passes_env_var(var))
+        .filter_map(|var| std::ffi::CString::new(var.into_vec()).ok())
+        .collect()
+}
+
+/// The variables of EnvironmentFile=, ready to be set in the forked child
+fn env_file_vars(srvc: &Service) -> Result<Vec<(std::ffi::CString, std::ffi::CString)>, String> {
+    Ok(srvc
+        .service_config
+        .environment_file_vars()?
+        .into_iter()
+        .filter_map(|(var, value)| {
+            Some((
+                std::ffi::CString::new(var).ok()?,
+                std::ffi::CString::new(value).ok()?,
+            ))
+        })
+        .collect())
+}
 
 fn start_service_with_filedescriptors(
     srvc: &mut Service,
@@ -10,7 +54,20 @@ fn start_service_with_filedescriptors(
 ) -> Result<(), RunCmdError> {
     // check if executable even exists
     let cmd = std::path::PathBuf::from(&srvc.service_config.exec.cmd);
-    if !cmd.exists() {
+    if let Some(root) = &srvc.service_config.root_directory {
+        // the executable is looked up in the new root, which BindPaths= etc. might only fill in the child. The exec
+        // there checks it
+        if !root.is_dir() {
+            error!(
+                "The service {} specified a RootDirectory that is not a directory: {:?}",
+                name, root
+            );
+            return Err(RunCmdError::SpawnError(
+                srvc.service_config.exec.cmd.clone(),
+                format!("RootDirectory {:?} is not a directory", root),
+            ));
+        }
+    } else if !cmd.exists() {
         error!(
             "The service {} specified an executable that does not exist: {:?}",
             name, &srvc.service_config.exec.cmd
@@ -19,8 +76,7 @@ fn start_service_with_filedescriptors(
             srvc.service_config.exec.cmd.clone(),
             format!("Executable does not exist"),
         ));
-    }
-    if !cmd.is_file() {
+    } else if !cmd.is_file() {
         error!(
             "The service {} specified an executable that is not a file: {:?}",
             name, &cmd
@@ -40,11 +96,18 @@ fn start_service_with_filedescriptors(
 
     super::fork_os_specific::pre_fork_os_specific(srvc).map_err(|e| RunCmdError::Generic(e))?;
 
+    // collected before forking, reading the environment takes a lock in the std that might be held at the time of forking
+    let env_changes = fork_child::EnvChanges {
+        removed: removed_env_vars(srvc),
+        set: env_file_vars(srvc).map_err(RunCmdError::Generic)?,
+    };
+
     // make sure we have the lock that the child will need
     match nix::unistd::fork() {
         Ok(nix::unistd::ForkResult::Parent { child, .. }) => {
             srvc.pid = Some(child);
             srvc.process_group = Some(nix::unistd::Pid::from_raw(-child.as_raw()));
+            srvc.joined_namespaces.clear();
         }
         Ok(nix::unistd::ForkResult::Child) => {
             let notifications_path = {
@@ -54,25 +117,14 @@ fn start_service_with_filedescriptors(
                     unreachable!();
                 }
             };
-            let stdout = {
-                if let Some(rwpair) = &srvc.stdout_dup {
-                    rwpair.1
-                } else {
-                    unreachable!();
-                }
-            };
-            let stderr = {
-                if let Some(rwpair) = &srvc.stderr_dup {
-                    rwpair.1
-                } else {
-                    unreachable!();
-                }
-            };
+            let stdout = output_fd(&srvc.stdout_dup, &srvc.service_config.standard_output);
+            let stderr = output_fd(&srvc.stderr_dup, &srvc.service_config.standard_error);
             fork_child::after_fork_child(
                 srvc,
                 &name,
                 fd_store,
                 &notifications_path,
+                &env_changes,
                 stdout,
                 stderr,
             );
@@ -88,6 +140,5 @@ pub fn start_service(
     fd_store: &FDStore,
 ) -> Result<(), super::RunCmdError> {
     start_service_with_filedescriptors(srvc, name, fd_store)?;
-    srvc.runtime_info.up_since = Some(std::time::Instant::now());
     Ok(())
 }
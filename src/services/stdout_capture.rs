@@ -0,0 +1,36 @@
+//! Keep the most recent lines of stdout of a service so they can be queried via the control interface
+
+use std::collections::VecDeque;
+
+#[derive(Debug, Default)]
+pub struct StdoutCapture {
+    lines: VecDeque<(u64, Vec<u8>)>,
+    size: usize,
+    next_seq: u64,
+}
+
+impl StdoutCapture {
+    /// Append a line and drop the oldest lines until the captured lines take at most max_size bytes.
+    /// The newest line is always kept, even if it alone is bigger than max_size.
+    pub fn push_line(&mut self, line: &[u8], max_size: usize) {
+        self.lines.push_back((self.next_seq, line.to_vec()));
+        self.size += line.len();
+        self.next_seq += 1;
+        while self.size > max_size && self.lines.len() > 1 {
+            let (_, dropped) = self.lines.pop_front().unwrap();
+            self.size -= dropped.len();
+        }
+    }
+
+    /// All still captured lines with a sequence number >= seq and the sequence number the next line will get.
+    /// Pass the returned number in the next call to only get new lines.
+    pub fn lines_since(&self, seq: u64) -> (Vec<Vec<u8>>, u64) {
+        let lines = self
+            .lines
+            .iter()
+            .filter(|(line_seq, _)| *line_seq >= seq)
+            .map(|(_, line)| line.clone())
+            .collect();
+        (lines, self.next_seq)
+    }
+}
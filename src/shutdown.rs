@@ -2,18 +2,24 @@ use crate::units::*;
 
 fn get_next_service_to_shutdown(
     unit_table_locked: &UnitTable,
-    status_table_locked: &StatusTable,
+    run_info: &RuntimeInfo,
 ) -> Option<UnitId> {
     for (_, unit) in unit_table_locked.iter() {
         let unit_locked = &mut *match unit.lock() {
             Ok(lock) => lock,
             Err(err) => err.into_inner(),
         };
+        // the status table comes after the units in the lock order
+        let status_table_locked = match run_info.status_table.read() {
+            Ok(lock) => lock,
+            Err(err) => err.into_inner(),
+        };
         let status = status_table_locked.get(&unit_locked.id).unwrap();
         {
             let status_locked = status.lock().unwrap();
             if !(*status_locked == UnitStatus::Started
                 || *status_locked == UnitStatus::Starting
+                || *status_locked == UnitStatus::Reloading
                 || *status_locked == UnitStatus::StartedWaitingForSocket)
             {
                 continue;
@@ -29,7 +35,9 @@ fn get_next_service_to_shutdown(
                 let status = status_table_locked.get(&next_id).unwrap();
                 let status_locked = status.lock().unwrap();
                 match *status_locked {
-                    UnitStatus::Stopped | UnitStatus::StoppedFinal(_) => false,
+                    UnitStatus::Stopped | UnitStatus::StoppedFinal(_) | UnitStatus::Failed(_) => {
+                        false
+                    }
                     _ => true,
                 }
             })
@@ -69,39 +77,7 @@ fn shutdown_unit(unit_locked: &mut Unit, run_info: ArcRuntimeInfo) {
                 }
                 Err(e) => error!("{}", e),
             }
-            if let Some(datagram) = &srvc.notifications {
-                match datagram.shutdown(std::net::Shutdown::Both) {
-                    Ok(()) => {
-                        trace!(
-                            "Closed notification socket for service unit: {}",
-                            unit_locked.conf.name()
-                        );
-                    }
-                    Err(e) => error!(
-                        "Error closing notification socket for service unit {}: {}",
-                        unit_locked.conf.name(),
-                        e
-                    ),
-                }
-            }
-            srvc.notifications = None;
-            if let Some(note_sock_path) = &srvc.notifications_path {
-                if note_sock_path.exists() {
-                    match std::fs::remove_file(note_sock_path) {
-                        Ok(()) => {
-                            trace!(
-                                "Removed notification socket for service unit: {}",
-                                unit_locked.conf.name()
-                            );
-                        }
-                        Err(e) => error!(
-                            "Error removing notification socket for service unit {}: {}",
-                            unit_locked.conf.name(),
-                            e
-                        ),
-                    }
-                }
-            }
+            // the notification socket has been closed and removed by stopping the service
         }
         UnitSpecialized::Socket(sock) => {
             trace!("Close socket unit: {}", unit_locked.conf.name());
@@ -131,8 +107,93 @@ fn shutdown_unit(unit_locked: &mut Unit, run_info: ArcRuntimeInfo) {
     }
 }
 
+/// What rustysd does after all units have been stopped
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum AfterShutdown {
+    Exit,
+    Reboot,
+    Poweroff,
+}
+
+fn finish_shutdown(after: AfterShutdown) {
+    match after {
+        AfterShutdown::Exit => {}
+        AfterShutdown::Reboot => reboot_system(false),
+        AfterShutdown::Poweroff => reboot_system(true),
+    }
+    std::process::exit(0);
+}
+
+#[cfg(target_os = "linux")]
+fn reboot_system(poweroff: bool) {
+    use nix::sys::reboot::RebootMode;
+    let mode = if poweroff {
+        RebootMode::RB_POWER_OFF
+    } else {
+        RebootMode::RB_AUTOBOOT
+    };
+    nix::unistd::sync();
+    match nix::sys::reboot::reboot(mode) {
+        Ok(_) => {}
+        Err(e) => error!("Could not {:?}: {}", mode, e),
+    }
+}
+#[cfg(not(target_os = "linux"))]
+fn reboot_system(poweroff: bool) {
+    error!(
+        "Rebooting/powering off (poweroff: {}) is not supported on this platform",
+        poweroff
+    );
+}
+
+/// Kill all processes of all services without running ExecStop= and friends
+fn kill_all_services(run_info: &ArcRuntimeInfo) {
+    let unit_table_locked = match run_info.unit_table.read() {
+        Ok(lock) => lock,
+        Err(err) => err.into_inner(),
+    };
+    for unit in unit_table_locked.values() {
+        let unit_locked = &mut *match unit.lock() {
+            Ok(lock) => lock,
+            Err(err) => err.into_inner(),
+        };
+        let name = unit_locked.conf.name();
+        if let UnitSpecialized::Service(srvc) = &mut unit_locked.specialized {
+            srvc.kill_all_remaining_processes(&name);
+        }
+    }
+}
+
+/// Execute the StartLimitAction= of a service that hit its start limit
+pub fn run_start_limit_action(action: StartLimitAction, run_info: ArcRuntimeInfo) {
+    match action {
+        StartLimitAction::None => {}
+        StartLimitAction::Reboot => shutdown_sequence_then(run_info, AfterShutdown::Reboot),
+        StartLimitAction::Poweroff => shutdown_sequence_then(run_info, AfterShutdown::Poweroff),
+        StartLimitAction::RebootForce => {
+            kill_all_services(&run_info);
+            finish_shutdown(AfterShutdown::Reboot);
+        }
+        StartLimitAction::PoweroffForce => {
+            kill_all_services(&run_info);
+            finish_shutdown(AfterShutdown::Poweroff);
+        }
+        StartLimitAction::ExitForce => {
+            kill_all_services(&run_info);
+            finish_shutdown(AfterShutdown::Exit);
+        }
+        StartLimitAction::RebootImmediate => finish_shutdown(AfterShutdown::Reboot),
+        StartLimitAction::PoweroffImmediate => finish_shutdown(AfterShutdown::Poweroff),
+    }
+}
+
 // TODO maybe this should be available everywhere for situations where normally a panic would occur?
 pub fn shutdown_sequence(run_info: ArcRuntimeInfo) {
+    shutdown_sequence_then(run_info, AfterShutdown::Exit);
+}
+
+/// Stop all units cleanly in the background, then exit rustysd or reboot/poweroff the system
+pub fn shutdown_sequence_then(run_info: ArcRuntimeInfo, after: AfterShutdown) {
     std::thread::spawn(move || {
         trace!("Shutting down");
         trace!("Get unit lock");
@@ -145,20 +206,7 @@ pub fn shutdown_sequence(run_info: ArcRuntimeInfo) {
         };
 
         trace!("Kill all units");
-        loop {
-            let id = {
-                let status_table_locked = match run_info.status_table.write() {
-                    Ok(lock) => lock,
-                    Err(err) => err.into_inner(),
-                };
-                if let Some(id) =
-                    get_next_service_to_shutdown(&*unit_table_locked, &*status_table_locked)
-                {
-                    id
-                } else {
-                    break;
-                }
-            };
+        while let Some(id) = get_next_service_to_shutdown(&unit_table_locked, &run_info) {
             let unit = unit_table_locked.get(&id).unwrap();
             trace!("Lock to kill unit: {}", id);
             let unit_locked = &mut *match unit.lock() {
@@ -191,6 +239,6 @@ pub fn shutdown_sequence(run_info: ArcRuntimeInfo) {
         }
 
         println!("Shutdown finished");
-        std::process::exit(0);
+        finish_shutdown(after);
     });
 }
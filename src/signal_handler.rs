@@ -40,6 +40,10 @@ pub fn handle_signals(
                     println!("Received termination signal. Rustysd checking out");
                     crate::shutdown::shutdown_sequence(run_info.clone());
                 }
+                signal_hook::SIGUSR1 => {
+                    trace!("Received SIGUSR1. Rotating the log files of all services");
+                    rotate_all_logs(&run_info);
+                }
 
                 _ => unreachable!(),
             }
@@ -47,6 +51,18 @@ pub fn handle_signals(
     }
 }
 
+pub fn rotate_all_logs(run_info: &ArcRuntimeInfo) {
+    for unit in run_info.unit_table.read().unwrap().values() {
+        let unit_locked = &mut *unit.lock().unwrap();
+        let name = unit_locked.conf.name();
+        if let UnitSpecialized::Service(srvc) = &mut unit_locked.specialized {
+            if let Err(e) = srvc.rotate_logs(&name) {
+                error!("Error while rotating logs of service {}: {}", name, e);
+            }
+        }
+    }
+}
+
 #[derive(Clone, Copy, Eq, PartialEq, Hash, Debug)]
 pub enum ChildTermination {
     Signal(nix::sys::signal::Signal),
@@ -42,14 +42,7 @@ pub fn start_socketactivation_thread(
                         }
 
                         if let Some(srvc_unit_id) = srvc_unit_id {
-                            if let Some(status) =
-                                run_info.status_table.read().unwrap().get(&srvc_unit_id)
-                            {
-                                let srvc_status = {
-                                    let status_locked = status.lock().unwrap();
-                                    status_locked.clone()
-                                };
-
+                            if let Some(srvc_status) = run_info.current_status(&srvc_unit_id) {
                                 if srvc_status != crate::units::UnitStatus::StartedWaitingForSocket
                                 {
                                     trace!(
@@ -59,6 +59,27 @@ impl SpecializedSocketConfig {
             SpecializedSocketConfig::Fifo(conf) => conf.close(rawfd),
         }
     }
+    /// TCP and UDP sockets, the ones IPAddressAllow= and IPAddressDeny= apply to
+    fn is_ip(&self) -> bool {
+        match self {
+            SpecializedSocketConfig::TcpSocket(_) | SpecializedSocketConfig::UdpSocket(_) => true,
+            SpecializedSocketConfig::UnixSocket(_) | SpecializedSocketConfig::Fifo(_) => false,
+        }
+    }
+    /// The path of the file this socket creates. None for network sockets and sockets in the abstract namespace
+    fn fs_path(&self) -> Option<std::path::PathBuf> {
+        match self {
+            SpecializedSocketConfig::UnixSocket(conf) => {
+                if conf.path().starts_with('@') {
+                    None
+                } else {
+                    Some(std::path::PathBuf::from(conf.path()))
+                }
+            }
+            SpecializedSocketConfig::Fifo(conf) => Some(conf.path.clone()),
+            SpecializedSocketConfig::TcpSocket(_) | SpecializedSocketConfig::UdpSocket(_) => None,
+        }
+    }
 }
 
 #[derive(Clone, Eq, PartialEq, Debug)]
@@ -68,6 +89,46 @@ pub enum UnixSocketConfig {
     Datagram(String),
 }
 
+/// SocketMode=, SocketUser= and SocketGroup=. Applied to all sockets and fifos of a unit that live in the filesystem.
+/// If not set the mode and ownership are left as they were after creating the file.
+#[derive(Clone, Eq, PartialEq, Debug, Default)]
+pub struct SocketFileOptions {
+    pub mode: Option<u32>,
+    pub user: Option<String>,
+    pub group: Option<String>,
+}
+
+impl SocketFileOptions {
+    pub fn apply(&self, path: &std::path::Path) -> Result<(), String> {
+        use std::os::unix::fs::PermissionsExt;
+        if let Some(mode) = self.mode {
+            std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode))
+                .map_err(|e| format!("Could not set mode of {:?}: {}", path, e))?;
+        }
+        let uid = match &self.user {
+            Some(user) => Some(if let Ok(uid) = user.parse::<u32>() {
+                nix::unistd::Uid::from_raw(uid)
+            } else {
+                crate::platform::pwnam::getpwnam_r(user)?.uid
+            }),
+            None => None,
+        };
+        let gid = match &self.group {
+            Some(group) => Some(if let Ok(gid) = group.parse::<u32>() {
+                nix::unistd::Gid::from_raw(gid)
+            } else {
+                crate::platform::grnam::getgrnam_r(group)?.gid
+            }),
+            None => None,
+        };
+        if uid.is_some() || gid.is_some() {
+            nix::unistd::chown(path, uid, gid)
+                .map_err(|e| format!("Could not set owner of {:?}: {}", path, e))?;
+        }
+        Ok(())
+    }
+}
+
 #[derive(Clone, Eq, PartialEq, Debug)]
 pub struct FifoConfig {
     pub path: std::path::PathBuf,
@@ -132,14 +193,19 @@ impl UnixSeqPacket {
 }
 
 impl UnixSocketConfig {
-    fn close(&self, rawfd: RawFd) -> Result<(), String> {
-        let strpath = match self {
+    fn path(&self) -> &str {
+        match self {
             UnixSocketConfig::Stream(s) => s,
             UnixSocketConfig::Datagram(s) => s,
             UnixSocketConfig::Sequential(s) => s,
-        };
+        }
+    }
+
+    fn close(&self, rawfd: RawFd) -> Result<(), String> {
+        let strpath = self.path();
         let path = std::path::PathBuf::from(strpath);
-        if path.exists() {
+        // abstract sockets have no file that needs to be removed
+        if !strpath.starts_with('@') && path.exists() {
             std::fs::remove_file(&path)
                 .map_err(|e| format!("Error removing file {:?}: {}", path, e))?;
         }
@@ -148,7 +214,33 @@ impl UnixSocketConfig {
         Ok(())
     }
 
+    /// Sockets in the linux abstract namespace, their address starts with '@'
+    fn open_abstract(&self, name: &str) -> Result<Box<dyn AsRawFd + Send + Sync>, String> {
+        use nix::sys::socket::SockType;
+        trace!("opening abstract unix socket: @{}", name);
+        match self {
+            UnixSocketConfig::Stream(_) => {
+                let fd = crate::platform::make_abstract_unix_socket(name, SockType::Stream)?;
+                Ok(Box::new(unsafe { UnixListener::from_raw_fd(fd) }))
+            }
+            UnixSocketConfig::Datagram(_) => {
+                let fd = crate::platform::make_abstract_unix_socket(name, SockType::Datagram)?;
+                Ok(Box::new(unsafe { UnixDatagram::from_raw_fd(fd) }))
+            }
+            UnixSocketConfig::Sequential(path) => {
+                let fd = crate::platform::make_abstract_unix_socket(name, SockType::SeqPacket)?;
+                Ok(Box::new(UnixSeqPacket(
+                    Some(fd),
+                    std::path::PathBuf::from(path),
+                )))
+            }
+        }
+    }
+
     fn open(&self) -> Result<Box<dyn AsRawFd + Send + Sync>, String> {
+        if let Some(name) = self.path().strip_prefix('@') {
+            return self.open_abstract(name);
+        }
         match self {
             UnixSocketConfig::Stream(path) => {
                 let spath = std::path::Path::new(&path);
@@ -270,6 +362,9 @@ pub struct Socket {
     pub exec_config: ExecConfig,
     pub sockets: Vec<SocketConfig>,
     pub services: Vec<String>,
+    pub file_options: SocketFileOptions,
+    /// IPAddressAllow= and IPAddressDeny=, applied to the TCP and UDP sockets
+    pub ip_filter: crate::platform::bpf_filter::IpAddressFilter,
     pub activated: bool,
 }
 
@@ -291,11 +386,20 @@ impl Socket {
         name: String,
         id: UnitId,
         fd_store: &mut FDStore,
-    ) -> std::io::Result<()> {
+    ) -> Result<(), String> {
         let mut fds = Vec::new();
         for idx in 0..self.sockets.len() {
             let conf = &mut self.sockets[idx];
             let as_raw_fd = conf.specialized.open().unwrap();
+            if let Some(path) = conf.specialized.fs_path() {
+                self.file_options.apply(&path)?;
+            }
+            if conf.specialized.is_ip() && !self.ip_filter.is_empty() {
+                crate::platform::bpf_filter::attach_ip_filter(
+                    as_raw_fd.as_raw_fd(),
+                    &self.ip_filter,
+                )?;
+            }
             // close these fd's on exec. They must not show up in child processes
             // the ńeeded fd's will be duped which unsets the flag again
             let new_fd = as_raw_fd.as_raw_fd();
@@ -0,0 +1,287 @@
+//! The pids of the running services are saved in a state file (state_file in the config) whenever a service starts or
+//! stops, so a new rustysd process (e.g. after an upgrade) can adopt the services instead of starting them again.
+//!
+//! A pid is only adopted if the process still has the start time saved with it (field 22 of /proc/<pid>/stat), so a
+//! recycled pid is never taken for the service. Adopted main processes that are not children of the new process are
+//! checked periodically and the service is stopped once they exited. The exit status of those is unknown.
+//!
+//! Socket fds are not part of the state. The adopted services keep their copies, but the socket units open new sockets.
+
+use crate::units::*;
+use serde_json::Value;
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct PersistedService {
+    pub name: String,
+    pub pid: i32,
+    /// Saved negated like in the service, so it can be used with kill()
+    pub process_group: Option<i32>,
+    /// In clock ticks after the boot, see process_start_time
+    pub start_time: u64,
+}
+
+/// The start time of the process in clock ticks after the boot (field 22 of /proc/<pid>/stat). Together with the pid
+/// it identifies a process. None if the process does not exist (or /proc is not available)
+pub fn process_start_time(pid: nix::unistd::Pid) -> Option<u64> {
+    let stat = std::fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?;
+    // the name in field 2 is in parentheses and might contain spaces and parentheses itself
+    let after_name = &stat[stat.rfind(')')? + 1..];
+    after_name.split_whitespace().nth(19)?.parse().ok()
+}
+
+/// All services that have a main process right now
+pub fn collect_state(run_info: &RuntimeInfo) -> Vec<PersistedService> {
+    let mut services = Vec::new();
+    for unit in run_info.unit_table.read().unwrap().values() {
+        let unit_locked = unit.lock().unwrap();
+        if let UnitSpecialized::Service(srvc) = &unit_locked.specialized {
+            if let Some(pid) = srvc.pid {
+                let start_time = match process_start_time(pid) {
+                    Some(start_time) => start_time,
+                    None => continue,
+                };
+                services.push(PersistedService {
+                    name: unit_locked.conf.name(),
+                    pid: pid.as_raw(),
+                    process_group: srvc.process_group.map(|pgid| pgid.as_raw()),
+                    start_time,
+                });
+            }
+        }
+    }
+    // sorted so the file only changes if the services change
+    services.sort_by(|a, b| a.name.cmp(&b.name));
+    services
+}
+
+pub fn format_state(services: &[PersistedService]) -> String {
+    let services = services
+        .iter()
+        .map(|srvc| {
+            let mut map = serde_json::Map::new();
+            map.insert("Name".into(), Value::String(srvc.name.clone()));
+            map.insert("Pid".into(), Value::from(srvc.pid));
+            map.insert(
+                "ProcessGroup".into(),
+                srvc.process_group.map(Value::from).unwrap_or(Value::Null),
+            );
+            map.insert("StartTime".into(), Value::from(srvc.start_time));
+            Value::Object(map)
+        })
+        .collect();
+    let mut state = serde_json::Map::new();
+    state.insert("Services".into(), Value::Array(services));
+    Value::Object(state).to_string()
+}
+
+pub fn parse_state(content: &str) -> Result<Vec<PersistedService>, String> {
+    let state: Value = serde_json::from_str(content).map_err(|e| format!("{}", e))?;
+    let services = match state.get("Services") {
+        Some(Value::Array(services)) => services,
+        _ => return Err("Services is missing or not an array".into()),
+    };
+    services
+        .iter()
+        .map(|srvc| {
+            let name = match srvc.get("Name") {
+                Some(Value::String(name)) => name.clone(),
+                _ => return Err(format!("Service without a name: {}", srvc)),
+            };
+            let pid = match srvc.get("Pid").and_then(Value::as_i64) {
+                Some(pid) if pid > 0 => pid as i32,
+                _ => return Err(format!("Service {} without a valid pid", name)),
+            };
+            let process_group = srvc
+                .get("ProcessGroup")
+                .and_then(Value::as_i64)
+                .map(|pgid| pgid as i32);
+            let start_time = match srvc.get("StartTime").and_then(Value::as_u64) {
+                Some(start_time) => start_time,
+                None => return Err(format!("Service {} without a start time", name)),
+            };
+            Ok(PersistedService {
+                name,
+                pid,
+                process_group,
+                start_time,
+            })
+        })
+        .collect()
+}
+
+/// Write the state to a temporary file next to the state file and rename it, so a crash never leaves half a file behind
+fn write_state_file(path: &std::path::Path, content: &str) -> std::io::Result<()> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let mut tmp_path = path.as_os_str().to_owned();
+    tmp_path.push(".tmp");
+    std::fs::write(&tmp_path, content)?;
+    std::fs::rename(&tmp_path, path)
+}
+
+/// Save the current state if a state file is configured. Must not be called while a unit is locked
+pub fn save_state(run_info: &RuntimeInfo) {
+    let path = match &run_info.config.state_file {
+        Some(path) => path,
+        None => return,
+    };
+    let _state_file_locked = run_info.state_file_lock.lock().unwrap();
+    let content = format_state(&collect_state(run_info));
+    if let Err(e) = write_state_file(path, &content) {
+        warn!("Could not write the state file {:?}: {}", path, e);
+    }
+}
+
+/// Read the state file. A missing file means there is nothing to adopt
+pub fn load_state(path: &std::path::Path) -> Vec<PersistedService> {
+    let content = match std::fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Vec::new(),
+        Err(e) => {
+            warn!("Could not read the state file {:?}: {}", path, e);
+            return Vec::new();
+        }
+    };
+    match parse_state(&content) {
+        Ok(services) => services,
+        Err(e) => {
+            warn!("Could not parse the state file {:?}: {}", path, e);
+            Vec::new()
+        }
+    }
+}
+
+/// Take over the services from the state that are still running: their pids go into the pid table and the services
+/// count as started, without executing anything. Has to happen before the units are activated
+pub fn adopt_services(run_info: &RuntimeInfo, services: Vec<PersistedService>) {
+    for persisted in services {
+        let pid = nix::unistd::Pid::from_raw(persisted.pid);
+        if process_start_time(pid) != Some(persisted.start_time) {
+            trace!(
+                "Service {} is not running anymore (pid {} is gone or belongs to another process), not adopting it",
+                persisted.name,
+                pid
+            );
+            continue;
+        }
+        let unit = run_info
+            .unit_table
+            .read()
+            .unwrap()
+            .values()
+            .find(|unit| unit.lock().unwrap().conf.name() == persisted.name)
+            .cloned();
+        let unit = match unit {
+            Some(unit) => unit,
+            None => {
+                warn!(
+                    "Service {} from the state file has no unit anymore, not adopting pid {}",
+                    persisted.name, pid
+                );
+                continue;
+            }
+        };
+        let unit_locked = &mut *unit.lock().unwrap();
+        let id = unit_locked.id;
+        if let UnitSpecialized::Service(srvc) = &mut unit_locked.specialized {
+            srvc.pid = Some(pid);
+            srvc.process_group = persisted.process_group.map(nix::unistd::Pid::from_raw);
+            srvc.runtime_info.up_since = Some(std::time::Instant::now());
+            srvc.runtime_info.adopted = true;
+            srvc.runtime_info.adopted_main_process = Some((pid, persisted.start_time));
+            if !srvc.socket_names.is_empty() {
+                warn!(
+                    "Service {} is adopted without its sockets. The socket units open new ones",
+                    persisted.name
+                );
+            }
+            run_info
+                .pid_table
+                .lock()
+                .unwrap()
+                .insert(pid, PidEntry::Service(id, srvc.service_config.srcv_type));
+            let status_table_locked = run_info.status_table.read().unwrap();
+            *status_table_locked.get(&id).unwrap().lock().unwrap() = UnitStatus::Started;
+            info!("Adopted service {} with pid {}", persisted.name, pid);
+        }
+    }
+}
+
+/// How often wait_for_adopted_exit checks whether the adopted process is still there
+const ADOPTED_EXIT_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_millis(50);
+
+/// Like PidTable::wait_for_service_exit, but the process is also gone once it does not have its start time anymore. It
+/// is reaped by someone else if it is not a child of this process. Removes the entry from the pid table in that case
+pub fn wait_for_adopted_exit(
+    pid_table: &PidTable,
+    pid: nix::unistd::Pid,
+    start_time: u64,
+    time_out: std::time::Duration,
+) -> bool {
+    let start = std::time::Instant::now();
+    loop {
+        {
+            let mut pid_table_locked = pid_table.lock().unwrap();
+            match pid_table_locked.get(&pid) {
+                Some(PidEntry::Service(_, _)) => {}
+                // reaped by the signal handler
+                _ => return true,
+            }
+            if process_start_time(pid) != Some(start_time) {
+                pid_table_locked.remove(&pid);
+                pid_table.notify_exited();
+                return true;
+            }
+        }
+        let elapsed = start.elapsed();
+        if elapsed >= time_out {
+            return false;
+        }
+        std::thread::sleep(std::cmp::min(
+            ADOPTED_EXIT_POLL_INTERVAL,
+            time_out - elapsed,
+        ));
+    }
+}
+
+/// Stop the services whose adopted main process exited without the signal handler noticing it. The exit status is
+/// unknown, so they are stopped like after a successful exit (together with the units that require them)
+pub fn check_adopted_services(run_info: &ArcRuntimeInfo) {
+    let mut exited = Vec::new();
+    for unit in run_info.unit_table.read().unwrap().values() {
+        let unit_locked = &mut *unit.lock().unwrap();
+        let id = unit_locked.id;
+        let name = unit_locked.conf.name();
+        if let UnitSpecialized::Service(srvc) = &mut unit_locked.specialized {
+            let (pid, start_time) = match srvc.runtime_info.adopted_main_process {
+                Some(adopted) => adopted,
+                None => continue,
+            };
+            // the service has been restarted with a new main process since
+            if srvc.pid != Some(pid) {
+                srvc.runtime_info.adopted_main_process = None;
+                continue;
+            }
+            if process_start_time(pid) == Some(start_time) {
+                continue;
+            }
+            srvc.runtime_info.adopted_main_process = None;
+            let mut pid_table_locked = run_info.pid_table.lock().unwrap();
+            // without an entry the signal handler reaped it and the exit handler takes care of the service
+            if pid_table_locked.remove(&pid).is_some() {
+                run_info.pid_table.notify_exited();
+                srvc.pid = None;
+                srvc.runtime_info.exited_at = Some(std::time::Instant::now());
+                exited.push((id, name));
+            }
+        }
+    }
+
+    for (id, name) in exited {
+        info!("The adopted main process of service {} exited", name);
+        if let Err(e) = crate::units::deactivate_unit_recursive(id, true, run_info.clone()) {
+            error!("Error while stopping service {}: {}", name, e);
+        }
+    }
+}
@@ -0,0 +1,96 @@
+//! A plain text summary of all units that is written to a file (status_file in the config), so the state of the system
+//! can be checked with cat on machines that do not have the control client. Each line has the name of a unit, its state
+//! and for services the last STATUS= message they sent.
+
+use crate::units::*;
+
+/// How often the status file is rewritten. It is only written if something changed
+const STATUS_FILE_INTERVAL: std::time::Duration = std::time::Duration::from_secs(2);
+
+fn state_name(status: &UnitStatus) -> &'static str {
+    match status {
+        UnitStatus::NeverStarted => "never-started",
+        UnitStatus::Starting => "starting",
+        UnitStatus::Started => "started",
+        UnitStatus::StartedWaitingForSocket => "waiting-for-socket",
+        UnitStatus::Reloading => "reloading",
+        UnitStatus::Stopping => "stopping",
+        UnitStatus::Stopped => "stopped",
+        UnitStatus::StoppedFinal(_) => "stopped-final",
+        UnitStatus::Failed(_) => "failed",
+    }
+}
+
+/// One line per unit, sorted by name, with the columns aligned
+pub fn format_status_summary(run_info: &RuntimeInfo) -> String {
+    let mut rows = Vec::new();
+    for (id, unit) in run_info.unit_table.read().unwrap().iter() {
+        let unit_locked = unit.lock().unwrap();
+        let state = run_info
+            .current_status(id)
+            .map(|status| state_name(&status))
+            .unwrap_or("unknown");
+        let status_msg = match &unit_locked.specialized {
+            UnitSpecialized::Service(srvc) => srvc.status_msgs.last().cloned().unwrap_or_default(),
+            _ => String::new(),
+        };
+        rows.push((unit_locked.conf.name(), state, status_msg));
+    }
+    rows.sort();
+
+    let name_width = rows
+        .iter()
+        .map(|(name, _, _)| name.len())
+        .max()
+        .unwrap_or(0);
+    let state_width = rows
+        .iter()
+        .map(|(_, state, _)| state.len())
+        .max()
+        .unwrap_or(0);
+    let mut summary = String::new();
+    for (name, state, status_msg) in rows {
+        let line = format!(
+            "{:name_width$}  {:state_width$}  {}",
+            name,
+            state,
+            status_msg,
+            name_width = name_width,
+            state_width = state_width
+        );
+        summary.push_str(line.trim_end());
+        summary.push('\n');
+    }
+    summary
+}
+
+/// Write the summary to a temporary file next to the status file and rename it, so readers never see half a file
+fn write_status_file(path: &std::path::Path, summary: &str) -> std::io::Result<()> {
+    let mut tmp_path = path.as_os_str().to_owned();
+    tmp_path.push(".tmp");
+    std::fs::write(&tmp_path, summary)?;
+    std::fs::rename(&tmp_path, path)
+}
+
+pub fn write_status_file_loop(run_info: ArcRuntimeInfo, path: std::path::PathBuf) {
+    if let Some(dir) = path.parent() {
+        if let Err(e) = std::fs::create_dir_all(dir) {
+            error!(
+                "Could not create the dir for the status file {:?}: {}",
+                path, e
+            );
+            return;
+        }
+    }
+    let mut last_summary = None;
+    loop {
+        let summary = format_status_summary(&run_info);
+        if last_summary.as_ref() != Some(&summary) {
+            match write_status_file(&path, &summary) {
+                Ok(()) => last_summary = Some(summary),
+                Err(e) => warn!("Could not write the status file {:?}: {}", path, e),
+            }
+        }
+        std::thread::sleep(STATUS_FILE_INTERVAL);
+    }
+}
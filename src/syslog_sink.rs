@@ -0,0 +1,110 @@
+//! Forward lines of service output to the local syslog daemon.
+//! The messages are sent as datagrams to /dev/log using the BSD syslog format (RFC 3164):
+//! `<priority>timestamp hostname ident[pid]: message`
+
+use std::os::unix::net::UnixDatagram;
+
+pub const SYSLOG_SOCKET_PATH: &str = "/dev/log";
+
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum SyslogFacility {
+    Kern = 0,
+    User = 1,
+    Mail = 2,
+    Daemon = 3,
+    Auth = 4,
+    Syslog = 5,
+    Lpr = 6,
+    News = 7,
+    Uucp = 8,
+    Cron = 9,
+    AuthPriv = 10,
+    Ftp = 11,
+    Local0 = 16,
+    Local1 = 17,
+    Local2 = 18,
+    Local3 = 19,
+    Local4 = 20,
+    Local5 = 21,
+    Local6 = 22,
+    Local7 = 23,
+}
+
+impl SyslogFacility {
+    pub fn from_name(name: &str) -> Option<SyslogFacility> {
+        let facility = match name {
+            "kern" => SyslogFacility::Kern,
+            "user" => SyslogFacility::User,
+            "mail" => SyslogFacility::Mail,
+            "daemon" => SyslogFacility::Daemon,
+            "auth" => SyslogFacility::Auth,
+            "syslog" => SyslogFacility::Syslog,
+            "lpr" => SyslogFacility::Lpr,
+            "news" => SyslogFacility::News,
+            "uucp" => SyslogFacility::Uucp,
+            "cron" => SyslogFacility::Cron,
+            "authpriv" => SyslogFacility::AuthPriv,
+            "ftp" => SyslogFacility::Ftp,
+            "local0" => SyslogFacility::Local0,
+            "local1" => SyslogFacility::Local1,
+            "local2" => SyslogFacility::Local2,
+            "local3" => SyslogFacility::Local3,
+            "local4" => SyslogFacility::Local4,
+            "local5" => SyslogFacility::Local5,
+            "local6" => SyslogFacility::Local6,
+            "local7" => SyslogFacility::Local7,
+            _ => return None,
+        };
+        Some(facility)
+    }
+}
+
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum SyslogSeverity {
+    Err = 3,
+    Info = 6,
+}
+
+pub fn format_message(
+    facility: SyslogFacility,
+    severity: SyslogSeverity,
+    ident: &str,
+    pid: nix::unistd::Pid,
+    msg: &[u8],
+) -> Vec<u8> {
+    let priority = facility as u8 * 8 + severity as u8;
+    let mut hostname_buf = [0u8; 256];
+    let hostname = match nix::unistd::gethostname(&mut hostname_buf) {
+        Ok(name) => name.to_string_lossy().into_owned(),
+        Err(_) => "localhost".to_owned(),
+    };
+
+    let mut buf = format!(
+        "<{}>{} {} {}[{}]: ",
+        priority,
+        chrono::Local::now().format("%b %e %H:%M:%S"),
+        hostname,
+        ident,
+        pid
+    )
+    .into_bytes();
+    buf.extend(msg);
+    buf
+}
+
+/// Send one line to the syslog daemon. Fails if /dev/log does not exist or nobody listens on it,
+/// the caller is expected to fall back to some other output in that case.
+pub fn send_line(
+    facility: SyslogFacility,
+    severity: SyslogSeverity,
+    ident: &str,
+    pid: nix::unistd::Pid,
+    msg: &[u8],
+) -> std::io::Result<()> {
+    let socket = UnixDatagram::unbound()?;
+    socket.send_to(
+        &format_message(facility, severity, ident, pid, msg),
+        SYSLOG_SOCKET_PATH,
+    )?;
+    Ok(())
+}
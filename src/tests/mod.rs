@@ -168,6 +168,8 @@ fn test_socket_parsing() {
     ListenFifo = {}
     Service= {}
     FileDescriptorName= {}
+    SocketMode= 0660
+    SocketGroup= sockgroup
 
     "#,
         descr,
@@ -220,6 +222,9 @@ fn test_socket_parsing() {
         panic!("No install config found, but there should be one");
     }
     if let crate::units::UnitSpecialized::Socket(sock) = socket_unit.specialized {
+        assert_eq!(sock.file_options.mode, Some(0o660));
+        assert_eq!(sock.file_options.user, None);
+        assert_eq!(sock.file_options.group, Some("sockgroup".to_owned()));
         if sock.sockets.len() == 8 {
             // streaming sockets
             if let crate::sockets::SpecializedSocketConfig::TcpSocket(tcpconf) =
@@ -400,6 +405,99 @@ fn test_unit_ordering() {
     assert!(unit_table.get(&id3).unwrap().install.after.contains(&id1));
 }
 
+#[test]
+fn test_install_section_resolving() {
+    let target_str = "
+    [Unit]
+    Description = Target
+    ";
+    let parsed_file = crate::units::parse_file(target_str).unwrap();
+    let target_unit = crate::units::parse_target(
+        parsed_file,
+        &std::path::PathBuf::from("/path/to/default.target"),
+        crate::units::UnitId(crate::units::UnitIdKind::Target, 1),
+    )
+    .unwrap();
+
+    // multi-user.target is not loaded, which must not prevent enabling the service for default.target
+    let service_str = "
+    [Service]
+    ExecStart = /path/to/startbin
+
+    [Install]
+    WantedBy = default.target
+    WantedBy = multi-user.target
+    ";
+    let parsed_file = crate::units::parse_file(service_str).unwrap();
+    let service_unit = crate::units::parse_service(
+        parsed_file,
+        &std::path::PathBuf::from("/path/to/enabled.service"),
+        crate::units::UnitId(crate::units::UnitIdKind::Service, 2),
+    )
+    .unwrap();
+
+    let target_id = target_unit.id;
+    let service_id = service_unit.id;
+    let mut unit_table = std::collections::HashMap::new();
+    unit_table.insert(target_id, target_unit);
+    unit_table.insert(service_id, service_unit);
+
+    crate::units::fill_dependencies(&mut unit_table);
+    crate::units::prune_units("default.target", &mut unit_table).unwrap();
+
+    let target = unit_table.get(&target_id).unwrap();
+    assert_eq!(target.install.wants, vec![service_id]);
+    // like in systemd the target is only reached after the services it wants have been started
+    assert_eq!(target.install.after, vec![service_id]);
+    let service = unit_table.get(&service_id).unwrap();
+    assert_eq!(service.install.wanted_by, vec![target_id]);
+    assert_eq!(service.install.before, vec![target_id]);
+}
+
+#[test]
+fn test_default_dependencies() {
+    let parsed_file = crate::units::parse_file("[Unit]\nDescription = Target").unwrap();
+    let sysinit = crate::units::parse_target(
+        parsed_file,
+        &std::path::PathBuf::from("/path/to/sysinit.target"),
+        crate::units::UnitId(crate::units::UnitIdKind::Target, 1),
+    )
+    .unwrap();
+    let parsed_file = crate::units::parse_file("[Service]\nExecStart = /bin/true").unwrap();
+    let normal = crate::units::parse_service(
+        parsed_file,
+        &std::path::PathBuf::from("/path/to/normal.service"),
+        crate::units::UnitId(crate::units::UnitIdKind::Service, 2),
+    )
+    .unwrap();
+    let parsed_file = crate::units::parse_file(
+        "[Unit]\nDefaultDependencies = no\n[Service]\nExecStart = /bin/true",
+    )
+    .unwrap();
+    let early = crate::units::parse_service(
+        parsed_file,
+        &std::path::PathBuf::from("/path/to/early.service"),
+        crate::units::UnitId(crate::units::UnitIdKind::Service, 3),
+    )
+    .unwrap();
+
+    let (sysinit_id, normal_id, early_id) = (sysinit.id, normal.id, early.id);
+    let mut unit_table = std::collections::HashMap::new();
+    unit_table.insert(sysinit_id, sysinit);
+    unit_table.insert(normal_id, normal);
+    unit_table.insert(early_id, early);
+
+    // shutdown.target is not loaded so no ordering against it is added
+    crate::units::add_default_dependencies(&mut unit_table);
+    crate::units::fill_dependencies(&mut unit_table);
+
+    let normal = unit_table.get(&normal_id).unwrap();
+    assert_eq!(normal.conf.after, vec!["sysinit.target".to_owned()]);
+    assert!(normal.conf.before.is_empty());
+    assert_eq!(normal.install.after, vec![sysinit_id]);
+    assert!(unit_table.get(&early_id).unwrap().install.after.is_empty());
+}
+
 #[test]
 fn test_circle() {
     let target1_str = format!(
@@ -483,3 +581,2616 @@ fn test_circle() {
         panic!("No circle found but there is one");
     }
 }
+
+#[test]
+fn test_uptime_formatting() {
+    use std::time::Duration;
+    assert_eq!(
+        crate::control::format_uptime(Duration::from_millis(5_500)),
+        "5s"
+    );
+    assert_eq!(
+        crate::control::format_uptime(Duration::from_secs(3 * 60 + 2)),
+        "3min 2s"
+    );
+    assert_eq!(
+        crate::control::format_uptime(Duration::from_secs(60 * 60)),
+        "1h 0min 0s"
+    );
+    assert_eq!(
+        crate::control::format_uptime(Duration::from_secs(2 * 24 * 60 * 60 + 61)),
+        "2d 0h 1min 1s"
+    );
+}
+
+#[test]
+fn test_output_line_splitting() {
+    let mut buffer = b"short\nthis line is too long\nrest".to_vec();
+    assert_eq!(
+        crate::services::next_line(&mut buffer, 10),
+        Some(b"short".to_vec())
+    );
+    assert_eq!(
+        crate::services::next_line(&mut buffer, 10),
+        Some(b"this line ".to_vec())
+    );
+    assert_eq!(
+        crate::services::next_line(&mut buffer, 10),
+        Some(b"is too lon".to_vec())
+    );
+    assert_eq!(
+        crate::services::next_line(&mut buffer, 10),
+        Some(b"g".to_vec())
+    );
+    assert_eq!(crate::services::next_line(&mut buffer, 10), None);
+    assert_eq!(buffer, b"rest".to_vec());
+
+    let mut buffer = b"0123456789abcdef".to_vec();
+    crate::services::cap_output_buffer(&mut buffer, 4);
+    assert_eq!(buffer, b"[truncated 12 bytes]cdef".to_vec());
+    crate::services::cap_output_buffer(&mut buffer, 0);
+    assert_eq!(buffer, b"[truncated 12 bytes]cdef".to_vec());
+}
+
+#[test]
+fn test_log_rate_limiting() {
+    let mut state = crate::services::LogRateLimitState::default();
+    let start = std::time::Instant::now();
+    let interval = Some(std::time::Duration::from_secs(1));
+    assert_eq!(state.allow(start, interval, Some(2)), (true, 0));
+    assert_eq!(state.allow(start, interval, Some(2)), (true, 0));
+    assert_eq!(state.allow(start, interval, Some(2)), (false, 0));
+    assert_eq!(state.allow(start, interval, Some(2)), (false, 0));
+    // the next window reports the suppressed lines once
+    let later = start + std::time::Duration::from_secs(1);
+    assert_eq!(state.allow(later, interval, Some(2)), (true, 2));
+    assert_eq!(state.allow(later, interval, Some(2)), (true, 0));
+
+    // no limit configured
+    let mut state = crate::services::LogRateLimitState::default();
+    for _ in 0..100 {
+        assert_eq!(state.allow(start, None, None), (true, 0));
+    }
+}
+
+#[test]
+fn test_log_extra_fields() {
+    assert_eq!(
+        crate::units::parse_log_extra_field("REGION=eu-west=1").unwrap(),
+        ("REGION".to_owned(), "eu-west=1".to_owned())
+    );
+    assert!(crate::units::parse_log_extra_field("region=eu").is_err());
+    assert!(crate::units::parse_log_extra_field("_PID=1").is_err());
+    assert!(crate::units::parse_log_extra_field("REGION").is_err());
+
+    let mut fields = std::collections::HashMap::new();
+    assert_eq!(crate::services::format_extra_fields_suffix(&fields), "");
+    fields.insert("VERSION".to_owned(), "1.2".to_owned());
+    fields.insert("ENV".to_owned(), "prod".to_owned());
+    assert_eq!(
+        crate::services::format_extra_fields_suffix(&fields),
+        " {ENV=prod VERSION=1.2}"
+    );
+}
+
+#[test]
+fn test_notification_datagram_handling() {
+    let test_service_str = r#"
+    [Service]
+    ExecStart = /path/to/startbin
+    Type = notify
+    "#;
+    let parsed_file = crate::units::parse_file(test_service_str).unwrap();
+    let mut unit = crate::units::parse_service(
+        parsed_file,
+        &std::path::PathBuf::from("/path/to/unitfile.service"),
+        crate::units::UnitId(crate::units::UnitIdKind::Service, 0),
+    )
+    .unwrap();
+
+    if let crate::units::UnitSpecialized::Service(srvc) = &mut unit.specialized {
+        // one datagram with two assignments, the last one without a trailing newline
+        srvc.notifications_buffer
+            .push_str("STATUS=foo=bar\nREADY=1");
+        crate::notification_handler::handle_notifications_from_buffer(
+            srvc,
+            "unitfile.service",
+            &crate::units::PidTable::default(),
+        );
+        assert_eq!(srvc.status_msgs, vec!["foo=bar".to_owned()]);
+        assert!(srvc.signaled_ready);
+        assert!(srvc.notifications_buffer.is_empty());
+    } else {
+        panic!("Parsed service is not a service");
+    }
+
+    // datagrams up to the limit are received whole, oversized ones are dropped without hurting the ones after them
+    let (receiver, sender) = std::os::unix::net::UnixDatagram::pair().unwrap();
+    let max = crate::notification_handler::NOTIFICATION_BUFFER_MAX;
+    let status = format!("STATUS={}", "x".repeat(max - "STATUS=".len()));
+    sender.send(status.as_bytes()).unwrap();
+    sender
+        .send(format!("STATUS={}", "y".repeat(max)).as_bytes())
+        .unwrap();
+    sender.send(b"READY=1").unwrap();
+    let mut buf = vec![0u8; 512];
+    let (bytes, _) =
+        crate::notification_handler::recv_notification_datagram(&receiver, &mut buf).unwrap();
+    assert_eq!(&buf[..bytes], status.as_bytes());
+    let (bytes, _) =
+        crate::notification_handler::recv_notification_datagram(&receiver, &mut buf).unwrap();
+    assert_eq!(bytes, 0);
+    let (bytes, _) =
+        crate::notification_handler::recv_notification_datagram(&receiver, &mut buf).unwrap();
+    assert_eq!(&buf[..bytes], b"READY=1");
+    // the receive buffer stops growing once it can hold the largest accepted datagram
+    assert!(buf.len() <= 2 * max);
+}
+
+#[test]
+fn test_notify_access() {
+    let test_service_str = r#"
+    [Service]
+    ExecStart = /path/to/startbin
+    Type = notify
+    NotifyAccess = main
+    "#;
+    let parsed_file = crate::units::parse_file(test_service_str).unwrap();
+    let mut unit = crate::units::parse_service(
+        parsed_file,
+        &std::path::PathBuf::from("/path/to/unitfile.service"),
+        crate::units::UnitId(crate::units::UnitIdKind::Service, 0),
+    )
+    .unwrap();
+
+    let test_dir =
+        std::env::temp_dir().join(format!("rustysd_notify_access_{}", std::process::id()));
+    std::fs::create_dir_all(&test_dir).unwrap();
+    let socket_path = test_dir.join("notify_socket");
+    let socket = std::os::unix::net::UnixDatagram::bind(&socket_path).unwrap();
+    crate::notification_handler::enable_sender_credentials(&socket).unwrap();
+    let sender_socket = std::os::unix::net::UnixDatagram::unbound().unwrap();
+    sender_socket.send_to(b"READY=1", &socket_path).unwrap();
+    let mut buf = vec![0u8; 4];
+    let (bytes, sender) =
+        crate::notification_handler::recv_notification_datagram(&socket, &mut buf).unwrap();
+    assert_eq!(&buf[..bytes], b"READY=1");
+    if cfg!(target_os = "linux") {
+        assert_eq!(sender, Some(nix::unistd::getpid()));
+    }
+    std::fs::remove_dir_all(&test_dir).unwrap();
+
+    if let crate::units::UnitSpecialized::Service(srvc) = &mut unit.specialized {
+        let main_pid = nix::unistd::Pid::from_raw(1000);
+        let other_pid = nix::unistd::Pid::from_raw(1001);
+        srvc.pid = Some(main_pid);
+        assert!(crate::notification_handler::notification_allowed(
+            srvc,
+            Some(main_pid)
+        ));
+        assert!(!crate::notification_handler::notification_allowed(
+            srvc,
+            Some(other_pid)
+        ));
+        srvc.service_config.notifyaccess = crate::units::NotifyKind::All;
+        assert!(crate::notification_handler::notification_allowed(
+            srvc,
+            Some(other_pid)
+        ));
+        srvc.service_config.notifyaccess = crate::units::NotifyKind::None;
+        assert!(!crate::notification_handler::notification_allowed(
+            srvc,
+            Some(main_pid)
+        ));
+    } else {
+        panic!("Parsed service is not a service");
+    }
+}
+
+#[test]
+fn test_exit_status_settings() {
+    use crate::signal_handler::ChildTermination;
+    use nix::sys::signal::Signal;
+    let test_service_str = r#"
+    [Service]
+    ExecStart = /path/to/startbin
+    SuccessExitStatus = 42 SIGUSR1
+    SuccessExitStatus = TERM
+    Restart = always
+    RestartPreventExitStatus = 3 4 SIGKILL
+    RestartForceExitStatus = 4
+    FinalKillSignal = QUIT
+    ReloadSignal = 10
+    OOMScoreAdjust = -500
+    IOSchedulingClass = idle
+    IOSchedulingPriority = 7
+    CPUSchedulingPolicy = fifo
+    CPUSchedulingPriority = 50
+    CPUSchedulingResetOnFork = yes
+    CPUAffinity = 0-2,5
+    CPUAffinity = 7
+    "#;
+    let parsed_file = crate::units::parse_file(test_service_str).unwrap();
+    let unit = crate::units::parse_service(
+        parsed_file,
+        &std::path::PathBuf::from("/path/to/unitfile.service"),
+        crate::units::UnitId(crate::units::UnitIdKind::Service, 0),
+    )
+    .unwrap();
+
+    if let crate::units::UnitSpecialized::Service(srvc) = &unit.specialized {
+        let conf = &srvc.service_config;
+        assert!(conf.is_success(&ChildTermination::Exit(0)));
+        assert!(conf.is_success(&ChildTermination::Exit(42)));
+        assert!(!conf.is_success(&ChildTermination::Exit(1)));
+        assert!(conf.is_success(&ChildTermination::Signal(Signal::SIGUSR1)));
+        assert!(conf.is_success(&ChildTermination::Signal(Signal::SIGTERM)));
+        assert!(!conf.is_success(&ChildTermination::Signal(Signal::SIGKILL)));
+
+        assert!(conf.should_restart(&ChildTermination::Exit(1)));
+        assert!(!conf.should_restart(&ChildTermination::Exit(3)));
+        // force takes precedence over prevent
+        assert!(conf.should_restart(&ChildTermination::Exit(4)));
+        assert!(!conf.should_restart(&ChildTermination::Signal(Signal::SIGKILL)));
+
+        assert_eq!(conf.final_kill_signal, Signal::SIGQUIT);
+        assert_eq!(conf.reload_signal, Signal::SIGUSR1);
+        assert_eq!(conf.oom_score_adjust, Some(-500));
+        assert_eq!(
+            conf.io_scheduling_class,
+            Some(crate::units::IoSchedClass::Idle)
+        );
+        assert_eq!(conf.io_scheduling_priority, Some(7));
+        assert_eq!(
+            conf.cpu_scheduling_policy,
+            Some(crate::units::SchedPolicy::Fifo)
+        );
+        assert_eq!(conf.cpu_scheduling_priority, Some(50));
+        assert!(conf.cpu_scheduling_reset_on_fork);
+        assert_eq!(conf.cpu_affinity, vec![0, 1, 2, 5, 7]);
+    } else {
+        panic!("Parsed service is not a service");
+    }
+
+    for out_of_range in &[
+        "OOMScoreAdjust = 1001",
+        "CPUSchedulingPolicy = rr\n    CPUSchedulingPriority = 0",
+        "CPUSchedulingPolicy = batch\n    CPUSchedulingPriority = 10",
+        "Nice = 20",
+    ] {
+        let service_str = format!(
+            "[Service]\n    ExecStart = /path/to/startbin\n    {}\n",
+            out_of_range
+        );
+        let parsed_file = crate::units::parse_file(&service_str).unwrap();
+        assert!(crate::units::parse_service(
+            parsed_file,
+            &std::path::PathBuf::from("/path/to/unitfile.service"),
+            crate::units::UnitId(crate::units::UnitIdKind::Service, 0),
+        )
+        .is_err());
+    }
+}
+
+#[test]
+fn test_start_limit() {
+    let test_service_str = r#"
+    [Service]
+    ExecStart = /path/to/startbin
+    StartLimitIntervalSec = 10
+    StartLimitBurst = 2
+    "#;
+    let parsed_file = crate::units::parse_file(test_service_str).unwrap();
+    let mut unit = crate::units::parse_service(
+        parsed_file,
+        &std::path::PathBuf::from("/path/to/unitfile.service"),
+        crate::units::UnitId(crate::units::UnitIdKind::Service, 0),
+    )
+    .unwrap();
+
+    if let crate::units::UnitSpecialized::Service(srvc) = &mut unit.specialized {
+        let start = std::time::Instant::now();
+        assert!(srvc.record_restart(start));
+        assert!(srvc.record_restart(start + std::time::Duration::from_secs(1)));
+        assert!(!srvc.record_restart(start + std::time::Duration::from_secs(2)));
+        // the first restart left the interval
+        assert!(srvc.record_restart(start + std::time::Duration::from_secs(10)));
+        assert_eq!(srvc.runtime_info.restarted, 3);
+
+        let reason = crate::services::RestartReason::MainProcessExited(
+            crate::signal_handler::ChildTermination::Exit(1),
+        );
+        srvc.set_restart_reason("unitfile.service", reason.clone());
+        assert_eq!(srvc.runtime_info.last_restart_reason, Some(reason.clone()));
+        assert_eq!(reason.to_string(), "main process exited with code 1");
+        assert_eq!(
+            crate::services::RestartReason::Dependency("dep.service".into()).to_string(),
+            "required unit dep.service was restarted"
+        );
+        assert_eq!(
+            crate::services::RestartReason::RuntimeMaxExceeded.to_string(),
+            "RuntimeMaxSec reached"
+        );
+    } else {
+        panic!("Parsed service is not a service");
+    }
+}
+
+#[test]
+fn test_failure_propagation_settings() {
+    let parse = |unit_section: &str| {
+        let parsed_file = crate::units::parse_file(&format!(
+            "[Unit]\n{}\n[Service]\nExecStart = /bin/true",
+            unit_section
+        ))
+        .unwrap();
+        crate::units::parse_service(
+            parsed_file,
+            &std::path::PathBuf::from("/path/to/unitfile.service"),
+            crate::units::UnitId(crate::units::UnitIdKind::Service, 0),
+        )
+    };
+
+    let unit = parse("Description = default").unwrap();
+    assert_eq!(
+        unit.conf.on_failure_job_mode,
+        crate::units::JobMode::Replace
+    );
+    assert!(unit.conf.propagates_failure());
+
+    let unit = parse("OnFailureJobMode = ignore-requirements").unwrap();
+    assert_eq!(
+        unit.conf.on_failure_job_mode,
+        crate::units::JobMode::IgnoreRequirements
+    );
+    assert!(!unit.conf.propagates_failure());
+
+    let unit = parse("PropagateFailureToRequires = no").unwrap();
+    assert!(!unit.conf.on_failure_propagate_to_requires);
+    assert!(!unit.conf.propagates_failure());
+
+    assert!(parse("OnFailureJobMode = isolate").is_err());
+}
+
+#[test]
+fn test_stdout_capture() {
+    let mut capture = crate::services::StdoutCapture::default();
+    capture.push_line(b"aaaa", 10);
+    capture.push_line(b"bbbb", 10);
+    capture.push_line(b"cccc", 10);
+    // the oldest line got dropped to stay below 10 bytes
+    assert_eq!(
+        capture.lines_since(0),
+        (vec![b"bbbb".to_vec(), b"cccc".to_vec()], 3)
+    );
+    assert_eq!(capture.lines_since(2), (vec![b"cccc".to_vec()], 3));
+    assert_eq!(capture.lines_since(3), (vec![], 3));
+}
+
+#[test]
+fn test_tmpfiles_parsing() {
+    use crate::tmpfiles::*;
+    let spec = "
+    # comment
+    d /run/test 0750 - - -
+    f  /run/test/file 0600 root root - some content
+    L /run/test/link - - - - /run/test/file
+    z /run/test/ignored 0644
+    ";
+    let entries = parse_spec(spec).unwrap();
+    assert_eq!(entries.len(), 3);
+    assert_eq!(entries[0].typ, TmpFileType::Directory);
+    assert_eq!(entries[0].mode, Some(0o750));
+    assert_eq!(entries[0].user, None);
+    assert_eq!(entries[1].typ, TmpFileType::File);
+    assert_eq!(entries[1].user, Some("root".to_owned()));
+    assert_eq!(entries[1].argument, Some("some content".to_owned()));
+    assert_eq!(entries[2].typ, TmpFileType::Symlink);
+    assert_eq!(entries[2].argument, Some("/run/test/file".to_owned()));
+
+    assert!(parse_line("d relative/path").is_err());
+    assert!(parse_line("L /run/test/link").is_err());
+}
+
+#[test]
+fn test_notification_socket_name() {
+    use crate::services::notification_socket_name;
+    assert_eq!(
+        notification_socket_name(
+            crate::config::DEFAULT_NOTIFICATION_SOCKET_NAME,
+            "test.service"
+        ),
+        Ok("test.service.notifiy_socket".to_owned())
+    );
+    assert_eq!(
+        notification_socket_name("%p-%i.%N.%%.sock", "getty@tty1.service"),
+        Ok("getty-tty1.getty@tty1.%.sock".to_owned())
+    );
+    assert_eq!(
+        notification_socket_name("%p_%i", "test.service"),
+        Ok("test_".to_owned())
+    );
+    assert!(notification_socket_name("%x", "test.service").is_err());
+    assert!(notification_socket_name("%n%", "test.service").is_err());
+    assert!(notification_socket_name("sub/%n", "test.service").is_err());
+
+    use crate::services::check_notification_socket_name_template;
+    assert!(check_notification_socket_name_template(
+        crate::config::DEFAULT_NOTIFICATION_SOCKET_NAME
+    )
+    .is_ok());
+    assert!(check_notification_socket_name_template("%N.sock").is_ok());
+    assert!(check_notification_socket_name_template("%p_%i").is_ok());
+    assert!(check_notification_socket_name_template("%x").is_err());
+    assert!(check_notification_socket_name_template("notify.sock").is_err());
+    assert!(check_notification_socket_name_template("%p.sock").is_err());
+    assert!(check_notification_socket_name_template("%%n").is_err());
+}
+
+#[test]
+fn test_device_rules() {
+    let parse_config = |settings: &str| {
+        let test_service_str = format!(
+            "
+    [Service]
+    ExecStart = /bin/true
+    {}
+    ",
+            settings
+        );
+        let parsed_file = crate::units::parse_file(&test_service_str).unwrap();
+        let id = crate::units::UnitId(crate::units::UnitIdKind::Service, 0);
+        crate::units::parse_service(
+            parsed_file,
+            &std::path::PathBuf::from("/path/to/device_test.service"),
+            id,
+        )
+        .map(|unit| match unit.specialized {
+            crate::units::UnitSpecialized::Service(srvc) => srvc.service_config,
+            _ => panic!("Parsed service is not a service"),
+        })
+    };
+
+    let conf = parse_config("").unwrap();
+    assert!(!conf.restricts_devices());
+    assert_eq!(conf.device_rules(), Ok(None));
+
+    let conf = parse_config(
+        "DevicePolicy = strict\n    DeviceAllow = /dev/null r\n    DeviceAllow = /dev/zero",
+    )
+    .unwrap();
+    let rules = conf.device_rules().unwrap().unwrap();
+    let lines: Vec<String> = rules.iter().map(|rule| rule.v1_line()).collect();
+    assert_eq!(lines, vec!["c 1:3 r".to_owned(), "c 1:5 rwm".to_owned()]);
+
+    // the pseudo devices come first, the allowed device is added to them
+    let conf = parse_config("DeviceAllow = /dev/null m").unwrap();
+    assert_eq!(conf.device_policy, crate::units::DevicePolicy::Auto);
+    let rules = conf.device_rules().unwrap().unwrap();
+    assert_eq!(rules[0].v1_line(), "c 1:3 rw");
+    assert_eq!(rules.last().unwrap().v1_line(), "c 1:3 m");
+
+    assert!(parse_config("DevicePolicy = open").is_err());
+    assert!(parse_config("DeviceAllow = /dev/null rx").is_err());
+    assert!(parse_config("DeviceAllow = null rw").is_err());
+
+    // every rule ends with returning 1, the program with returning 0
+    let prog = crate::platform::cgroups::devices::build_device_program(&rules);
+    let exits = prog.iter().filter(|insn| insn.code == 0x95).count();
+    assert_eq!(exits, rules.len() + 1);
+}
+
+#[test]
+fn test_env_file_parsing() {
+    use crate::env_file::*;
+    let content = r#"
+    # comment
+    ; also a comment
+    PLAIN=value
+    SPACED = some value  # inline comment
+    DOUBLE="a \"quoted\" value\n with \$HOME # no comment"
+    SINGLE='no \n escapes'
+    MIXED=a"b c"'d e'f
+    HASH=a#b
+    EMPTY=
+    "#;
+    let vars = parse_env_file(content).unwrap();
+    let expected = [
+        ("PLAIN", "value"),
+        ("SPACED", "some value"),
+        ("DOUBLE", "a \"quoted\" value\n with $HOME # no comment"),
+        ("SINGLE", "no \\n escapes"),
+        ("MIXED", "ab cd ef"),
+        ("HASH", "a#b"),
+        ("EMPTY", ""),
+    ];
+    assert_eq!(vars.len(), expected.len());
+    for ((var, value), (expected_var, expected_value)) in vars.iter().zip(expected.iter()) {
+        assert_eq!(var, expected_var);
+        assert_eq!(value, expected_value);
+    }
+
+    assert!(parse_env_file("NO_ASSIGNMENT").is_err());
+    assert!(parse_env_file("1VAR=value").is_err());
+    assert!(parse_env_file("VAR=\"unterminated").is_err());
+
+    let missing = std::path::PathBuf::from("/nonexistent/rustysd_env_file");
+    assert_eq!(read_env_files(&[(missing.clone(), true)]), Ok(vec![]));
+    assert!(read_env_files(&[(missing, false)]).is_err());
+}
+
+#[test]
+fn test_condition_parsing() {
+    let target_str = "
+    [Unit]
+    Description = Target
+    ConditionPathExists = /
+    ConditionPathExists = !/this/path/should/not/exist
+    ConditionDirectoryNotEmpty = /this/path/should/not/exist
+    AssertPathIsDirectory = /
+    ";
+
+    let parsed_file = crate::units::parse_file(target_str).unwrap();
+    let target_unit = crate::units::parse_target(
+        parsed_file,
+        &std::path::PathBuf::from("/path/to/1.target"),
+        crate::units::UnitId(crate::units::UnitIdKind::Target, 1),
+    )
+    .unwrap();
+
+    let conditions = &target_unit.conf.conditions;
+    assert_eq!(conditions.len(), 3);
+    assert_eq!(
+        conditions[1],
+        crate::units::PathCheck {
+            kind: crate::units::PathCheckKind::PathExists,
+            path: std::path::PathBuf::from("/this/path/should/not/exist"),
+            negate: true,
+        }
+    );
+    assert!(conditions[0].is_met());
+    assert!(conditions[1].is_met());
+    assert!(!conditions[2].is_met());
+
+    let asserts = &target_unit.conf.asserts;
+    assert_eq!(asserts.len(), 1);
+    assert_eq!(
+        asserts[0].kind,
+        crate::units::PathCheckKind::PathIsDirectory
+    );
+    assert!(asserts[0].is_met());
+
+    let relative_str = "
+    [Unit]
+    ConditionPathExists = relative/path
+    ";
+    let parsed_file = crate::units::parse_file(relative_str).unwrap();
+    assert!(crate::units::parse_target(
+        parsed_file,
+        &std::path::PathBuf::from("/path/to/2.target"),
+        crate::units::UnitId(crate::units::UnitIdKind::Target, 2),
+    )
+    .is_err());
+}
+
+/// RuntimeInfo with only the given service in the status table. There is no signal handler in the tests,
+/// so the returned thread reaps the helper processes until done is set.
+fn make_test_run_info(
+    test_dir: &std::path::Path,
+    id: crate::units::UnitId,
+    done: std::sync::Arc<std::sync::atomic::AtomicBool>,
+) -> (crate::units::ArcRuntimeInfo, std::thread::JoinHandle<()>) {
+    use std::sync::{Arc, Mutex};
+
+    let mut status_table = std::collections::HashMap::new();
+    status_table.insert(
+        id,
+        Arc::new(crate::lock_order::OrderedMutex::new(
+            crate::lock_order::LockLevel::Status,
+            crate::units::UnitStatus::Starting,
+        )),
+    );
+    let run_info = Arc::new(crate::units::RuntimeInfo {
+        unit_table: Arc::new(crate::lock_order::OrderedRwLock::new(
+            crate::lock_order::LockLevel::UnitTable,
+            std::collections::HashMap::new(),
+        )),
+        status_table: Arc::new(crate::lock_order::OrderedRwLock::new(
+            crate::lock_order::LockLevel::StatusTable,
+            status_table,
+        )),
+        pid_table: Arc::new(crate::units::PidTable::default()),
+        fd_store: Arc::new(crate::lock_order::OrderedRwLock::new(
+            crate::lock_order::LockLevel::FdStore,
+            crate::fd_store::FDStore::default(),
+        )),
+        config: crate::config::Config {
+            unit_dirs: vec![test_dir.join("units")],
+            target_unit: "default.target".into(),
+            notification_sockets_dir: test_dir.join("notify"),
+            notification_socket_name: crate::config::DEFAULT_NOTIFICATION_SOCKET_NAME.to_owned(),
+            max_log_line_length: 1024,
+            max_output_buffer_size: 1024,
+            stdout_capture_size: 1024,
+            log_format: crate::config::LogFormat::Plain,
+            log_to_journald: false,
+            watch_unit_dirs: false,
+            status_file: None,
+            state_file: None,
+        },
+        last_id: Arc::new(Mutex::new(0)),
+        events: crate::units::EventSubscribers::default(),
+        state_file_lock: Mutex::new(()),
+    });
+
+    let reaper_run_info = run_info.clone();
+    let reaper = std::thread::spawn(move || {
+        let pid_table = &reaper_run_info.pid_table;
+        while !done.load(std::sync::atomic::Ordering::SeqCst) {
+            {
+                let mut pid_table_locked = pid_table.lock().unwrap();
+                let children: Vec<_> = pid_table_locked
+                    .iter()
+                    .filter(|(_, entry)| {
+                        matches!(
+                            entry,
+                            crate::units::PidEntry::Helper(_, _)
+                                | crate::units::PidEntry::Service(
+                                    _,
+                                    crate::units::ServiceType::OneShot
+                                )
+                        )
+                    })
+                    .map(|(pid, entry)| (*pid, entry.clone()))
+                    .collect();
+                for (pid, entry) in children {
+                    let flags = Some(nix::sys::wait::WaitPidFlag::WNOHANG);
+                    let code = match nix::sys::wait::waitpid(pid, flags) {
+                        Ok(nix::sys::wait::WaitStatus::Exited(_, code)) => {
+                            crate::signal_handler::ChildTermination::Exit(code)
+                        }
+                        Ok(nix::sys::wait::WaitStatus::Signaled(_, signal, _)) => {
+                            crate::signal_handler::ChildTermination::Signal(signal)
+                        }
+                        _ => continue,
+                    };
+                    let exited = if let crate::units::PidEntry::Helper(_, _) = entry {
+                        crate::units::PidEntry::HelperExited(code)
+                    } else {
+                        crate::units::PidEntry::OneshotExited(code)
+                    };
+                    pid_table_locked.insert(pid, exited);
+                    pid_table.notify_exited();
+                }
+            }
+            std::thread::sleep(std::time::Duration::from_millis(1));
+        }
+    });
+    (run_info, reaper)
+}
+
+/// Load the units from the unit dirs of the config into the (cleared) tables, with the status status_of returns for
+/// their name. Returns the ids by name
+fn load_test_units(
+    run_info: &crate::units::RuntimeInfo,
+    status_of: impl Fn(&str) -> crate::units::UnitStatus,
+) -> std::collections::HashMap<String, crate::units::UnitId> {
+    use std::sync::Arc;
+
+    let last_id = &mut *run_info.last_id.lock().unwrap();
+    let units = crate::units::load_all_units(&run_info.config.unit_dirs, last_id, "default.target")
+        .unwrap();
+    let mut unit_table = run_info.unit_table.write().unwrap();
+    let mut status_table = run_info.status_table.write().unwrap();
+    status_table.clear();
+    let mut ids = std::collections::HashMap::new();
+    for (id, unit) in units {
+        let name = unit.conf.name();
+        status_table.insert(
+            id,
+            Arc::new(crate::lock_order::OrderedMutex::new(
+                crate::lock_order::LockLevel::Status,
+                status_of(&name),
+            )),
+        );
+        unit_table.insert(
+            id,
+            Arc::new(crate::lock_order::OrderedMutex::new(
+                crate::lock_order::LockLevel::Unit,
+                unit,
+            )),
+        );
+        ids.insert(name, id);
+    }
+    ids
+}
+
+#[test]
+fn test_failed_prestart_skips_start() {
+    let test_dir = std::env::temp_dir().join(format!("rustysd_prestart_{}", std::process::id()));
+    std::fs::create_dir_all(&test_dir).unwrap();
+    let poststop_marker = test_dir.join("poststop_ran");
+
+    let test_service_str = format!(
+        r#"
+    [Service]
+    ExecStartPre = /bin/false
+    ExecStart = /bin/sleep 10
+    ExecStopPost = /bin/touch {}
+    "#,
+        poststop_marker.to_str().unwrap()
+    );
+    let parsed_file = crate::units::parse_file(&test_service_str).unwrap();
+    let id = crate::units::UnitId(crate::units::UnitIdKind::Service, 0);
+    let mut unit = crate::units::parse_service(
+        parsed_file,
+        &std::path::PathBuf::from("/path/to/prestart_test.service"),
+        id,
+    )
+    .unwrap();
+
+    let done = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
+    let (run_info, reaper) = make_test_run_info(&test_dir, id, done.clone());
+
+    if let crate::units::UnitSpecialized::Service(srvc) = &mut unit.specialized {
+        let res = srvc.start(
+            id,
+            "prestart_test.service",
+            run_info.clone(),
+            test_dir.join("notify"),
+            &[],
+            false,
+        );
+        // ExecStartPre= failed so the main process must never have been forked
+        match res {
+            Err(crate::services::ServiceErrorReason::PrestartFailed(_)) => {}
+            Err(e) => panic!("Expected the prestart to fail, but got: {}", e),
+            Ok(_) => panic!("Expected the prestart to fail, but the service started"),
+        }
+        assert!(srvc.pid.is_none());
+        assert!(srvc.runtime_info.up_since.is_none());
+        // but ExecStopPost= did run
+        assert!(poststop_marker.exists());
+    } else {
+        panic!("Parsed service is not a service");
+    }
+
+    done.store(true, std::sync::atomic::Ordering::SeqCst);
+    reaper.join().unwrap();
+    std::fs::remove_dir_all(&test_dir).unwrap();
+}
+
+#[test]
+fn test_helper_output_larger_than_pipe_buffer() {
+    let test_dir = std::env::temp_dir().join(format!("rustysd_helper_out_{}", std::process::id()));
+    std::fs::create_dir_all(&test_dir).unwrap();
+
+    // the output is read while waiting, otherwise the helper blocks on the full pipe until the timeout
+    let test_service_str = r#"
+    [Service]
+    Type = oneshot
+    ExecStartPre = /bin/sh -c "head -c 200000 /dev/zero"
+    ExecStart = /bin/true
+    StandardOutput = null
+    TimeoutStartSec = 5
+    "#;
+    let parsed_file = crate::units::parse_file(test_service_str).unwrap();
+    let id = crate::units::UnitId(crate::units::UnitIdKind::Service, 0);
+    let mut unit = crate::units::parse_service(
+        parsed_file,
+        &std::path::PathBuf::from("/path/to/helper_out_test.service"),
+        id,
+    )
+    .unwrap();
+
+    let done = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
+    let (run_info, reaper) = make_test_run_info(&test_dir, id, done.clone());
+
+    if let crate::units::UnitSpecialized::Service(srvc) = &mut unit.specialized {
+        let start_time = std::time::Instant::now();
+        let res = srvc.start(
+            id,
+            "helper_out_test.service",
+            run_info.clone(),
+            test_dir.join("notify"),
+            &[],
+            false,
+        );
+        if let Err(e) = res {
+            panic!("Expected the service to start, but got: {}", e);
+        }
+        assert!(start_time.elapsed() < std::time::Duration::from_secs(5));
+    } else {
+        panic!("Parsed service is not a service");
+    }
+
+    done.store(true, std::sync::atomic::Ordering::SeqCst);
+    reaper.join().unwrap();
+    std::fs::remove_dir_all(&test_dir).unwrap();
+}
+
+#[test]
+fn test_poststart_runs_after_ready() {
+    let test_dir = std::env::temp_dir().join(format!("rustysd_ready_{}", std::process::id()));
+    std::fs::create_dir_all(&test_dir).unwrap();
+    let start_marker = test_dir.join("start_ran");
+
+    // ExecStartPost= fails if it runs before the oneshot ExecStart= has finished
+    let test_service_str = format!(
+        r#"
+    [Service]
+    Type = oneshot
+    ExecStart = /bin/touch {marker}
+    ExecStartPost = /bin/test -e {marker}
+    "#,
+        marker = start_marker.to_str().unwrap()
+    );
+    let parsed_file = crate::units::parse_file(&test_service_str).unwrap();
+    let id = crate::units::UnitId(crate::units::UnitIdKind::Service, 0);
+    let mut unit = crate::units::parse_service(
+        parsed_file,
+        &std::path::PathBuf::from("/path/to/ready_test.service"),
+        id,
+    )
+    .unwrap();
+
+    let done = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
+    let (run_info, reaper) = make_test_run_info(&test_dir, id, done.clone());
+
+    if let crate::units::UnitSpecialized::Service(srvc) = &mut unit.specialized {
+        let res = srvc.start(
+            id,
+            "ready_test.service",
+            run_info.clone(),
+            test_dir.join("notify"),
+            &[],
+            false,
+        );
+        match res {
+            Ok(crate::services::StartResult::Started) => {}
+            Ok(_) => panic!("Expected the service to be started"),
+            Err(e) => panic!("Expected the service to be started, but got: {}", e),
+        }
+        assert!(srvc.runtime_info.up_since.is_some());
+    } else {
+        panic!("Parsed service is not a service");
+    }
+
+    done.store(true, std::sync::atomic::Ordering::SeqCst);
+    reaper.join().unwrap();
+    std::fs::remove_dir_all(&test_dir).unwrap();
+}
+
+#[test]
+fn test_nice_is_applied() {
+    let test_dir = std::env::temp_dir().join(format!("rustysd_nice_{}", std::process::id()));
+    std::fs::create_dir_all(&test_dir).unwrap();
+
+    let test_service_str = r#"
+    [Service]
+    ExecStart = /bin/sleep 10
+    Nice = 10
+    "#;
+    let parsed_file = crate::units::parse_file(test_service_str).unwrap();
+    let id = crate::units::UnitId(crate::units::UnitIdKind::Service, 0);
+    let mut unit = crate::units::parse_service(
+        parsed_file,
+        &std::path::PathBuf::from("/path/to/nice_test.service"),
+        id,
+    )
+    .unwrap();
+
+    let done = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
+    let (run_info, reaper) = make_test_run_info(&test_dir, id, done.clone());
+
+    if let crate::units::UnitSpecialized::Service(srvc) = &mut unit.specialized {
+        if let Err(e) = srvc.start(
+            id,
+            "nice_test.service",
+            run_info.clone(),
+            test_dir.join("notify"),
+            &[],
+            false,
+        ) {
+            panic!("Expected the service to be started, but got: {}", e);
+        }
+        let pid = srvc.pid.unwrap();
+
+        // the niceness is set right before exec, so wait until the process runs sleep
+        let stat_path = format!("/proc/{}/stat", pid);
+        let start_time = std::time::Instant::now();
+        let niceness = loop {
+            let stat = std::fs::read_to_string(&stat_path).unwrap();
+            // the fields after the command name start with the state, nice is the 19th field
+            let (comm, rest) = stat.split_at(stat.rfind(')').unwrap());
+            if comm.ends_with("(sleep") {
+                break rest[1..].split_whitespace().nth(16).unwrap().to_owned();
+            }
+            assert!(start_time.elapsed() < std::time::Duration::from_secs(5));
+            std::thread::sleep(std::time::Duration::from_millis(1));
+        };
+        assert_eq!(niceness, "10");
+
+        nix::sys::signal::kill(pid, nix::sys::signal::Signal::SIGKILL).unwrap();
+        nix::sys::wait::waitpid(pid, None).unwrap();
+    } else {
+        panic!("Parsed service is not a service");
+    }
+
+    done.store(true, std::sync::atomic::Ordering::SeqCst);
+    reaper.join().unwrap();
+    std::fs::remove_dir_all(&test_dir).unwrap();
+}
+
+#[test]
+fn test_subscribe_gets_started_event() {
+    let test_dir = std::env::temp_dir().join(format!("rustysd_events_{}", std::process::id()));
+    std::fs::create_dir_all(&test_dir).unwrap();
+
+    let test_service_str = r#"
+    [Service]
+    Type = oneshot
+    ExecStart = /bin/true
+    "#;
+    let parsed_file = crate::units::parse_file(test_service_str).unwrap();
+    let id = crate::units::UnitId(crate::units::UnitIdKind::Service, 0);
+    let mut unit = crate::units::parse_service(
+        parsed_file,
+        &std::path::PathBuf::from("/path/to/events_test.service"),
+        id,
+    )
+    .unwrap();
+
+    let done = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
+    let (run_info, reaper) = make_test_run_info(&test_dir, id, done.clone());
+    let events = run_info.events.subscribe();
+    // subscribers that are gone must not keep the others from getting events
+    drop(run_info.events.subscribe());
+
+    if let crate::units::UnitSpecialized::Service(srvc) = &mut unit.specialized {
+        if let Err(e) = srvc.start(
+            id,
+            "events_test.service",
+            run_info.clone(),
+            test_dir.join("notify"),
+            &[],
+            false,
+        ) {
+            panic!("Expected the service to be started, but got: {}", e);
+        }
+    } else {
+        panic!("Parsed service is not a service");
+    }
+
+    let event = events.try_recv().unwrap();
+    assert_eq!(event.unit_name, "events_test.service");
+    match event.kind {
+        crate::units::UnitEventKind::Started(_) => {}
+        kind => panic!("Expected a started event, got: {:?}", kind),
+    }
+
+    done.store(true, std::sync::atomic::Ordering::SeqCst);
+    reaper.join().unwrap();
+    std::fs::remove_dir_all(&test_dir).unwrap();
+}
+
+#[test]
+fn test_capabilities_parsing() {
+    let test_service_str = r#"
+    [Service]
+    ExecStart = /bin/true
+    AmbientCapabilities = CAP_NET_BIND_SERVICE
+    AmbientCapabilities = cap_chown
+    CapabilityBoundingSet = ~CAP_SYS_ADMIN CAP_SYS_MODULE
+    CapabilityBoundingSet = ~CAP_CHOWN
+    "#;
+    let parsed_file = crate::units::parse_file(test_service_str).unwrap();
+    let unit = crate::units::parse_service(
+        parsed_file,
+        &std::path::PathBuf::from("/path/to/caps_test.service"),
+        crate::units::UnitId(crate::units::UnitIdKind::Service, 0),
+    )
+    .unwrap();
+    if let crate::units::UnitSpecialized::Service(srvc) = &unit.specialized {
+        // CAP_CHOWN is 0, CAP_NET_BIND_SERVICE is 10
+        assert_eq!(srvc.service_config.ambient_capabilities, 1 | 1 << 10);
+        // CAP_SYS_MODULE is 16, CAP_SYS_ADMIN is 21
+        let bounding_set = srvc.service_config.capability_bounding_set.unwrap();
+        assert_eq!(bounding_set & (1 | 1 << 16 | 1 << 21), 0);
+        assert_ne!(bounding_set & 1 << 10, 0);
+    } else {
+        panic!("Parsed service is not a service");
+    }
+
+    let unknown_capability = r#"
+    [Service]
+    ExecStart = /bin/true
+    AmbientCapabilities = CAP_NOT_A_CAPABILITY
+    "#;
+    let parsed_file = crate::units::parse_file(unknown_capability).unwrap();
+    assert!(crate::units::parse_service(
+        parsed_file,
+        &std::path::PathBuf::from("/path/to/caps_test.service"),
+        crate::units::UnitId(crate::units::UnitIdKind::Service, 0),
+    )
+    .is_err());
+}
+
+#[test]
+fn test_sandboxing_settings() {
+    let test_dir = std::env::temp_dir().join(format!("rustysd_sandbox_{}", std::process::id()));
+    std::fs::create_dir_all(&test_dir).unwrap();
+    // the service must not see the files in the /tmp of rustysd
+    let marker = std::path::PathBuf::from("/tmp")
+        .join(format!("rustysd_sandbox_marker_{}", std::process::id()));
+    std::fs::write(&marker, "").unwrap();
+    // a root that only contains /usr (and the links into it that the system has), bound in by BindReadOnlyPaths=
+    let root = test_dir.join("root");
+    std::fs::create_dir_all(&root).unwrap();
+    for link in &["bin", "lib", "lib64"] {
+        if let Ok(target) = std::fs::read_link(std::path::Path::new("/").join(link)) {
+            std::os::unix::fs::symlink(target, root.join(link)).unwrap();
+        }
+    }
+    // the destination is below the root like everything else that gets mounted
+    let root_directory = format!(
+        "RootDirectory = {}\n    BindReadOnlyPaths = /usr:/usr",
+        root.display()
+    );
+    // PrivateTmp= has to mount the private /tmp below the root too
+    let tmp_root = test_dir.join("tmp_root");
+    std::fs::create_dir_all(tmp_root.join("tmp")).unwrap();
+    for link in &["bin", "lib", "lib64"] {
+        if let Ok(target) = std::fs::read_link(std::path::Path::new("/").join(link)) {
+            std::os::unix::fs::symlink(target, tmp_root.join(link)).unwrap();
+        }
+    }
+    let root_directory_private_tmp = format!(
+        "RootDirectory = {}\n    BindReadOnlyPaths = /usr:/usr\n    PrivateTmp = yes",
+        tmp_root.display()
+    );
+    // the same root with /usr already bound in (read-only, so remove_dir_all can not touch it), RootDirectory= alone
+    // has to create the mount namespace for MountAPIVFS=
+    let bound_root = test_dir.join("bound_root");
+    std::fs::create_dir_all(bound_root.join("usr")).unwrap();
+    for link in &["bin", "lib", "lib64"] {
+        if let Ok(target) = std::fs::read_link(std::path::Path::new("/").join(link)) {
+            std::os::unix::fs::symlink(target, bound_root.join(link)).unwrap();
+        }
+    }
+    let bound_usr = bound_root.join("usr");
+    nix::mount::mount(
+        Some("/usr"),
+        &bound_usr,
+        None::<&str>,
+        nix::mount::MsFlags::MS_BIND,
+        None::<&str>,
+    )
+    .unwrap();
+    nix::mount::mount(
+        None::<&str>,
+        &bound_usr,
+        None::<&str>,
+        nix::mount::MsFlags::MS_BIND
+            | nix::mount::MsFlags::MS_REMOUNT
+            | nix::mount::MsFlags::MS_RDONLY,
+        None::<&str>,
+    )
+    .unwrap();
+    let root_directory_api_vfs = format!(
+        "RootDirectory = {}\n    MountAPIVFS = yes",
+        bound_root.display()
+    );
+    let own_mount_ns = std::fs::read_link("/proc/self/ns/mnt").unwrap();
+
+    let checks = [
+        ("PrivateTmp = yes", format!("test ! -e {:?}", marker)),
+        ("ProtectSystem = yes", "test ! -w /usr".to_owned()),
+        (
+            "ProtectKernelTunables = yes",
+            "test ! -w /proc/sys/kernel/hostname".to_owned(),
+        ),
+        // setarch -R tries to disable address space randomization with personality()
+        ("LockPersonality = yes", "! setarch -R true".to_owned()),
+        (
+            "NoNewPrivileges = yes",
+            "grep -q 'NoNewPrivs:.*1' /proc/self/status".to_owned(),
+        ),
+        // the tests run as root, with CAP_SYS_ADMIN the seccomp settings do not imply NoNewPrivileges=
+        (
+            "RestrictRealtime = yes",
+            "grep -q 'NoNewPrivs:.*0' /proc/self/status".to_owned(),
+        ),
+        // with cgroup v1 the controllers are separate mounts that need to be read-only too
+        (
+            "ProtectControlGroups = yes",
+            "test ! -w /sys/fs/cgroup && test ! -w /sys/fs/cgroup/$(ls /sys/fs/cgroup | head -n1)".to_owned(),
+        ),
+        // CAP_SYS_MODULE is bit 16 of the bounding set, so the fifth hex digit from the right must be even
+        (
+            "ProtectKernelModules = yes",
+            "! grep -q 'CapBnd:.*[13579bdf]....$' /proc/self/status".to_owned(),
+        ),
+        // the mount point is created in the private /tmp, so nothing is left behind
+        (
+            "PrivateTmp = yes\n    BindPaths = /usr/bin:/tmp/bin",
+            "test -x /tmp/bin/sh && test -w /tmp/bin".to_owned(),
+        ),
+        (
+            "PrivateTmp = yes\n    BindReadOnlyPaths = /usr/bin:/tmp/bin",
+            "test -x /tmp/bin/sh && test ! -w /tmp/bin".to_owned(),
+        ),
+        // the tests run as root, so hidepid= hides no processes. Only the entries that do not belong to a process are gone
+        (
+            "ProcSubset = pid",
+            "test -r /proc/self/status && test ! -e /proc/sys && test ! -e /proc/meminfo".to_owned(),
+        ),
+        (
+            "TemporaryFileSystem = /etc:ro",
+            "test ! -e /etc/passwd && test ! -w /etc".to_owned(),
+        ),
+        // the mount point is created in the private /tmp
+        (
+            "PrivateTmp = yes\n    TemporaryFileSystem = /tmp/a/b:mode=0700,size=1m",
+            "test $(stat -c %a /tmp/a/b) = 700 && touch /tmp/a/b/file".to_owned(),
+        ),
+        // on its own it still gets a mount namespace, the mounts must not leak to rustysd
+        (
+            "MountAPIVFS = yes",
+            format!(
+                "test $(readlink /proc/self/ns/mnt) != {} && test -r /proc/self/status",
+                own_mount_ns.display()
+            ),
+        ),
+        // everything is mounted already, so this must not change anything
+        (
+            "PrivateTmp = yes\n    MountAPIVFS = yes",
+            "test -r /proc/self/status && test -d /sys/kernel && test -c /dev/null && test -d /dev/pts".to_owned(),
+        ),
+        (
+            root_directory.as_str(),
+            "test -x /bin/sh && test ! -e /proc && test ! -e /tmp".to_owned(),
+        ),
+        (
+            root_directory_api_vfs.as_str(),
+            // /dev only has the standard nodes, no block devices of the host
+            "test -r /proc/self/status && test -c /dev/null && ! find /dev -type b | grep -q . && test ! -e /tmp"
+                .to_owned(),
+        ),
+        (
+            root_directory_private_tmp.as_str(),
+            "test $(stat -f -c %T /tmp) = tmpfs && touch /tmp/file".to_owned(),
+        ),
+        ("RestrictNamespaces = ~net", "! unshare -n true".to_owned()),
+        ("RestrictRealtime = yes", "! chrt -f 1 true".to_owned()),
+        // SIGPIPE is signal 13, so it is the fourth hex digit from the right of the ignored signals
+        (
+            "IgnoreSIGPIPE = yes",
+            "grep -q 'SigIgn:.*[13579bdf]...$' /proc/self/status && grep -q 'SigBlk:[[:space:]]*0*$' /proc/self/status".to_owned(),
+        ),
+        (
+            "IgnoreSIGPIPE = no",
+            "! grep -q 'SigIgn:.*[13579bdf]...$' /proc/self/status".to_owned(),
+        ),
+        // the variables rustysd sets itself are not affected
+        (
+            "PassEnvironment = PATH",
+            "test -n $PATH && test -z $HOME && test -n $NOTIFY_SOCKET".to_owned(),
+        ),
+        ("UnsetEnvironment = HO*", "test -n $PATH && test -z $HOME".to_owned()),
+        // python creates AF_INET sockets by default
+        (
+            "RestrictAddressFamilies = AF_UNIX",
+            "! python3 -c 'import socket; socket.socket()'".to_owned(),
+        ),
+        // commas would split the setting, so the arguments (fd, length, flags, prot) are collected in a list.
+        // 7 is PROT_READ | PROT_WRITE | PROT_EXEC
+        (
+            "MemoryDenyWriteExecute = yes",
+            "! python3 -c 'import mmap; a = [-1]; a.append(4096); a.append(mmap.MAP_PRIVATE | mmap.MAP_ANONYMOUS); a.append(7); mmap.mmap(*a)'".to_owned(),
+        ),
+    ];
+    for (setting, check) in &checks {
+        let test_service_str = format!(
+            r#"
+    [Service]
+    Type = oneshot
+    ExecStart = /bin/sh -c "{}"
+    {}
+    "#,
+            check, setting
+        );
+        let parsed_file = crate::units::parse_file(&test_service_str).unwrap();
+        let id = crate::units::UnitId(crate::units::UnitIdKind::Service, 0);
+        let mut unit = crate::units::parse_service(
+            parsed_file,
+            &std::path::PathBuf::from("/path/to/sandbox_test.service"),
+            id,
+        )
+        .unwrap();
+
+        let done = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let (run_info, reaper) = make_test_run_info(&test_dir, id, done.clone());
+        if let crate::units::UnitSpecialized::Service(srvc) = &mut unit.specialized {
+            if let Err(e) = srvc.start(
+                id,
+                "sandbox_test.service",
+                run_info.clone(),
+                test_dir.join("notify"),
+                &[],
+                false,
+            ) {
+                panic!("Check for {} failed: {}", setting, e);
+            }
+        } else {
+            panic!("Parsed service is not a service");
+        }
+        done.store(true, std::sync::atomic::Ordering::SeqCst);
+        reaper.join().unwrap();
+    }
+
+    // the API file systems were only mounted in the mount namespace of the service
+    let mountinfo = std::fs::read_to_string("/proc/self/mountinfo").unwrap();
+    assert!(!mountinfo.contains(bound_root.join("proc").to_str().unwrap()));
+    assert!(!tmp_root.join("tmp/file").exists());
+    nix::mount::umount(&bound_usr).unwrap();
+
+    std::fs::remove_file(&marker).unwrap();
+    std::fs::remove_dir_all(&test_dir).unwrap();
+}
+
+#[test]
+fn test_oneshot_multiple_exec_start() {
+    let test_dir = std::env::temp_dir().join(format!("rustysd_multi_exec_{}", std::process::id()));
+    std::fs::create_dir_all(&test_dir).unwrap();
+    let out = test_dir.join("out");
+
+    // the second line only runs if the first one succeeded (or failed with the - prefix)
+    for (first, expected) in &[
+        ("/bin/true", Some("second\n")),
+        ("/bin/false", None),
+        ("-/bin/false", Some("second\n")),
+    ] {
+        let _ = std::fs::remove_file(&out);
+        let test_service_str = format!(
+            r#"
+    [Service]
+    Type = oneshot
+    ExecStart = {}
+    ExecStart = /bin/sh -c "echo second >> {:?}"
+    "#,
+            first, out
+        );
+        let parsed_file = crate::units::parse_file(&test_service_str).unwrap();
+        let id = crate::units::UnitId(crate::units::UnitIdKind::Service, 0);
+        let mut unit = crate::units::parse_service(
+            parsed_file,
+            &std::path::PathBuf::from("/path/to/multi_exec_test.service"),
+            id,
+        )
+        .unwrap();
+
+        let done = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let (run_info, reaper) = make_test_run_info(&test_dir, id, done.clone());
+        if let crate::units::UnitSpecialized::Service(srvc) = &mut unit.specialized {
+            assert_eq!(srvc.service_config.additional_exec.len(), 1);
+            let res = srvc.start(
+                id,
+                "multi_exec_test.service",
+                run_info.clone(),
+                test_dir.join("notify"),
+                &[],
+                false,
+            );
+            assert_eq!(res.is_ok(), expected.is_some());
+        } else {
+            panic!("Parsed service is not a service");
+        }
+        done.store(true, std::sync::atomic::Ordering::SeqCst);
+        reaper.join().unwrap();
+        assert_eq!(std::fs::read_to_string(&out).ok().as_deref(), *expected);
+    }
+
+    // other types keep a single ExecStart=
+    let parsed_file = crate::units::parse_file(
+        r#"
+    [Service]
+    ExecStart = /bin/true
+    ExecStart = /bin/true
+    "#,
+    )
+    .unwrap();
+    assert!(crate::units::parse_service(
+        parsed_file,
+        &std::path::PathBuf::from("/path/to/multi_exec_test.service"),
+        crate::units::UnitId(crate::units::UnitIdKind::Service, 0),
+    )
+    .is_err());
+
+    std::fs::remove_dir_all(&test_dir).unwrap();
+}
+
+#[test]
+fn test_umask() {
+    let test_dir = std::env::temp_dir().join(format!("rustysd_umask_{}", std::process::id()));
+    std::fs::create_dir_all(&test_dir).unwrap();
+    let created = test_dir.join("created_file");
+
+    let test_service_str = format!(
+        r#"
+    [Service]
+    Type = oneshot
+    ExecStart = /bin/sh -c "touch {:?}"
+    UMask = 0027
+    "#,
+        created
+    );
+    let parsed_file = crate::units::parse_file(&test_service_str).unwrap();
+    let id = crate::units::UnitId(crate::units::UnitIdKind::Service, 0);
+    let mut unit = crate::units::parse_service(
+        parsed_file,
+        &std::path::PathBuf::from("/path/to/umask_test.service"),
+        id,
+    )
+    .unwrap();
+
+    let done = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
+    let (run_info, reaper) = make_test_run_info(&test_dir, id, done.clone());
+    if let crate::units::UnitSpecialized::Service(srvc) = &mut unit.specialized {
+        assert_eq!(srvc.service_config.umask, Some(0o027));
+        if let Err(e) = srvc.start(
+            id,
+            "umask_test.service",
+            run_info.clone(),
+            test_dir.join("notify"),
+            &[],
+            false,
+        ) {
+            panic!("{}", e);
+        }
+    } else {
+        panic!("Parsed service is not a service");
+    }
+    done.store(true, std::sync::atomic::Ordering::SeqCst);
+    reaper.join().unwrap();
+
+    // touch creates files with 0666, the umask removes write for the group and everything for others
+    use std::os::unix::fs::PermissionsExt;
+    let mode = std::fs::metadata(&created).unwrap().permissions().mode();
+    assert_eq!(mode & 0o777, 0o640);
+
+    std::fs::remove_dir_all(&test_dir).unwrap();
+}
+
+#[test]
+fn test_seccomp_filter() {
+    let test_dir = std::env::temp_dir().join(format!("rustysd_seccomp_{}", std::process::id()));
+    std::fs::create_dir_all(&test_dir).unwrap();
+
+    for invalid in &["@system-service not_a_syscall", "~@not-a-group"] {
+        let test_service_str = format!(
+            r#"
+    [Service]
+    ExecStart = /bin/true
+    SeccompFilter = {}
+    "#,
+            invalid
+        );
+        let parsed_file = crate::units::parse_file(&test_service_str).unwrap();
+        assert!(crate::units::parse_service(
+            parsed_file,
+            &std::path::PathBuf::from("/path/to/seccomp_test.service"),
+            crate::units::UnitId(crate::units::UnitIdKind::Service, 0),
+        )
+        .is_err());
+    }
+
+    // later lines of the other kind remove syscalls from the list
+    let filter = crate::platform::seccomp::resolve_filter(&[
+        crate::platform::seccomp::FilterEntry::parse("@io-event"),
+        crate::platform::seccomp::FilterEntry::parse("~epoll_ctl"),
+    ])
+    .unwrap();
+    assert!(!filter.deny_list);
+    assert!(filter.syscalls.contains(&libc::SYS_epoll_pwait));
+    assert!(!filter.syscalls.contains(&libc::SYS_epoll_ctl));
+
+    // RestrictAddressFamilies= lines are merged the same way
+    let parse_families = |lines: &[&str]| {
+        let lines = lines
+            .iter()
+            .map(|line| line.to_string())
+            .collect::<Vec<_>>();
+        crate::platform::seccomp::parse_address_families(&lines)
+    };
+    let families = parse_families(&["AF_UNIX AF_INET", "~AF_INET"])
+        .unwrap()
+        .unwrap();
+    assert!(!families.deny_list);
+    assert_eq!(families.families, vec![libc::AF_UNIX as u32]);
+    let families = parse_families(&["~AF_PACKET"]).unwrap().unwrap();
+    assert!(families.deny_list);
+    assert_eq!(families.families, vec![libc::AF_PACKET as u32]);
+    assert!(parse_families(&["none"])
+        .unwrap()
+        .unwrap()
+        .families
+        .is_empty());
+    assert!(parse_families(&["AF_INET", ""]).unwrap().is_none());
+    assert!(parse_families(&["AF_NOT_A_FAMILY"]).is_err());
+
+    let parse_namespaces = |lines: &[&str]| {
+        let lines = lines
+            .iter()
+            .map(|line| line.to_string())
+            .collect::<Vec<_>>();
+        crate::platform::seccomp::parse_namespaces(&lines)
+    };
+    assert_eq!(parse_namespaces(&["yes"]).unwrap(), Some(0));
+    assert_eq!(parse_namespaces(&["no"]).unwrap(), None);
+    assert_eq!(
+        parse_namespaces(&["net ipc", "~ipc"]).unwrap(),
+        Some(libc::CLONE_NEWNET as u32)
+    );
+    assert!(parse_namespaces(&["not_a_namespace"]).is_err());
+
+    // /bin/true needs more than the implicitly allowed syscalls to get to its exit
+    // and it can not even be exec'd if @process is forbidden
+    for (filter, should_start) in &[
+        ("@system-service", true),
+        ("exit_group", false),
+        ("~@network-io", true),
+        ("~@process", false),
+    ] {
+        let test_service_str = format!(
+            r#"
+    [Service]
+    Type = oneshot
+    ExecStart = /bin/true
+    SystemCallFilter = {}
+    "#,
+            filter
+        );
+        let parsed_file = crate::units::parse_file(&test_service_str).unwrap();
+        let id = crate::units::UnitId(crate::units::UnitIdKind::Service, 0);
+        let mut unit = crate::units::parse_service(
+            parsed_file,
+            &std::path::PathBuf::from("/path/to/seccomp_test.service"),
+            id,
+        )
+        .unwrap();
+        if let crate::units::UnitSpecialized::Service(srvc) = &unit.specialized {
+            assert_eq!(
+                srvc.service_config.seccomp_filter,
+                Some(vec![crate::platform::seccomp::FilterEntry::parse(filter)])
+            );
+        }
+
+        let done = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let (run_info, reaper) = make_test_run_info(&test_dir, id, done.clone());
+        if let crate::units::UnitSpecialized::Service(srvc) = &mut unit.specialized {
+            let res = srvc.start(
+                id,
+                "seccomp_test.service",
+                run_info.clone(),
+                test_dir.join("notify"),
+                &[],
+                false,
+            );
+            match (res, should_start) {
+                (Ok(_), true) => {}
+                (
+                    Err(crate::services::ServiceErrorReason::StartFailed(
+                        crate::services::RunCmdError::BadExitCode(
+                            _,
+                            crate::signal_handler::ChildTermination::Signal(
+                                nix::sys::signal::Signal::SIGSYS,
+                            ),
+                        ),
+                    )),
+                    false,
+                ) => {}
+                (Ok(_), false) => panic!("Expected {} to kill the service", filter),
+                (Err(e), _) => panic!("Unexpected result with filter {}: {}", filter, e),
+            }
+        } else {
+            panic!("Parsed service is not a service");
+        }
+        done.store(true, std::sync::atomic::Ordering::SeqCst);
+        reaper.join().unwrap();
+    }
+
+    std::fs::remove_dir_all(&test_dir).unwrap();
+}
+
+#[test]
+fn test_daemon_reload() {
+    use std::sync::Arc;
+    let test_dir = std::env::temp_dir().join(format!("rustysd_reload_{}", std::process::id()));
+    let units_dir = test_dir.join("units");
+    std::fs::create_dir_all(&units_dir).unwrap();
+    let write_service = |name: &str, exec: &str| {
+        let content = format!(
+            "[Service]\nExecStart = {}\n[Install]\nWantedBy = default.target\n",
+            exec
+        );
+        std::fs::write(units_dir.join(name), content).unwrap();
+    };
+    std::fs::write(
+        units_dir.join("default.target"),
+        "[Unit]\nDescription = default\n",
+    )
+    .unwrap();
+    write_service("stopped.service", "/bin/old");
+    write_service("running.service", "/bin/old");
+
+    let done = Arc::new(std::sync::atomic::AtomicBool::new(false));
+    let dummy_id = crate::units::UnitId(crate::units::UnitIdKind::Target, 0);
+    let (run_info, reaper) = make_test_run_info(&test_dir, dummy_id, done.clone());
+    load_test_units(&run_info, |name| {
+        if name == "running.service" {
+            crate::units::UnitStatus::Started
+        } else {
+            crate::units::UnitStatus::NeverStarted
+        }
+    });
+    write_service("stopped.service", "/bin/new");
+    write_service("running.service", "/bin/new");
+    std::fs::write(
+        units_dir.join("added.service"),
+        "[Unit]\nRequires = stopped.service\nAfter = stopped.service\n[Service]\nExecStart = /bin/new\n[Install]\nWantedBy = default.target\n",
+    )
+    .unwrap();
+    let report = crate::units::reload_all_units(run_info.clone()).unwrap();
+    assert_eq!(report.added, vec!["added.service".to_owned()]);
+    assert_eq!(report.updated, vec!["stopped.service".to_owned()]);
+    assert_eq!(report.needs_restart, vec!["running.service".to_owned()]);
+    assert!(report.not_reloaded.is_empty());
+
+    for unit in run_info.unit_table.read().unwrap().values() {
+        let unit = unit.lock().unwrap();
+        if let crate::units::UnitSpecialized::Service(srvc) = &unit.specialized {
+            let expected_cmd = if unit.conf.name() == "running.service" {
+                assert!(unit.pending_reload.is_some());
+                "/bin/old"
+            } else {
+                assert!(unit.pending_reload.is_none());
+                "/bin/new"
+            };
+            assert_eq!(srvc.service_config.exec.cmd, expected_cmd);
+        }
+    }
+    assert_eq!(run_info.unit_table.read().unwrap().len(), 4);
+
+    // the relations of the added unit only point to units that exist
+    let unit_table = run_info.unit_table.read().unwrap();
+    let id_of = |name: &str| {
+        unit_table
+            .values()
+            .map(|unit| unit.lock().unwrap())
+            .find(|unit| unit.conf.name() == name)
+            .unwrap()
+            .id
+    };
+    let (added_id, stopped_id) = (id_of("added.service"), id_of("stopped.service"));
+    // looked up before any unit is held, id_of locks every unit it passes
+    let default_id = id_of("default.target");
+    let status_table = run_info.status_table.read().unwrap();
+    {
+        let added = unit_table.get(&added_id).unwrap().lock().unwrap();
+        let install = &added.install;
+        for id in install
+            .after
+            .iter()
+            .chain(&install.before)
+            .chain(&install.requires)
+            .chain(&install.required_by)
+            .chain(&install.wants)
+            .chain(&install.wanted_by)
+        {
+            assert!(unit_table.contains_key(id), "dangling id {}", id);
+            assert!(status_table.contains_key(id), "dangling id {}", id);
+        }
+        assert_eq!(install.after, vec![stopped_id]);
+        assert_eq!(install.requires, vec![stopped_id]);
+        assert_eq!(install.wanted_by, vec![default_id]);
+    }
+    let stopped = unit_table.get(&stopped_id).unwrap().lock().unwrap();
+    assert!(stopped.install.before.contains(&added_id));
+    assert!(stopped.install.required_by.contains(&added_id));
+    drop(stopped);
+    drop(status_table);
+    drop(unit_table);
+
+    done.store(true, std::sync::atomic::Ordering::SeqCst);
+    reaper.join().unwrap();
+    std::fs::remove_dir_all(&test_dir).unwrap();
+}
+
+#[test]
+fn test_status_summary() {
+    let test_dir = std::env::temp_dir().join(format!("rustysd_status_sum_{}", std::process::id()));
+    let id = crate::units::UnitId(crate::units::UnitIdKind::Service, 0);
+    let parsed_file = crate::units::parse_file(
+        r#"
+    [Service]
+    ExecStart = /bin/true
+    "#,
+    )
+    .unwrap();
+    let mut unit = crate::units::parse_service(
+        parsed_file,
+        &std::path::PathBuf::from("/path/to/summary.service"),
+        id,
+    )
+    .unwrap();
+    if let crate::units::UnitSpecialized::Service(srvc) = &mut unit.specialized {
+        srvc.status_msgs.push("Starting up".to_owned());
+        srvc.status_msgs.push("Serving 3 clients".to_owned());
+    }
+    let target_id = crate::units::UnitId(crate::units::UnitIdKind::Target, 1);
+    let target = crate::units::parse_target(
+        crate::units::parse_file("[Unit]\nDescription = target\n").unwrap(),
+        &std::path::PathBuf::from("/path/to/a.target"),
+        target_id,
+    )
+    .unwrap();
+
+    let done = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(true));
+    let (run_info, reaper) = make_test_run_info(&test_dir, id, done);
+    reaper.join().unwrap();
+    {
+        let mut unit_table = run_info.unit_table.write().unwrap();
+        unit_table.insert(
+            id,
+            std::sync::Arc::new(crate::lock_order::OrderedMutex::new(
+                crate::lock_order::LockLevel::Unit,
+                unit,
+            )),
+        );
+        unit_table.insert(
+            target_id,
+            std::sync::Arc::new(crate::lock_order::OrderedMutex::new(
+                crate::lock_order::LockLevel::Unit,
+                target,
+            )),
+        );
+        run_info.status_table.write().unwrap().insert(
+            target_id,
+            std::sync::Arc::new(crate::lock_order::OrderedMutex::new(
+                crate::lock_order::LockLevel::Status,
+                crate::units::UnitStatus::NeverStarted,
+            )),
+        );
+    }
+
+    assert_eq!(
+        crate::status_file::format_status_summary(&run_info),
+        "a.target         never-started\nsummary.service  starting       Serving 3 clients\n"
+    );
+
+    let metrics = crate::control::metrics::format_metrics(&run_info);
+    for line in &[
+        "rustysd_service_restarts_total{unit=\"summary.service\"} 0",
+        "rustysd_service_up{unit=\"summary.service\"} 0",
+        "rustysd_service_uptime_seconds{unit=\"summary.service\"} 0.000",
+        "rustysd_units_active 0",
+        "rustysd_units_failed 0",
+    ] {
+        assert!(metrics.lines().any(|l| l == *line), "{} missing", line);
+    }
+}
+
+#[test]
+fn test_boot_order() {
+    use std::sync::Arc;
+    let test_dir = std::env::temp_dir().join(format!("rustysd_test_boot_{}", std::process::id()));
+    let units_dir = test_dir.join("units");
+    std::fs::create_dir_all(&units_dir).unwrap();
+    let write_service = |name: &str, after: &[&str]| {
+        let unit_section = if after.is_empty() {
+            String::new()
+        } else {
+            format!("[Unit]\nAfter = {}\n", after.join(","))
+        };
+        let content = format!(
+            "{}[Service]\nExecStart = /bin/false\n[Install]\nWantedBy = default.target\n",
+            unit_section
+        );
+        std::fs::write(units_dir.join(name), content).unwrap();
+    };
+    std::fs::write(
+        units_dir.join("default.target"),
+        "[Unit]\nDescription = default\n",
+    )
+    .unwrap();
+    write_service("first.service", &[]);
+    write_service("second.service", &["first.service"]);
+    write_service("third.service", &["second.service", "first.service"]);
+
+    let done = Arc::new(std::sync::atomic::AtomicBool::new(false));
+    let dummy_id = crate::units::UnitId(crate::units::UnitIdKind::Target, 0);
+    let (run_info, reaper) = make_test_run_info(&test_dir, dummy_id, done.clone());
+    load_test_units(&run_info, |_| crate::units::UnitStatus::NeverStarted);
+    let order = crate::units::test_boot(run_info.clone());
+    let unit_table = run_info.unit_table.read().unwrap();
+    let order = order
+        .iter()
+        .map(|id| unit_table.get(id).unwrap().lock().unwrap().conf.name())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        order,
+        vec![
+            "first.service",
+            "second.service",
+            "third.service",
+            "default.target"
+        ]
+    );
+    // nothing was started
+    assert!(run_info.pid_table.lock().unwrap().is_empty());
+    drop(unit_table);
+
+    done.store(true, std::sync::atomic::Ordering::SeqCst);
+    reaper.join().unwrap();
+    std::fs::remove_dir_all(&test_dir).unwrap();
+}
+
+#[test]
+fn test_wait_for_service_exit() {
+    use std::sync::Arc;
+    let pid_table = Arc::new(crate::units::PidTable::default());
+    let pid = nix::unistd::Pid::from_raw(4_000_000);
+    let id = crate::units::UnitId(crate::units::UnitIdKind::Service, 0);
+    pid_table.lock().unwrap().insert(
+        pid,
+        crate::units::PidEntry::Service(id, crate::units::ServiceType::Simple),
+    );
+
+    let timeout = std::time::Duration::from_millis(100);
+    assert!(!pid_table.wait_for_service_exit(pid, timeout));
+
+    // stands in for the exit handler
+    let reaper_pid_table = pid_table.clone();
+    let reaper = std::thread::spawn(move || {
+        std::thread::sleep(std::time::Duration::from_millis(50));
+        reaper_pid_table.lock().unwrap().remove(&pid);
+        reaper_pid_table.notify_exited();
+    });
+    let start = std::time::Instant::now();
+    assert!(pid_table.wait_for_service_exit(pid, std::time::Duration::from_secs(10)));
+    assert!(start.elapsed() < std::time::Duration::from_secs(5));
+    reaper.join().unwrap();
+}
+
+#[test]
+fn test_restart_dependents_order() {
+    use std::sync::Arc;
+    let test_dir =
+        std::env::temp_dir().join(format!("rustysd_test_restart_deps_{}", std::process::id()));
+    let units_dir = test_dir.join("units");
+    std::fs::create_dir_all(&units_dir).unwrap();
+    let write_service = |name: &str, requires: &[&str]| {
+        let unit_section = if requires.is_empty() {
+            String::new()
+        } else {
+            format!(
+                "[Unit]\nRequires = {}\nAfter = {}\n",
+                requires.join(","),
+                requires.join(",")
+            )
+        };
+        let content = format!(
+            "{}[Service]\nExecStart = /bin/false\n[Install]\nWantedBy = default.target\n",
+            unit_section
+        );
+        std::fs::write(units_dir.join(name), content).unwrap();
+    };
+    std::fs::write(
+        units_dir.join("default.target"),
+        "[Unit]\nDescription = default\n",
+    )
+    .unwrap();
+    write_service("base.service", &[]);
+    write_service("middle.service", &["base.service"]);
+    write_service("top.service", &["middle.service", "base.service"]);
+    write_service("stopped.service", &["base.service"]);
+
+    let done = Arc::new(std::sync::atomic::AtomicBool::new(false));
+    let dummy_id = crate::units::UnitId(crate::units::UnitIdKind::Target, 0);
+    let (run_info, reaper) = make_test_run_info(&test_dir, dummy_id, done.clone());
+    let ids = load_test_units(&run_info, |name| {
+        if name == "stopped.service" {
+            crate::units::UnitStatus::Stopped
+        } else {
+            crate::units::UnitStatus::Started
+        }
+    });
+    let mut dependents = Vec::new();
+    crate::units::collect_running_dependents(ids["base.service"], &run_info, &mut dependents);
+    let unit_table = run_info.unit_table.read().unwrap();
+    let dependents = dependents
+        .iter()
+        .map(|id| unit_table.get(id).unwrap().lock().unwrap().conf.name())
+        .collect::<Vec<_>>();
+    // stopped in this order, started again in the reverse one
+    assert_eq!(dependents, vec!["top.service", "middle.service"]);
+    drop(unit_table);
+
+    done.store(true, std::sync::atomic::Ordering::SeqCst);
+    reaper.join().unwrap();
+    std::fs::remove_dir_all(&test_dir).unwrap();
+}
+
+#[test]
+fn test_state_persist() {
+    use crate::state_persist::{format_state, parse_state, PersistedService};
+    use std::sync::Arc;
+
+    let services = vec![
+        PersistedService {
+            name: "a.service".into(),
+            pid: 100,
+            process_group: Some(-100),
+            start_time: 1000,
+        },
+        PersistedService {
+            name: "b.service".into(),
+            pid: 200,
+            process_group: None,
+            start_time: 2000,
+        },
+    ];
+    assert_eq!(parse_state(&format_state(&services)).unwrap(), services);
+    assert!(parse_state("{}").is_err());
+    assert!(parse_state(r#"{"Services": [{"Name": "a.service", "Pid": 0}]}"#).is_err());
+    assert!(parse_state(r#"{"Services": [{"Name": "a.service", "Pid": 100}]}"#).is_err());
+
+    let test_dir = std::env::temp_dir().join(format!("rustysd_test_state_{}", std::process::id()));
+    let units_dir = test_dir.join("units");
+    std::fs::create_dir_all(&units_dir).unwrap();
+    std::fs::write(
+        units_dir.join("default.target"),
+        "[Unit]\nDescription = default\n",
+    )
+    .unwrap();
+    std::fs::write(
+        units_dir.join("adopted.service"),
+        "[Service]\nExecStart = /bin/false\n[Install]\nWantedBy = default.target\n",
+    )
+    .unwrap();
+
+    let done = Arc::new(std::sync::atomic::AtomicBool::new(false));
+    let dummy_id = crate::units::UnitId(crate::units::UnitIdKind::Target, 0);
+    let (run_info, reaper) = make_test_run_info(&test_dir, dummy_id, done.clone());
+    let srvc_id =
+        load_test_units(&run_info, |_| crate::units::UnitStatus::NeverStarted)["adopted.service"];
+
+    // stands in for the service a previous rustysd started
+    let mut child = std::process::Command::new("/bin/sleep")
+        .arg("10")
+        .spawn()
+        .unwrap();
+    let pid = child.id() as i32;
+    let start_time =
+        crate::state_persist::process_start_time(nix::unistd::Pid::from_raw(pid)).unwrap();
+
+    // a different start time means the pid has been reused by another process since
+    crate::state_persist::adopt_services(
+        &run_info,
+        vec![PersistedService {
+            name: "adopted.service".into(),
+            pid,
+            process_group: None,
+            start_time: start_time + 1,
+        }],
+    );
+    assert_eq!(
+        run_info.current_status(&srvc_id),
+        Some(crate::units::UnitStatus::NeverStarted)
+    );
+
+    crate::state_persist::adopt_services(
+        &run_info,
+        vec![
+            PersistedService {
+                name: "adopted.service".into(),
+                pid,
+                process_group: None,
+                start_time,
+            },
+            PersistedService {
+                name: "unknown.service".into(),
+                pid,
+                process_group: None,
+                start_time,
+            },
+        ],
+    );
+    assert_eq!(
+        run_info.current_status(&srvc_id),
+        Some(crate::units::UnitStatus::Started)
+    );
+    assert!(run_info
+        .pid_table
+        .lock()
+        .unwrap()
+        .contains_key(&nix::unistd::Pid::from_raw(pid)));
+
+    // the activation during the boot does not start the service again
+    match crate::units::activate_unit(
+        srvc_id,
+        run_info.clone(),
+        test_dir.join("notify"),
+        Arc::new(Vec::new()),
+        true,
+    ) {
+        Ok(crate::units::StartResult::Started(_)) => {}
+        _ => panic!("Activating the adopted service failed"),
+    }
+    assert_eq!(
+        crate::state_persist::collect_state(&run_info),
+        vec![PersistedService {
+            name: "adopted.service".into(),
+            pid,
+            process_group: None,
+            start_time,
+        }]
+    );
+
+    // reaped here instead of by the signal handler, like a process that is not a child of rustysd
+    child.kill().unwrap();
+    child.wait().unwrap();
+    crate::state_persist::check_adopted_services(&run_info);
+    assert_eq!(
+        run_info.current_status(&srvc_id),
+        Some(crate::units::UnitStatus::StoppedFinal(
+            "Deactivated cleanly".into()
+        ))
+    );
+    assert!(run_info.pid_table.lock().unwrap().is_empty());
+    assert!(crate::state_persist::collect_state(&run_info).is_empty());
+
+    done.store(true, std::sync::atomic::Ordering::SeqCst);
+    reaper.join().unwrap();
+    std::fs::remove_dir_all(&test_dir).unwrap();
+}
+
+#[test]
+fn test_exec_condition_skips_start() {
+    let test_dir = std::env::temp_dir().join(format!("rustysd_condition_{}", std::process::id()));
+    std::fs::create_dir_all(&test_dir).unwrap();
+    let prestart_marker = test_dir.join("prestart_ran");
+
+    let test_service_str = format!(
+        r#"
+    [Service]
+    ExecCondition = /bin/true
+    ExecCondition = /bin/false
+    ExecStartPre = /bin/touch {}
+    ExecStart = /bin/sleep 10
+    "#,
+        prestart_marker.to_str().unwrap()
+    );
+    let parsed_file = crate::units::parse_file(&test_service_str).unwrap();
+    let id = crate::units::UnitId(crate::units::UnitIdKind::Service, 0);
+    let mut unit = crate::units::parse_service(
+        parsed_file,
+        &std::path::PathBuf::from("/path/to/condition_test.service"),
+        id,
+    )
+    .unwrap();
+
+    let done = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
+    let (run_info, reaper) = make_test_run_info(&test_dir, id, done.clone());
+
+    if let crate::units::UnitSpecialized::Service(srvc) = &mut unit.specialized {
+        let res = srvc.start(
+            id,
+            "condition_test.service",
+            run_info.clone(),
+            test_dir.join("notify"),
+            &[],
+            false,
+        );
+        // /bin/false exits with 1, so the service is skipped and not failed
+        match res {
+            Ok(crate::services::StartResult::Ignored) => {}
+            Ok(_) => panic!("Expected the service to be skipped, but it started"),
+            Err(e) => panic!("Expected the service to be skipped, but got: {}", e),
+        }
+        assert!(srvc.pid.is_none());
+        assert!(!prestart_marker.exists());
+    } else {
+        panic!("Parsed service is not a service");
+    }
+
+    done.store(true, std::sync::atomic::Ordering::SeqCst);
+    reaper.join().unwrap();
+    std::fs::remove_dir_all(&test_dir).unwrap();
+}
+
+#[test]
+fn test_notification_socket_removed_on_stop() {
+    let test_dir = std::env::temp_dir().join(format!("rustysd_notify_{}", std::process::id()));
+    std::fs::create_dir_all(&test_dir).unwrap();
+
+    // the failing ExecStartPre= leaves the service prepared (with its notification socket) but not running
+    let test_service_str = r#"
+    [Service]
+    ExecStartPre = /bin/false
+    ExecStart = /bin/sleep 10
+    "#;
+    let parsed_file = crate::units::parse_file(test_service_str).unwrap();
+    let id = crate::units::UnitId(crate::units::UnitIdKind::Service, 0);
+    let mut unit = crate::units::parse_service(
+        parsed_file,
+        &std::path::PathBuf::from("/path/to/notify_test.service"),
+        id,
+    )
+    .unwrap();
+
+    let done = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
+    let (run_info, reaper) = make_test_run_info(&test_dir, id, done.clone());
+
+    if let crate::units::UnitSpecialized::Service(srvc) = &mut unit.specialized {
+        let name = "notify_test.service";
+        assert!(srvc
+            .start(
+                id,
+                name,
+                run_info.clone(),
+                test_dir.join("notify"),
+                &[],
+                false
+            )
+            .is_err());
+        let note_sock_path = srvc.notifications_path.clone().unwrap();
+        assert!(note_sock_path.exists());
+
+        if let Err(e) = srvc.kill(id, name, run_info.clone()) {
+            panic!("Stopping the service failed: {}", e);
+        }
+        assert!(!note_sock_path.exists());
+        assert!(srvc.notifications.is_none());
+        assert!(srvc.notifications_path.is_none());
+    } else {
+        panic!("Parsed service is not a service");
+    }
+
+    done.store(true, std::sync::atomic::Ordering::SeqCst);
+    reaper.join().unwrap();
+    std::fs::remove_dir_all(&test_dir).unwrap();
+}
+
+#[test]
+fn test_joins_namespace_of() {
+    let test_dir = std::env::temp_dir().join(format!("rustysd_joins_ns_{}", std::process::id()));
+    std::fs::create_dir_all(&test_dir).unwrap();
+
+    let test_service_str = r#"
+    [Unit]
+    JoinsNamespaceOf = owner.service
+    [Service]
+    ExecStart = /bin/sleep 10
+    "#;
+    let parsed_file = crate::units::parse_file(test_service_str).unwrap();
+    let id = crate::units::UnitId(crate::units::UnitIdKind::Service, 0);
+    let mut unit = crate::units::parse_service(
+        parsed_file,
+        &std::path::PathBuf::from("/path/to/joiner.service"),
+        id,
+    )
+    .unwrap();
+    assert_eq!(
+        unit.conf.joins_namespace_of,
+        vec!["owner.service".to_owned()]
+    );
+
+    // stands in for the main process of owner.service
+    let mut owner = std::process::Command::new("unshare")
+        .args(&["-n", "/bin/sleep", "10"])
+        .spawn()
+        .unwrap();
+    let owner_ns = format!("/proc/{}/ns/net", owner.id());
+    let own_ns = std::fs::read_link("/proc/self/ns/net").unwrap();
+    let wait_for_ns = |path: &str, unexpected: &std::path::Path| {
+        for _ in 0..1000 {
+            if let Ok(ns) = std::fs::read_link(path) {
+                if ns != unexpected {
+                    return ns;
+                }
+            }
+            std::thread::sleep(std::time::Duration::from_millis(1));
+        }
+        panic!("{} did not change its namespace", path);
+    };
+    let owner_net = wait_for_ns(&owner_ns, &own_ns);
+
+    let done = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
+    let (run_info, reaper) = make_test_run_info(&test_dir, id, done.clone());
+
+    if let crate::units::UnitSpecialized::Service(srvc) = &mut unit.specialized {
+        // activate_unit opens these from the pid of the running owner.service
+        srvc.joined_namespaces = vec![std::fs::File::open(&owner_ns).unwrap()];
+        let name = "joiner.service";
+        if let Err(e) = srvc.start(
+            id,
+            name,
+            run_info.clone(),
+            test_dir.join("notify"),
+            &[],
+            false,
+        ) {
+            panic!("Starting the service failed: {}", e);
+        }
+        assert!(srvc.joined_namespaces.is_empty());
+        let pid = srvc.pid.unwrap();
+        let joiner_ns = format!("/proc/{}/ns/net", pid);
+        assert_eq!(wait_for_ns(&joiner_ns, &own_ns), owner_net);
+
+        nix::sys::signal::kill(pid, nix::sys::signal::Signal::SIGKILL).unwrap();
+        nix::sys::wait::waitpid(pid, None).unwrap();
+    } else {
+        panic!("Parsed service is not a service");
+    }
+    owner.kill().unwrap();
+    owner.wait().unwrap();
+
+    done.store(true, std::sync::atomic::Ordering::SeqCst);
+    reaper.join().unwrap();
+    std::fs::remove_dir_all(&test_dir).unwrap();
+}
+
+#[test]
+fn test_ip_address_filter() {
+    use crate::platform::bpf_filter::{attach_ip_filter, parse_ip_address_lines, IpAddressFilter};
+    use std::os::unix::io::AsRawFd;
+
+    // an empty line resets the list
+    let lines = vec![
+        "10.0.0.0/8 localhost".to_owned(),
+        "".to_owned(),
+        "192.168.1.0/24".to_owned(),
+    ];
+    assert_eq!(parse_ip_address_lines(&lines).unwrap().len(), 1);
+    assert!(parse_ip_address_lines(&["10.0.0.0/33".to_owned()]).is_err());
+
+    let receives_from_localhost = |filter: &IpAddressFilter| {
+        let receiver = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        attach_ip_filter(receiver.as_raw_fd(), filter).unwrap();
+        receiver
+            .set_read_timeout(Some(std::time::Duration::from_millis(200)))
+            .unwrap();
+        let sender = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        sender
+            .send_to(b"ping", receiver.local_addr().unwrap())
+            .unwrap();
+        receiver.recv(&mut [0u8; 16]).is_ok()
+    };
+
+    let deny_localhost = IpAddressFilter {
+        allow: Vec::new(),
+        deny: parse_ip_address_lines(&["localhost".to_owned()]).unwrap(),
+    };
+    assert!(!receives_from_localhost(&deny_localhost));
+
+    // allowed addresses win over denied ones
+    let allow_one = IpAddressFilter {
+        allow: parse_ip_address_lines(&["127.0.0.1/32".to_owned()]).unwrap(),
+        deny: parse_ip_address_lines(&["any".to_owned()]).unwrap(),
+    };
+    assert!(receives_from_localhost(&allow_one));
+
+    let deny_other = IpAddressFilter {
+        allow: Vec::new(),
+        deny: parse_ip_address_lines(&["10.0.0.0/8 ::1".to_owned()]).unwrap(),
+    };
+    assert!(receives_from_localhost(&deny_other));
+}
+
+#[test]
+fn test_service_ip_address_filter() {
+    let test_service_str = r#"
+    [Service]
+    ExecStart = /bin/true
+    IPAddressAllow = 10.1.2.3
+    IPAddressDeny = 10.0.0.0/8
+    IPAddressDeny = ::1 fe80::/64
+    "#;
+    let parsed_file = crate::units::parse_file(test_service_str).unwrap();
+    let id = crate::units::UnitId(crate::units::UnitIdKind::Service, 0);
+    let unit = crate::units::parse_service(
+        parsed_file,
+        &std::path::PathBuf::from("/path/to/ip_filter.service"),
+        id,
+    )
+    .unwrap();
+    let filter = match &unit.specialized {
+        crate::units::UnitSpecialized::Service(srvc) => srvc.service_config.ip_filter.clone(),
+        _ => unreachable!(),
+    };
+    assert_eq!(filter.allow.len(), 1);
+    assert_eq!(filter.deny.len(), 3);
+
+    // each prefix returns, and each family and the program itself end with passing the packet
+    for egress in &[false, true] {
+        let prog = crate::platform::cgroups::ip_filter::build_ip_program(&filter, *egress);
+        let exits = prog.iter().filter(|insn| insn.code == 0x95).count();
+        assert_eq!(exits, 4 + 2 + 1);
+    }
+
+    let parsed_file =
+        crate::units::parse_file("[Service]\nExecStart = /bin/true\nIPAddressDeny = 10.0.0.0/33\n")
+            .unwrap();
+    assert!(crate::units::parse_service(
+        parsed_file,
+        &std::path::PathBuf::from("/path/to/ip_filter.service"),
+        id,
+    )
+    .is_err());
+}
+
+#[test]
+fn test_exec_reload() {
+    let test_dir = std::env::temp_dir().join(format!("rustysd_reload_{}", std::process::id()));
+    std::fs::create_dir_all(&test_dir).unwrap();
+    let marker = test_dir.join("reloaded");
+
+    let test_service_str = format!(
+        r#"
+    [Service]
+    ExecStart = /bin/sleep 10
+    ExecReload = /bin/touch {}
+    "#,
+        marker.to_str().unwrap()
+    );
+    let parsed_file = crate::units::parse_file(&test_service_str).unwrap();
+    let id = crate::units::UnitId(crate::units::UnitIdKind::Service, 0);
+    let mut unit = crate::units::parse_service(
+        parsed_file,
+        &std::path::PathBuf::from("/path/to/reload_test.service"),
+        id,
+    )
+    .unwrap();
+
+    let done = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
+    let (run_info, reaper) = make_test_run_info(&test_dir, id, done.clone());
+
+    if let crate::units::UnitSpecialized::Service(srvc) = &mut unit.specialized {
+        let name = "reload_test.service";
+        if let Err(e) = srvc.start(
+            id,
+            name,
+            run_info.clone(),
+            test_dir.join("notify"),
+            &[],
+            false,
+        ) {
+            panic!("Starting the service failed: {}", e);
+        }
+        let pid = srvc.pid.unwrap();
+        if let Err(e) = srvc.reload(id, name, run_info.clone()) {
+            panic!("Reloading the service failed: {}", e);
+        }
+        assert!(marker.exists());
+        assert_eq!(srvc.pid, Some(pid));
+
+        // without ExecReload= the main process gets SIGHUP, which terminates sleep
+        srvc.service_config.reload.clear();
+        if let Err(e) = srvc.reload(id, name, run_info.clone()) {
+            panic!("Reloading the service failed: {}", e);
+        }
+        assert_eq!(
+            nix::sys::wait::waitpid(pid, None),
+            Ok(nix::sys::wait::WaitStatus::Signaled(
+                pid,
+                nix::sys::signal::Signal::SIGHUP,
+                false
+            ))
+        );
+    } else {
+        panic!("Parsed service is not a service");
+    }
+
+    done.store(true, std::sync::atomic::Ordering::SeqCst);
+    reaper.join().unwrap();
+    std::fs::remove_dir_all(&test_dir).unwrap();
+}
+
+#[test]
+fn test_standard_output_null_has_no_pipe() {
+    let test_dir = std::env::temp_dir().join(format!("rustysd_null_out_{}", std::process::id()));
+    std::fs::create_dir_all(&test_dir).unwrap();
+
+    let test_service_str = r#"
+    [Service]
+    Type = oneshot
+    ExecStart = /bin/echo discarded
+    StandardOutput = null
+    "#;
+    let parsed_file = crate::units::parse_file(test_service_str).unwrap();
+    let id = crate::units::UnitId(crate::units::UnitIdKind::Service, 0);
+    let mut unit = crate::units::parse_service(
+        parsed_file,
+        &std::path::PathBuf::from("/path/to/null_test.service"),
+        id,
+    )
+    .unwrap();
+
+    let done = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
+    let (run_info, reaper) = make_test_run_info(&test_dir, id, done.clone());
+
+    if let crate::units::UnitSpecialized::Service(srvc) = &mut unit.specialized {
+        if let Err(e) = srvc.start(
+            id,
+            "null_test.service",
+            run_info.clone(),
+            test_dir.join("notify"),
+            &[],
+            false,
+        ) {
+            panic!("Starting the service failed: {}", e);
+        }
+        assert!(srvc.stdout_dup.is_none());
+        assert!(srvc.stderr_dup.is_some());
+    } else {
+        panic!("Parsed service is not a service");
+    }
+
+    done.store(true, std::sync::atomic::Ordering::SeqCst);
+    reaper.join().unwrap();
+    std::fs::remove_dir_all(&test_dir).unwrap();
+}
+
+#[test]
+fn test_main_pid_notification() {
+    let test_service_str = r#"
+    [Service]
+    ExecStart = /path/to/startbin
+    Type = notify
+    "#;
+    let parsed_file = crate::units::parse_file(test_service_str).unwrap();
+    let id = crate::units::UnitId(crate::units::UnitIdKind::Service, 0);
+    let mut unit = crate::units::parse_service(
+        parsed_file,
+        &std::path::PathBuf::from("/path/to/unitfile.service"),
+        id,
+    )
+    .unwrap();
+
+    // the test process plays the main process that forked the daemon
+    let own_pid = nix::unistd::getpid();
+    let mut daemon = std::process::Command::new("/bin/sleep")
+        .arg("10")
+        .spawn()
+        .unwrap();
+    let daemon_pid = nix::unistd::Pid::from_raw(daemon.id() as i32);
+    let pid_table = crate::units::PidTable::default();
+    pid_table.lock().unwrap().insert(
+        own_pid,
+        crate::units::PidEntry::Service(id, crate::units::ServiceType::Notify),
+    );
+
+    if let crate::units::UnitSpecialized::Service(srvc) = &mut unit.specialized {
+        // with another main process the daemon is only a child of "rustysd", like an orphan of some other service
+        let mut other_main = std::process::Command::new("/bin/sleep")
+            .arg("10")
+            .spawn()
+            .unwrap();
+        let other_main_pid = nix::unistd::Pid::from_raw(other_main.id() as i32);
+        pid_table.lock().unwrap().insert(
+            other_main_pid,
+            crate::units::PidEntry::Service(id, crate::units::ServiceType::Notify),
+        );
+        srvc.pid = Some(other_main_pid);
+        crate::notification_handler::handle_notification_message(
+            &format!("MAINPID={}", daemon_pid),
+            srvc,
+            "unitfile.service",
+            &pid_table,
+        );
+        assert_eq!(srvc.pid, Some(other_main_pid));
+        pid_table.lock().unwrap().remove(&other_main_pid);
+        other_main.kill().unwrap();
+        other_main.wait().unwrap();
+
+        srvc.pid = Some(own_pid);
+
+        // pid 1 is not a descendant of the main process
+        crate::notification_handler::handle_notification_message(
+            "MAINPID=1",
+            srvc,
+            "unitfile.service",
+            &pid_table,
+        );
+        assert_eq!(srvc.pid, Some(own_pid));
+
+        crate::notification_handler::handle_notification_message(
+            &format!("MAINPID={}", daemon_pid),
+            srvc,
+            "unitfile.service",
+            &pid_table,
+        );
+        assert_eq!(srvc.pid, Some(daemon_pid));
+        let pid_table_locked = pid_table.lock().unwrap();
+        assert!(!pid_table_locked.contains_key(&own_pid));
+        assert_eq!(
+            pid_table_locked.get(&daemon_pid),
+            Some(&crate::units::PidEntry::Service(
+                id,
+                crate::units::ServiceType::Notify
+            ))
+        );
+    } else {
+        panic!("Parsed service is not a service");
+    }
+
+    daemon.kill().unwrap();
+    daemon.wait().unwrap();
+}
+
+#[test]
+fn test_health_check() {
+    let test_dir = std::env::temp_dir().join(format!("rustysd_health_{}", std::process::id()));
+    std::fs::create_dir_all(&test_dir).unwrap();
+
+    let test_service_str = r#"
+    [Service]
+    ExecStart = /bin/sleep 10
+    ExecHealthCheck = /bin/false
+    ExecHealthCheckIntervalSec = 1
+    ExecHealthCheckMaxFailures = 2
+    "#;
+    let parsed_file = crate::units::parse_file(test_service_str).unwrap();
+    let id = crate::units::UnitId(crate::units::UnitIdKind::Service, 0);
+    let mut unit = crate::units::parse_service(
+        parsed_file,
+        &std::path::PathBuf::from("/path/to/health_test.service"),
+        id,
+    )
+    .unwrap();
+
+    let done = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
+    let (run_info, reaper) = make_test_run_info(&test_dir, id, done.clone());
+
+    if let crate::units::UnitSpecialized::Service(srvc) = &mut unit.specialized {
+        let name = "health_test.service";
+        if let Err(e) = srvc.start(
+            id,
+            name,
+            run_info.clone(),
+            test_dir.join("notify"),
+            &[],
+            false,
+        ) {
+            panic!("Starting the service failed: {}", e);
+        }
+        let now = std::time::Instant::now();
+        assert!(!srvc.health_check_due(now));
+        assert!(srvc.health_check_due(now + std::time::Duration::from_secs(1)));
+
+        assert!(srvc.run_health_check(id, name, run_info.clone()).is_err());
+        assert!(!srvc.is_unhealthy());
+        assert!(srvc.run_health_check(id, name, run_info.clone()).is_err());
+        assert!(srvc.is_unhealthy());
+
+        // one successful check resets the count
+        srvc.service_config.health_check = Some(crate::units::Commandline {
+            cmd: "/bin/true".into(),
+            args: Vec::new(),
+            prefixes: Vec::new(),
+        });
+        assert!(srvc.run_health_check(id, name, run_info.clone()).is_ok());
+        assert_eq!(srvc.runtime_info.health_check_failures, 0);
+        assert!(!srvc.is_unhealthy());
+
+        let pid = srvc.pid.unwrap();
+        nix::sys::signal::kill(pid, nix::sys::signal::Signal::SIGKILL).unwrap();
+        nix::sys::wait::waitpid(pid, None).unwrap();
+    } else {
+        panic!("Parsed service is not a service");
+    }
+
+    done.store(true, std::sync::atomic::Ordering::SeqCst);
+    reaper.join().unwrap();
+    std::fs::remove_dir_all(&test_dir).unwrap();
+}
+
+#[test]
+fn test_health_check_start_limit() {
+    use std::sync::Arc;
+    let test_dir =
+        std::env::temp_dir().join(format!("rustysd_health_limit_{}", std::process::id()));
+    std::fs::create_dir_all(&test_dir).unwrap();
+
+    let test_service_str = r#"
+    [Service]
+    ExecStart = /bin/sleep 10
+    ExecHealthCheck = /bin/false
+    ExecHealthCheckMaxFailures = 1
+    Restart = always
+    StartLimitBurst = 0
+    TimeoutStopSec = 1
+    "#;
+    let parsed_file = crate::units::parse_file(test_service_str).unwrap();
+    let id = crate::units::UnitId(crate::units::UnitIdKind::Service, 0);
+    let mut unit = crate::units::parse_service(
+        parsed_file,
+        &std::path::PathBuf::from("/path/to/health_limit.service"),
+        id,
+    )
+    .unwrap();
+
+    let done = Arc::new(std::sync::atomic::AtomicBool::new(false));
+    let (run_info, reaper) = make_test_run_info(&test_dir, id, done.clone());
+    let pid = if let crate::units::UnitSpecialized::Service(srvc) = &mut unit.specialized {
+        if let Err(e) = srvc.start(
+            id,
+            "health_limit.service",
+            run_info.clone(),
+            test_dir.join("notify"),
+            &[],
+            false,
+        ) {
+            panic!("Starting the service failed: {}", e);
+        }
+        // the first check is due right away
+        srvc.runtime_info.up_since =
+            Some(std::time::Instant::now() - srvc.service_config.health_check_interval);
+        srvc.pid.unwrap()
+    } else {
+        panic!("Parsed service is not a service");
+    };
+    run_info.unit_table.write().unwrap().insert(
+        id,
+        Arc::new(crate::lock_order::OrderedMutex::new(
+            crate::lock_order::LockLevel::Unit,
+            unit,
+        )),
+    );
+    *run_info
+        .status_table
+        .read()
+        .unwrap()
+        .get(&id)
+        .unwrap()
+        .lock()
+        .unwrap() = crate::units::UnitStatus::Started;
+    let events = run_info.events.subscribe();
+
+    crate::services::check_health(&run_info, &test_dir.join("notify"), &Arc::new(Vec::new()));
+    assert_eq!(
+        run_info.current_status(&id),
+        Some(crate::units::UnitStatus::Failed("Start limit hit".into()))
+    );
+    assert!(events.try_iter().any(|event| matches!(
+        event.kind,
+        crate::units::UnitEventKind::Failed(ref reason) if reason == "Start limit hit"
+    )));
+    nix::sys::wait::waitpid(pid, None).unwrap();
+
+    done.store(true, std::sync::atomic::Ordering::SeqCst);
+    reaper.join().unwrap();
+    std::fs::remove_dir_all(&test_dir).unwrap();
+}
+
+#[cfg(feature = "debug_lock_order")]
+#[test]
+#[should_panic(expected = "Lock order violation")]
+fn test_lock_order_violation() {
+    use crate::lock_order::{LockLevel, OrderedMutex, OrderedRwLock};
+    let unit_table = OrderedRwLock::new(LockLevel::UnitTable, ());
+    let status_table = OrderedRwLock::new(LockLevel::StatusTable, ());
+    let unit = OrderedMutex::new(LockLevel::Unit, ());
+
+    // the right order, locks of the same level can be held together
+    {
+        let _units = unit_table.read().unwrap();
+        let _unit = unit.lock().unwrap();
+        let _status_table = status_table.read().unwrap();
+        let _status_table_again = status_table.read().unwrap();
+    }
+    let _status_table = status_table.read().unwrap();
+    let _unit = unit.lock().unwrap();
+}
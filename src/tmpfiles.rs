@@ -0,0 +1,183 @@
+//! Create files, directories and symlinks described in a tmpfiles.d(5) style spec before a service starts.
+//! Each line looks like `Type Path Mode User Group Age Argument`, a '-' means "use the default".
+//! Only the types `d` (directory), `f` (file) and `L` (symlink) are supported for now, other lines are skipped with a warning.
+//! Existing objects are not replaced, but mode and ownership of existing files and directories get adjusted.
+//! If User/Group are '-' the ownership is left as it is (which is root if rustysd runs as root).
+
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum TmpFileType {
+    Directory,
+    File,
+    Symlink,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct TmpFileEntry {
+    pub typ: TmpFileType,
+    pub path: PathBuf,
+    pub mode: Option<u32>,
+    pub user: Option<String>,
+    pub group: Option<String>,
+    pub argument: Option<String>,
+}
+
+fn optional_field(field: Option<&str>) -> Option<&str> {
+    match field {
+        None | Some("-") => None,
+        Some(field) => Some(field),
+    }
+}
+
+/// Take the next whitespace separated field from the start of rest
+fn next_field<'a>(rest: &mut &'a str) -> Option<&'a str> {
+    let trimmed = rest.trim_start();
+    if trimmed.is_empty() {
+        return None;
+    }
+    let end = trimmed.find(char::is_whitespace).unwrap_or(trimmed.len());
+    let (field, remaining) = trimmed.split_at(end);
+    *rest = remaining;
+    Some(field)
+}
+
+/// Parse one line of a spec. Returns Ok(None) for empty lines, comments and unsupported types.
+pub fn parse_line(line: &str) -> Result<Option<TmpFileEntry>, String> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+        return Ok(None);
+    }
+    let mut rest = line;
+    let typ = match next_field(&mut rest) {
+        Some("d") => TmpFileType::Directory,
+        Some("f") => TmpFileType::File,
+        Some("L") => TmpFileType::Symlink,
+        Some(other) => {
+            warn!(
+                "Unsupported tmpfiles line type: {} in line: {}",
+                other, line
+            );
+            return Ok(None);
+        }
+        None => return Ok(None),
+    };
+    let path = match next_field(&mut rest) {
+        Some(path) => PathBuf::from(path),
+        None => return Err(format!("Missing path in tmpfiles line: {}", line)),
+    };
+    if !path.is_absolute() {
+        return Err(format!("Path in tmpfiles line must be absolute: {}", line));
+    }
+    let mode = match optional_field(next_field(&mut rest)) {
+        Some(mode) => Some(
+            u32::from_str_radix(mode, 8)
+                .map_err(|_| format!("Invalid mode in tmpfiles line: {}", line))?,
+        ),
+        None => None,
+    };
+    let user = optional_field(next_field(&mut rest)).map(str::to_owned);
+    let group = optional_field(next_field(&mut rest)).map(str::to_owned);
+    // age is not supported, nothing gets cleaned up
+    let _age = next_field(&mut rest);
+    // the argument is the rest of the line and may contain whitespace
+    let argument = match rest.trim() {
+        "" | "-" => None,
+        argument => Some(argument.to_owned()),
+    };
+
+    if typ == TmpFileType::Symlink && argument.is_none() {
+        return Err(format!("Missing symlink target in tmpfiles line: {}", line));
+    }
+
+    Ok(Some(TmpFileEntry {
+        typ,
+        path,
+        mode,
+        user,
+        group,
+        argument,
+    }))
+}
+
+pub fn parse_spec(content: &str) -> Result<Vec<TmpFileEntry>, String> {
+    let mut entries = Vec::new();
+    for line in content.lines() {
+        if let Some(entry) = parse_line(line)? {
+            entries.push(entry);
+        }
+    }
+    Ok(entries)
+}
+
+fn lookup_owner(
+    entry: &TmpFileEntry,
+) -> Result<(Option<nix::unistd::Uid>, Option<nix::unistd::Gid>), String> {
+    let uid = match &entry.user {
+        Some(user) => Some(if let Ok(uid) = user.parse::<u32>() {
+            nix::unistd::Uid::from_raw(uid)
+        } else {
+            crate::platform::pwnam::getpwnam_r(user)?.uid
+        }),
+        None => None,
+    };
+    let gid = match &entry.group {
+        Some(group) => Some(if let Ok(gid) = group.parse::<u32>() {
+            nix::unistd::Gid::from_raw(gid)
+        } else {
+            crate::platform::grnam::getgrnam_r(group)?.gid
+        }),
+        None => None,
+    };
+    Ok((uid, gid))
+}
+
+fn set_mode_and_owner(entry: &TmpFileEntry, default_mode: u32) -> Result<(), String> {
+    let mode = entry.mode.unwrap_or(default_mode);
+    std::fs::set_permissions(&entry.path, std::fs::Permissions::from_mode(mode))
+        .map_err(|e| format!("Could not set mode of {:?}: {}", entry.path, e))?;
+    let (uid, gid) = lookup_owner(entry)?;
+    if uid.is_some() || gid.is_some() {
+        nix::unistd::chown(&entry.path, uid, gid)
+            .map_err(|e| format!("Could not set owner of {:?}: {}", entry.path, e))?;
+    }
+    Ok(())
+}
+
+pub fn apply_entry(entry: &TmpFileEntry) -> Result<(), String> {
+    match entry.typ {
+        TmpFileType::Directory => {
+            std::fs::create_dir_all(&entry.path)
+                .map_err(|e| format!("Could not create directory {:?}: {}", entry.path, e))?;
+            set_mode_and_owner(entry, 0o755)
+        }
+        TmpFileType::File => {
+            if !entry.path.exists() {
+                let content = entry.argument.as_deref().unwrap_or("");
+                std::fs::write(&entry.path, content)
+                    .map_err(|e| format!("Could not create file {:?}: {}", entry.path, e))?;
+            }
+            set_mode_and_owner(entry, 0o644)
+        }
+        TmpFileType::Symlink => {
+            // mode and ownership do not apply to symlinks
+            if std::fs::symlink_metadata(&entry.path).is_err() {
+                let target = entry.argument.as_ref().unwrap();
+                std::os::unix::fs::symlink(target, &entry.path)
+                    .map_err(|e| format!("Could not create symlink {:?}: {}", entry.path, e))?;
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Read the spec file and create everything described in it
+pub fn apply_spec(path: &Path) -> Result<(), String> {
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| format!("Could not read tmpfiles spec {:?}: {}", path, e))?;
+    for entry in parse_spec(&content)? {
+        apply_entry(&entry)?;
+    }
+    Ok(())
+}
@@ -1,6 +1,7 @@
 //! Activate units (recursively and parallel along the dependency tree)
 
 use super::units::*;
+use super::UnitEventKind;
 use crate::platform::EventFd;
 use crate::services::ServiceErrorReason;
 use std::sync::{Arc, Mutex};
@@ -19,6 +20,7 @@ pub enum UnitOperationErrorReason {
     SocketCloseError(String),
     ServiceStartError(ServiceErrorReason),
     ServiceStopError(ServiceErrorReason),
+    AssertionFailed(String),
 }
 
 impl std::fmt::Display for UnitOperationError {
@@ -59,6 +61,13 @@ impl std::fmt::Display for UnitOperationError {
                     self.unit_name, self.unit_id, msg
                 )?;
             }
+            UnitOperationErrorReason::AssertionFailed(assertion) => {
+                write!(
+                    f,
+                    "Unit {} (ID {}) failed to start because an assertion failed: {}",
+                    self.unit_name, self.unit_id, assertion
+                )?;
+            }
             UnitOperationErrorReason::SocketCloseError(msg) => {
                 write!(
                     f,
@@ -112,6 +121,20 @@ fn activate_units_recursive(
                     };
                     tpool_copy.execute(next_services_job);
                 }
+                Ok(StartResult::Ignored(next_services_ids)) => {
+                    // The unit was skipped because of an unmet condition. Units ordered after it can still be started
+                    let next_services_job = move || {
+                        activate_units_recursive(
+                            next_services_ids,
+                            run_info_copy2,
+                            tpool_copy2,
+                            note_sock_copy2,
+                            eventfds_copy2,
+                            errors_copy2,
+                        );
+                    };
+                    tpool_copy.execute(next_services_job);
+                }
                 Ok(StartResult::WaitForDependencies) => {
                     // Thats ok. The unit is waiting for more dependencies and will be
                     // activated again when another dependency has finished starting
@@ -125,9 +148,138 @@ fn activate_units_recursive(
     }
 }
 
+/// The units this unit is ordered after that have not been started yet. Units that are only ordered after (not required)
+/// just need to have been started once, required ones must be running.
+fn unstarted_dependencies<'a>(install: &'a Install, status_table: &StatusTable) -> Vec<&'a UnitId> {
+    install.after.iter().fold(Vec::new(), |mut acc, elem| {
+        let required = install.requires.contains(elem);
+
+        let status = status_table.get(elem).unwrap();
+        let status_locked = status.lock().unwrap();
+        let ready = if required {
+            *status_locked == UnitStatus::Started
+                || *status_locked == UnitStatus::Reloading
+                || *status_locked == UnitStatus::StartedWaitingForSocket
+        } else {
+            *status_locked != UnitStatus::NeverStarted
+        };
+
+        if !ready {
+            acc.push(elem);
+        }
+        acc
+    })
+}
+
+/// The units that are not ordered after any other unit. Activation starts with these
+fn find_root_units(run_info: &RuntimeInfo) -> Vec<UnitId> {
+    let mut root_units = Vec::new();
+    for (id, unit) in &*run_info.unit_table.read().unwrap() {
+        let unit_locked = unit.lock().unwrap();
+        if unit_locked.install.after.is_empty() {
+            root_units.push(*id);
+            trace!("Root unit: {}", unit_locked.conf.name());
+        }
+    }
+    // sorted so the order of the test boot does not depend on the order in the hashmap
+    root_units.sort();
+    root_units
+}
+
+/// Walk the units in the order activate_units would start them without starting anything (for --test-boot). Every unit
+/// counts as started as soon as it is reached, so this shows the order the After=/Before= graph allows. Conditions are
+/// not evaluated. Units that depend on each other are listed in order, the real boot starts unrelated units in parallel.
+///
+/// This changes the status of the units in the run_info, it must not be used for a run_info that is used to actually
+/// start units afterwards.
+pub fn test_boot(run_info: ArcRuntimeInfo) -> Vec<UnitId> {
+    let mut order = Vec::new();
+    let mut to_activate: std::collections::VecDeque<UnitId> = find_root_units(&run_info).into();
+    while let Some(id) = to_activate.pop_front() {
+        let unit = match run_info.unit_table.read().unwrap().get(&id) {
+            Some(unit) => unit.clone(),
+            None => continue,
+        };
+        let unit_locked = unit.lock().unwrap();
+        let status_table_locked = run_info.status_table.read().unwrap();
+        if !unstarted_dependencies(&unit_locked.install, &status_table_locked).is_empty() {
+            // like in the real boot the unit is reached again from its last dependency
+            continue;
+        }
+        let mut status_locked = status_table_locked.get(&id).unwrap().lock().unwrap();
+        if *status_locked != UnitStatus::NeverStarted {
+            continue;
+        }
+        *status_locked = UnitStatus::Started;
+        order.push(id);
+        to_activate.extend(unit_locked.install.before.iter().copied());
+    }
+    order
+}
+
 pub enum StartResult {
     Started(Vec<UnitId>),
     WaitForDependencies,
+    /// A Condition* setting was not met so the unit has not been started
+    Ignored(Vec<UnitId>),
+}
+
+/// Open the network and IPC namespaces of the main processes of the services named in JoinsNamespaceOf=. The other units
+/// can only be found through the unit table, so this has to happen before the unit itself is locked for the activation.
+/// Services that are not running are skipped like systemd does
+fn open_joined_namespaces(
+    id: UnitId,
+    unit: &Arc<crate::lock_order::OrderedMutex<Unit>>,
+    units_locked: &UnitTable,
+) -> Vec<std::fs::File> {
+    let (name, joins) = {
+        let unit_locked = unit.lock().unwrap();
+        match &unit_locked.specialized {
+            UnitSpecialized::Service(_) => (
+                unit_locked.conf.name(),
+                unit_locked.conf.joins_namespace_of.clone(),
+            ),
+            _ => return Vec::new(),
+        }
+    };
+
+    let mut files = Vec::new();
+    for other_name in &joins {
+        let pid = units_locked
+            .iter()
+            .filter(|(other_id, _)| **other_id != id)
+            .find_map(|(_, other)| {
+                let other_locked = other.lock().unwrap();
+                if other_locked.conf.name() != *other_name {
+                    return None;
+                }
+                match &other_locked.specialized {
+                    UnitSpecialized::Service(srvc) => srvc.pid,
+                    _ => None,
+                }
+            });
+        let pid = match pid {
+            Some(pid) => pid,
+            None => {
+                warn!(
+                    "Service {} does not join the namespaces of {}, it is not running",
+                    name, other_name
+                );
+                continue;
+            }
+        };
+        for ns in &["net", "ipc"] {
+            let path = format!("/proc/{}/ns/{}", pid, ns);
+            match std::fs::File::open(&path) {
+                Ok(file) => files.push(file),
+                Err(e) => warn!(
+                    "Service {} can not join the namespace {}: {}",
+                    name, path, e
+                ),
+            }
+        }
+    }
+    files
 }
 
 pub fn activate_unit(
@@ -143,10 +295,13 @@ pub fn activate_unit(
     // 1.5) Check if this unit should be started right now
     // 2) Then lock the needed other units (only for sockets of services right now)
     // With that we always maintain a consistent order between locks so deadlocks shouldnt occur
-    let unit = {
+    let (unit, joined_namespaces) = {
         let units_locked = run_info.unit_table.read().unwrap();
         match units_locked.get(&id_to_start) {
-            Some(unit) => Arc::clone(unit),
+            Some(unit) => (
+                Arc::clone(unit),
+                open_joined_namespaces(id_to_start, unit, &units_locked),
+            ),
             None => {
                 // If this occurs, there is a flaw in the handling of dependencies
                 // IDs should be purged globally when units get removed
@@ -164,32 +319,15 @@ pub fn activate_unit(
     let mut unit_locked = unit.lock().unwrap();
     trace!("Locked unit: {}", id_to_start);
     let name = unit_locked.conf.name();
+    if let UnitSpecialized::Service(srvc) = &mut unit_locked.specialized {
+        srvc.joined_namespaces = joined_namespaces;
+    }
 
     let status_table_locked = run_info.status_table.read().unwrap();
 
     // if not all dependencies are yet started ignore this call. This unit will be activated again when
     // the next dependency gets ready
-    let unstarted_deps = unit_locked
-        .install
-        .after
-        .iter()
-        .fold(Vec::new(), |mut acc, elem| {
-            let required = unit_locked.install.requires.contains(elem);
-
-            let status = status_table_locked.get(elem).unwrap();
-            let status_locked = status.lock().unwrap();
-            let ready = if required {
-                *status_locked == UnitStatus::Started
-                    || *status_locked == UnitStatus::StartedWaitingForSocket
-            } else {
-                *status_locked != UnitStatus::NeverStarted
-            };
-
-            if !ready {
-                acc.push(elem);
-            }
-            acc
-        });
+    let unstarted_deps = unstarted_dependencies(&unit_locked.install, &status_table_locked);
     if !unstarted_deps.is_empty() {
         trace!(
             "Unit: {} ignores activation. Not all dependencies have been started (still waiting for: {:?})",
@@ -199,6 +337,43 @@ pub fn activate_unit(
         return Ok(StartResult::WaitForDependencies);
     }
 
+    // Skip the unit if one of its conditions is not met. This is not an error
+    if let Some(condition) = unit_locked.conf.conditions.iter().find(|c| !c.is_met()) {
+        trace!(
+            "Unit: {} is skipped because a condition was not met: Condition{}",
+            name,
+            condition
+        );
+        let status = status_table_locked.get(&id_to_start).unwrap();
+        let mut status_locked = status.lock().unwrap();
+        *status_locked =
+            UnitStatus::StoppedFinal(format!("Condition not met: Condition{}", condition));
+        return Ok(StartResult::Ignored(unit_locked.install.before.clone()));
+    }
+
+    // Fail the activation if one of the asserts is not met
+    if let Some(assertion) = unit_locked.conf.asserts.iter().find(|a| !a.is_met()) {
+        let e = UnitOperationError {
+            reason: UnitOperationErrorReason::AssertionFailed(format!("Assert{}", assertion)),
+            unit_name: name.clone(),
+            unit_id: id_to_start,
+        };
+        let status = status_table_locked.get(&id_to_start).unwrap();
+        let mut status_locked = status.lock().unwrap();
+        *status_locked = UnitStatus::StoppedFinal(format!("{}", e));
+        return Err(e);
+    }
+
+    // Services adopted from the state file are running already. The units ordered after them can be started
+    let unit_locked_ref = &mut *unit_locked;
+    if let UnitSpecialized::Service(srvc) = &mut unit_locked_ref.specialized {
+        if srvc.runtime_info.adopted {
+            srvc.runtime_info.adopted = false;
+            trace!("Unit: {} is running already, it was adopted", name);
+            return Ok(StartResult::Started(unit_locked_ref.install.before.clone()));
+        }
+    }
+
     // Check if the unit is currently starting. Update the status to starting if not
     {
         let status = status_table_locked.get(&id_to_start).unwrap();
@@ -225,7 +400,7 @@ pub fn activate_unit(
     }
     let next_services_ids = unit_locked.install.before.clone();
 
-    unit_locked
+    let result = unit_locked
         .activate(
             run_info.clone(),
             notification_socket_path.clone(),
@@ -237,6 +412,9 @@ pub fn activate_unit(
             let status_table_locked = run_info.status_table.read().unwrap();
             let status = status_table_locked.get(&unit_locked.id).unwrap();
             let mut status_locked = status.lock().unwrap();
+            if new_status == UnitStatus::Started {
+                run_info.events.publish(&name, UnitEventKind::Ready);
+            }
             *status_locked = new_status;
             StartResult::Started(next_services_ids)
         })
@@ -246,9 +424,16 @@ pub fn activate_unit(
             let status = status_table_locked.get(&unit_locked.id).unwrap();
             let mut status_locked = status.lock().unwrap();
             *status_locked = UnitStatus::StoppedFinal(format!("{}", e));
+            run_info
+                .events
+                .publish(&name, UnitEventKind::Failed(format!("{}", e)));
             e
-        })
+        });
     // drop all the locks "at once". Ordering of dropping should be irrelevant?
+    drop(status_table_locked);
+    drop(unit_locked);
+    crate::state_persist::save_state(&run_info);
+    result
 }
 
 pub fn activate_units(
@@ -256,15 +441,7 @@ pub fn activate_units(
     notification_socket_path: std::path::PathBuf,
     eventfds: Vec<EventFd>,
 ) {
-    let mut root_units = Vec::new();
-
-    for (id, unit) in &*run_info.unit_table.read().unwrap() {
-        let unit_locked = unit.lock().unwrap();
-        if unit_locked.install.after.is_empty() {
-            root_units.push(*id);
-            trace!("Root unit: {}", unit_locked.conf.name());
-        }
-    }
+    let root_units = find_root_units(&run_info);
 
     // TODO make configurable or at least make guess about amount fo threads
     let tpool = ThreadPool::new(6);
@@ -20,6 +20,58 @@ pub fn deactivate_unit_recursive(
 
     deactivate_unit(id_to_kill, killfinal, run_info.clone())
 }
+
+/// Like deactivate_unit_recursive but for a unit that failed. Every running unit that gets stopped because of this is logged.
+/// If the failed unit does not propagate its failure only the unit itself is stopped.
+pub fn deactivate_failed_unit(
+    failed_id: UnitId,
+    run_info: ArcRuntimeInfo,
+) -> Result<(), UnitOperationError> {
+    let (failed_name, propagate) = {
+        let unit = {
+            let unit_table_locked = run_info.unit_table.read().unwrap();
+            unit_table_locked.get(&failed_id).unwrap().clone()
+        };
+        let unit_locked = unit.lock().unwrap();
+        (
+            unit_locked.conf.name(),
+            unit_locked.conf.propagates_failure(),
+        )
+    };
+    if propagate {
+        log_requiring_units(failed_id, &failed_name, &run_info);
+        deactivate_unit_recursive(failed_id, true, run_info)
+    } else {
+        deactivate_unit(failed_id, true, run_info)
+    }
+}
+
+fn log_requiring_units(id: UnitId, failed_name: &str, run_info: &ArcRuntimeInfo) {
+    let required_by = {
+        let unit_table_locked = run_info.unit_table.read().unwrap();
+        let unit_locked = unit_table_locked.get(&id).unwrap().lock().unwrap();
+        unit_locked.install.required_by.clone()
+    };
+    for dependent in required_by {
+        if let Some(UnitStatus::Started)
+        | Some(UnitStatus::Starting)
+        | Some(UnitStatus::Reloading)
+        | Some(UnitStatus::StartedWaitingForSocket) = run_info.current_status(&dependent)
+        {
+            let name = {
+                let unit_table_locked = run_info.unit_table.read().unwrap();
+                let unit_locked = unit_table_locked.get(&dependent).unwrap().lock().unwrap();
+                unit_locked.conf.name()
+            };
+            warn!(
+                "Stopping unit {} because unit {} which it requires has failed",
+                name, failed_name
+            );
+        }
+        log_requiring_units(dependent, failed_name, run_info);
+    }
+}
+
 pub fn deactivate_unit(
     id_to_kill: UnitId,
     killfinal: bool,
@@ -29,26 +81,30 @@ pub fn deactivate_unit(
         let unit_table_locked = run_info.unit_table.read().unwrap();
         unit_table_locked.get(&id_to_kill).unwrap().clone()
     };
-    let unit_locked = &mut *unit.lock().unwrap();
+    let mut unit_locked = unit.lock().unwrap();
 
     {
         let status_table_locked = run_info.status_table.read().unwrap();
         let status = status_table_locked.get(&id_to_kill).unwrap();
         let status_locked = &mut *status.lock().unwrap();
         match *status_locked {
-            UnitStatus::Started | UnitStatus::StartedWaitingForSocket | UnitStatus::Starting => {
+            UnitStatus::Started
+            | UnitStatus::StartedWaitingForSocket
+            | UnitStatus::Starting
+            | UnitStatus::Reloading => {
                 *status_locked = UnitStatus::Stopping;
             }
             UnitStatus::NeverStarted
             | UnitStatus::Stopped
             | UnitStatus::StoppedFinal(_)
+            | UnitStatus::Failed(_)
             | UnitStatus::Stopping => {
                 return Ok(());
             }
         }
     }
-    unit_locked.deactivate(run_info.clone())?;
-    {
+    let result = unit_locked.deactivate(run_info.clone());
+    if result.is_ok() {
         let status_table_locked = run_info.status_table.read().unwrap();
         let status = status_table_locked.get(&id_to_kill).unwrap();
         let mut status_locked = status.lock().unwrap();
@@ -58,7 +114,9 @@ pub fn deactivate_unit(
             *status_locked = UnitStatus::Stopped;
         }
     }
-    Ok(())
+    drop(unit_locked);
+    crate::state_persist::save_state(&run_info);
+    result
 }
 
 pub fn deactivate_units_recursive(
@@ -99,3 +157,74 @@ pub fn reactivate_unit(
     )
     .map(|_| ())
 }
+
+/// Restart a unit and, like systemd does for Requires=, the running units that require it. These are stopped before the
+/// unit and started again after it. Services that get restarted this way remember the restarted unit as restart reason
+pub fn reactivate_unit_recursive(
+    id_to_restart: UnitId,
+    run_info: ArcRuntimeInfo,
+    notification_socket_path: std::path::PathBuf,
+    eventfds: Arc<Vec<EventFd>>,
+) -> std::result::Result<(), UnitOperationError> {
+    // dependents come after the units they require in here, so this is the order to stop them in
+    let mut dependents = Vec::new();
+    collect_running_dependents(id_to_restart, &run_info, &mut dependents);
+    deactivate_units(dependents.clone(), false, run_info.clone())?;
+
+    reactivate_unit(
+        id_to_restart,
+        run_info.clone(),
+        notification_socket_path.clone(),
+        eventfds.clone(),
+    )?;
+
+    let restarted_name = {
+        let unit_table_locked = run_info.unit_table.read().unwrap();
+        let unit_locked = unit_table_locked.get(&id_to_restart).unwrap().lock().unwrap();
+        unit_locked.conf.name()
+    };
+    for dependent in dependents.into_iter().rev() {
+        {
+            let unit_table_locked = run_info.unit_table.read().unwrap();
+            let unit_locked = &mut *unit_table_locked.get(&dependent).unwrap().lock().unwrap();
+            let name = unit_locked.conf.name();
+            if let UnitSpecialized::Service(srvc) = &mut unit_locked.specialized {
+                let reason = crate::services::RestartReason::Dependency(restarted_name.clone());
+                srvc.set_restart_reason(&name, reason.clone());
+                run_info
+                    .events
+                    .publish(&name, UnitEventKind::Restarted(reason));
+            }
+        }
+        crate::units::activate_unit(
+            dependent,
+            run_info.clone(),
+            notification_socket_path.clone(),
+            eventfds.clone(),
+            true,
+        )?;
+    }
+    Ok(())
+}
+
+/// Collect the running units that (transitively) require the unit. Every unit comes after all units that require it
+pub fn collect_running_dependents(id: UnitId, run_info: &ArcRuntimeInfo, collected: &mut Vec<UnitId>) {
+    let required_by = {
+        let unit_table_locked = run_info.unit_table.read().unwrap();
+        let unit_locked = unit_table_locked.get(&id).unwrap().lock().unwrap();
+        unit_locked.install.required_by.clone()
+    };
+    for dependent in required_by {
+        if collected.contains(&dependent) {
+            continue;
+        }
+        if let Some(UnitStatus::Started)
+        | Some(UnitStatus::Starting)
+        | Some(UnitStatus::Reloading)
+        | Some(UnitStatus::StartedWaitingForSocket) = run_info.current_status(&dependent)
+        {
+            collect_running_dependents(dependent, run_info, collected);
+            collected.push(dependent);
+        }
+    }
+}
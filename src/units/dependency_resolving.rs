@@ -138,15 +138,12 @@ pub fn add_implicit_before_after(units: &mut HashMap<UnitId, Unit>) {
     let mut after = Vec::new();
     for unit in (*units).values_mut() {
         if let Some(conf) = &unit.install.install_config {
-            for name in &conf.wanted_by {
-                let id = name_to_id[name.as_str()];
-                before.push((id, unit.id));
-                after.push((unit.id, id));
-            }
-            for name in &conf.required_by {
-                let id = name_to_id[name.as_str()];
-                before.push((id, unit.id));
-                after.push((unit.id, id));
+            for name in conf.wanted_by.iter().chain(conf.required_by.iter()) {
+                // units that are not loaded have been warned about while resolving the install section
+                if let Some(id) = name_to_id.get(name.as_str()) {
+                    before.push((*id, unit.id));
+                    after.push((unit.id, *id));
+                }
             }
         }
     }
@@ -161,6 +158,68 @@ pub fn add_implicit_before_after(units: &mut HashMap<UnitId, Unit>) {
     }
 }
 
+/// Turn WantedBy=/RequiredBy= of the [Install] section into (wanted, wanting) / (required, requiring) pairs. These are
+/// applied like the Wants=/Requires= of the wanting unit, so enabling a unit for a target works the same as the target
+/// listing the unit itself. Units that reference a unit that is not loaded (e.g. WantedBy=multi-user.target without
+/// having that target) are not enabled for it.
+fn resolve_install_section(
+    unit: &Unit,
+    name_to_id: &HashMap<String, UnitId>,
+    wanted_by: &mut Vec<(UnitId, UnitId)>,
+    required_by: &mut Vec<(UnitId, UnitId)>,
+) {
+    if let Some(conf) = &unit.install.install_config {
+        for name in &conf.wanted_by {
+            match name_to_id.get(name.as_str()) {
+                Some(id) => wanted_by.push((unit.id, *id)),
+                None => warn!(
+                    "Unit {} is wanted by {} which is not loaded. Ignoring WantedBy={}",
+                    unit.conf.name(),
+                    name,
+                    name
+                ),
+            }
+        }
+        for name in &conf.required_by {
+            match name_to_id.get(name.as_str()) {
+                Some(id) => required_by.push((unit.id, *id)),
+                None => warn!(
+                    "Unit {} is required by {} which is not loaded. Ignoring RequiredBy={}",
+                    unit.conf.name(),
+                    name,
+                    name
+                ),
+            }
+        }
+    }
+}
+
+pub const SYSINIT_TARGET: &str = "sysinit.target";
+pub const SHUTDOWN_TARGET: &str = "shutdown.target";
+
+/// Services are implicitly ordered After=sysinit.target and Before=shutdown.target (if these targets are loaded)
+/// unless they set DefaultDependencies=no. This needs to run before fill_dependencies.
+pub fn add_default_dependencies(units: &mut HashMap<UnitId, Unit>) {
+    let names: Vec<String> = units.values().map(|unit| unit.conf.name()).collect();
+    for unit in units.values_mut() {
+        add_default_dependencies_to_unit(unit, &names);
+    }
+}
+
+/// Add the implicit ordering to this unit. known_names are the names of all loaded units.
+pub fn add_default_dependencies_to_unit(unit: &mut Unit, known_names: &[String]) {
+    if !unit.is_service() || !unit.conf.default_dependencies {
+        return;
+    }
+    let known = |name: &str| known_names.iter().any(|known| known == name);
+    if known(SYSINIT_TARGET) && !unit.conf.after.iter().any(|name| name == SYSINIT_TARGET) {
+        unit.conf.after.push(SYSINIT_TARGET.to_owned());
+    }
+    if known(SHUTDOWN_TARGET) && !unit.conf.before.iter().any(|name| name == SHUTDOWN_TARGET) {
+        unit.conf.before.push(SHUTDOWN_TARGET.to_owned());
+    }
+}
+
 // make edges between units visible on bot sides: required <-> required_by  after <-> before
 pub fn fill_dependencies(units: &mut HashMap<UnitId, Unit>) {
     let mut name_to_id = HashMap::new();
@@ -198,16 +257,7 @@ pub fn fill_dependencies(units: &mut HashMap<UnitId, Unit>) {
             before.push((unit.id, id))
         }
 
-        if let Some(conf) = &unit.install.install_config {
-            for name in &conf.wanted_by {
-                let id = name_to_id[name.as_str()];
-                wanted_by.push((unit.id, id));
-            }
-            for name in &conf.required_by {
-                let id = name_to_id[name.as_str()];
-                required_by.push((unit.id, id));
-            }
-        }
+        resolve_install_section(unit, &name_to_id, &mut wanted_by, &mut required_by);
     }
 
     for (wanted, wanting) in wanted_by {
@@ -353,3 +403,31 @@ pub fn apply_sockets_to_services(
 
     Ok(())
 }
+
+/// Every socket named in Sockets= must exist, otherwise a typo would silently leave the service without its socket.
+/// This needs to run before pruning, sockets that are not needed by the target get removed there.
+pub fn check_sockets_exist(units: &HashMap<UnitId, Unit>) -> Result<(), String> {
+    let socket_names: Vec<String> = units
+        .values()
+        .filter(|unit| unit.id.0 == UnitIdKind::Socket)
+        .map(|unit| unit.conf.name())
+        .collect();
+    for srvc_unit in units.values() {
+        if let UnitSpecialized::Service(srvc) = &srvc_unit.specialized {
+            let missing: Vec<_> = srvc
+                .service_config
+                .sockets
+                .iter()
+                .filter(|name| !socket_names.contains(name))
+                .collect();
+            if !missing.is_empty() {
+                return Err(format!(
+                    "Service {} references sockets in Sockets= that do not exist: {:?}",
+                    srvc_unit.conf.name(),
+                    missing
+                ));
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,54 @@
+//! State changes of units that are published to everyone who subscribed via the control interface (subscribe command)
+
+use crate::services::RestartReason;
+use crate::signal_handler::ChildTermination;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::Mutex;
+
+#[derive(Debug, Clone)]
+pub enum UnitEventKind {
+    /// The main process of the service has been spawned
+    Started(nix::unistd::Pid),
+    /// The unit has been started completely (for services this includes waiting for the readiness notification)
+    Ready,
+    /// The main process of the service exited
+    Exited(ChildTermination),
+    /// The unit could not be started or failed while running
+    Failed(String),
+    /// The service is being restarted
+    Restarted(RestartReason),
+    /// ExecHealthCheck= of the service failed ExecHealthCheckMaxFailures= times in a row
+    Unhealthy(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct UnitEvent {
+    pub unit_name: String,
+    pub kind: UnitEventKind,
+}
+
+#[derive(Default)]
+pub struct EventSubscribers {
+    subscribers: Mutex<Vec<Sender<UnitEvent>>>,
+}
+
+impl EventSubscribers {
+    /// All events published from now on are sent to the returned receiver, until it is dropped
+    pub fn subscribe(&self) -> Receiver<UnitEvent> {
+        let (tx, rx) = channel();
+        self.subscribers.lock().unwrap().push(tx);
+        rx
+    }
+
+    pub fn publish(&self, unit_name: &str, kind: UnitEventKind) {
+        let event = UnitEvent {
+            unit_name: unit_name.to_owned(),
+            kind,
+        };
+        // subscribers that went away are removed the next time something is published
+        self.subscribers
+            .lock()
+            .unwrap()
+            .retain(|subscriber| subscriber.send(event.clone()).is_ok());
+    }
+}
@@ -1,8 +1,9 @@
+use crate::lock_order::{LockLevel, OrderedMutex};
 use crate::units;
 use std::collections::HashMap;
 use std::fs;
 use std::path::PathBuf;
-use std::sync::{Arc, Mutex};
+use std::sync::Arc;
 
 fn find_new_unit_path(unit_dirs: &[PathBuf], find_name: &str) -> Result<Option<PathBuf>, String> {
     for dir in unit_dirs {
@@ -83,10 +84,7 @@ pub fn collect_names_needed(new_unit: &units::Unit, names_needed: &mut Vec<Strin
     names_needed.extend(new_unit.conf.after.iter().cloned());
     names_needed.extend(new_unit.conf.before.iter().cloned());
 
-    if let Some(conf) = &new_unit.install.install_config {
-        names_needed.extend(conf.required_by.iter().cloned());
-        names_needed.extend(conf.wanted_by.iter().cloned());
-    }
+    // WantedBy=/RequiredBy= may reference units that are not loaded (e.g. multi-user.target). These are just ignored
     if let units::UnitSpecialized::Socket(sock) = &new_unit.specialized {
         names_needed.extend(sock.services.iter().cloned());
     }
@@ -101,12 +99,15 @@ fn check_all_names_exist(
     new_units: &HashMap<units::UnitId, units::Unit>,
     unit_table_locked: &units::UnitTable,
 ) -> Result<(), String> {
-    let mut names_needed = Vec::new();
+    // map each needed name to the unit that references it, so errors can name the referencing unit
+    let mut names_needed = std::collections::HashMap::new();
     for new_unit in new_units.values() {
-        collect_names_needed(new_unit, &mut names_needed);
+        let mut names = Vec::new();
+        collect_names_needed(new_unit, &mut names);
+        for name in names {
+            names_needed.insert(name, new_unit.conf.name());
+        }
     }
-    let mut names_needed: std::collections::HashMap<_, _> =
-        names_needed.iter().map(|name| (name, ())).collect();
 
     for unit in unit_table_locked.values() {
         let unit_locked = unit.lock().unwrap();
@@ -130,70 +131,116 @@ fn check_all_names_exist(
     if names_needed.len() > 0 {
         return Err(format!(
             "Names referenced by unit but not found in the known set of units: {:?}",
-            names_needed.keys().collect::<Vec<_>>()
+            names_needed
+                .iter()
+                .map(|(name, referenced_by)| format!("{} (referenced by {})", name, referenced_by))
+                .collect::<Vec<_>>()
         ));
     }
     Ok(())
 }
 
+fn push_unique(ids: &mut Vec<units::UnitId>, id: units::UnitId) {
+    if !ids.contains(&id) {
+        ids.push(id);
+    }
+}
+
+/// Add the relations that the config of `unit` declares to `other` on both units
+fn connect_units(unit: &mut units::Unit, other: &mut units::Unit) {
+    let name = other.conf.name();
+    let (id, other_id) = (unit.id, other.id);
+    if unit.conf.after.contains(&name) {
+        push_unique(&mut unit.install.after, other_id);
+        push_unique(&mut other.install.before, id);
+    }
+    if unit.conf.before.contains(&name) {
+        push_unique(&mut unit.install.before, other_id);
+        push_unique(&mut other.install.after, id);
+    }
+    if unit.conf.requires.contains(&name) {
+        push_unique(&mut unit.install.requires, other_id);
+        push_unique(&mut other.install.required_by, id);
+    }
+    if unit.conf.wants.contains(&name) {
+        push_unique(&mut unit.install.wants, other_id);
+        push_unique(&mut other.install.wanted_by, id);
+    }
+    if let Some(conf) = &unit.install.install_config {
+        let required_by = conf.required_by.contains(&name);
+        let wanted_by = conf.wanted_by.contains(&name);
+        if required_by {
+            push_unique(&mut unit.install.required_by, other_id);
+            push_unique(&mut other.install.requires, id);
+        }
+        if wanted_by {
+            push_unique(&mut unit.install.wanted_by, other_id);
+            push_unique(&mut other.install.wants, id);
+        }
+        // same implicit ordering as when loading all units
+        if required_by || wanted_by {
+            push_unique(&mut unit.install.before, other_id);
+            push_unique(&mut other.install.after, id);
+        }
+    }
+}
+
 /// Activates a new unit by
 /// 1. (not yet but will be) checking the units referenced by this new unit
 /// 1. inserting it into the unit_table of run_info
 /// 1. activate the unit
 /// 1. removing the unit again if the activation fails
 pub fn insert_new_units(
-    new_units: HashMap<units::UnitId, units::Unit>,
+    mut new_units: HashMap<units::UnitId, units::Unit>,
     run_info: units::ArcRuntimeInfo,
 ) -> Result<(), String> {
     // TODO check if new unit only refs existing units
     // TODO check if all ref'd units are not failed
     {
         let unit_table_locked = &mut *run_info.unit_table.write().unwrap();
+
+        // the new units need the same implicit ordering as units that were loaded at startup
+        let mut known_names: Vec<String> = unit_table_locked
+            .values()
+            .map(|unit| unit.lock().unwrap().conf.name())
+            .collect();
+        known_names.extend(new_units.values().map(|unit| unit.conf.name()));
+        for new_unit in new_units.values_mut() {
+            units::add_default_dependencies_to_unit(new_unit, &known_names);
+        }
+
         trace!("Check all names exist");
         check_all_names_exist(&new_units, &unit_table_locked)?;
 
         for (new_id, mut new_unit) in new_units.into_iter() {
             trace!("Add new unit: {}", new_unit.conf.name());
-            // Setup relations of before <-> after / requires <-> requiredby
+            // Units loaded with load_all_units have ids in here that were handed out to fresh copies of the existing
+            // units. These are in no table, so the relations are built again from the names only
+            new_unit.install = units::Install {
+                install_config: new_unit.install.install_config.take(),
+                ..units::Install::default()
+            };
+            // Setup relations of before <-> after / requires <-> requiredby in both directions. The table also
+            // contains the new units that were added before this one
             for unit in unit_table_locked.values() {
                 let mut unit_locked = unit.lock().unwrap();
-                let name = unit_locked.conf.name();
-                let id = unit_locked.id;
-                if new_unit.conf.after.contains(&name) {
-                    new_unit.install.after.push(id);
-                    unit_locked.install.before.push(new_id);
-                }
-                if new_unit.conf.before.contains(&name) {
-                    new_unit.install.before.push(id);
-                    unit_locked.install.after.push(new_id);
-                }
-                if new_unit.conf.requires.contains(&name) {
-                    new_unit.install.requires.push(id);
-                    unit_locked.install.required_by.push(new_id);
-                }
-                if new_unit.conf.wants.contains(&name) {
-                    new_unit.install.wants.push(id);
-                    unit_locked.install.wanted_by.push(new_id);
-                }
-                if let Some(conf) = &new_unit.install.install_config {
-                    if conf.required_by.contains(&name) {
-                        new_unit.install.required_by.push(id);
-                        unit_locked.install.requires.push(new_id);
-                    }
-                    if conf.wanted_by.contains(&name) {
-                        new_unit.install.wanted_by.push(id);
-                        unit_locked.install.wants.push(new_id);
-                    }
-                }
+                connect_units(&mut new_unit, &mut unit_locked);
+                connect_units(&mut unit_locked, &mut new_unit);
             }
             {
-                unit_table_locked.insert(new_id, Arc::new(Mutex::new(new_unit)));
+                unit_table_locked.insert(
+                    new_id,
+                    Arc::new(OrderedMutex::new(LockLevel::Unit, new_unit)),
+                );
             }
             {
                 let status_table_locked = &mut *run_info.status_table.write().unwrap();
                 status_table_locked.insert(
                     new_id,
-                    Arc::new(Mutex::new(units::UnitStatus::NeverStarted)),
+                    Arc::new(OrderedMutex::new(
+                        LockLevel::Status,
+                        units::UnitStatus::NeverStarted,
+                    )),
                 );
             }
         }
@@ -53,7 +53,9 @@ pub fn load_all_units(
     unit_table.extend(service_unit_table);
     unit_table.extend(socket_unit_table);
     unit_table.extend(target_unit_table);
+    add_default_dependencies(&mut unit_table);
     fill_dependencies(&mut unit_table);
+    check_sockets_exist(&unit_table).map_err(|e| DependencyError { msg: e })?;
 
     prune_units(target_unit, &mut unit_table).unwrap();
     trace!("Finished pruning units");
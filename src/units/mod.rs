@@ -3,17 +3,23 @@
 mod activate;
 mod deactivate;
 mod dependency_resolving;
+mod events;
 mod insert_new;
 mod loading;
+mod reload;
 mod sanity_check;
 mod unit_parsing;
 mod units;
+mod watch;
 
 pub use activate::*;
 pub use deactivate::*;
 pub use dependency_resolving::*;
+pub use events::*;
 pub use insert_new::*;
 pub use loading::load_all_units;
+pub use reload::*;
 pub use sanity_check::*;
 pub use unit_parsing::*;
 pub use units::*;
+pub use watch::*;
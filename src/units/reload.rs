@@ -0,0 +1,117 @@
+//! Reload the unit files at runtime (daemon-reload). New units are added, changed configs of units that are not running
+//! are replaced. Running units keep their config (and their processes) until they are started the next time.
+
+use crate::lock_order::OrderedMutex;
+use crate::units::*;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// What a reload did with the units that were found in the unit dirs
+#[derive(Debug, Default)]
+pub struct ReloadReport {
+    /// Units that were not known before
+    pub added: Vec<String>,
+    /// Units that were not running, their new config is used from now on
+    pub updated: Vec<String>,
+    /// Units that were running, their new config is used the next time they are started
+    pub needs_restart: Vec<String>,
+    /// Units whose dependencies changed. Changing the dependency graph at runtime is not supported, rustysd needs to be
+    /// restarted for these
+    pub not_reloaded: Vec<String>,
+}
+
+/// Whether anything (except runtime state) differs between the two units
+fn config_changed(old: &Unit, new: &Unit) -> bool {
+    if old.conf != new.conf {
+        return true;
+    }
+    match (&old.specialized, &new.specialized) {
+        (UnitSpecialized::Service(old), UnitSpecialized::Service(new)) => {
+            old.service_config != new.service_config
+        }
+        (UnitSpecialized::Socket(old), UnitSpecialized::Socket(new)) => {
+            old.name != new.name
+                || old.exec_config != new.exec_config
+                || old.sockets != new.sockets
+                || old.services != new.services
+                || old.file_options != new.file_options
+                || old.ip_filter != new.ip_filter
+        }
+        (UnitSpecialized::Target, UnitSpecialized::Target) => false,
+        _ => true,
+    }
+}
+
+fn dependencies_changed(old: &UnitConfig, new: &UnitConfig) -> bool {
+    old.wants != new.wants
+        || old.requires != new.requires
+        || old.before != new.before
+        || old.after != new.after
+}
+
+/// Parse all unit files again and compare them to the units that are currently known
+pub fn reload_all_units(run_info: ArcRuntimeInfo) -> Result<ReloadReport, String> {
+    let mut last_id = *run_info.last_id.lock().unwrap();
+    let units = load_all_units(
+        &run_info.config.unit_dirs,
+        &mut last_id,
+        &run_info.config.target_unit,
+    )
+    .map_err(|e| format!("Error while loading unit definitons: {:?}", e))?;
+    // the ids used for loading must not be handed out again
+    *run_info.last_id.lock().unwrap() = last_id;
+
+    let mut report = ReloadReport::default();
+    let mut new_units = HashMap::new();
+    {
+        let unit_table_locked = run_info.unit_table.read().unwrap();
+        let existing: HashMap<String, Arc<OrderedMutex<Unit>>> = unit_table_locked
+            .values()
+            .map(|unit| (unit.lock().unwrap().conf.name(), unit.clone()))
+            .collect();
+
+        for (id, new_unit) in units {
+            let name = new_unit.conf.name();
+            let unit = match existing.get(&name) {
+                Some(unit) => unit,
+                None => {
+                    report.added.push(name);
+                    new_units.insert(id, new_unit);
+                    continue;
+                }
+            };
+            let mut unit_locked = unit.lock().unwrap();
+            if !config_changed(&unit_locked, &new_unit) {
+                continue;
+            }
+            if dependencies_changed(&unit_locked.conf, &new_unit.conf) {
+                warn!(
+                    "Dependencies of unit {} changed. This needs a restart of rustysd to take effect",
+                    name
+                );
+                report.not_reloaded.push(name);
+                continue;
+            }
+            let running = matches!(
+                run_info.current_status(&unit_locked.id),
+                Some(UnitStatus::Started)
+                    | Some(UnitStatus::Starting)
+                    | Some(UnitStatus::Reloading)
+            );
+            if running {
+                trace!(
+                    "Unit {} changed while running, keep the new config until its next start",
+                    name
+                );
+                unit_locked.pending_reload = Some(Box::new(new_unit));
+                report.needs_restart.push(name);
+            } else {
+                trace!("Unit {} changed, use the new config", name);
+                unit_locked.apply_reloaded_config(new_unit);
+                report.updated.push(name);
+            }
+        }
+    }
+    insert_new_units(new_units, run_info)?;
+    Ok(report)
+}
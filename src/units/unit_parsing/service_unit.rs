@@ -1,4 +1,4 @@
-use crate::services::{Service, ServiceRuntimeInfo};
+use crate::services::{LogRateLimitState, Service, ServiceRuntimeInfo, StdoutCapture};
 use crate::units::*;
 use std::path::PathBuf;
 
@@ -129,6 +129,13 @@ pub fn parse_service(
             requires: Vec::new(),
             before: Vec::new(),
             after: Vec::new(),
+
+            conditions: Vec::new(),
+            asserts: Vec::new(),
+            default_dependencies: true,
+            on_failure_propagate_to_requires: true,
+            on_failure_job_mode: JobMode::Replace,
+            joins_namespace_of: Vec::new(),
         }),
         install: Install {
             wants: Vec::new(),
@@ -145,6 +152,7 @@ pub fn parse_service(
             gid,
             pid: None,
             signaled_ready: false,
+            signaled_reloading: false,
 
             service_config,
             socket_names: Vec::new(),
@@ -155,7 +163,16 @@ pub fn parse_service(
 
             runtime_info: ServiceRuntimeInfo {
                 restarted: 0,
+                last_restart_reason: None,
                 up_since: None,
+                last_exit: None,
+                exited_at: None,
+                oom_kills: 0,
+                restart_times: std::collections::VecDeque::new(),
+                health_check_failures: 0,
+                last_health_check: None,
+                adopted: false,
+                adopted_main_process: None,
             },
 
             notifications: None,
@@ -165,9 +182,15 @@ pub fn parse_service(
             notifications_buffer: String::new(),
             stdout_buffer: Vec::new(),
             stderr_buffer: Vec::new(),
+            log_rate_limit: LogRateLimitState::default(),
+            stdout_capture: StdoutCapture::default(),
+            stdout_file: None,
+            stderr_file: None,
+            joined_namespaces: Vec::new(),
 
             platform_specific,
         }),
+        pending_reload: None,
     })
 }
 
@@ -198,6 +221,171 @@ fn parse_timeout(descr: &str) -> Timeout {
     }
 }
 
+/// Parse one KEY=value entry of LogExtraFields=. The key has to be a valid journal field name:
+/// uppercase letters, digits and underscores, not starting with an underscore
+pub fn parse_log_extra_field(field: &str) -> Result<(String, String), ParsingErrorReason> {
+    let pos = match field.find('=') {
+        Some(pos) => pos,
+        None => {
+            return Err(ParsingErrorReason::UnknownSetting(
+                "LogExtraFields".to_owned(),
+                field.to_owned(),
+            ))
+        }
+    };
+    let (key, value) = (&field[..pos], &field[pos + 1..]);
+    let valid_key = !key.is_empty()
+        && !key.starts_with('_')
+        && key
+            .chars()
+            .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || c == '_');
+    if !valid_key {
+        return Err(ParsingErrorReason::UnknownSetting(
+            "LogExtraFields".to_owned(),
+            field.to_owned(),
+        ));
+    }
+    Ok((key.to_owned(), value.to_owned()))
+}
+
+/// Parse the space separated directories of RuntimeDirectory=, StateDirectory= or CacheDirectory=.
+/// They must be relative and must not leave their base directory.
+fn parse_exec_directories(
+    setting: &str,
+    raw_lines: Option<Vec<(u32, String)>>,
+) -> Result<Vec<String>, ParsingErrorReason> {
+    let mut dirs = Vec::new();
+    for (_line, entry) in raw_lines.unwrap_or_default() {
+        for dir in entry.split_whitespace() {
+            let path = std::path::Path::new(dir);
+            let valid = path.is_relative()
+                && path
+                    .components()
+                    .all(|c| matches!(c, std::path::Component::Normal(_)));
+            if !valid {
+                return Err(ParsingErrorReason::UnknownSetting(
+                    setting.to_owned(),
+                    dir.to_owned(),
+                ));
+            }
+            dirs.push(dir.to_owned());
+        }
+    }
+    Ok(dirs)
+}
+
+fn parse_timeout_failure_mode(
+    setting: &str,
+    raw: Option<Vec<(u32, String)>>,
+) -> Result<TimeoutFailureMode, ParsingErrorReason> {
+    match raw {
+        Some(vec) => {
+            if vec.len() == 1 {
+                match vec[0].1.as_str() {
+                    "terminate" => Ok(TimeoutFailureMode::Terminate),
+                    "kill" => Ok(TimeoutFailureMode::Kill),
+                    "abort" => Ok(TimeoutFailureMode::Abort),
+                    name => Err(ParsingErrorReason::UnknownSetting(
+                        setting.to_owned(),
+                        name.to_owned(),
+                    )),
+                }
+            } else {
+                Err(ParsingErrorReason::SettingTooManyValues(
+                    setting.to_owned(),
+                    super::map_tupels_to_second(vec),
+                ))
+            }
+        }
+        None => Ok(TimeoutFailureMode::Terminate),
+    }
+}
+
+/// Parse signal names with or without the SIG prefix or signal numbers
+fn parse_signal_name(name: &str) -> Option<nix::sys::signal::Signal> {
+    if let Ok(num) = name.parse::<libc::c_int>() {
+        return nix::sys::signal::Signal::iterator().find(|signal| *signal as libc::c_int == num);
+    }
+    let name = if name.starts_with("SIG") {
+        name.to_owned()
+    } else {
+        format!("SIG{}", name)
+    };
+    name.parse::<nix::sys::signal::Signal>().ok()
+}
+
+/// Parse the space separated exit codes and signal names of settings like SuccessExitStatus=
+fn parse_exit_status_list(
+    setting: &str,
+    raw_lines: Option<Vec<(u32, String)>>,
+) -> Result<(Vec<i32>, Vec<nix::sys::signal::Signal>), ParsingErrorReason> {
+    let mut codes = Vec::new();
+    let mut signals = Vec::new();
+    for (_line, entry) in raw_lines.unwrap_or_default() {
+        for status in entry.split_whitespace() {
+            if let Ok(code) = status.parse::<i32>() {
+                codes.push(code);
+            } else if let Some(signal) = parse_signal_name(status) {
+                signals.push(signal);
+            } else {
+                return Err(ParsingErrorReason::UnknownSetting(
+                    setting.to_owned(),
+                    status.to_owned(),
+                ));
+            }
+        }
+    }
+    Ok((codes, signals))
+}
+
+fn parse_output_target(
+    setting: &str,
+    raw_lines: Option<Vec<(u32, String)>>,
+) -> Result<StdOutputTarget, ParsingErrorReason> {
+    let vec = match raw_lines {
+        Some(vec) => vec,
+        None => return Ok(StdOutputTarget::Default),
+    };
+    if vec.len() != 1 {
+        return Err(ParsingErrorReason::SettingTooManyValues(
+            setting.to_owned(),
+            super::map_tupels_to_second(vec),
+        ));
+    }
+    let value = vec[0].1.as_str();
+    let target = match value {
+        "inherit" | "journal" | "syslog" | "journal+console" | "syslog+console" => {
+            StdOutputTarget::Default
+        }
+        "null" => StdOutputTarget::Null,
+        _ => {
+            let (path, append) = if let Some(path) = value.strip_prefix("file:") {
+                (path, false)
+            } else if let Some(path) = value.strip_prefix("append:") {
+                (path, true)
+            } else {
+                return Err(ParsingErrorReason::UnsupportedSetting(format!(
+                    "{}={}",
+                    setting, value
+                )));
+            };
+            let path = std::path::PathBuf::from(path);
+            if !path.is_absolute() {
+                return Err(ParsingErrorReason::UnknownSetting(
+                    setting.to_owned(),
+                    value.to_owned(),
+                ));
+            }
+            if append {
+                StdOutputTarget::AppendFile(path)
+            } else {
+                StdOutputTarget::File(path)
+            }
+        }
+    };
+    Ok(target)
+}
+
 fn parse_cmdlines(raw_lines: &Vec<(u32, String)>) -> Result<Vec<Commandline>, ParsingErrorReason> {
     let mut cmdlines = Vec::new();
     for (_line, cmdline) in raw_lines {
@@ -268,22 +456,280 @@ fn parse_cmdline(raw_line: &str) -> Result<Commandline, ParsingErrorReason> {
     })
 }
 
+/// Parse CPUAffinity= lines. Each line is a list of CPU indices or ranges like 0-3, separated by whitespace or commas.
+/// An empty line resets the list
+fn parse_cpu_affinity(lines: &[(u32, String)]) -> Result<Vec<usize>, ParsingErrorReason> {
+    let mut cpus = Vec::new();
+    for (_, line) in lines {
+        if line.trim().is_empty() {
+            cpus.clear();
+            continue;
+        }
+        for word in line.split(|c: char| c.is_whitespace() || c == ',') {
+            if word.is_empty() {
+                continue;
+            }
+            let bad_value =
+                || ParsingErrorReason::UnknownSetting("CPUAffinity".to_owned(), word.to_owned());
+            let mut range = word.splitn(2, '-');
+            let first = range
+                .next()
+                .unwrap()
+                .parse::<usize>()
+                .map_err(|_| bad_value())?;
+            let last = match range.next() {
+                Some(last) => last.parse::<usize>().map_err(|_| bad_value())?,
+                None => first,
+            };
+            if last < first {
+                return Err(bad_value());
+            }
+            for cpu in first..=last {
+                if !cpus.contains(&cpu) {
+                    cpus.push(cpu);
+                }
+            }
+        }
+    }
+    Ok(cpus)
+}
+
+/// Parse the lines of PassEnvironment= or UnsetEnvironment=. Lines are merged, an empty line resets the list
+fn parse_env_var_names(lines: Vec<(u32, String)>) -> Vec<String> {
+    let mut names = Vec::new();
+    for (_, line) in lines {
+        if line.trim().is_empty() {
+            names.clear();
+        }
+        names.extend(line.split_whitespace().map(str::to_owned));
+    }
+    names
+}
+
+/// Parse the lines of EnvironmentFile=. Each line is one absolute path, the '-' prefix marks files that may be missing.
+/// An empty line resets the list
+fn parse_environment_files(
+    lines: Vec<(u32, String)>,
+) -> Result<Vec<(std::path::PathBuf, bool)>, ParsingErrorReason> {
+    let mut files = Vec::new();
+    for (_, line) in lines {
+        let line = line.trim();
+        if line.is_empty() {
+            files.clear();
+            continue;
+        }
+        let (path, ignore_missing) = match line.strip_prefix('-') {
+            Some(path) => (path, true),
+            None => (line, false),
+        };
+        let path = std::path::PathBuf::from(path);
+        if !path.is_absolute() {
+            return Err(ParsingErrorReason::UnknownSetting(
+                "EnvironmentFile".to_owned(),
+                line.to_owned(),
+            ));
+        }
+        files.push((path, ignore_missing));
+    }
+    Ok(files)
+}
+
+/// Parse the lines of BindPaths= or BindReadOnlyPaths=. Each entry is SOURCE[:DESTINATION], without a destination the
+/// source is mounted onto itself. Lines are merged, an empty line resets the list
+fn parse_bind_paths(
+    setting: &str,
+    lines: Vec<(u32, String)>,
+) -> Result<Vec<(std::path::PathBuf, std::path::PathBuf)>, ParsingErrorReason> {
+    let mut paths = Vec::new();
+    for (_, line) in lines {
+        if line.trim().is_empty() {
+            paths.clear();
+            continue;
+        }
+        for entry in line.split_whitespace() {
+            let mut parts = entry.split(':');
+            let source = std::path::PathBuf::from(parts.next().unwrap_or(""));
+            let destination = match parts.next() {
+                Some(destination) => std::path::PathBuf::from(destination),
+                None => source.clone(),
+            };
+            if parts.next().is_some() || !source.is_absolute() || !destination.is_absolute() {
+                return Err(ParsingErrorReason::UnknownSetting(
+                    setting.to_owned(),
+                    entry.to_owned(),
+                ));
+            }
+            paths.push((source, destination));
+        }
+    }
+    Ok(paths)
+}
+
+/// Parse the values of TemporaryFileSystem=. Each entry is PATH[:OPTIONS] with an absolute path, the options are
+/// comma separated mount options. Values are merged, an empty value resets the list
+fn parse_temporary_file_systems(
+    values: Vec<(u32, String)>,
+) -> Result<Vec<(std::path::PathBuf, String)>, ParsingErrorReason> {
+    let mut file_systems: Vec<(std::path::PathBuf, String)> = Vec::new();
+    for (_, value) in values {
+        if value.trim().is_empty() {
+            file_systems.clear();
+            continue;
+        }
+        for (idx, entry) in value.split_whitespace().enumerate() {
+            // the values are split at the commas, so the first word of a value that is not a path continues the
+            // options of the entry before
+            if idx == 0 && !entry.starts_with('/') {
+                if let Some((_, options)) = file_systems.last_mut() {
+                    options.push(',');
+                    options.push_str(entry);
+                    continue;
+                }
+            }
+            let (path, options) = match entry.find(':') {
+                Some(pos) => (&entry[..pos], &entry[pos + 1..]),
+                None => (entry, ""),
+            };
+            let path = std::path::PathBuf::from(path);
+            if !path.is_absolute() {
+                return Err(ParsingErrorReason::UnknownSetting(
+                    "TemporaryFileSystem".to_owned(),
+                    entry.to_owned(),
+                ));
+            }
+            file_systems.push((path, options.to_owned()));
+        }
+    }
+    Ok(file_systems)
+}
+
+/// Parse the lines of DeviceAllow=. Each line is a device and optionally the access (r, w and m). Lines are merged, an
+/// empty line resets the list
+fn parse_device_allow(
+    lines: Vec<(u32, String)>,
+) -> Result<Vec<(String, String)>, ParsingErrorReason> {
+    let mut devices = Vec::new();
+    for (_, line) in lines {
+        let mut fields = line.split_whitespace();
+        let device = match fields.next() {
+            Some(device) => device,
+            None => {
+                devices.clear();
+                continue;
+            }
+        };
+        let access = fields.next().unwrap_or("");
+        let valid_device = device.starts_with("/dev/")
+            || device.starts_with("char-")
+            || device.starts_with("block-");
+        if !valid_device
+            || fields.next().is_some()
+            || crate::platform::cgroups::devices::parse_access(access).is_err()
+        {
+            return Err(ParsingErrorReason::UnknownSetting(
+                "DeviceAllow".to_owned(),
+                line.to_owned(),
+            ));
+        }
+        devices.push((device.to_owned(), access.to_owned()));
+    }
+    Ok(devices)
+}
+
 fn parse_service_section(mut section: ParsedSection) -> Result<ServiceConfig, ParsingErrorReason> {
     let exec = section.remove("EXECSTART");
     let stop = section.remove("EXECSTOP");
+    let reload = section.remove("EXECRELOAD");
     let stoppost = section.remove("EXECSTOPPOST");
+    let condition = section.remove("EXECCONDITION");
     let startpre = section.remove("EXECSTARTPRE");
+    let exec_start_pre_check = section.remove("EXECSTARTPRECHECK");
+    let pre_check_interval = section.remove("EXECSTARTPRECHECKINTERVALSEC");
+    let pre_check_max_retries = section.remove("EXECSTARTPRECHECKMAXRETRIES");
+    let health_check = section.remove("EXECHEALTHCHECK");
+    let health_check_interval = section.remove("EXECHEALTHCHECKINTERVALSEC");
+    let health_check_max_failures = section.remove("EXECHEALTHCHECKMAXFAILURES");
     let startpost = section.remove("EXECSTARTPOST");
     let starttimeout = section.remove("TIMEOUTSTARTSEC");
     let stoptimeout = section.remove("TIMEOUTSTOPSEC");
+    let timeout_start_failure_mode = section.remove("TIMEOUTSTARTFAILUREMODE");
+    let timeout_stop_failure_mode = section.remove("TIMEOUTSTOPFAILUREMODE");
     let generaltimeout = section.remove("TIMEOUTSEC");
+    let runtime_max = section.remove("RUNTIMEMAXSEC");
+    let start_limit_interval = section.remove("STARTLIMITINTERVALSEC");
+    let start_limit_burst = section.remove("STARTLIMITBURST");
+    let start_limit_action = section.remove("STARTLIMITACTION");
 
     let restart = section.remove("RESTART");
+    let oom_policy = section.remove("OOMPOLICY");
+    let oom_score_adjust = section.remove("OOMSCOREADJUST");
+    let io_scheduling_class = section.remove("IOSCHEDULINGCLASS");
+    let io_scheduling_priority = section.remove("IOSCHEDULINGPRIORITY");
+    let cpu_scheduling_policy = section.remove("CPUSCHEDULINGPOLICY");
+    let cpu_scheduling_priority = section.remove("CPUSCHEDULINGPRIORITY");
+    let cpu_scheduling_reset_on_fork = section.remove("CPUSCHEDULINGRESETONFORK");
+    let nice = section.remove("NICE");
+    let umask = section.remove("UMASK");
+    let cpu_affinity = section.remove("CPUAFFINITY");
+    let no_new_privileges = section.remove("NONEWPRIVILEGES");
+    let ignore_sigpipe = section.remove("IGNORESIGPIPE");
+    let private_tmp = section.remove("PRIVATETMP");
+    let mount_api_vfs = section.remove("MOUNTAPIVFS");
+    let root_directory = section.remove("ROOTDIRECTORY");
+    let proc_subset = section.remove("PROCSUBSET");
+    let device_policy = section.remove("DEVICEPOLICY");
+    let device_allow = section.remove("DEVICEALLOW");
+    let ip_address_allow = section.remove("IPADDRESSALLOW");
+    let ip_address_deny = section.remove("IPADDRESSDENY");
+    let temporary_file_system = section.remove("TEMPORARYFILESYSTEM");
+    let bind_paths = section.remove("BINDPATHS");
+    let bind_read_only_paths = section.remove("BINDREADONLYPATHS");
+    let protect_system = section.remove("PROTECTSYSTEM");
+    let protect_kernel_tunables = section.remove("PROTECTKERNELTUNABLES");
+    let protect_kernel_modules = section.remove("PROTECTKERNELMODULES");
+    let protect_control_groups = section.remove("PROTECTCONTROLGROUPS");
+    let lock_personality = section.remove("LOCKPERSONALITY");
+    let memory_deny_write_execute = section.remove("MEMORYDENYWRITEEXECUTE");
+    let restrict_address_families = section.remove("RESTRICTADDRESSFAMILIES");
+    let restrict_namespaces = section.remove("RESTRICTNAMESPACES");
+    let restrict_realtime = section.remove("RESTRICTREALTIME");
+    let seccomp_filter = section.remove("SECCOMPFILTER");
+    let system_call_filter = section.remove("SYSTEMCALLFILTER");
+    let ambient_capabilities = section.remove("AMBIENTCAPABILITIES");
+    let capability_bounding_set = section.remove("CAPABILITYBOUNDINGSET");
+    let apparmor_profile = section.remove("APPARMORPROFILE");
+    let pass_environment = section.remove("PASSENVIRONMENT");
+    let unset_environment = section.remove("UNSETENVIRONMENT");
+    let environment_file = section.remove("ENVIRONMENTFILE");
+    let selinux_context = section.remove("SELINUXCONTEXT");
+    let smack_process_label = section.remove("SMACKPROCESSLABEL");
+    let success_exit_status = section.remove("SUCCESSEXITSTATUS");
+    let restart_prevent_exit_status = section.remove("RESTARTPREVENTEXITSTATUS");
+    let restart_force_exit_status = section.remove("RESTARTFORCEEXITSTATUS");
     let sockets = section.remove("SOCKETS");
     let notify_access = section.remove("NOTIFYACCESS");
     let srcv_type = section.remove("TYPE");
     let accept = section.remove("ACCEPT");
     let dbus_name = section.remove("BUSNAME");
+    let syslog_identifier = section.remove("SYSLOGIDENTIFIER");
+    let syslog_facility = section.remove("SYSLOGFACILITY");
+    let reload_signal = section.remove("RELOADSIGNAL");
+    let final_kill_signal = section.remove("FINALKILLSIGNAL");
+    let kill_mode = section.remove("KILLMODE");
+    let send_sighup = section.remove("SENDSIGHUP");
+    let send_sigkill = section.remove("SENDSIGKILL");
+    let log_rate_limit_interval = section.remove("LOGRATELIMITINTERVALSEC");
+    let log_rate_limit_burst = section.remove("LOGRATELIMITBURST");
+    let log_extra_fields = section.remove("LOGEXTRAFIELDS");
+    let runtime_directory = section.remove("RUNTIMEDIRECTORY");
+    let runtime_directory_mode = section.remove("RUNTIMEDIRECTORYMODE");
+    let state_directory = section.remove("STATEDIRECTORY");
+    let cache_directory = section.remove("CACHEDIRECTORY");
+    let logs_directory = section.remove("LOGSDIRECTORY");
+    let configuration_directory = section.remove("CONFIGURATIONDIRECTORY");
+    let tmpfiles_d = section.remove("TMPFILESD");
+    let standard_output = section.remove("STANDARDOUTPUT");
+    let standard_error = section.remove("STANDARDERROR");
 
     let exec_config = super::parse_exec_section(&mut section)?;
 
@@ -319,6 +765,10 @@ fn parse_service_section(mut section: ParsedSection) -> Result<ServiceConfig, Pa
         }
         None => None,
     };
+    let timeout_start_failure_mode =
+        parse_timeout_failure_mode("TimeoutStartFailureMode", timeout_start_failure_mode)?;
+    let timeout_stop_failure_mode =
+        parse_timeout_failure_mode("TimeoutStopFailureMode", timeout_stop_failure_mode)?;
     let generaltimeout = match generaltimeout {
         Some(vec) => {
             if vec.len() == 1 {
@@ -332,18 +782,87 @@ fn parse_service_section(mut section: ParsedSection) -> Result<ServiceConfig, Pa
         }
         None => None,
     };
-
-    let exec = match exec {
-        Some(mut vec) => {
+    let start_limit_interval = match start_limit_interval {
+        Some(vec) => {
+            if vec.len() == 1 {
+                match parse_timeout(&vec[0].1) {
+                    Timeout::Duration(dur) => Some(dur),
+                    Timeout::Infinity => None,
+                }
+            } else {
+                return Err(ParsingErrorReason::SettingTooManyValues(
+                    "StartLimitIntervalSec".to_owned(),
+                    super::map_tupels_to_second(vec),
+                ));
+            }
+        }
+        None => None,
+    };
+    let start_limit_burst = match start_limit_burst {
+        Some(vec) => {
+            if vec.len() == 1 {
+                match vec[0].1.parse::<u32>() {
+                    Ok(burst) => Some(burst),
+                    Err(_) => {
+                        return Err(ParsingErrorReason::UnknownSetting(
+                            "StartLimitBurst".to_owned(),
+                            vec[0].1.to_owned(),
+                        ))
+                    }
+                }
+            } else {
+                return Err(ParsingErrorReason::SettingTooManyValues(
+                    "StartLimitBurst".to_owned(),
+                    super::map_tupels_to_second(vec),
+                ));
+            }
+        }
+        None => None,
+    };
+    let start_limit_action = match start_limit_action {
+        Some(vec) => {
+            if vec.len() == 1 {
+                match vec[0].1.as_str() {
+                    "none" => StartLimitAction::None,
+                    "reboot" => StartLimitAction::Reboot,
+                    "reboot-force" => StartLimitAction::RebootForce,
+                    "reboot-immediate" => StartLimitAction::RebootImmediate,
+                    "poweroff" => StartLimitAction::Poweroff,
+                    "poweroff-force" => StartLimitAction::PoweroffForce,
+                    "poweroff-immediate" => StartLimitAction::PoweroffImmediate,
+                    "exit-force" => StartLimitAction::ExitForce,
+                    name => {
+                        return Err(ParsingErrorReason::UnknownSetting(
+                            "StartLimitAction".to_owned(),
+                            name.to_owned(),
+                        ))
+                    }
+                }
+            } else {
+                return Err(ParsingErrorReason::SettingTooManyValues(
+                    "StartLimitAction".to_owned(),
+                    super::map_tupels_to_second(vec),
+                ));
+            }
+        }
+        None => StartLimitAction::None,
+    };
+    let runtime_max = match runtime_max {
+        Some(vec) => {
             if vec.len() == 1 {
-                parse_cmdline(&vec.remove(0).1)?
+                Some(parse_timeout(&vec[0].1))
             } else {
                 return Err(ParsingErrorReason::SettingTooManyValues(
-                    "ExecStart".to_owned(),
+                    "RuntimeMaxSec".to_owned(),
                     super::map_tupels_to_second(vec),
                 ));
             }
         }
+        None => None,
+    };
+
+    let mut exec = match exec {
+        Some(vec) => parse_cmdlines(&vec)?,
         None => return Err(ParsingErrorReason::MissingSetting("ExecStart".to_owned())),
     };
 
@@ -353,6 +872,7 @@ fn parse_service_section(mut section: ParsedSection) -> Result<ServiceConfig, Pa
                 match vec[0].1.as_str() {
                     "simple" => ServiceType::Simple,
                     "notify" => ServiceType::Notify,
+                    "notify-reload" => ServiceType::NotifyReload,
                     "oneshot" => ServiceType::OneShot,
                     "dbus" => {
                         if cfg!(feature = "dbus_support") {
@@ -382,91 +902,1045 @@ fn parse_service_section(mut section: ParsedSection) -> Result<ServiceConfig, Pa
         None => ServiceType::Simple,
     };
 
-    let notifyaccess = match notify_access {
+    // only oneshot services may have more than one ExecStart=
+    if exec.len() > 1 && srcv_type != ServiceType::OneShot {
+        return Err(ParsingErrorReason::SettingTooManyValues(
+            "ExecStart".to_owned(),
+            exec.iter().map(|cmdline| cmdline.to_string()).collect(),
+        ));
+    }
+    let additional_exec = exec.split_off(1);
+    let exec = exec.remove(0);
+
+    let oom_policy = match oom_policy {
         Some(vec) => {
             if vec.len() == 1 {
                 match vec[0].1.as_str() {
-                    "all" => NotifyKind::All,
-                    "main" => NotifyKind::Main,
-                    "exec" => NotifyKind::Exec,
-                    "none" => NotifyKind::None,
+                    "continue" => OomPolicy::Continue,
+                    "stop" => OomPolicy::Stop,
+                    "kill" => OomPolicy::Kill,
                     name => {
                         return Err(ParsingErrorReason::UnknownSetting(
-                            "NotifyAccess".to_owned(),
+                            "OOMPolicy".to_owned(),
                             name.to_owned(),
                         ))
                     }
                 }
             } else {
                 return Err(ParsingErrorReason::SettingTooManyValues(
-                    "NotifyAccess".to_owned(),
+                    "OOMPolicy".to_owned(),
                     super::map_tupels_to_second(vec),
                 ));
             }
         }
-        None => NotifyKind::Main,
+        None => OomPolicy::Stop,
     };
 
-    let stop = match stop {
-        Some(vec) => parse_cmdlines(&vec)?,
-        None => Vec::new(),
-    };
-    let stoppost = match stoppost {
-        Some(vec) => parse_cmdlines(&vec)?,
-        None => Vec::new(),
-    };
-    let startpre = match startpre {
-        Some(vec) => parse_cmdlines(&vec)?,
-        None => Vec::new(),
+    let oom_score_adjust = match oom_score_adjust {
+        Some(vec) => {
+            if vec.len() == 1 {
+                let raw = vec[0].1.trim();
+                match raw.parse::<i32>() {
+                    Ok(adj) if (-1000..=1000).contains(&adj) => Some(adj),
+                    Ok(adj) => {
+                        return Err(ParsingErrorReason::Generic(format!(
+                            "OOMScoreAdjust must be between -1000 and 1000, got: {}",
+                            adj
+                        )))
+                    }
+                    Err(_) => {
+                        return Err(ParsingErrorReason::UnknownSetting(
+                            "OOMScoreAdjust".to_owned(),
+                            raw.to_owned(),
+                        ))
+                    }
+                }
+            } else {
+                return Err(ParsingErrorReason::SettingTooManyValues(
+                    "OOMScoreAdjust".to_owned(),
+                    super::map_tupels_to_second(vec),
+                ));
+            }
+        }
+        None => None,
     };
-    let startpost = match startpost {
-        Some(vec) => parse_cmdlines(&vec)?,
-        None => Vec::new(),
+
+    let io_scheduling_class = match io_scheduling_class {
+        Some(vec) => {
+            if vec.len() == 1 {
+                match vec[0].1.trim() {
+                    "realtime" => Some(IoSchedClass::Realtime),
+                    "best-effort" => Some(IoSchedClass::BestEffort),
+                    "idle" => Some(IoSchedClass::Idle),
+                    name => {
+                        return Err(ParsingErrorReason::UnknownSetting(
+                            "IOSchedulingClass".to_owned(),
+                            name.to_owned(),
+                        ))
+                    }
+                }
+            } else {
+                return Err(ParsingErrorReason::SettingTooManyValues(
+                    "IOSchedulingClass".to_owned(),
+                    super::map_tupels_to_second(vec),
+                ));
+            }
+        }
+        None => None,
     };
 
-    let restart = match restart {
+    let io_scheduling_priority = match io_scheduling_priority {
         Some(vec) => {
             if vec.len() == 1 {
-                match vec[0].1.to_uppercase().as_str() {
-                    "ALWAYS" => ServiceRestart::Always,
-                    "NO" => ServiceRestart::No,
+                let raw = vec[0].1.trim();
+                match raw.parse::<i32>() {
+                    Ok(prio) if (0..=7).contains(&prio) => Some(prio),
+                    Ok(prio) => {
+                        return Err(ParsingErrorReason::Generic(format!(
+                            "IOSchedulingPriority must be between 0 and 7, got: {}",
+                            prio
+                        )))
+                    }
+                    Err(_) => {
+                        return Err(ParsingErrorReason::UnknownSetting(
+                            "IOSchedulingPriority".to_owned(),
+                            raw.to_owned(),
+                        ))
+                    }
+                }
+            } else {
+                return Err(ParsingErrorReason::SettingTooManyValues(
+                    "IOSchedulingPriority".to_owned(),
+                    super::map_tupels_to_second(vec),
+                ));
+            }
+        }
+        None => None,
+    };
 
+    let cpu_scheduling_policy = match cpu_scheduling_policy {
+        Some(vec) => {
+            if vec.len() == 1 {
+                match vec[0].1.trim() {
+                    "other" => Some(SchedPolicy::Other),
+                    "batch" => Some(SchedPolicy::Batch),
+                    "idle" => Some(SchedPolicy::Idle),
+                    "fifo" => Some(SchedPolicy::Fifo),
+                    "rr" => Some(SchedPolicy::RR),
                     name => {
                         return Err(ParsingErrorReason::UnknownSetting(
-                            "Restart".to_owned(),
+                            "CPUSchedulingPolicy".to_owned(),
                             name.to_owned(),
                         ))
                     }
                 }
             } else {
                 return Err(ParsingErrorReason::SettingTooManyValues(
-                    "Restart".to_owned(),
+                    "CPUSchedulingPolicy".to_owned(),
                     super::map_tupels_to_second(vec),
                 ));
             }
         }
-        None => ServiceRestart::No,
+        None => None,
     };
-    let accept = match accept {
+
+    let cpu_scheduling_priority = match cpu_scheduling_priority {
+        Some(vec) => {
+            if vec.len() == 1 {
+                let raw = vec[0].1.trim();
+                let prio = raw.parse::<i32>().map_err(|_| {
+                    ParsingErrorReason::UnknownSetting(
+                        "CPUSchedulingPriority".to_owned(),
+                        raw.to_owned(),
+                    )
+                })?;
+                let policy = cpu_scheduling_policy.unwrap_or(SchedPolicy::Other);
+                let valid = if policy.is_realtime() {
+                    (1..=99).contains(&prio)
+                } else {
+                    prio == 0
+                };
+                if !valid {
+                    return Err(ParsingErrorReason::Generic(format!(
+                        "CPUSchedulingPriority must be between 1 and 99 for the fifo and rr policies and 0 for all others. Got {} for policy {:?}",
+                        prio, policy
+                    )));
+                }
+                Some(prio)
+            } else {
+                return Err(ParsingErrorReason::SettingTooManyValues(
+                    "CPUSchedulingPriority".to_owned(),
+                    super::map_tupels_to_second(vec),
+                ));
+            }
+        }
+        None => None,
+    };
+
+    let cpu_scheduling_reset_on_fork = match cpu_scheduling_reset_on_fork {
         Some(vec) => {
             if vec.len() == 1 {
                 string_to_bool(&vec[0].1)
             } else {
                 return Err(ParsingErrorReason::SettingTooManyValues(
-                    "Accept".to_owned(),
+                    "CPUSchedulingResetOnFork".to_owned(),
                     super::map_tupels_to_second(vec),
                 ));
             }
         }
         None => false,
     };
-    let dbus_name = match dbus_name {
+
+    let nice = match nice {
         Some(vec) => {
             if vec.len() == 1 {
-                Some(vec[0].1.to_owned())
+                let raw = vec[0].1.trim();
+                match raw.parse::<i32>() {
+                    Ok(nice) if (-20..=19).contains(&nice) => Some(nice),
+                    Ok(nice) => {
+                        return Err(ParsingErrorReason::Generic(format!(
+                            "Nice must be between -20 and 19, got: {}",
+                            nice
+                        )))
+                    }
+                    Err(_) => {
+                        return Err(ParsingErrorReason::UnknownSetting(
+                            "Nice".to_owned(),
+                            raw.to_owned(),
+                        ))
+                    }
+                }
             } else {
                 return Err(ParsingErrorReason::SettingTooManyValues(
-                    "BusName".to_owned(),
+                    "Nice".to_owned(),
+                    super::map_tupels_to_second(vec),
+                ));
+            }
+        }
+        None => None,
+    };
+
+    let cpu_affinity = match cpu_affinity {
+        Some(vec) => parse_cpu_affinity(&vec)?,
+        None => Vec::new(),
+    };
+
+    let no_new_privileges = match no_new_privileges {
+        Some(vec) => {
+            if vec.len() == 1 {
+                string_to_bool(&vec[0].1)
+            } else {
+                return Err(ParsingErrorReason::SettingTooManyValues(
+                    "NoNewPrivileges".to_owned(),
+                    super::map_tupels_to_second(vec),
+                ));
+            }
+        }
+        None => false,
+    };
+
+    let ignore_sigpipe = match ignore_sigpipe {
+        Some(vec) => {
+            if vec.len() == 1 {
+                string_to_bool(&vec[0].1)
+            } else {
+                return Err(ParsingErrorReason::SettingTooManyValues(
+                    "IgnoreSIGPIPE".to_owned(),
+                    super::map_tupels_to_second(vec),
+                ));
+            }
+        }
+        None => true,
+    };
+
+    let root_directory = match root_directory {
+        Some(vec) => {
+            if vec.len() == 1 {
+                let path = std::path::PathBuf::from(&vec[0].1);
+                if vec[0].1.is_empty() {
+                    None
+                } else if path.is_absolute() {
+                    Some(path)
+                } else {
+                    return Err(ParsingErrorReason::Generic(format!(
+                        "Path in RootDirectory={} must be absolute",
+                        vec[0].1
+                    )));
+                }
+            } else {
+                return Err(ParsingErrorReason::SettingTooManyValues(
+                    "RootDirectory".to_owned(),
+                    super::map_tupels_to_second(vec),
+                ));
+            }
+        }
+        None => None,
+    };
+
+    let proc_subset = match proc_subset {
+        Some(vec) => {
+            if vec.len() == 1 {
+                match vec[0].1.as_str() {
+                    "all" => ProcSubset::All,
+                    "pid" => ProcSubset::Pid,
+                    other => {
+                        return Err(ParsingErrorReason::UnknownSetting(
+                            "ProcSubset".to_owned(),
+                            other.to_owned(),
+                        ))
+                    }
+                }
+            } else {
+                return Err(ParsingErrorReason::SettingTooManyValues(
+                    "ProcSubset".to_owned(),
+                    super::map_tupels_to_second(vec),
+                ));
+            }
+        }
+        None => ProcSubset::All,
+    };
+    let temporary_file_system = match temporary_file_system {
+        Some(lines) => parse_temporary_file_systems(lines)?,
+        None => Vec::new(),
+    };
+    let bind_paths = match bind_paths {
+        Some(lines) => parse_bind_paths("BindPaths", lines)?,
+        None => Vec::new(),
+    };
+    let bind_read_only_paths = match bind_read_only_paths {
+        Some(lines) => parse_bind_paths("BindReadOnlyPaths", lines)?,
+        None => Vec::new(),
+    };
+    let device_policy = match device_policy {
+        Some(vec) => {
+            if vec.len() == 1 {
+                match vec[0].1.as_str() {
+                    "auto" => DevicePolicy::Auto,
+                    "closed" => DevicePolicy::Closed,
+                    "strict" => DevicePolicy::Strict,
+                    other => {
+                        return Err(ParsingErrorReason::UnknownSetting(
+                            "DevicePolicy".to_owned(),
+                            other.to_owned(),
+                        ))
+                    }
+                }
+            } else {
+                return Err(ParsingErrorReason::SettingTooManyValues(
+                    "DevicePolicy".to_owned(),
+                    super::map_tupels_to_second(vec),
+                ));
+            }
+        }
+        None => DevicePolicy::Auto,
+    };
+    let device_allow = match device_allow {
+        Some(lines) => parse_device_allow(lines)?,
+        None => Vec::new(),
+    };
+    let ip_filter = super::parse_ip_address_filter(ip_address_allow, ip_address_deny)?;
+    let mount_api_vfs = match mount_api_vfs {
+        Some(vec) => {
+            if vec.len() == 1 {
+                string_to_bool(&vec[0].1)
+            } else {
+                return Err(ParsingErrorReason::SettingTooManyValues(
+                    "MountAPIVFS".to_owned(),
+                    super::map_tupels_to_second(vec),
+                ));
+            }
+        }
+        None => false,
+    };
+    let private_tmp = match private_tmp {
+        Some(vec) => {
+            if vec.len() == 1 {
+                string_to_bool(&vec[0].1)
+            } else {
+                return Err(ParsingErrorReason::SettingTooManyValues(
+                    "PrivateTmp".to_owned(),
+                    super::map_tupels_to_second(vec),
+                ));
+            }
+        }
+        None => false,
+    };
+
+    let protect_kernel_tunables = match protect_kernel_tunables {
+        Some(vec) => {
+            if vec.len() == 1 {
+                string_to_bool(&vec[0].1)
+            } else {
+                return Err(ParsingErrorReason::SettingTooManyValues(
+                    "ProtectKernelTunables".to_owned(),
+                    super::map_tupels_to_second(vec),
+                ));
+            }
+        }
+        None => false,
+    };
+
+    let protect_kernel_modules = match protect_kernel_modules {
+        Some(vec) => {
+            if vec.len() == 1 {
+                string_to_bool(&vec[0].1)
+            } else {
+                return Err(ParsingErrorReason::SettingTooManyValues(
+                    "ProtectKernelModules".to_owned(),
+                    super::map_tupels_to_second(vec),
+                ));
+            }
+        }
+        None => false,
+    };
+
+    let protect_control_groups = match protect_control_groups {
+        Some(vec) => {
+            if vec.len() == 1 {
+                string_to_bool(&vec[0].1)
+            } else {
+                return Err(ParsingErrorReason::SettingTooManyValues(
+                    "ProtectControlGroups".to_owned(),
+                    super::map_tupels_to_second(vec),
+                ));
+            }
+        }
+        None => false,
+    };
+
+    let protect_system = match protect_system {
+        Some(vec) => {
+            if vec.len() == 1 {
+                match vec[0].1.trim() {
+                    "full" => ProtectSystem::Full,
+                    "strict" => {
+                        return Err(ParsingErrorReason::Generic(
+                            "ProtectSystem=strict is not supported".to_owned(),
+                        ))
+                    }
+                    value if string_to_bool(value) => ProtectSystem::Yes,
+                    "no" | "false" | "0" | "" => ProtectSystem::No,
+                    name => {
+                        return Err(ParsingErrorReason::UnknownSetting(
+                            "ProtectSystem".to_owned(),
+                            name.to_owned(),
+                        ))
+                    }
+                }
+            } else {
+                return Err(ParsingErrorReason::SettingTooManyValues(
+                    "ProtectSystem".to_owned(),
+                    super::map_tupels_to_second(vec),
+                ));
+            }
+        }
+        None => ProtectSystem::No,
+    };
+
+    let lock_personality = match lock_personality {
+        Some(vec) => {
+            if vec.len() == 1 {
+                string_to_bool(&vec[0].1)
+            } else {
+                return Err(ParsingErrorReason::SettingTooManyValues(
+                    "LockPersonality".to_owned(),
+                    super::map_tupels_to_second(vec),
+                ));
+            }
+        }
+        None => false,
+    };
+
+    let memory_deny_write_execute = match memory_deny_write_execute {
+        Some(vec) => {
+            if vec.len() == 1 {
+                string_to_bool(&vec[0].1)
+            } else {
+                return Err(ParsingErrorReason::SettingTooManyValues(
+                    "MemoryDenyWriteExecute".to_owned(),
+                    super::map_tupels_to_second(vec),
+                ));
+            }
+        }
+        None => false,
+    };
+
+    let restrict_realtime = match restrict_realtime {
+        Some(vec) => {
+            if vec.len() == 1 {
+                string_to_bool(&vec[0].1)
+            } else {
+                return Err(ParsingErrorReason::SettingTooManyValues(
+                    "RestrictRealtime".to_owned(),
+                    super::map_tupels_to_second(vec),
+                ));
+            }
+        }
+        None => false,
+    };
+
+    let parse_capabilities = |setting: &str, vec: Vec<(u32, String)>| {
+        crate::platform::capabilities::parse_capability_lines(&super::map_tupels_to_second(vec))
+            .map_err(|e| ParsingErrorReason::Generic(format!("Invalid {}: {}", setting, e)))
+    };
+    let ambient_capabilities = match ambient_capabilities {
+        Some(vec) => parse_capabilities("AmbientCapabilities", vec)?,
+        None => 0,
+    };
+    let capability_bounding_set = match capability_bounding_set {
+        Some(vec) => Some(parse_capabilities("CapabilityBoundingSet", vec)?),
+        None => None,
+    };
+
+    let pass_environment = pass_environment.map(parse_env_var_names);
+    let unset_environment = unset_environment
+        .map(parse_env_var_names)
+        .unwrap_or_default();
+    let environment_file = match environment_file {
+        Some(lines) => parse_environment_files(lines)?,
+        None => Vec::new(),
+    };
+
+    let apparmor_profile = match apparmor_profile {
+        Some(vec) => {
+            if vec.len() == 1 {
+                Some(vec[0].1.to_owned())
+            } else {
+                return Err(ParsingErrorReason::SettingTooManyValues(
+                    "AppArmorProfile".to_owned(),
+                    super::map_tupels_to_second(vec),
+                ));
+            }
+        }
+        None => None,
+    };
+    let selinux_context = match selinux_context {
+        Some(vec) => {
+            if vec.len() == 1 {
+                Some(vec[0].1.to_owned())
+            } else {
+                return Err(ParsingErrorReason::SettingTooManyValues(
+                    "SELinuxContext".to_owned(),
+                    super::map_tupels_to_second(vec),
+                ));
+            }
+        }
+        None => None,
+    };
+    let umask = match umask {
+        Some(vec) => {
+            if vec.len() == 1 {
+                match u32::from_str_radix(&vec[0].1, 8) {
+                    Ok(mask) if mask <= 0o777 => Some(mask),
+                    _ => {
+                        return Err(ParsingErrorReason::UnknownSetting(
+                            "UMask".to_owned(),
+                            vec[0].1.to_owned(),
+                        ))
+                    }
+                }
+            } else {
+                return Err(ParsingErrorReason::SettingTooManyValues(
+                    "UMask".to_owned(),
+                    super::map_tupels_to_second(vec),
+                ));
+            }
+        }
+        None => None,
+    };
+    let smack_process_label = match smack_process_label {
+        Some(vec) => {
+            if vec.len() == 1 {
+                Some(vec[0].1.to_owned())
+            } else {
+                return Err(ParsingErrorReason::SettingTooManyValues(
+                    "SmackProcessLabel".to_owned(),
+                    super::map_tupels_to_second(vec),
+                ));
+            }
+        }
+        None => None,
+    };
+
+    // SeccompFilter= is the older name of SystemCallFilter=, lines of both are used in the order they appear in
+    let seccomp_filter = match (seccomp_filter, system_call_filter) {
+        (None, None) => None,
+        (old, new) => {
+            let mut lines = old.unwrap_or_default();
+            lines.extend(new.unwrap_or_default());
+            lines.sort_by_key(|(line_no, _)| *line_no);
+            let mut entries = Vec::new();
+            for (_, line) in lines {
+                if line.trim().is_empty() {
+                    entries.clear();
+                } else {
+                    entries.push(crate::platform::seccomp::FilterEntry::parse(&line));
+                }
+            }
+            // fail when loading the unit instead of when starting the service
+            crate::platform::seccomp::resolve_filter(&entries).map_err(|e| {
+                ParsingErrorReason::Generic(format!("Invalid SystemCallFilter: {}", e))
+            })?;
+            if entries.is_empty() {
+                None
+            } else {
+                Some(entries)
+            }
+        }
+    };
+
+    let restrict_address_families = match restrict_address_families {
+        Some(vec) => {
+            crate::platform::seccomp::parse_address_families(&super::map_tupels_to_second(vec))
+                .map_err(|e| {
+                    ParsingErrorReason::Generic(format!("Invalid RestrictAddressFamilies: {}", e))
+                })?
+        }
+        None => None,
+    };
+
+    let restrict_namespaces = match restrict_namespaces {
+        Some(vec) => crate::platform::seccomp::parse_namespaces(&super::map_tupels_to_second(vec))
+            .map_err(|e| {
+                ParsingErrorReason::Generic(format!("Invalid RestrictNamespaces: {}", e))
+            })?,
+        None => None,
+    };
+
+    let (success_exit_status, success_exit_signals) =
+        parse_exit_status_list("SuccessExitStatus", success_exit_status)?;
+    let (restart_prevent_exit_status, restart_prevent_exit_signals) =
+        parse_exit_status_list("RestartPreventExitStatus", restart_prevent_exit_status)?;
+    let (restart_force_exit_status, restart_force_exit_signals) =
+        parse_exit_status_list("RestartForceExitStatus", restart_force_exit_status)?;
+
+    let notifyaccess = match notify_access {
+        Some(vec) => {
+            if vec.len() == 1 {
+                match vec[0].1.as_str() {
+                    "all" => NotifyKind::All,
+                    "main" => NotifyKind::Main,
+                    "exec" => NotifyKind::Exec,
+                    "none" => NotifyKind::None,
+                    name => {
+                        return Err(ParsingErrorReason::UnknownSetting(
+                            "NotifyAccess".to_owned(),
+                            name.to_owned(),
+                        ))
+                    }
+                }
+            } else {
+                return Err(ParsingErrorReason::SettingTooManyValues(
+                    "NotifyAccess".to_owned(),
+                    super::map_tupels_to_second(vec),
+                ));
+            }
+        }
+        None => NotifyKind::Main,
+    };
+
+    let stop = match stop {
+        Some(vec) => parse_cmdlines(&vec)?,
+        None => Vec::new(),
+    };
+    let reload = match reload {
+        Some(vec) => parse_cmdlines(&vec)?,
+        None => Vec::new(),
+    };
+    let stoppost = match stoppost {
+        Some(vec) => parse_cmdlines(&vec)?,
+        None => Vec::new(),
+    };
+    let condition = match condition {
+        Some(vec) => parse_cmdlines(&vec)?,
+        None => Vec::new(),
+    };
+    let startpre = match startpre {
+        Some(vec) => parse_cmdlines(&vec)?,
+        None => Vec::new(),
+    };
+    let startpost = match startpost {
+        Some(vec) => parse_cmdlines(&vec)?,
+        None => Vec::new(),
+    };
+    let exec_start_pre_check = match exec_start_pre_check {
+        Some(vec) => {
+            if vec.len() == 1 {
+                parse_cmdlines(&vec)?.pop()
+            } else {
+                return Err(ParsingErrorReason::SettingTooManyValues(
+                    "ExecStartPreCheck".to_owned(),
+                    super::map_tupels_to_second(vec),
+                ));
+            }
+        }
+        None => None,
+    };
+    let pre_check_interval = match pre_check_interval {
+        Some(vec) => {
+            if vec.len() == 1 {
+                match parse_timeout(&vec[0].1) {
+                    Timeout::Duration(dur) => dur,
+                    Timeout::Infinity => {
+                        return Err(ParsingErrorReason::UnknownSetting(
+                            "ExecStartPreCheckIntervalSec".to_owned(),
+                            vec[0].1.to_owned(),
+                        ))
+                    }
+                }
+            } else {
+                return Err(ParsingErrorReason::SettingTooManyValues(
+                    "ExecStartPreCheckIntervalSec".to_owned(),
+                    super::map_tupels_to_second(vec),
+                ));
+            }
+        }
+        None => std::time::Duration::from_secs(1),
+    };
+    let pre_check_max_retries = match pre_check_max_retries {
+        Some(vec) => {
+            if vec.len() == 1 {
+                match vec[0].1.parse::<u32>() {
+                    Ok(retries) => retries,
+                    Err(_) => {
+                        return Err(ParsingErrorReason::UnknownSetting(
+                            "ExecStartPreCheckMaxRetries".to_owned(),
+                            vec[0].1.to_owned(),
+                        ))
+                    }
+                }
+            } else {
+                return Err(ParsingErrorReason::SettingTooManyValues(
+                    "ExecStartPreCheckMaxRetries".to_owned(),
+                    super::map_tupels_to_second(vec),
+                ));
+            }
+        }
+        None => 3,
+    };
+    let health_check = match health_check {
+        Some(vec) => {
+            if vec.len() == 1 {
+                parse_cmdlines(&vec)?.pop()
+            } else {
+                return Err(ParsingErrorReason::SettingTooManyValues(
+                    "ExecHealthCheck".to_owned(),
+                    super::map_tupels_to_second(vec),
+                ));
+            }
+        }
+        None => None,
+    };
+    let health_check_interval = match health_check_interval {
+        Some(vec) => {
+            if vec.len() == 1 {
+                match parse_timeout(&vec[0].1) {
+                    Timeout::Duration(dur) if dur.as_nanos() > 0 => dur,
+                    _ => {
+                        return Err(ParsingErrorReason::UnknownSetting(
+                            "ExecHealthCheckIntervalSec".to_owned(),
+                            vec[0].1.to_owned(),
+                        ))
+                    }
+                }
+            } else {
+                return Err(ParsingErrorReason::SettingTooManyValues(
+                    "ExecHealthCheckIntervalSec".to_owned(),
+                    super::map_tupels_to_second(vec),
+                ));
+            }
+        }
+        None => std::time::Duration::from_secs(30),
+    };
+    let health_check_max_failures = match health_check_max_failures {
+        Some(vec) => {
+            if vec.len() == 1 {
+                match vec[0].1.parse::<u32>() {
+                    Ok(failures) if failures > 0 => failures,
+                    _ => {
+                        return Err(ParsingErrorReason::UnknownSetting(
+                            "ExecHealthCheckMaxFailures".to_owned(),
+                            vec[0].1.to_owned(),
+                        ))
+                    }
+                }
+            } else {
+                return Err(ParsingErrorReason::SettingTooManyValues(
+                    "ExecHealthCheckMaxFailures".to_owned(),
+                    super::map_tupels_to_second(vec),
+                ));
+            }
+        }
+        None => 3,
+    };
+
+    let restart = match restart {
+        Some(vec) => {
+            if vec.len() == 1 {
+                match vec[0].1.to_uppercase().as_str() {
+                    "ALWAYS" => ServiceRestart::Always,
+                    "NO" => ServiceRestart::No,
+
+                    name => {
+                        return Err(ParsingErrorReason::UnknownSetting(
+                            "Restart".to_owned(),
+                            name.to_owned(),
+                        ))
+                    }
+                }
+            } else {
+                return Err(ParsingErrorReason::SettingTooManyValues(
+                    "Restart".to_owned(),
+                    super::map_tupels_to_second(vec),
+                ));
+            }
+        }
+        None => ServiceRestart::No,
+    };
+    let accept = match accept {
+        Some(vec) => {
+            if vec.len() == 1 {
+                string_to_bool(&vec[0].1)
+            } else {
+                return Err(ParsingErrorReason::SettingTooManyValues(
+                    "Accept".to_owned(),
+                    super::map_tupels_to_second(vec),
+                ));
+            }
+        }
+        None => false,
+    };
+    let dbus_name = match dbus_name {
+        Some(vec) => {
+            if vec.len() == 1 {
+                Some(vec[0].1.to_owned())
+            } else {
+                return Err(ParsingErrorReason::SettingTooManyValues(
+                    "BusName".to_owned(),
+                    super::map_tupels_to_second(vec),
+                ));
+            }
+        }
+        None => None,
+    };
+
+    let syslog_identifier = match syslog_identifier {
+        Some(vec) => {
+            if vec.len() == 1 {
+                Some(vec[0].1.to_owned())
+            } else {
+                return Err(ParsingErrorReason::SettingTooManyValues(
+                    "SyslogIdentifier".to_owned(),
+                    super::map_tupels_to_second(vec),
+                ));
+            }
+        }
+        None => None,
+    };
+    let syslog_facility = match syslog_facility {
+        Some(vec) => {
+            if vec.len() == 1 {
+                match crate::syslog_sink::SyslogFacility::from_name(&vec[0].1) {
+                    Some(facility) => Some(facility),
+                    None => {
+                        return Err(ParsingErrorReason::UnknownSetting(
+                            "SyslogFacility".to_owned(),
+                            vec[0].1.to_owned(),
+                        ))
+                    }
+                }
+            } else {
+                return Err(ParsingErrorReason::SettingTooManyValues(
+                    "SyslogFacility".to_owned(),
+                    super::map_tupels_to_second(vec),
+                ));
+            }
+        }
+        None => None,
+    };
+
+    let reload_signal = match reload_signal {
+        Some(vec) => {
+            if vec.len() == 1 {
+                match parse_signal_name(&vec[0].1) {
+                    Some(signal) => signal,
+                    None => {
+                        return Err(ParsingErrorReason::UnknownSetting(
+                            "ReloadSignal".to_owned(),
+                            vec[0].1.to_owned(),
+                        ))
+                    }
+                }
+            } else {
+                return Err(ParsingErrorReason::SettingTooManyValues(
+                    "ReloadSignal".to_owned(),
+                    super::map_tupels_to_second(vec),
+                ));
+            }
+        }
+        None => nix::sys::signal::Signal::SIGHUP,
+    };
+    let final_kill_signal = match final_kill_signal {
+        Some(vec) => {
+            if vec.len() == 1 {
+                match parse_signal_name(&vec[0].1) {
+                    Some(signal) => signal,
+                    None => {
+                        return Err(ParsingErrorReason::UnknownSetting(
+                            "FinalKillSignal".to_owned(),
+                            vec[0].1.to_owned(),
+                        ))
+                    }
+                }
+            } else {
+                return Err(ParsingErrorReason::SettingTooManyValues(
+                    "FinalKillSignal".to_owned(),
+                    super::map_tupels_to_second(vec),
+                ));
+            }
+        }
+        None => nix::sys::signal::Signal::SIGKILL,
+    };
+    let kill_mode = match kill_mode {
+        Some(vec) => {
+            if vec.len() == 1 {
+                match vec[0].1.as_str() {
+                    "control-group" => KillMode::ControlGroup,
+                    "process-group" => KillMode::ProcessGroup,
+                    "process" => KillMode::Process,
+                    "none" => KillMode::None,
+                    name => {
+                        return Err(ParsingErrorReason::UnknownSetting(
+                            "KillMode".to_owned(),
+                            name.to_owned(),
+                        ))
+                    }
+                }
+            } else {
+                return Err(ParsingErrorReason::SettingTooManyValues(
+                    "KillMode".to_owned(),
+                    super::map_tupels_to_second(vec),
+                ));
+            }
+        }
+        None => KillMode::ControlGroup,
+    };
+    let send_sighup = match send_sighup {
+        Some(vec) => {
+            if vec.len() == 1 {
+                string_to_bool(&vec[0].1)
+            } else {
+                return Err(ParsingErrorReason::SettingTooManyValues(
+                    "SendSIGHUP".to_owned(),
+                    super::map_tupels_to_second(vec),
+                ));
+            }
+        }
+        None => false,
+    };
+    let send_sigkill = match send_sigkill {
+        Some(vec) => {
+            if vec.len() == 1 {
+                string_to_bool(&vec[0].1)
+            } else {
+                return Err(ParsingErrorReason::SettingTooManyValues(
+                    "SendSIGKILL".to_owned(),
+                    super::map_tupels_to_second(vec),
+                ));
+            }
+        }
+        None => true,
+    };
+
+    let log_rate_limit_interval = match log_rate_limit_interval {
+        Some(vec) => {
+            if vec.len() == 1 {
+                match parse_timeout(&vec[0].1) {
+                    Timeout::Duration(dur) => Some(dur),
+                    Timeout::Infinity => None,
+                }
+            } else {
+                return Err(ParsingErrorReason::SettingTooManyValues(
+                    "LogRateLimitIntervalSec".to_owned(),
+                    super::map_tupels_to_second(vec),
+                ));
+            }
+        }
+        None => None,
+    };
+    let log_rate_limit_burst = match log_rate_limit_burst {
+        Some(vec) => {
+            if vec.len() == 1 {
+                match vec[0].1.parse::<u32>() {
+                    Ok(burst) => Some(burst),
+                    Err(_) => {
+                        return Err(ParsingErrorReason::UnknownSetting(
+                            "LogRateLimitBurst".to_owned(),
+                            vec[0].1.to_owned(),
+                        ))
+                    }
+                }
+            } else {
+                return Err(ParsingErrorReason::SettingTooManyValues(
+                    "LogRateLimitBurst".to_owned(),
+                    super::map_tupels_to_second(vec),
+                ));
+            }
+        }
+        None => None,
+    };
+
+    let mut extra_fields = std::collections::HashMap::new();
+    for (_line, entry) in log_extra_fields.unwrap_or_default() {
+        for field in entry.split_whitespace() {
+            let (key, value) = parse_log_extra_field(field)?;
+            extra_fields.insert(key, value);
+        }
+    }
+
+    let runtime_directory = parse_exec_directories("RuntimeDirectory", runtime_directory)?;
+    let state_directory = parse_exec_directories("StateDirectory", state_directory)?;
+    let cache_directory = parse_exec_directories("CacheDirectory", cache_directory)?;
+    let logs_directory = parse_exec_directories("LogsDirectory", logs_directory)?;
+    let configuration_directory =
+        parse_exec_directories("ConfigurationDirectory", configuration_directory)?;
+    let tmpfiles_d = match tmpfiles_d {
+        Some(vec) => {
+            if vec.len() == 1 {
+                let path = std::path::PathBuf::from(&vec[0].1);
+                if !path.is_absolute() {
+                    return Err(ParsingErrorReason::UnknownSetting(
+                        "TmpFilesD".to_owned(),
+                        vec[0].1.to_owned(),
+                    ));
+                }
+                Some(path)
+            } else {
+                return Err(ParsingErrorReason::SettingTooManyValues(
+                    "TmpFilesD".to_owned(),
+                    super::map_tupels_to_second(vec),
+                ));
+            }
+        }
+        None => None,
+    };
+    let standard_output = parse_output_target("StandardOutput", standard_output)?;
+    let standard_error = parse_output_target("StandardError", standard_error)?;
+    let runtime_directory_mode = match runtime_directory_mode {
+        Some(vec) => {
+            if vec.len() == 1 {
+                match u32::from_str_radix(&vec[0].1, 8) {
+                    Ok(mode) => Some(mode),
+                    Err(_) => {
+                        return Err(ParsingErrorReason::UnknownSetting(
+                            "RuntimeDirectoryMode".to_owned(),
+                            vec[0].1.to_owned(),
+                        ))
+                    }
+                }
+            } else {
+                return Err(ParsingErrorReason::SettingTooManyValues(
+                    "RuntimeDirectoryMode".to_owned(),
                     super::map_tupels_to_second(vec),
                 ));
             }
@@ -485,16 +1959,96 @@ fn parse_service_section(mut section: ParsedSection) -> Result<ServiceConfig, Pa
         srcv_type,
         notifyaccess,
         restart,
+        oom_policy,
+        oom_score_adjust,
+        io_scheduling_class,
+        io_scheduling_priority,
+        cpu_scheduling_policy,
+        cpu_scheduling_priority,
+        cpu_scheduling_reset_on_fork,
+        nice,
+        umask,
+        cpu_affinity,
+        no_new_privileges,
+        ignore_sigpipe,
+        private_tmp,
+        mount_api_vfs,
+        root_directory,
+        proc_subset,
+        device_policy,
+        device_allow,
+        ip_filter,
+        temporary_file_system,
+        bind_paths,
+        bind_read_only_paths,
+        protect_system,
+        protect_kernel_tunables,
+        protect_kernel_modules,
+        protect_control_groups,
+        lock_personality,
+        memory_deny_write_execute,
+        restrict_address_families,
+        restrict_namespaces,
+        restrict_realtime,
+        seccomp_filter,
+        ambient_capabilities,
+        capability_bounding_set,
+        pass_environment,
+        unset_environment,
+        environment_file,
+        apparmor_profile,
+        selinux_context,
+        smack_process_label,
+        success_exit_status,
+        success_exit_signals,
+        restart_prevent_exit_status,
+        restart_prevent_exit_signals,
+        restart_force_exit_status,
+        restart_force_exit_signals,
         accept,
         dbus_name,
         exec,
+        additional_exec,
         stop,
+        reload,
         stoppost,
+        condition,
         startpre,
         startpost,
+        exec_start_pre_check,
+        pre_check_interval,
+        pre_check_max_retries,
+        health_check,
+        health_check_interval,
+        health_check_max_failures,
         starttimeout,
         stoptimeout,
         generaltimeout,
+        timeout_start_failure_mode,
+        timeout_stop_failure_mode,
+        start_limit_interval,
+        start_limit_burst,
+        start_limit_action,
+        runtime_max,
         sockets: map_tupels_to_second(sockets.unwrap_or_default()),
+        syslog_identifier,
+        syslog_facility,
+        reload_signal,
+        final_kill_signal,
+        kill_mode,
+        send_sighup,
+        send_sigkill,
+        log_rate_limit_interval,
+        log_rate_limit_burst,
+        log_extra_fields: extra_fields,
+        runtime_directory,
+        runtime_directory_mode,
+        state_directory,
+        cache_directory,
+        logs_directory,
+        configuration_directory,
+        tmpfiles_d,
+        standard_output,
+        standard_error,
     })
 }
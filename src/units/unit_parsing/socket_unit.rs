@@ -1,3 +1,4 @@
+use crate::platform::bpf_filter::IpAddressFilter;
 use crate::sockets::*;
 use crate::units::*;
 use std::path::PathBuf;
@@ -32,8 +33,8 @@ pub fn parse_socket(
         }
     }
 
-    let (sock_name, services, sock_configs) = match socket_configs {
-        Some(triple) => triple,
+    let (sock_name, services, sock_configs, file_options, ip_filter) = match socket_configs {
+        Some(conf) => conf,
         None => return Err(ParsingErrorReason::SectionNotFound("Socket".to_owned())),
     };
 
@@ -61,8 +62,11 @@ pub fn parse_socket(
             name: sock_name,
             sockets: sock_configs,
             services,
+            file_options,
+            ip_filter,
             exec_config,
         }),
+        pending_reload: None,
     })
 }
 
@@ -76,23 +80,37 @@ fn parse_ipv6_addr(addr: &str) -> Result<std::net::SocketAddrV6, std::net::AddrP
     sock
 }
 
+/// Paths or names in the linux abstract namespace that start with '@'
 fn parse_unix_addr(addr: &str) -> Result<String, ()> {
-    if addr.starts_with('/') || addr.starts_with("./") {
+    if addr.starts_with('/') || addr.starts_with("./") || (addr.starts_with('@') && addr.len() > 1)
+    {
         Ok(addr.to_owned())
     } else {
         Err(())
     }
 }
 
-fn parse_socket_section(
-    mut section: ParsedSection,
-) -> Result<(String, Vec<String>, Vec<SocketConfig>), ParsingErrorReason> {
+/// FileDescriptorName=, the services, the sockets, the options for the socket files and the IP address filter
+type SocketSection = (
+    String,
+    Vec<String>,
+    Vec<SocketConfig>,
+    SocketFileOptions,
+    IpAddressFilter,
+);
+
+fn parse_socket_section(mut section: ParsedSection) -> Result<SocketSection, ParsingErrorReason> {
     let fdname = section.remove("FILEDESCRIPTORNAME");
     let services = section.remove("SERVICE");
     let streams = section.remove("LISTENSTREAM");
     let datagrams = section.remove("LISTENDATAGRAM");
     let seqpacks = section.remove("LISTENSEQUENTIALPACKET");
     let fifos = section.remove("LISTENFIFO");
+    let socket_mode = section.remove("SOCKETMODE");
+    let socket_user = section.remove("SOCKETUSER");
+    let socket_group = section.remove("SOCKETGROUP");
+    let ip_address_allow = section.remove("IPADDRESSALLOW");
+    let ip_address_deny = section.remove("IPADDRESSDENY");
 
     if !section.is_empty() {
         return Err(ParsingErrorReason::UnusedSetting(
@@ -117,6 +135,57 @@ fn parse_socket_section(
 
     let fdname = fdname.unwrap_or("unknown".into());
 
+    let mode = match socket_mode {
+        Some(vec) => {
+            if vec.len() == 1 {
+                match u32::from_str_radix(&vec[0].1, 8) {
+                    Ok(mode) if mode <= 0o7777 => Some(mode),
+                    _ => {
+                        return Err(ParsingErrorReason::Generic(format!(
+                            "SocketMode is not a valid octal file mode: {}",
+                            vec[0].1
+                        )))
+                    }
+                }
+            } else {
+                return Err(ParsingErrorReason::SettingTooManyValues(
+                    "SocketMode".into(),
+                    super::map_tupels_to_second(vec),
+                ));
+            }
+        }
+        None => None,
+    };
+    let user = match socket_user {
+        Some(mut vec) => {
+            if vec.len() == 1 {
+                Some(vec.remove(0).1)
+            } else {
+                return Err(ParsingErrorReason::SettingTooManyValues(
+                    "SocketUser".into(),
+                    super::map_tupels_to_second(vec),
+                ));
+            }
+        }
+        None => None,
+    };
+    let group = match socket_group {
+        Some(mut vec) => {
+            if vec.len() == 1 {
+                Some(vec.remove(0).1)
+            } else {
+                return Err(ParsingErrorReason::SettingTooManyValues(
+                    "SocketGroup".into(),
+                    super::map_tupels_to_second(vec),
+                ));
+            }
+        }
+        None => None,
+    };
+    let file_options = SocketFileOptions { mode, user, group };
+
+    let ip_filter = super::parse_ip_address_filter(ip_address_allow, ip_address_deny)?;
+
     let services = services
         .map(|vec| super::map_tupels_to_second(vec))
         .unwrap_or_default();
@@ -156,7 +225,8 @@ fn parse_socket_section(
     for kind in socket_kinds {
         let specialized: SpecializedSocketConfig = match &kind {
             SocketKind::Fifo(addr) => {
-                if parse_unix_addr(addr).is_ok() {
+                // fifos can not live in the abstract namespace
+                if parse_unix_addr(addr).is_ok() && !addr.starts_with('@') {
                     SpecializedSocketConfig::Fifo(FifoConfig {
                         path: std::path::PathBuf::from(addr),
                     })
@@ -206,5 +276,5 @@ fn parse_socket_section(
         socket_configs.push(SocketConfig { kind, specialized });
     }
 
-    Ok((fdname, services, socket_configs))
+    Ok((fdname, services, socket_configs, file_options, ip_filter))
 }
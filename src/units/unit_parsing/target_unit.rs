@@ -41,5 +41,6 @@ pub fn parse_target(
             after: Vec::new(),
         },
         specialized: UnitSpecialized::Target,
+        pending_reload: None,
     })
 }
@@ -83,6 +83,92 @@ pub fn parse_unit_section(
     let after = section.remove("AFTER");
     let before = section.remove("BEFORE");
     let description = section.remove("DESCRIPTION");
+    let default_dependencies = section.remove("DEFAULTDEPENDENCIES");
+    let propagate_failure = section.remove("PROPAGATEFAILURETOREQUIRES");
+    let on_failure_job_mode = section.remove("ONFAILUREJOBMODE");
+    let joins_namespace_of = section.remove("JOINSNAMESPACEOF");
+
+    let mut conditions = Vec::new();
+    for (setting, kind) in &[
+        ("PATHEXISTS", PathCheckKind::PathExists),
+        ("PATHISDIRECTORY", PathCheckKind::PathIsDirectory),
+        ("FILENOTEMPTY", PathCheckKind::FileNotEmpty),
+        ("DIRECTORYNOTEMPTY", PathCheckKind::DirectoryNotEmpty),
+    ] {
+        if let Some(vec) = section.remove(&format!("CONDITION{}", setting)) {
+            for (_, value) in vec {
+                conditions.push(parse_path_check(*kind, &value)?);
+            }
+        }
+    }
+
+    let mut asserts = Vec::new();
+    for (setting, kind) in &[
+        ("PATHEXISTS", PathCheckKind::PathExists),
+        ("PATHISDIRECTORY", PathCheckKind::PathIsDirectory),
+    ] {
+        if let Some(vec) = section.remove(&format!("ASSERT{}", setting)) {
+            for (_, value) in vec {
+                asserts.push(parse_path_check(*kind, &value)?);
+            }
+        }
+    }
+
+    let default_dependencies = match default_dependencies {
+        Some(vec) => {
+            if vec.len() == 1 {
+                string_to_bool(&vec[0].1)
+            } else {
+                return Err(ParsingErrorReason::SettingTooManyValues(
+                    "DefaultDependencies".to_owned(),
+                    super::map_tupels_to_second(vec),
+                ));
+            }
+        }
+        None => true,
+    };
+    let on_failure_propagate_to_requires = match propagate_failure {
+        Some(vec) => {
+            if vec.len() == 1 {
+                string_to_bool(&vec[0].1)
+            } else {
+                return Err(ParsingErrorReason::SettingTooManyValues(
+                    "PropagateFailureToRequires".to_owned(),
+                    super::map_tupels_to_second(vec),
+                ));
+            }
+        }
+        None => true,
+    };
+    let on_failure_job_mode = match on_failure_job_mode {
+        Some(vec) => {
+            if vec.len() == 1 {
+                match vec[0].1.as_str() {
+                    "replace" => JobMode::Replace,
+                    "ignore-dependencies" => JobMode::IgnoreDependencies,
+                    "ignore-requirements" => JobMode::IgnoreRequirements,
+                    "fail" | "replace-irreversibly" | "isolate" | "flush" => {
+                        return Err(ParsingErrorReason::UnsupportedSetting(format!(
+                            "OnFailureJobMode={}",
+                            vec[0].1
+                        )))
+                    }
+                    name => {
+                        return Err(ParsingErrorReason::UnknownSetting(
+                            "OnFailureJobMode".to_owned(),
+                            name.to_owned(),
+                        ))
+                    }
+                }
+            } else {
+                return Err(ParsingErrorReason::SettingTooManyValues(
+                    "OnFailureJobMode".to_owned(),
+                    super::map_tupels_to_second(vec),
+                ));
+            }
+        }
+        None => JobMode::Replace,
+    };
 
     if !section.is_empty() {
         return Err(ParsingErrorReason::UnusedSetting(
@@ -97,6 +183,51 @@ pub fn parse_unit_section(
         requires: map_tupels_to_second(requires.unwrap_or_default()),
         after: map_tupels_to_second(after.unwrap_or_default()),
         before: map_tupels_to_second(before.unwrap_or_default()),
+        conditions,
+        asserts,
+        default_dependencies,
+        on_failure_propagate_to_requires,
+        on_failure_job_mode,
+        joins_namespace_of: map_tupels_to_second(joins_namespace_of.unwrap_or_default()),
+    })
+}
+
+/// Parse the value of settings like ConditionPathExists= or AssertPathExists=. A leading '!' negates the check.
+pub fn parse_path_check(kind: PathCheckKind, value: &str) -> Result<PathCheck, ParsingErrorReason> {
+    let (negate, path) = match value.strip_prefix('!') {
+        Some(path) => (true, path),
+        None => (false, value),
+    };
+    if path.starts_with('|') {
+        return Err(ParsingErrorReason::UnsupportedSetting(format!(
+            "Triggering conditions ('|' prefix) are not supported: {}={}",
+            kind, value
+        )));
+    }
+    let path = PathBuf::from(path);
+    if !path.is_absolute() {
+        return Err(ParsingErrorReason::Generic(format!(
+            "Path in {}={} must be absolute",
+            kind, value
+        )));
+    }
+    Ok(PathCheck { kind, path, negate })
+}
+
+/// Parse the lines of IPAddressAllow= and IPAddressDeny= (of socket and service units)
+pub fn parse_ip_address_filter(
+    allow: Option<Vec<(u32, String)>>,
+    deny: Option<Vec<(u32, String)>>,
+) -> Result<crate::platform::bpf_filter::IpAddressFilter, ParsingErrorReason> {
+    use crate::platform::bpf_filter::{parse_ip_address_lines, IpAddressFilter};
+    let parse_prefixes = |setting: &str, vec: Option<Vec<(u32, String)>>| match vec {
+        Some(vec) => parse_ip_address_lines(&map_tupels_to_second(vec))
+            .map_err(|e| ParsingErrorReason::Generic(format!("Invalid {}: {}", setting, e))),
+        None => Ok(Vec::new()),
+    };
+    Ok(IpAddressFilter {
+        allow: parse_prefixes("IPAddressAllow", allow)?,
+        deny: parse_prefixes("IPAddressDeny", deny)?,
     })
 }
 
@@ -4,9 +4,12 @@ use crate::services::Service;
 use crate::sockets::{Socket, SocketKind, SpecializedSocketConfig};
 use crate::units::*;
 
+use crate::lock_order::{
+    acquire, wrap_guard, LockLevel, OrderedGuard, OrderedMutex, OrderedRwLock,
+};
 use nix::unistd::Pid;
 use std::collections::HashMap;
-use std::sync::{Arc, Mutex, RwLock};
+use std::sync::{Arc, Condvar, LockResult, Mutex, MutexGuard};
 use std::{fmt, path::PathBuf};
 
 #[derive(Clone, Copy, Eq, PartialEq, Hash, Debug)]
@@ -43,16 +46,99 @@ impl std::cmp::Ord for UnitId {
     }
 }
 
-pub type UnitTable = HashMap<UnitId, Arc<Mutex<Unit>>>;
-pub type ArcMutUnitTable = Arc<RwLock<UnitTable>>;
+pub type UnitTable = HashMap<UnitId, Arc<OrderedMutex<Unit>>>;
+pub type ArcMutUnitTable = Arc<OrderedRwLock<UnitTable>>;
 
-pub type StatusTable = HashMap<UnitId, Arc<Mutex<UnitStatus>>>;
-pub type ArcMutStatusTable = Arc<RwLock<StatusTable>>;
+pub type StatusTable = HashMap<UnitId, Arc<OrderedMutex<UnitStatus>>>;
+pub type ArcMutStatusTable = Arc<OrderedRwLock<StatusTable>>;
 
-pub type PidTable = HashMap<Pid, PidEntry>;
-pub type ArcMutPidTable = Arc<Mutex<PidTable>>;
+/// The pids of all processes rustysd spawned. Waiters for a process exit block on the condvar which is notified
+/// whenever a child is reaped, instead of polling the table.
+#[derive(Default)]
+pub struct PidTable {
+    table: Mutex<HashMap<Pid, PidEntry>>,
+    exited: Condvar,
+}
+
+impl PidTable {
+    pub fn lock(&self) -> LockResult<OrderedGuard<MutexGuard<'_, HashMap<Pid, PidEntry>>>> {
+        let held = acquire(LockLevel::PidTable);
+        wrap_guard(self.table.lock(), held)
+    }
+
+    /// Wake up all threads waiting in wait_for. Must be called after a child got reaped
+    pub fn notify_exited(&self) {
+        self.exited.notify_all();
+    }
+
+    /// Wait until the exit of the helper or oneshot process with this pid is saved in the table and remove the entry.
+    /// Returns None if the timeout is reached before that.
+    pub fn wait_for(
+        &self,
+        pid: Pid,
+        time_out: Option<std::time::Duration>,
+    ) -> Option<crate::signal_handler::ChildTermination> {
+        let start_time = std::time::Instant::now();
+        let _held = acquire(LockLevel::PidTable);
+        let mut table_locked = self.table.lock().unwrap();
+        loop {
+            match table_locked.get(&pid) {
+                Some(PidEntry::Service(_, _)) | Some(PidEntry::Helper(_, _)) => {
+                    // still running, need to wait longer
+                }
+                Some(PidEntry::OneshotExited(_)) | Some(PidEntry::HelperExited(_)) => {
+                    match table_locked.remove(&pid).unwrap() {
+                        PidEntry::OneshotExited(code) | PidEntry::HelperExited(code) => {
+                            return Some(code)
+                        }
+                        _ => unreachable!(),
+                    }
+                }
+                None => {
+                    // Should not happen. Either there is a running entry or an exited entry
+                    unreachable!("No entry for child found")
+                }
+            }
+            table_locked = match time_out {
+                Some(time_out) => {
+                    let elapsed = start_time.elapsed();
+                    if elapsed >= time_out {
+                        return None;
+                    }
+                    self.exited
+                        .wait_timeout(table_locked, time_out - elapsed)
+                        .unwrap()
+                        .0
+                }
+                None => self.exited.wait(table_locked).unwrap(),
+            };
+        }
+    }
 
-pub type ArcMutFDStore = Arc<RwLock<FDStore>>;
+    /// Wait until the main process of a (not oneshot) service with this pid has been reaped, which removes its entry.
+    /// Returns false if the timeout is reached before that.
+    pub fn wait_for_service_exit(&self, pid: Pid, time_out: std::time::Duration) -> bool {
+        let start_time = std::time::Instant::now();
+        let _held = acquire(LockLevel::PidTable);
+        let mut table_locked = self.table.lock().unwrap();
+        while let Some(PidEntry::Service(_, _)) = table_locked.get(&pid) {
+            let elapsed = start_time.elapsed();
+            if elapsed >= time_out {
+                return false;
+            }
+            table_locked = self
+                .exited
+                .wait_timeout(table_locked, time_out - elapsed)
+                .unwrap()
+                .0;
+        }
+        true
+    }
+}
+
+pub type ArcMutPidTable = Arc<PidTable>;
+
+pub type ArcMutFDStore = Arc<OrderedRwLock<FDStore>>;
 
 pub struct RuntimeInfo {
     pub unit_table: ArcMutUnitTable,
@@ -61,14 +147,32 @@ pub struct RuntimeInfo {
     pub fd_store: ArcMutFDStore,
     pub config: crate::config::Config,
     pub last_id: Arc<Mutex<u64>>,
+    /// Subscribers of the control interface that want to know about state changes of units
+    pub events: EventSubscribers,
+    /// Held while the state file is collected and written, so concurrent starts and stops do not write it at the same
+    /// time and the last write always has the newest state
+    pub state_file_lock: Mutex<()>,
+}
+
+impl RuntimeInfo {
+    /// The status of a unit as it is kept in the status_table. The status_table is the only place where the status of
+    /// a unit (including services) is stored, all start/stop paths update it there.
+    /// Returns None if the unit is not known.
+    pub fn current_status(&self, id: &UnitId) -> Option<UnitStatus> {
+        self.status_table
+            .read()
+            .unwrap()
+            .get(id)
+            .map(|status| status.lock().unwrap().clone())
+    }
 }
 
 // This will be passed through to all the different threads as a central state struct
 pub type ArcRuntimeInfo = Arc<RuntimeInfo>;
 
 pub fn lock_all(
-    units: &mut Vec<(UnitId, Arc<Mutex<Unit>>)>,
-) -> HashMap<UnitId, std::sync::MutexGuard<'_, Unit>> {
+    units: &mut Vec<(UnitId, Arc<OrderedMutex<Unit>>)>,
+) -> HashMap<UnitId, OrderedGuard<MutexGuard<'_, Unit>>> {
     let mut units_locked = HashMap::new();
     // sort to make sure units always get locked in the same ordering
     units.sort_by(|(lid, _), (rid, _)| lid.cmp(rid));
@@ -97,9 +201,13 @@ pub enum UnitStatus {
     Starting,
     Started,
     StartedWaitingForSocket,
+    /// Started and currently being reloaded (reload-unit on the control interface)
+    Reloading,
     Stopping,
     Stopped,
     StoppedFinal(String),
+    /// Stopped and will not be started again automatically, for example because the start limit was hit
+    Failed(String),
 }
 
 #[derive(Debug)]
@@ -133,6 +241,9 @@ pub struct Unit {
     pub specialized: UnitSpecialized,
 
     pub install: Install,
+
+    /// Set by a daemon-reload if the unit file changed while the unit was running. Applied on the next start
+    pub pending_reload: Option<Box<Unit>>,
 }
 
 impl Unit {
@@ -174,6 +285,26 @@ impl Unit {
         self.install.after.dedup();
     }
 
+    /// Replace the config with the one of the reloaded unit. Runtime state (pids, open sockets, ...) and the
+    /// dependency graph are kept
+    pub fn apply_reloaded_config(&mut self, new: Unit) {
+        self.conf = new.conf;
+        match (&mut self.specialized, new.specialized) {
+            (UnitSpecialized::Service(srvc), UnitSpecialized::Service(new_srvc)) => {
+                srvc.service_config = new_srvc.service_config;
+            }
+            (UnitSpecialized::Socket(sock), UnitSpecialized::Socket(new_sock)) => {
+                sock.name = new_sock.name;
+                sock.exec_config = new_sock.exec_config;
+                sock.sockets = new_sock.sockets;
+                sock.services = new_sock.services;
+                sock.file_options = new_sock.file_options;
+                sock.ip_filter = new_sock.ip_filter;
+            }
+            _ => {}
+        }
+    }
+
     pub fn activate(
         &mut self,
         run_info: ArcRuntimeInfo,
@@ -181,6 +312,10 @@ impl Unit {
         eventfds: &[EventFd],
         allow_ignore: bool,
     ) -> Result<UnitStatus, UnitOperationError> {
+        if let Some(new) = self.pending_reload.take() {
+            trace!("Use the reloaded config for unit {}", self.conf.name());
+            self.apply_reloaded_config(*new);
+        }
         match &mut self.specialized {
             UnitSpecialized::Target => trace!("Reached target {}", self.conf.name()),
             UnitSpecialized::Socket(sock) => {
@@ -192,7 +327,7 @@ impl Unit {
                 .map_err(|e| UnitOperationError {
                     unit_name: self.conf.name(),
                     unit_id: self.id,
-                    reason: UnitOperationErrorReason::SocketOpenError(format!("{}", e)),
+                    reason: UnitOperationErrorReason::SocketOpenError(e),
                 })?;
             }
             UnitSpecialized::Service(srvc) => {
@@ -214,6 +349,11 @@ impl Unit {
                     crate::services::StartResult::WaitingForSocket => {
                         return Ok(UnitStatus::StartedWaitingForSocket)
                     }
+                    crate::services::StartResult::Ignored => {
+                        return Ok(UnitStatus::StoppedFinal(
+                            "Condition not met: ExecCondition=".into(),
+                        ))
+                    }
                 }
             }
         }
@@ -244,7 +384,7 @@ impl Unit {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub struct UnitConfig {
     pub filepath: PathBuf,
 
@@ -254,6 +394,85 @@ pub struct UnitConfig {
     pub requires: Vec<String>,
     pub before: Vec<String>,
     pub after: Vec<String>,
+
+    /// Condition* settings. If one of these is not met the unit is skipped
+    pub conditions: Vec<PathCheck>,
+    /// Assert* settings. If one of these is not met the activation of the unit fails
+    pub asserts: Vec<PathCheck>,
+
+    /// If false no implicit ordering against sysinit.target and shutdown.target is added
+    pub default_dependencies: bool,
+
+    /// If true the units that require this unit are stopped when this unit fails
+    pub on_failure_propagate_to_requires: bool,
+    pub on_failure_job_mode: JobMode,
+
+    /// JoinsNamespaceOf=. Services that start while one of these services is running enter its network and IPC
+    /// namespace
+    pub joins_namespace_of: Vec<String>,
+}
+
+/// OnFailureJobMode=. Decides what happens to the units requiring a unit that failed
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum JobMode {
+    /// Stop the units requiring the failed unit
+    Replace,
+    /// Leave all other units alone
+    IgnoreDependencies,
+    /// Leave the units requiring the failed unit alone
+    IgnoreRequirements,
+}
+
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum PathCheckKind {
+    PathExists,
+    PathIsDirectory,
+    FileNotEmpty,
+    DirectoryNotEmpty,
+}
+
+impl fmt::Display for PathCheckKind {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.write_str(format!("{:?}", self).as_str())
+    }
+}
+
+/// A check on the filesystem like ConditionPathExists=/some/path or AssertPathExists=!/some/path
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct PathCheck {
+    pub kind: PathCheckKind,
+    pub path: PathBuf,
+    pub negate: bool,
+}
+
+impl PathCheck {
+    pub fn is_met(&self) -> bool {
+        let result = match self.kind {
+            PathCheckKind::PathExists => self.path.exists(),
+            PathCheckKind::PathIsDirectory => self.path.is_dir(),
+            PathCheckKind::FileNotEmpty => match std::fs::metadata(&self.path) {
+                Ok(meta) => meta.is_file() && meta.len() > 0,
+                Err(_) => false,
+            },
+            PathCheckKind::DirectoryNotEmpty => match std::fs::read_dir(&self.path) {
+                Ok(mut entries) => entries.next().is_some(),
+                Err(_) => false,
+            },
+        };
+        result != self.negate
+    }
+}
+
+impl fmt::Display for PathCheck {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            fmt,
+            "{}={}{:?}",
+            self.kind,
+            if self.negate { "!" } else { "" },
+            self.path
+        )
+    }
 }
 
 impl UnitConfig {
@@ -275,9 +494,14 @@ impl UnitConfig {
         let split: Vec<_> = name.split('.').collect();
         split[0..split.len() - 1].join(".")
     }
+
+    /// Whether the units requiring this unit should be stopped if this unit fails
+    pub fn propagates_failure(&self) -> bool {
+        self.on_failure_propagate_to_requires && self.on_failure_job_mode == JobMode::Replace
+    }
 }
 
-#[derive(Clone)]
+#[derive(Clone, PartialEq)]
 pub struct SocketConfig {
     pub kind: SocketKind,
     pub specialized: SpecializedSocketConfig,
@@ -306,6 +530,9 @@ pub struct InstallConfig {
 pub enum ServiceType {
     Simple,
     Notify,
+    /// Like Notify but reloads are requested by sending ReloadSignal= to the service. The service
+    /// then has to send RELOADING=1 and READY=1 when it is done
+    NotifyReload,
     Dbus,
     OneShot,
 }
@@ -324,6 +551,123 @@ pub enum ServiceRestart {
     No,
 }
 
+/// What happens when a service hits its start limit
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum StartLimitAction {
+    /// Only mark the service as failed
+    None,
+    /// Stop all units cleanly, then reboot
+    Reboot,
+    /// Kill all services without stopping them cleanly, then reboot
+    RebootForce,
+    /// Reboot right away
+    RebootImmediate,
+    Poweroff,
+    PoweroffForce,
+    PoweroffImmediate,
+    /// Kill all services without stopping them cleanly, then exit rustysd
+    ExitForce,
+}
+
+/// Which signal is sent to the main process of a service if starting or stopping it takes too long
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum TimeoutFailureMode {
+    /// SIGTERM on start timeout. On stop timeout the service already got SIGTERM, so stop waiting
+    Terminate,
+    /// SIGKILL
+    Kill,
+    /// SIGABRT so the service can dump core
+    Abort,
+}
+
+/// KillMode=. Which of the remaining processes get killed when the service is stopped
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum KillMode {
+    /// The process group and, with the cgroups feature, every process in the cgroup of the service
+    ControlGroup,
+    /// Only the process group of the service
+    ProcessGroup,
+    /// Only the main process
+    Process,
+    /// No process is killed, only ExecStop= is run
+    None,
+}
+
+/// What happens if the kernel OOM killer killed a process of the service
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum OomPolicy {
+    /// Handle the exit like any other exit (so Restart= applies)
+    Continue,
+    /// Stop the service and do not restart it
+    Stop,
+    /// Kill all remaining processes of the service and do not restart it
+    Kill,
+}
+
+/// The CPU scheduling policy that is set with sched_setscheduler before exec
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum SchedPolicy {
+    Other,
+    Batch,
+    Idle,
+    Fifo,
+    RR,
+}
+
+impl SchedPolicy {
+    /// Fifo and RR are the realtime policies, they need a priority between 1 and 99. All others need 0
+    pub fn is_realtime(self) -> bool {
+        self == SchedPolicy::Fifo || self == SchedPolicy::RR
+    }
+}
+
+/// The IO scheduling class that is set with ioprio_set before exec
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum IoSchedClass {
+    Realtime,
+    BestEffort,
+    Idle,
+}
+
+/// Which devices the service may access (DevicePolicy=)
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum DevicePolicy {
+    /// No restrictions unless DeviceAllow= is set, then the same as Closed
+    Auto,
+    /// The devices of DeviceAllow= and the standard pseudo devices like /dev/null
+    Closed,
+    /// Only the devices of DeviceAllow=
+    Strict,
+}
+
+/// Which parts of /proc the service can see (ProcSubset=)
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum ProcSubset {
+    All,
+    /// Only the /proc entries of the processes of the service (and no other entries on kernels >= 5.8)
+    Pid,
+}
+
+/// Which parts of the file system are mounted read-only for the service (ProtectSystem=)
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum ProtectSystem {
+    No,
+    /// /usr and /boot
+    Yes,
+    /// /usr, /boot and /etc
+    Full,
+}
+
+/// Where StandardOutput=/StandardError= send the output of the service
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub enum StdOutputTarget {
+    /// The usual output of rustysd (stdout/stderr, syslog or journal)
+    Default,
+    Null,
+    File(std::path::PathBuf),
+    AppendFile(std::path::PathBuf),
+}
+
 #[derive(Clone, Eq, PartialEq, Debug)]
 pub enum Timeout {
     Duration(std::time::Duration),
@@ -363,21 +707,278 @@ impl ToString for Commandline {
 #[derive(Clone, Eq, PartialEq, Debug)]
 pub struct ServiceConfig {
     pub restart: ServiceRestart,
+    pub oom_policy: OomPolicy,
+    /// OOMScoreAdjust=, written to /proc/self/oom_score_adj before exec. Between -1000 and 1000
+    pub oom_score_adjust: Option<i32>,
+    /// IOSchedulingClass= and IOSchedulingPriority=, applied with ioprio_set before exec. The priority is between 0 and 7
+    pub io_scheduling_class: Option<IoSchedClass>,
+    pub io_scheduling_priority: Option<i32>,
+    /// CPUSchedulingPolicy=, CPUSchedulingPriority= and CPUSchedulingResetOnFork=, applied with sched_setscheduler before exec
+    pub cpu_scheduling_policy: Option<SchedPolicy>,
+    pub cpu_scheduling_priority: Option<i32>,
+    pub cpu_scheduling_reset_on_fork: bool,
+    /// Nice=, the niceness of the service processes. Between -20 and 19
+    pub nice: Option<i32>,
+    /// CPUAffinity=, the CPUs the service processes may run on. Empty means no restriction
+    pub cpu_affinity: Vec<usize>,
+    /// UMask=, the file mode creation mask of the service processes. None inherits the one of rustysd
+    pub umask: Option<u32>,
+    /// NoNewPrivileges=, set no_new_privs so neither the service nor its children can gain privileges through exec
+    pub no_new_privileges: bool,
+    /// IgnoreSIGPIPE=, start the service with SIGPIPE ignored. All other signals start with their default action
+    pub ignore_sigpipe: bool,
+    /// PrivateTmp=, give the service its own (empty) /tmp and /var/tmp
+    pub private_tmp: bool,
+    /// DevicePolicy=, together with DeviceAllow= this restricts the devices the service can access. Needs the cgroups
+    /// feature
+    pub device_policy: DevicePolicy,
+    /// DeviceAllow=, (device, access) pairs. The device is a path or char-NAME / block-NAME, the access is made of r, w
+    /// and m
+    pub device_allow: Vec<(String, String)>,
+    /// IPAddressAllow= and IPAddressDeny=, filter the IP traffic of all processes of the service. Needs the cgroups
+    /// feature and cgroup v2
+    pub ip_filter: crate::platform::bpf_filter::IpAddressFilter,
+    /// MountAPIVFS=, mount /proc, /sys, /dev, /dev/pts and /dev/shm in the mount namespace of the service if they are
+    /// missing (below RootDirectory= if it is set). /dev is a tmpfs with only the standard device nodes. The service
+    /// gets its own mount namespace for this
+    pub mount_api_vfs: bool,
+    /// RootDirectory=, the main process is started with this as its root (with chroot) in its own mount namespace. The
+    /// mounts of the other sandboxing settings are done below the new root. Sources of BindPaths= and
+    /// BindReadOnlyPaths= are still paths in the file system of rustysd
+    pub root_directory: Option<PathBuf>,
+    /// ProcSubset=, a new /proc is mounted for the service that hides the processes of other users
+    pub proc_subset: ProcSubset,
+    /// TemporaryFileSystem=, (path, options) pairs. An empty tmpfs with the options is mounted on each path
+    pub temporary_file_system: Vec<(PathBuf, String)>,
+    /// BindPaths=, (source, destination) pairs that are bind mounted for the service
+    pub bind_paths: Vec<(PathBuf, PathBuf)>,
+    /// BindReadOnlyPaths=, like BindPaths= but the mounts are read-only
+    pub bind_read_only_paths: Vec<(PathBuf, PathBuf)>,
+    pub protect_system: ProtectSystem,
+    /// ProtectKernelTunables=, make /proc/sys, /sys/kernel and similar read-only for the service
+    pub protect_kernel_tunables: bool,
+    /// ProtectKernelModules=, forbid loading and unloading kernel modules with a seccomp filter and drop CAP_SYS_MODULE
+    pub protect_kernel_modules: bool,
+    /// ProtectControlGroups=, make the cgroup hierarchy below /sys/fs/cgroup read-only for the service
+    pub protect_control_groups: bool,
+    /// LockPersonality=, forbid changing the personality (execution domain) with a seccomp filter
+    pub lock_personality: bool,
+    /// MemoryDenyWriteExecute=, forbid memory that is writable and executable with a seccomp filter
+    pub memory_deny_write_execute: bool,
+    /// RestrictAddressFamilies=, the socket address families the service may (or with ~ may not) use
+    pub restrict_address_families: Option<crate::platform::seccomp::AddressFamilies>,
+    /// RestrictNamespaces=, the CLONE_NEW* flags of the namespace types the service may create or join. None allows all
+    pub restrict_namespaces: Option<u32>,
+    /// RestrictRealtime=, forbid realtime scheduling policies with a seccomp filter
+    pub restrict_realtime: bool,
+    /// SystemCallFilter= (or SeccompFilter=), the syscalls and groups the service may use. All others kill the process.
+    /// Lines with the ~ prefix list the syscalls that kill the process instead
+    pub seccomp_filter: Option<Vec<crate::platform::seccomp::FilterEntry>>,
+    /// AmbientCapabilities=, as bitmask. These stay with the service even if it does not run as root
+    pub ambient_capabilities: u64,
+    /// CapabilityBoundingSet=, as bitmask. None leaves the bounding set as it is
+    pub capability_bounding_set: Option<u64>,
+    /// PassEnvironment=, the variables of the environment of rustysd the service gets. None (or "*") passes all of them
+    pub pass_environment: Option<Vec<String>>,
+    /// UnsetEnvironment=, patterns (with * and ?) of variables that are removed even if PassEnvironment= passes them
+    pub unset_environment: Vec<String>,
+    /// EnvironmentFile=, files with variable assignments that are read before each start. The bool is set for files
+    /// with the '-' prefix, these are skipped if they do not exist
+    pub environment_file: Vec<(PathBuf, bool)>,
+    /// AppArmorProfile=, the profile the service is exec'd into. Ignored if AppArmor is not enabled
+    pub apparmor_profile: Option<String>,
+    /// SELinuxContext=, the context the service is exec'd into. Ignored if SELinux is not enabled
+    pub selinux_context: Option<String>,
+    /// SmackProcessLabel=, the Smack label the service runs with. Ignored if Smack is not enabled
+    pub smack_process_label: Option<String>,
+    /// SuccessExitStatus=, exit codes and signals that are handled like a clean exit
+    pub success_exit_status: Vec<i32>,
+    pub success_exit_signals: Vec<nix::sys::signal::Signal>,
+    /// RestartPreventExitStatus=, never restart after these exits even with Restart=always
+    pub restart_prevent_exit_status: Vec<i32>,
+    pub restart_prevent_exit_signals: Vec<nix::sys::signal::Signal>,
+    /// RestartForceExitStatus=, always restart after these exits even with Restart=no
+    pub restart_force_exit_status: Vec<i32>,
+    pub restart_force_exit_signals: Vec<nix::sys::signal::Signal>,
     pub accept: bool,
     pub notifyaccess: NotifyKind,
     pub exec: Commandline,
+    /// The ExecStart= lines after the first one, only Type=oneshot services can have these. They are run one after
+    /// another once the main process (the first line) exited successfully
+    pub additional_exec: Vec<Commandline>,
     pub stop: Vec<Commandline>,
+    /// ExecReload=, run to reload a running service
+    pub reload: Vec<Commandline>,
     pub stoppost: Vec<Commandline>,
+    /// ExecCondition=, run before ExecStartPre=. Exit codes 1 to 254 skip the service instead of failing it
+    pub condition: Vec<Commandline>,
     pub startpre: Vec<Commandline>,
     pub startpost: Vec<Commandline>,
+    /// Run before ExecStartPre=. If it fails it is retried up to pre_check_max_retries times
+    /// with pre_check_interval in between before the start is aborted
+    pub exec_start_pre_check: Option<Commandline>,
+    pub pre_check_interval: std::time::Duration,
+    pub pre_check_max_retries: u32,
+    /// ExecHealthCheck=, run every health_check_interval while the service is running. After health_check_max_failures
+    /// failed checks in a row the service is unhealthy and gets restarted if Restart=always
+    pub health_check: Option<Commandline>,
+    pub health_check_interval: std::time::Duration,
+    pub health_check_max_failures: u32,
     pub srcv_type: ServiceType,
     pub starttimeout: Option<Timeout>,
     pub stoptimeout: Option<Timeout>,
     pub generaltimeout: Option<Timeout>,
+    /// TimeoutStartFailureMode= and TimeoutStopFailureMode=
+    pub timeout_start_failure_mode: TimeoutFailureMode,
+    pub timeout_stop_failure_mode: TimeoutFailureMode,
+    /// StartLimitIntervalSec= and StartLimitBurst=. If one of them is set the service is restarted at most
+    /// burst times per interval. After that it is marked as failed
+    pub start_limit_interval: Option<std::time::Duration>,
+    pub start_limit_burst: Option<u32>,
+    pub start_limit_action: StartLimitAction,
+    /// RuntimeMaxSec=, the service gets stopped if it runs longer than this (and started again if Restart=always)
+    pub runtime_max: Option<Timeout>,
 
     pub exec_config: ExecConfig,
 
     pub dbus_name: Option<String>,
 
     pub sockets: Vec<String>,
+
+    /// If one of these is set the output of the service is sent to syslog instead of stdout/stderr
+    pub syslog_identifier: Option<String>,
+    pub syslog_facility: Option<crate::syslog_sink::SyslogFacility>,
+
+    pub reload_signal: nix::sys::signal::Signal,
+    /// Sent to all processes of the service that are left after stopping it
+    pub final_kill_signal: nix::sys::signal::Signal,
+    pub kill_mode: KillMode,
+    /// SendSIGHUP=, send SIGHUP to the remaining processes before the FinalKillSignal=
+    pub send_sighup: bool,
+    /// SendSIGKILL=, if false the remaining processes are not killed after the SIGTERM
+    pub send_sigkill: bool,
+
+    /// If one of these is set at most burst lines of output are logged per interval. The rest is dropped
+    pub log_rate_limit_interval: Option<std::time::Duration>,
+    pub log_rate_limit_burst: Option<u32>,
+
+    /// Added to every line of output of the service
+    pub log_extra_fields: std::collections::HashMap<String, String>,
+
+    /// Directories (relative to /run, /var/lib and /var/cache) that are created before the service starts.
+    /// Only the runtime directories get removed again when the service stops
+    pub runtime_directory: Vec<String>,
+    pub runtime_directory_mode: Option<u32>,
+    pub state_directory: Vec<String>,
+    pub cache_directory: Vec<String>,
+    /// Relative to /var/log
+    pub logs_directory: Vec<String>,
+    /// Relative to /etc. Owned by root so the service can read but not change its configuration
+    pub configuration_directory: Vec<String>,
+    /// A tmpfiles.d style spec that is applied before the service starts
+    pub tmpfiles_d: Option<PathBuf>,
+
+    pub standard_output: StdOutputTarget,
+    pub standard_error: StdOutputTarget,
+}
+
+/// Match a name against a pattern where * matches any number of characters and ? exactly one
+fn glob_matches(pattern: &[u8], name: &[u8]) -> bool {
+    match (pattern.split_first(), name.split_first()) {
+        (None, None) => true,
+        (Some((b'*', rest)), _) => {
+            glob_matches(rest, name) || (!name.is_empty() && glob_matches(pattern, &name[1..]))
+        }
+        (Some((b'?', rest)), Some((_, name_rest))) => glob_matches(rest, name_rest),
+        (Some((c, rest)), Some((name_c, name_rest))) => {
+            c == name_c && glob_matches(rest, name_rest)
+        }
+        _ => false,
+    }
+}
+
+impl ServiceConfig {
+    /// Whether the variable of the environment of rustysd is passed to the processes of the service
+    pub fn passes_env_var(&self, var: &std::ffi::OsStr) -> bool {
+        use std::os::unix::ffi::OsStrExt;
+        if self.unsets_env_var(var.as_bytes()) {
+            return false;
+        }
+        match &self.pass_environment {
+            Some(names) => names.iter().any(|name| name == "*" || var == name.as_str()),
+            None => true,
+        }
+    }
+
+    /// Whether UnsetEnvironment= removes the variable. This applies to the variables from EnvironmentFile= too
+    pub fn unsets_env_var(&self, var: &[u8]) -> bool {
+        self.unset_environment
+            .iter()
+            .any(|pattern| glob_matches(pattern.as_bytes(), var))
+    }
+
+    /// Read the files of EnvironmentFile=, without the variables UnsetEnvironment= removes
+    pub fn environment_file_vars(&self) -> Result<Vec<(String, String)>, String> {
+        let mut vars = crate::env_file::read_env_files(&self.environment_file)?;
+        vars.retain(|(var, _)| !self.unsets_env_var(var.as_bytes()));
+        Ok(vars)
+    }
+
+    /// Whether DevicePolicy= and DeviceAllow= restrict the devices of the service at all
+    pub fn restricts_devices(&self) -> bool {
+        self.device_policy != DevicePolicy::Auto || !self.device_allow.is_empty()
+    }
+
+    /// The devices the service may access, None if it may access all of them. Device paths are resolved now, so this
+    /// is done before each start
+    pub fn device_rules(
+        &self,
+    ) -> Result<Option<Vec<crate::platform::cgroups::devices::DeviceRule>>, String> {
+        use crate::platform::cgroups::devices::{parse_access, DeviceRule};
+        if !self.restricts_devices() {
+            return Ok(None);
+        }
+        let mut rules = if self.device_policy == DevicePolicy::Strict {
+            Vec::new()
+        } else {
+            DeviceRule::pseudo_devices()?
+        };
+        for (device, access) in &self.device_allow {
+            rules.extend(DeviceRule::resolve(device, parse_access(access)?)?);
+        }
+        Ok(Some(rules))
+    }
+
+    /// Whether a process of this service terminated successfully. Exit code 0 is always a success,
+    /// SuccessExitStatus= can add more exit codes and signals
+    pub fn is_success(&self, termination: &crate::signal_handler::ChildTermination) -> bool {
+        use crate::signal_handler::ChildTermination;
+        match termination {
+            ChildTermination::Exit(code) => *code == 0 || self.success_exit_status.contains(code),
+            ChildTermination::Signal(signal) => self.success_exit_signals.contains(signal),
+        }
+    }
+
+    /// Whether the service should be restarted after its main process terminated.
+    /// RestartForceExitStatus= takes precedence over RestartPreventExitStatus= which takes precedence over Restart=
+    pub fn should_restart(&self, termination: &crate::signal_handler::ChildTermination) -> bool {
+        use crate::signal_handler::ChildTermination;
+        let (forced, prevented) = match termination {
+            ChildTermination::Exit(code) => (
+                self.restart_force_exit_status.contains(code),
+                self.restart_prevent_exit_status.contains(code),
+            ),
+            ChildTermination::Signal(signal) => (
+                self.restart_force_exit_signals.contains(signal),
+                self.restart_prevent_exit_signals.contains(signal),
+            ),
+        };
+        if forced {
+            true
+        } else if prevented {
+            false
+        } else {
+            self.restart == ServiceRestart::Always
+        }
+    }
 }
@@ -0,0 +1,152 @@
+//! Watch the unit dirs with inotify and reload the units when files in them change. This is opt-in (watch_unit_dirs in
+//! the config) because new units that are wanted by a running unit get started without anyone asking for it.
+
+use crate::platform::EventFd;
+use crate::units::*;
+use std::sync::Arc;
+
+/// Changes are collected until the unit dirs have been quiet for this long. Editors tend to write files in multiple steps
+/// and copying a bunch of unit files should only trigger one reload.
+const DEBOUNCE_TIMEOUT_MS: i32 = 500;
+
+#[cfg(target_os = "linux")]
+pub fn watch_unit_dirs_loop(
+    run_info: ArcRuntimeInfo,
+    notification_socket_path: std::path::PathBuf,
+    eventfds: Arc<Vec<EventFd>>,
+) {
+    use nix::poll::{poll, PollFd, PollFlags};
+    use nix::sys::inotify::{AddWatchFlags, InitFlags, Inotify};
+    use std::os::unix::io::AsRawFd;
+
+    let inotify = match Inotify::init(InitFlags::IN_CLOEXEC) {
+        Ok(inotify) => inotify,
+        Err(e) => {
+            error!("Could not setup inotify for the unit dirs: {}", e);
+            return;
+        }
+    };
+    let flags = AddWatchFlags::IN_CREATE
+        | AddWatchFlags::IN_CLOSE_WRITE
+        | AddWatchFlags::IN_DELETE
+        | AddWatchFlags::IN_MOVED_FROM
+        | AddWatchFlags::IN_MOVED_TO;
+    for dir in &run_info.config.unit_dirs {
+        if let Err(e) = inotify.add_watch(dir, flags) {
+            warn!("Could not watch unit dir {:?}: {}", dir, e);
+        }
+    }
+
+    loop {
+        // block until something happens
+        if let Err(e) = inotify.read_events() {
+            error!(
+                "Error while reading inotify events for the unit dirs: {}",
+                e
+            );
+            return;
+        }
+        // then wait until nothing happens for a while
+        loop {
+            let mut fds = [PollFd::new(inotify.as_raw_fd(), PollFlags::POLLIN)];
+            match poll(&mut fds, DEBOUNCE_TIMEOUT_MS) {
+                Ok(0) => break,
+                Ok(_) => {
+                    let _ = inotify.read_events();
+                }
+                Err(e) => {
+                    error!(
+                        "Error while waiting for inotify events for the unit dirs: {}",
+                        e
+                    );
+                    return;
+                }
+            }
+        }
+        trace!("Unit dirs changed, reload units");
+        reload_and_activate(&run_info, &notification_socket_path, &eventfds);
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn watch_unit_dirs_loop(
+    _run_info: ArcRuntimeInfo,
+    _notification_socket_path: std::path::PathBuf,
+    _eventfds: Arc<Vec<EventFd>>,
+) {
+    warn!("Watching the unit dirs is only supported on linux");
+}
+
+/// Reload all units and activate the new ones that are wanted/required by a unit that has already been started
+pub fn reload_and_activate(
+    run_info: &ArcRuntimeInfo,
+    notification_socket_path: &std::path::Path,
+    eventfds: &Arc<Vec<EventFd>>,
+) {
+    let report = match reload_all_units(run_info.clone()) {
+        Ok(report) => report,
+        Err(e) => {
+            error!(
+                "Error while reloading the units after the unit dirs changed: {}",
+                e
+            );
+            return;
+        }
+    };
+    info!(
+        "Reloaded units. Added: {:?} Updated: {:?} Needs restart: {:?} Not reloaded: {:?}",
+        report.added, report.updated, report.needs_restart, report.not_reloaded
+    );
+
+    let mut to_activate: Vec<UnitId> = {
+        let unit_table_locked = run_info.unit_table.read().unwrap();
+        unit_table_locked
+            .values()
+            .filter_map(|unit| {
+                let unit_locked = unit.lock().unwrap();
+                if !report.added.contains(&unit_locked.conf.name()) {
+                    return None;
+                }
+                let wanted = unit_locked
+                    .install
+                    .wanted_by
+                    .iter()
+                    .chain(unit_locked.install.required_by.iter())
+                    .any(|id| run_info.current_status(id) == Some(UnitStatus::Started));
+                if wanted {
+                    Some(unit_locked.id)
+                } else {
+                    None
+                }
+            })
+            .collect()
+    };
+
+    // new units might be ordered after each other. Retry the ones that are waiting as long as others got started
+    while !to_activate.is_empty() {
+        let mut waiting = Vec::new();
+        for id in to_activate.iter().copied() {
+            match activate_unit(
+                id,
+                run_info.clone(),
+                notification_socket_path.to_path_buf(),
+                eventfds.clone(),
+                true,
+            ) {
+                Ok(StartResult::WaitForDependencies) => waiting.push(id),
+                Ok(_) => {}
+                Err(e) => error!("Error while activating new unit: {}", e),
+            }
+        }
+        if waiting.len() == to_activate.len() {
+            for id in waiting {
+                warn!(
+                    "New unit {} was not activated, its dependencies did not start",
+                    id
+                );
+            }
+            break;
+        }
+        to_activate = waiting;
+    }
+}
@@ -32,23 +32,23 @@ USED_FEATURES = {}
 SUPPORTED_FEATURES = {
     "READY": {"icon": ICON_TICK, "text": "Waiting for ready notification for service-type notify is supported"},
     "STATUS": {"icon": ICON_TICK, "text": "Sending free-text status updates to be displayed for the user is supported"},
-    "NOTIFY_SOCKET": {"icon": ICON_TICK, "text": "Listening to a notification socket is supported (see section fd_notifiy for details on which messages are understood). NotifyAccess= is not fully supported though."},
+    "NOTIFY_SOCKET": {"icon": ICON_TICK, "text": "Listening to a notification socket is supported (see section fd_notifiy for details on which messages are understood). The senders are checked against NotifyAccess="},
     "LISTEN_FDS": {"icon": ICON_TICK, "text": "Providing number of filedescriptors is supported"},
     "LISTEN_FDNAMES": {"icon": ICON_TICK, "text": "Providing names for filedescriptors is supported"},
     "LISTEN_PID": {"icon": ICON_TICK, "text": "Provifing the listen_pid to the child is supported"},
     "After": {"icon": ICON_TICK, "text":  "Ordering of units according to before/after relation is supported fully"},
     "Before": {"icon": ICON_TICK, "text": "Ordering of units according to before/after relation is supported fully"},
-    "Type": {"icon": ICON_QMARK, "text": "Types are partly supported. Simple, dbus, notify, oneshot are supported. Forking, idle are not."},
+    "Type": {"icon": ICON_QMARK, "text": "Types are partly supported. Simple, dbus, notify, notify-reload, oneshot are supported. Forking, idle are not."},
     "Restart": {"icon": ICON_QMARK, "text": "Restart is partially supported. The settings 'always' and 'no' are supported"},
     "BusName": {"icon": ICON_TICK, "text": "Setting a bus name to wait for services of type dbus is supported."},
-    "NotifyAccess": {"icon": ICON_QMARK, "text": "Not fully supported. All settings are accepted but are not being enforced right now. Acts as if 'all' was set."},
+    "NotifyAccess": {"icon": ICON_TICK, "text": "Supported. The sender is checked with SO_PASSCRED. exec allows the main process and all processes in its process group (not the processes of ExecStartPre= etc.)"},
     "Sockets": {"icon": ICON_QMARK, "text": "Adding more socket files to servcies is supported. But only so that one socket belongs to only one service (sytsemd allows for sockets to belong to multiple services)."},
-    "ListenStream": {"icon": ICON_TICK, "text": "Opening streaming sockets is supported. The whole IPv4 and IPv6 stuff needs some attention though"},
-    "ListenDatagram": {"icon": ICON_TICK, "text": "Opening datagram sockets is supported. The whole IPv4 and IPv6 stuff needs some attention though"},
-    "ListenSequentialPacket": {"icon": ICON_TICK, "text": "Opening sequential packet sockets is supported."},
-    "ListenFIFO": {"icon": ICON_TICK, "text": "Opening FIFOs is supported. Filemode setting is not supported as of yet though."},
+    "ListenStream": {"icon": ICON_TICK, "text": "Opening streaming sockets is supported, unix sockets can be in the abstract namespace (@name). The whole IPv4 and IPv6 stuff needs some attention though"},
+    "ListenDatagram": {"icon": ICON_TICK, "text": "Opening datagram sockets is supported, unix sockets can be in the abstract namespace (@name). The whole IPv4 and IPv6 stuff needs some attention though"},
+    "ListenSequentialPacket": {"icon": ICON_TICK, "text": "Opening sequential packet sockets is supported, also in the abstract namespace (@name)."},
+    "ListenFIFO": {"icon": ICON_TICK, "text": "Opening FIFOs is supported. The file mode and owner can be set with SocketMode=, SocketUser= and SocketGroup="},
     "Accept": {"icon": ICON_QMARK, "text": "Only the setting 'no' is supported. Inted-style activation is not yet supported."},
-    "ExecStart": {"icon": ICON_TICK, "text": "Exec'ing the command given is supported. The return value is checked for oneshot services. Ignoring the return value with the '-' prefix is supported, other prefixes are not."},
+    "ExecStart": {"icon": ICON_TICK, "text": "Exec'ing the command given is supported. The return value is checked for oneshot services, which can have multiple ExecStart lines that are run one after another. Ignoring the return value with the '-' prefix is supported, other prefixes are not."},
     "ExecStartPre": {"icon": ICON_QMARK,  "text": "Allowing commands to be run is supported. The return value is checked. Ignoring the return value with the '-' prefix is supported, other prefixes are not."},
     "ExecStartPost": {"icon": ICON_QMARK, "text": "Allowing commands to be run is supported. The return value is checked. Ignoring the return value with the '-' prefix is supported, other prefixes are not."},
     "ExecStop": {"icon": ICON_QMARK,      "text": "Allowing commands to be run is supported. The return value is checked. Ignoring the return value with the '-' prefix is supported, other prefixes are not."},
@@ -66,6 +66,88 @@ SUPPORTED_FEATURES = {
     "User": {"icon": ICON_QMARK, "text": "The user id can be set for starting services. Currently only done for the main executable"},
     "Group": {"icon": ICON_QMARK, "text": "The group id can be set for starting services. Currently only done for the main executable"},
     "SupplementaryGroups": {"icon": ICON_QMARK, "text": "The supplementary group ids can be set for starting services. Currently only done for the main executable"},
+    "ConditionPathExists": {"icon": ICON_TICK, "text": "Skipping units if a path does (not) exist is supported"},
+    "ConditionPathIsDirectory": {"icon": ICON_TICK, "text": "Skipping units if a path is (not) a directory is supported"},
+    "ConditionFileNotEmpty": {"icon": ICON_TICK, "text": "Skipping units if a file is empty is supported"},
+    "ConditionDirectoryNotEmpty": {"icon": ICON_TICK, "text": "Skipping units if a directory is empty is supported"},
+    "AssertPathExists": {"icon": ICON_TICK, "text": "Failing the activation of units if a path does (not) exist is supported"},
+    "AssertPathIsDirectory": {"icon": ICON_TICK, "text": "Failing the activation of units if a path is (not) a directory is supported"},
+    "SyslogIdentifier": {"icon": ICON_TICK, "text": "Sending the output of a service to syslog (via /dev/log) with this identifier is supported"},
+    "SyslogFacility": {"icon": ICON_TICK, "text": "Sending the output of a service to syslog (via /dev/log) with this facility is supported"},
+    "RELOADING": {"icon": ICON_TICK, "text": "Waiting for the reload to finish is supported for service-type notify-reload"},
+    "ReloadSignal": {"icon": ICON_TICK, "text": "Setting the signal that is sent to services of type notify-reload to request a reload is supported"},
+    "LogRateLimitIntervalSec": {"icon": ICON_TICK, "text": "Limiting the amount of logged lines of service output per interval is supported"},
+    "LogRateLimitBurst": {"icon": ICON_TICK, "text": "Limiting the amount of logged lines of service output per interval is supported"},
+    "LogExtraFields": {"icon": ICON_TICK, "text": "Adding static fields to every line of service output is supported (appended to plain output, added to json output and sent as journal fields)"},
+    "RuntimeDirectory": {"icon": ICON_QMARK, "text": "Creating the directories (owned by User=/Group=) is supported. Only RuntimeDirectory= directories get removed when the service stops. The environment variables are not set."},
+    "StateDirectory": {"icon": ICON_QMARK, "text": "Creating the directories (owned by User=/Group=) is supported. Only RuntimeDirectory= directories get removed when the service stops. The environment variables are not set."},
+    "CacheDirectory": {"icon": ICON_QMARK, "text": "Creating the directories (owned by User=/Group=) is supported. Only RuntimeDirectory= directories get removed when the service stops. The environment variables are not set."},
+    "RuntimeDirectoryMode": {"icon": ICON_TICK, "text": "Setting the mode of the runtime directories is supported"},
+    "LogsDirectory": {"icon": ICON_QMARK, "text": "Creating the directories (owned by User=/Group=) is supported. The environment variable is not set."},
+    "StandardOutput": {"icon": ICON_QMARK, "text": "file:, append: and null are supported (the files can be rotated with SIGUSR1 or the rotate-logs command). Everything else uses the normal output of rustysd."},
+    "StandardError": {"icon": ICON_QMARK, "text": "file:, append: and null are supported (the files can be rotated with SIGUSR1 or the rotate-logs command). Everything else uses the normal output of rustysd."},
+    "DefaultDependencies": {"icon": ICON_QMARK, "text": "Services are ordered After=sysinit.target and Before=shutdown.target if these targets exist, unless DefaultDependencies=no is set. Requires=/Conflicts= are not added."},
+    "ConfigurationDirectory": {"icon": ICON_QMARK, "text": "Creating the directories (owned by root) is supported. The environment variable is not set."},
+    "RuntimeMaxSec": {"icon": ICON_TICK, "text": "Stopping services (SIGTERM, then the normal stop) after they ran for longer than the given time is supported. With Restart=always they are started again"},
+    "OOMPolicy": {"icon": ICON_QMARK, "text": "Supported if rustysd is built with cgroups and the memory controller is enabled (cgroup v2). OOM kills are only checked when the main process exits."},
+    "SuccessExitStatus": {"icon": ICON_TICK, "text": "Additional exit codes and signals that count as a successful exit are supported"},
+    "RestartPreventExitStatus": {"icon": ICON_TICK, "text": "Exit codes and signals that prevent a restart are supported"},
+    "RestartForceExitStatus": {"icon": ICON_TICK, "text": "Exit codes and signals that force a restart are supported"},
+    "StartLimitIntervalSec": {"icon": ICON_QMARK, "text": "Supported in the [Service] section. Defaults to 10s if only StartLimitBurst= is set"},
+    "StartLimitBurst": {"icon": ICON_QMARK, "text": "Supported in the [Service] section. Defaults to 5 if only StartLimitIntervalSec= is set"},
+    "StartLimitAction": {"icon": ICON_QMARK, "text": "Supported in the [Service] section: none, reboot, reboot-force, reboot-immediate, poweroff, poweroff-force, poweroff-immediate and exit-force"},
+    "SocketMode": {"icon": ICON_TICK, "text": "Supported for unix sockets and FIFOs that live in the filesystem"},
+    "SocketUser": {"icon": ICON_TICK, "text": "Supported for unix sockets and FIFOs that live in the filesystem"},
+    "SocketGroup": {"icon": ICON_TICK, "text": "Supported for unix sockets and FIFOs that live in the filesystem"},
+    "OnFailureJobMode": {"icon": ICON_QMARK, "text": "replace (stop the units requiring the failed unit), ignore-dependencies and ignore-requirements are supported. OnFailure= units are not. The rustysd specific PropagateFailureToRequires=no also keeps the requiring units running"},
+    "TimeoutStartFailureMode": {"icon": ICON_TICK, "text": "Supported, the signal is sent to the main process when the start timeout is reached"},
+    "TimeoutStopFailureMode": {"icon": ICON_TICK, "text": "Supported. kill and abort send SIGKILL/SIGABRT to the main process if it did not exit after SIGTERM, terminate stops waiting"},
+    "FinalKillSignal": {"icon": ICON_TICK, "text": "Supported, sent to all processes that are left after stopping the service (instead of SIGKILL)"},
+    "KillMode": {"icon": ICON_TICK, "text": "All modes are supported. control-group only kills the whole cgroup if rustysd is built with the cgroups feature, otherwise it is the same as process-group"},
+    "SendSIGHUP": {"icon": ICON_TICK, "text": "Supported, sent to the remaining processes right before the FinalKillSignal="},
+    "SendSIGKILL": {"icon": ICON_TICK, "text": "Supported"},
+    "OOMScoreAdjust": {"icon": ICON_TICK, "text": "Written to /proc/self/oom_score_adj in the child before exec"},
+    "IOSchedulingClass": {"icon": ICON_TICK, "text": "Set with ioprio_set in the child before exec"},
+    "IOSchedulingPriority": {"icon": ICON_TICK, "text": "Set with ioprio_set in the child before exec"},
+    "CPUSchedulingPolicy": {"icon": ICON_TICK, "text": "Set with sched_setscheduler in the child before exec"},
+    "CPUSchedulingPriority": {"icon": ICON_TICK, "text": "Set with sched_setscheduler in the child before exec"},
+    "CPUSchedulingResetOnFork": {"icon": ICON_TICK, "text": "Set with sched_setscheduler in the child before exec"},
+    "ExecCondition": {"icon": ICON_TICK, "text": "Exit codes 1-254 skip the service, higher codes fail it"},
+    "Nice": {"icon": ICON_TICK, "text": "Set with setpriority in the child before exec"},
+    "CPUAffinity": {"icon": ICON_TICK, "text": "Set with sched_setaffinity in the child before exec"},
+    "SystemCallFilter": {"icon": ICON_QMARK, "text": "Allow lists and ~ deny lists of syscall names and the groups @system-service, @network-io, @file-system, @process and @io-event. SeccompFilter= is accepted as an alias"},
+    "SELinuxContext": {"icon": ICON_QMARK, "text": "Set for the exec of the service. Skipped if SELinux is not enabled. The - prefix is not supported"},
+    "AppArmorProfile": {"icon": ICON_QMARK, "text": "Set for the exec of the service. Skipped if AppArmor is not enabled. The - prefix is not supported"},
+    "CapabilityBoundingSet": {"icon": ICON_TICK, "text": "Capability names and the ~ prefix, lines are merged"},
+    "AmbientCapabilities": {"icon": ICON_TICK, "text": "Capability names and the ~ prefix, lines are merged. Works for services that do not run as root"},
+    "SmackProcessLabel": {"icon": ICON_QMARK, "text": "Set before privileges are dropped. Skipped if Smack is not enabled. The - prefix is not supported"},
+    "NoNewPrivileges": {"icon": ICON_TICK, "text": "Implied by the seccomp based settings (SystemCallFilter=, LockPersonality=, ...) if the service runs without CAP_SYS_ADMIN, like in systemd"},
+    "PrivateTmp": {"icon": ICON_QMARK, "text": "A fresh tmpfs on /tmp and /var/tmp for the main process. Not shared with ExecStartPre= etc."},
+    "ProtectSystem": {"icon": ICON_QMARK, "text": "yes and full. strict is not supported"},
+    "LockPersonality": {"icon": ICON_TICK, "text": "A seccomp filter that only allows personality() with the current personality"},
+    "MemoryDenyWriteExecute": {"icon": ICON_TICK, "text": "seccomp filter for mmap/mprotect/pkey_mprotect/shmat"},
+    "UMask": {"icon": ICON_TICK, "text": ""},
+    "ProtectKernelTunables": {"icon": ICON_TICK, "text": ""},
+    "ProtectKernelModules": {"icon": ICON_TICK, "text": "seccomp filter and CAP_SYS_MODULE is dropped from the bounding set. The module dirs stay accessible"},
+    "ProtectControlGroups": {"icon": ICON_TICK, "text": ""},
+    "RestrictAddressFamilies": {"icon": ICON_TICK, "text": ""},
+    "RestrictNamespaces": {"icon": ICON_TICK, "text": ""},
+    "RestrictRealtime": {"icon": ICON_TICK, "text": "sched_setscheduler with SCHED_FIFO/SCHED_RR is denied with seccomp, sched_setattr is denied completely"},
+    "IPAddressAllow": {"icon": ICON_TICK, "text": "For the TCP and UDP sockets of socket units with a classic BPF socket filter. For services with eBPF programs on their cgroup (needs the cgroups feature and cgroup v2)"},
+    "IPAddressDeny": {"icon": ICON_TICK, "text": "For the TCP and UDP sockets of socket units with a classic BPF socket filter. For services with eBPF programs on their cgroup (needs the cgroups feature and cgroup v2)"},
+    "ExecReload": {"icon": ICON_TICK, "text": "Run by reload-unit, services without it get SIGHUP (or their ReloadSignal= with Type=notify-reload)"},
+    "PassEnvironment": {"icon": ICON_TICK, "text": "Variables that are not listed are removed from the environment of the service"},
+    "IgnoreSIGPIPE": {"icon": ICON_TICK, "text": ""},
+    "UnsetEnvironment": {"icon": ICON_TICK, "text": "Only names, with * and ? as wildcards. Does not apply to the variables rustysd sets itself"},
+    "EnvironmentFile": {"icon": ICON_TICK, "text": "Quoted values and comments are supported, the variables of rustysd (LISTEN_FDS, NOTIFY_SOCKET, ...) take precedence"},
+    "BindPaths": {"icon": ICON_TICK, "text": "SOURCE[:DESTINATION], missing destinations are created. The '-' prefix and mount options are not supported"},
+    "BindReadOnlyPaths": {"icon": ICON_TICK, "text": "Like BindPaths="},
+    "MountAPIVFS": {"icon": ICON_QMARK, "text": "Only mounts /proc, /sys, /dev (a tmpfs with the standard device nodes), /dev/pts and /dev/shm if they are missing in the mount namespace (or RootDirectory=) of the service"},
+    "DevicePolicy": {"icon": ICON_QMARK, "text": "Needs the cgroups feature. Uses the devices controller with cgroup v1 and an eBPF device filter with cgroup v2"},
+    "DeviceAllow": {"icon": ICON_QMARK, "text": "Device paths and char-/block- driver names (without globs). Needs the cgroups feature"},
+    "JoinsNamespaceOf": {"icon": ICON_QMARK, "text": "Only the network and IPC namespace, taken from the main process of the running service"},
+    "RootDirectory": {"icon": ICON_QMARK, "text": "Only a chroot of the main process, the mounts of the other sandboxing settings are done below the new root"},
+    "TemporaryFileSystem": {"icon": ICON_TICK, "text": ""},
 }
 
 def main():